@@ -0,0 +1,134 @@
+use std::fmt::{Display, Formatter};
+
+use crate::board::*;
+
+/// Critical values of the chi-square distribution at the 5% and 1% significance levels,
+/// for 1 degree of freedom (2/4 ratio) and N*N - 1 = 15 degrees of freedom (positions).
+const CRITICAL_1_DOF: (f64, f64) = (3.841, 6.635);
+const CRITICAL_15_DOF: (f64, f64) = (24.996, 30.578);
+
+/// Records every tile spawn to check statistically that the chance player is fair:
+/// 2s and larger tiles with the probabilities of the ruleset (0.9/0.1 in the original game,
+/// depending on the level with a ramp), and uniformly among the empty cells.
+#[derive(Clone, Default)]
+pub struct SpawnAudit {
+    /// Number of spawns with exponent 1 (a 2) and larger (a 4, or an 8 with a ramp)
+    num_twos: u64,
+    num_fours: u64,
+    /// Expected number of 2s, from the probabilities of the boards they spawned on
+    expected_twos: f64,
+    /// Number of spawns on each cell
+    observed: [[u64; N]; N],
+    /// Expected number of spawns on each cell, if they are uniform among the empty cells
+    expected: [[f64; N]; N],
+}
+
+impl SpawnAudit {
+    /// Records a spawn that happened on the given board (before the tile was placed).
+    pub fn record(&mut self, before: &Board, spawn: Spawn) {
+        match spawn.exponent {
+            1 => self.num_twos += 1,
+            _ => self.num_fours += 1,
+        }
+        self.expected_twos += crate::rules::rules().two_probability(before);
+        self.observed[spawn.row][spawn.col] += 1;
+        let num_empty = before.num_empty() as f64;
+        for i in 0..N {
+            for j in 0..N {
+                if before.get(i, j) == 0 {
+                    self.expected[i][j] += 1.0 / num_empty;
+                }
+            }
+        }
+    }
+
+    /// Merges the spawns recorded by another audit into this one.
+    pub fn merge(&mut self, other: &SpawnAudit) {
+        self.num_twos += other.num_twos;
+        self.num_fours += other.num_fours;
+        self.expected_twos += other.expected_twos;
+        for i in 0..N {
+            for j in 0..N {
+                self.observed[i][j] += other.observed[i][j];
+                self.expected[i][j] += other.expected[i][j];
+            }
+        }
+    }
+
+    pub fn num_spawns(&self) -> u64 {
+        self.num_twos + self.num_fours
+    }
+
+    /// Chi-square statistic of the 2/4 ratio (1 degree of freedom)
+    pub fn ratio_chi_square(&self) -> f64 {
+        let total = self.num_spawns() as f64;
+        chi_square(&[(self.num_twos as f64, self.expected_twos), (self.num_fours as f64, total - self.expected_twos)])
+    }
+
+    /// Chi-square statistic of the spawn positions (N*N - 1 degrees of freedom)
+    pub fn position_chi_square(&self) -> f64 {
+        let cells: Vec<(f64, f64)> = (0..N)
+            .flat_map(|i| (0..N).map(move |j| (i, j)))
+            .map(|(i, j)| (self.observed[i][j] as f64, self.expected[i][j]))
+            .collect();
+        chi_square(&cells)
+    }
+}
+
+/// Pearson's chi-square statistic for a list of `(observed, expected)` counts
+fn chi_square(counts: &[(f64, f64)]) -> f64 {
+    counts
+        .iter()
+        .filter(|(_, expected)| *expected > 0.0)
+        .map(|(observed, expected)| (observed - expected).powi(2) / expected)
+        .sum()
+}
+
+fn verdict(statistic: f64, (critical_5, critical_1): (f64, f64)) -> &'static str {
+    if statistic > critical_1 {
+        "REJECTED at 1%"
+    } else if statistic > critical_5 {
+        "REJECTED at 5%"
+    } else {
+        "ok"
+    }
+}
+
+impl Display for SpawnAudit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let total = self.num_spawns();
+        writeln!(f, "Number of spawns: {total}")?;
+        writeln!(
+            f,
+            "2/4 ratio: {} / {} ({:.4} twos, expected {:.4})",
+            self.num_twos,
+            self.num_fours,
+            self.num_twos as f64 / total.max(1) as f64,
+            self.expected_twos / total.max(1) as f64
+        )?;
+        let ratio = self.ratio_chi_square();
+        writeln!(f, "  chi-square = {ratio:.3} (1 dof): {}", verdict(ratio, CRITICAL_1_DOF))?;
+        writeln!(f, "Spawns per cell (observed / expected):")?;
+        for i in 0..N {
+            for j in 0..N {
+                write!(f, "{:>9} / {:<9.0}", self.observed[i][j], self.expected[i][j])?;
+            }
+            writeln!(f)?;
+        }
+        let position = self.position_chi_square();
+        writeln!(f, "  chi-square = {position:.3} ({} dof): {}", N * N - 1, verdict(position, CRITICAL_15_DOF))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chi_square() {
+        assert_eq!(chi_square(&[(90.0, 90.0), (10.0, 10.0)]), 0.0);
+        // (80 - 90)^2 / 90 + (20 - 10)^2 / 10
+        assert!((chi_square(&[(80.0, 90.0), (20.0, 10.0)]) - (100.0 / 90.0 + 10.0)).abs() < 1e-9);
+    }
+}
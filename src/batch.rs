@@ -0,0 +1,94 @@
+//! Batched drawing of the shapes of the board: the rounded cells and tiles are accumulated into
+//! a single mesh, drawn with one call, instead of the three rectangles and four circles of each
+//! of them. The values of the tiles are drawn after all the shapes, so that their glyphs, which
+//! use the texture of the font, do not split the batch either: a board costs a couple of draw
+//! calls, whatever the number of tiles, which keeps several boards per frame affordable on
+//! weak integrated GPUs.
+
+use macroquad::models::{draw_mesh, Mesh, Vertex};
+use macroquad::prelude::Color;
+
+// Segments of the arc of each rounded corner
+const CORNER_SEGMENTS: usize = 6;
+// Size of a mesh, below the capacity of a draw call of macroquad (10000 vertices and 5000
+// indices by default): larger batches are drawn in several meshes
+const MAX_VERTICES: usize = 4000;
+const MAX_INDICES: usize = 4800;
+
+/// Shapes waiting to be drawn together
+#[derive(Default)]
+pub struct ShapeBatch {
+    vertices: Vec<Vertex>,
+    indices: Vec<u16>,
+}
+
+impl ShapeBatch {
+    /// Adds a rectangle whose corners are rounded with the given radius.
+    pub fn rounded_rect(&mut self, x: f32, y: f32, w: f32, h: f32, radius: f32, color: Color) -> &mut ShapeBatch {
+        let r = radius.min(w / 2.0).min(h / 2.0).max(0.0);
+        // the outline, clockwise on the screen from the left end of the top left corner
+        let mut outline = Vec::with_capacity(4 * (CORNER_SEGMENTS + 1));
+        if r == 0.0 {
+            outline.extend([(x, y), (x + w, y), (x + w, y + h), (x, y + h)]);
+        } else {
+            let corners = [(x + r, y + r), (x + w - r, y + r), (x + w - r, y + h - r), (x + r, y + h - r)];
+            for (k, (cx, cy)) in corners.into_iter().enumerate() {
+                let start = std::f32::consts::PI * (1.0 + k as f32 / 2.0);
+                for s in 0..=CORNER_SEGMENTS {
+                    let angle = start + std::f32::consts::FRAC_PI_2 * s as f32 / CORNER_SEGMENTS as f32;
+                    outline.push((cx + r * angle.cos(), cy + r * angle.sin()));
+                }
+            }
+        }
+        // a fan around the center, the shape being convex
+        if self.vertices.len() + outline.len() + 1 > MAX_VERTICES || self.indices.len() + 3 * outline.len() > MAX_INDICES {
+            self.draw();
+        }
+        let center = self.vertices.len() as u16;
+        self.vertices.push(Vertex::new(x + w / 2.0, y + h / 2.0, 0.0, 0.0, 0.0, color));
+        for (i, &(px, py)) in outline.iter().enumerate() {
+            self.vertices.push(Vertex::new(px, py, 0.0, 0.0, 0.0, color));
+            let next = (i + 1) % outline.len();
+            self.indices.extend([center, center + 1 + i as u16, center + 1 + next as u16]);
+        }
+        self
+    }
+
+    /// Draws the shapes added so far and empties the batch.
+    pub fn draw(&mut self) {
+        if self.indices.is_empty() {
+            return;
+        }
+        let mesh = Mesh { vertices: std::mem::take(&mut self.vertices), indices: std::mem::take(&mut self.indices), texture: None };
+        draw_mesh(&mesh);
+        // (the buffers are reused)
+        (self.vertices, self.indices) = (mesh.vertices, mesh.indices);
+        self.vertices.clear();
+        self.indices.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rounded_rect() {
+        let mut batch = ShapeBatch::default();
+        batch.rounded_rect(10.0, 20.0, 100.0, 50.0, 8.0, Color::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(batch.vertices.len(), 1 + 4 * (CORNER_SEGMENTS + 1));
+        assert_eq!(batch.indices.len(), 3 * 4 * (CORNER_SEGMENTS + 1));
+        for vertex in &batch.vertices {
+            let (x, y) = (vertex.position.x, vertex.position.y);
+            assert!((10.0 - 1e-3..=110.0 + 1e-3).contains(&x) && (20.0 - 1e-3..=70.0 + 1e-3).contains(&y), "{x},{y}");
+        }
+        // the corners are cut: no vertex at the corner of the rectangle
+        assert!(batch.vertices.iter().all(|v| (v.position.x, v.position.y) != (10.0, 20.0)));
+
+        // without radius, a plain rectangle; the indices refer to the vertices of each shape
+        batch.rounded_rect(0.0, 0.0, 10.0, 10.0, 0.0, Color::new(0.0, 0.0, 0.0, 1.0));
+        assert_eq!(batch.vertices.len(), 2 + 4 * (CORNER_SEGMENTS + 1) + 4);
+        assert!(batch.indices.iter().all(|&i| (i as usize) < batch.vertices.len()));
+        assert_eq!(*batch.indices.iter().rev().nth(2).unwrap() as usize, 1 + 4 * (CORNER_SEGMENTS + 1));
+    }
+}
@@ -1,15 +1,50 @@
 #![allow(unused)]
 
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
 use anyhow::Context;
+// The engine, from the library crate (see `lib.rs`)
+use ai_2048::{board, cache, calibration, color, compat, death, eval, external, game, layout, notation, plugin, rules, search, spawn, strategy, testing};
+#[cfg(feature = "gui")]
+use ai_2048::{batch, fonts};
 use board::PlayableBoard;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use rayon::prelude::*;
+use audit::SpawnAudit;
+use checkpoint::Checkpoint;
+use notify::Notifier;
+use notation::{GameOutcome, GameRecord, NOTATION_EXTENSION};
+use plystats::{PlySample, PlyStats};
+use replays::ReplayAnalysis;
+use cache::CacheCapacity;
+use diagnostics::{Caps, Diagnostics};
+use eval::Weights;
+use game::{Game, Input};
+use search::{SearchParams, SearchResult, Tiebreak};
+use spawn::{HostileSpawns, PreviewSpawns, SeededSpawns, SpawnStream};
+use strategy::{Budget, ExpectimaxStrategy, Strategy};
+use summary::{Destination, GameSummary};
+use surface::{Surface, Sweep};
 
-mod board;
-mod eval;
-mod search;
+mod audit;
+mod checkpoint;
+mod crash;
+mod diagnostics;
+mod notify;
+mod plystats;
+mod render;
+mod replays;
+mod report;
+mod summary;
+mod surface;
+mod telemetry;
+mod tips;
+
+// Counts the allocations and the live heap reported by `--diagnostics`
+#[global_allocator]
+static ALLOCATOR: diagnostics::CountingAllocator = diagnostics::CountingAllocator;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -21,16 +56,264 @@ struct Args {
     /// Number of games to play
     #[arg(short, long, default_value = "8")]
     num_games: u64,
+
+    /// Seed of the tile spawns: game `i` of the run always gets the same spawns for the same
+    /// moves, so that runs with different options are compared on the same games
+    #[arg(long, default_value = "2048")]
+    seed: u64,
+
+    /// Number of actions the expectimax search looks ahead
+    #[arg(short, long, default_value = "3", value_parser = parse_depth, global = true)]
+    depth: usize,
+
+    /// Agent playing the games (see `strategy.rs` for the list), or `external:<command>` for
+    /// an external engine
+    #[arg(long, default_value = "expectimax")]
+    strategy: String,
+
+    /// Blend between expectation (0.0) and worst case (1.0) at chance nodes
+    #[arg(short, long, default_value = "0.0", value_parser = parse_mix, global = true)]
+    adversarial_mix: f32,
+
+    /// Weight of the game score gained during the search (0.0: heuristic only)
+    #[arg(short, long, default_value = "0.0", value_parser = parse_non_negative, global = true)]
+    score_weight: f32,
+
+    /// Maximum number of extra plies searched on forced moves and large merges at the horizon
+    #[arg(short, long, default_value = "1", global = true)]
+    extensions: usize,
+
+    /// Relative margin within which the agent keeps its previous direction (or the one keeping
+    /// the largest tile in its corner) over a slightly better move (0.0: always the best)
+    #[arg(long, default_value = "0.0", global = true)]
+    hysteresis: f32,
+
+    /// Choice among the moves of exactly equal value: `fixed` (the first of up, down, left,
+    /// right), `heuristic` (the best position after the move) or `seeded:<seed>`
+    #[arg(long, default_value = "fixed", value_parser = Tiebreak::parse, global = true)]
+    tiebreak: Tiebreak,
+
+    /// Probability below which a line of spawns is not searched further but evaluated with the
+    /// heuristic (0.0: search every line), e.g. 0.0001 to search deeper in the same time
+    #[arg(long, default_value = "0.0", global = true)]
+    prob_cutoff: f32,
+
+    /// Maximum size in megabytes of the cache of the search of each game
+    #[arg(long, default_value = "256", global = true)]
+    cache_mb: usize,
+
+    /// Spawn the tiles adversarially instead of randomly, to measure the worst-case robustness
+    /// of the strategy: the worst tile for the player according to the evaluation of the agent,
+    /// searched this many moves ahead (the seed only gives the first tile)
+    #[arg(long, value_name = "DEPTH", num_args = 0..=1, default_missing_value = "1")]
+    hostile_spawns: Option<usize>,
+
+    /// Give the strategy this many milliseconds per move: the expectimax search deepens until
+    /// the time is up (`--depth` is then the deepest search), the games depending on the
+    /// speed of the machine
+    #[arg(long, value_name = "MS")]
+    move_time: Option<u64>,
+
+    /// Show the value of the next tile to the strategy before each move (as in Threes), only
+    /// its cell being left to chance
+    #[arg(long)]
+    spawn_preview: bool,
+
+    /// Instead of benchmarking the agent, play games with random moves and check that the
+    /// tile spawns are statistically fair
+    #[arg(long)]
+    audit_spawns: bool,
+
+    /// Export the average number of empty cells, max tile and evaluation at each move number
+    /// to this file (JSON if it ends with `.json`, CSV otherwise)
+    #[arg(long)]
+    ply_stats: Option<PathBuf>,
+
+    /// Save every game in the game notation in this directory
+    #[arg(long)]
+    record: Option<PathBuf>,
+
+    /// Write a static HTML report of the games (distributions, histograms, best and worst
+    /// games) to this file
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Save every game in this directory as soon as it ends, so that an interrupted run can
+    /// be resumed with `--resume`
+    #[arg(long, conflicts_with = "resume")]
+    checkpoint: Option<PathBuf>,
+
+    /// Resume the run checkpointed in this directory: the finished games are loaded and only
+    /// the missing ones are played. The options must be the same, except the number of games.
+    #[arg(long)]
+    resume: Option<PathBuf>,
+
+    /// Opt in to submit the anonymized results of the run (strategy, depth, distribution of
+    /// the scores, version) to this HTTP endpoint. Results that cannot be sent are queued and
+    /// sent with the next run. Setting AI2048_TELEMETRY_OFF disables it.
+    #[arg(long)]
+    telemetry: Option<String>,
+
+    /// Directory of the results waiting to be submitted
+    #[arg(long, default_value = "telemetry-queue")]
+    telemetry_queue: PathBuf,
+
+    /// Show a desktop notification when the run finishes, and whenever a game beats the best
+    /// score of the games finished before it
+    #[arg(long, global = true)]
+    notify: bool,
+
+    /// Post the same notifications as JSON (event, message and summary of the run or of the
+    /// game) to this HTTP endpoint
+    #[arg(long, value_name = "URL", global = true)]
+    webhook: Option<String>,
+
+    /// Write a JSON summary of every game played as soon as it ends (seed, score, max tile,
+    /// moves, duration, cause of death...), one per line, to this file or to the standard
+    /// output with `-`. Defaults to the value of AI2048_SUMMARY.
+    #[arg(long)]
+    summary: Option<String>,
+
+    /// Report the memory of the process (resident set, live heap, allocations), the sizes of
+    /// the caches of the searches and of the histories kept, every this many games
+    #[arg(long, value_name = "GAMES")]
+    diagnostics: Option<u64>,
+
+    /// With `--diagnostics`, trim the caches of the searches whenever the resident set of the
+    /// process exceeds this many megabytes (the games may then differ from an untrimmed run)
+    #[arg(long, requires = "diagnostics")]
+    max_rss_mb: Option<u64>,
+
+    /// With `--diagnostics`, trim the caches of the searches whenever the live heap exceeds
+    /// this many megabytes
+    #[arg(long, requires = "diagnostics")]
+    max_heap_mb: Option<u64>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Grade every move of the recorded games of a directory against the search and report
+    /// aggregate statistics
+    AnalyzeReplays {
+        /// Directory containing games in the game notation
+        dir: PathBuf,
+
+        /// Also write the analysis as a Markdown report, with images of the misplayed positions
+        #[arg(long)]
+        markdown: Option<PathBuf>,
+
+        /// Only analyze the games played with these rules (the id printed in the summaries,
+        /// `standard` for the original game), or `all`. Defaults to the rules of AI2048_RULES.
+        #[arg(long)]
+        ruleset: Option<String>,
+    },
+
+    /// Sweep one or two weights of the heuristic over a grid, play a quick batch of games
+    /// with the expectimax search at every point and render the average scores as a heatmap
+    WeightSurface {
+        /// Weight on the horizontal axis and its values, as `name=start:end:steps`
+        /// (e.g. `empty=0:500:6`)
+        #[arg(value_parser = Sweep::parse)]
+        x: Sweep,
+
+        /// Weight on the vertical axis, in the same format
+        #[arg(value_parser = Sweep::parse)]
+        y: Option<Sweep>,
+
+        /// Number of games played at every point (the same seeds at all points)
+        #[arg(long, default_value = "4")]
+        games: u64,
+
+        /// PNG image of the heatmap
+        #[arg(long, default_value = "surface.png")]
+        output: PathBuf,
+    },
+}
+
+/// Outcome of a single game
+struct GameResult {
+    /// Number of actions played
+    score: f32,
+    /// Final board
+    board: PlayableBoard,
+    /// State of the board after each move
+    plies: Vec<PlySample>,
+    /// All the moves of the game
+    record: GameRecord,
+    /// Summary of the game, for the games played by this run
+    summary: Option<GameSummary>,
+    /// Entries, capacity and evictions of the cache of the search at the end of the game, for
+    /// the games played by this run
+    cache: Option<(usize, usize, u64)>,
+}
+
+impl GameResult {
+    /// Result of a game played earlier, sampling the positions with the given `weights`
+    fn of_record(record: GameRecord, weights: &Weights) -> anyhow::Result<GameResult> {
+        let positions = record.positions()?;
+        Ok(GameResult {
+            score: record.moves.len() as f32,
+            board: *positions.last().expect("the final position is always present"),
+            plies: positions.iter().map(|board| PlySample::of(board, weights)).collect(),
+            record,
+            summary: None,
+            cache: None,
+        })
+    }
+}
+
+// A depth of search, at least 1
+fn parse_depth(text: &str) -> anyhow::Result<usize> {
+    let depth: usize = text.parse()?;
+    anyhow::ensure!(depth >= 1, "depth must be at least 1");
+    Ok(depth)
+}
+
+// A blend of the adversarial mix, in [0, 1]
+fn parse_mix(text: &str) -> anyhow::Result<f32> {
+    let mix: f32 = text.parse()?;
+    anyhow::ensure!((0.0..=1.0).contains(&mix), "mix must be in [0, 1]");
+    Ok(mix)
+}
+
+// A finite number, at least 0
+fn parse_non_negative(text: &str) -> anyhow::Result<f32> {
+    let value: f32 = text.parse()?;
+    anyhow::ensure!(value.is_finite() && value >= 0.0, "expected a non-negative number");
+    Ok(value)
 }
 
 fn main() -> anyhow::Result<()> {
     // retrieve command line arguments
     let args: Args = Args::parse();
+    crash::install();
+    let ruleset = rules::init()?;
+    if *ruleset != rules::Ruleset::default() {
+        println!("Rules: {ruleset:?}");
+    }
+    if let Some(plugin) = plugin::init()? {
+        println!("Evaluation plugin: {}", plugin.id());
+    }
 
     // number of game to play
     let num_games = args.num_games;
     // maximum allow runtime for each game
     let timeout = Duration::from_secs(args.timeout);
+    // parameters of the search used by every game
+    let params = SearchParams {
+        depth: args.depth,
+        adversarial_mix: args.adversarial_mix,
+        score_weight: args.score_weight,
+        extensions: args.extensions,
+        cache_capacity: CacheCapacity::Megabytes(args.cache_mb),
+        hysteresis: args.hysteresis,
+        tiebreak: args.tiebreak,
+        probability_cutoff: args.prob_cutoff,
+        ..Default::default()
+    };
 
     // configure the global thread pool of rayon to have as many threads as we have *physical* CPUs
     rayon::ThreadPoolBuilder::new()
@@ -38,17 +321,110 @@ fn main() -> anyhow::Result<()> {
         .build_global()
         .unwrap();
 
-    // run all games on the thread pool and collect the results
+    let notifier = (args.notify || args.webhook.is_some()).then(|| Notifier::new(args.notify, args.webhook.clone()));
+
+    if let Some(Command::AnalyzeReplays { dir, markdown, ruleset: filter }) = &args.command {
+        let id = filter.clone().unwrap_or_else(|| ruleset.id());
+        let analysis = ReplayAnalysis::of_dir(dir, &params, (id != "all").then_some(id.as_str()))?;
+        println!("{analysis}");
+        if let Some(path) = markdown {
+            analysis.write_markdown(path)?;
+            println!("Report written to {}", path.display());
+        }
+        if let Some(notifier) = &notifier {
+            notifier.send("finished", &format!("Analysis of the replays of {} done", dir.display()), None);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::WeightSurface { x, y, games, output }) = &args.command {
+        let timeout = Duration::from_secs(args.timeout);
+        let surface = Surface::compute(x.clone(), y.clone(), &params.weights, |weights| {
+            let params = SearchParams { weights: *weights, ..params };
+            let scores: Vec<f32> = (0..*games)
+                .into_par_iter()
+                .filter_map(|i| play(timeout, Box::new(ExpectimaxStrategy::new(params)), game_seed(args.seed, i), None, false, Budget::default(), weights).ok())
+                .map(|result| result.score)
+                .collect();
+            scores.iter().sum::<f32>() / scores.len().max(1) as f32
+        })?;
+        println!("Average score (#actions):\n{surface}");
+        surface.write_png(output)?;
+        println!("Heatmap written to {}", output.display());
+        if let Some(notifier) = &notifier {
+            notifier.send("finished", &format!("Weight surface done, heatmap written to {}", output.display()), None);
+        }
+        return Ok(());
+    }
+
+    if args.audit_spawns {
+        // audit games are fast (random moves), each one returns its own record of spawns
+        let audit = (0..num_games)
+            .into_par_iter()
+            .map(|_i| audit_spawns())
+            .reduce(SpawnAudit::default, |mut a, b| {
+                a.merge(&b);
+                a
+            });
+        println!("{audit}");
+        return Ok(());
+    }
+
+    // the evaluation of the agent, searched by the hostile spawns
+    let hostile = args.hostile_spawns.map(|depth| SearchParams { depth, ..params });
+    let budget = Budget { time: args.move_time.map(Duration::from_millis), ..Default::default() };
+
+    // fails early on an unknown strategy
+    let strategy_name = strategy::by_name(&args.strategy, &params)?.name();
+
+    let summaries = args.summary.as_deref().map(Destination::parse).or_else(Destination::from_env);
+
+    let diagnostics = args.diagnostics.map(Diagnostics::new);
+    Caps { max_rss_mb: args.max_rss_mb, max_heap_mb: args.max_heap_mb }.watch();
+
+    // games already finished by an interrupted run, and where to save the new ones
+    let options = run_options(&args);
+    let (checkpoint, finished) = match (&args.checkpoint, &args.resume) {
+        (_, Some(dir)) => {
+            let (checkpoint, finished) = Checkpoint::resume(dir, &options)?;
+            println!("Resuming from {} with {} finished games", dir.display(), finished.len());
+            (Some(checkpoint), finished)
+        }
+        (Some(dir), None) => (Some(Checkpoint::create(dir, &options)?), BTreeMap::new()),
+        (None, None) => (None, BTreeMap::new()),
+    };
+
+    // run all games on the thread pool and collect the results, each game with its own
+    // strategy and spawns seeded by its index: the results do not depend on the scheduling
     let results: Vec<_> = (0..num_games)
         .into_par_iter()
-        .map(|_i| play(timeout))
+        .map(|i| match finished.get(&i) {
+            Some(record) => GameResult::of_record(record.clone(), &params.weights),
+            None => {
+                let strategy = strategy::by_name(&args.strategy, &params)?;
+                let result = play(timeout, strategy, game_seed(args.seed, i), hostile.as_ref(), args.spawn_preview, budget, &params.weights)?;
+                if let Some(checkpoint) = &checkpoint {
+                    checkpoint.save_game(i, &result.record)?;
+                }
+                if let (Some(destination), Some(summary)) = (&summaries, &result.summary) {
+                    destination.write(summary)?;
+                }
+                if let (Some(notifier), Some(summary)) = (&notifier, &result.summary) {
+                    notifier.game_finished(summary.score, &summary.to_json());
+                }
+                if let Some(diagnostics) = &diagnostics {
+                    diagnostics.game_finished(result.plies.len(), result.record.moves.len(), result.cache);
+                }
+                Ok(result)
+            }
+        })
         .collect();
 
     // print all results
     for res in &results {
         match res {
             // This line now works correctly due to Display implementation in board.rs
-            Ok((score, board)) => println!("score (#actions): {score}\n{board}\n"),
+            Ok(GameResult { score, board, .. }) => println!("score (#actions): {score}\n{board}\n"),
             Err(e) => println!("{e}"),
         }
     }
@@ -58,8 +434,8 @@ fn main() -> anyhow::Result<()> {
     println!("How many time a tile was reached:");
     for tile in 3..=15 {
         let mut count = 0;
-        for (_, board) in &valid_results {
-            if board.has_at_least_tile(tile) {
+        for result in &valid_results {
+            if result.board.has_at_least_tile(tile) {
                 count += 1;
             }
         }
@@ -75,38 +451,197 @@ fn main() -> anyhow::Result<()> {
         results.len() - valid_results.len()
     );
     let average_score: f32 =
-        valid_results.iter().map(|(score, _)| *score).sum::<f32>() / (valid_results.len() as f32);
+        valid_results.iter().map(|result| result.score).sum::<f32>() / (valid_results.len() as f32);
     println!("Average score (#actions):   {:6.2}", average_score);
 
+    println!("\nCauses of death:");
+    for cause in death::ALL_CAUSES {
+        let count = valid_results.iter().filter(|result| result.record.cause == Some(cause)).count();
+        if count > 0 {
+            println!("{:>15}: {count}", cause.name());
+        }
+    }
+
+    if let Some(dir) = &args.record {
+        std::fs::create_dir_all(dir)?;
+        for (i, result) in valid_results.iter().enumerate() {
+            result
+                .record
+                .save(&dir.join(format!("game-{i:04}.{NOTATION_EXTENSION}")))?;
+        }
+        println!("Games saved in {}", dir.display());
+    }
+
+    if let Some(path) = &args.report {
+        let run = report::StrategyRun {
+            // runs of different rules are not comparable
+            name: if *ruleset == rules::Ruleset::default() { strategy_name } else { format!("{strategy_name} [rules {}]", ruleset.id()) },
+            games: valid_results.iter().map(|result| (result.score, result.board.board())).collect(),
+        };
+        report::write_html(path, &[run])?;
+        println!("Report written to {}", path.display());
+    }
+
+    let batch_summary = |strategy: String| telemetry::BatchSummary {
+        strategy,
+        depth: args.depth,
+        ruleset: ruleset.id(),
+        scores: valid_results.iter().map(|result| result.score).collect(),
+        max_tiles: valid_results.iter().map(|result| result.board.max_tile()).collect(),
+    };
+
+    if let Some(endpoint) = &args.telemetry {
+        if telemetry::disabled() {
+            println!("Telemetry disabled by {}", telemetry::OFF_SWITCH);
+        } else {
+            // the command of external engines may reveal local paths
+            let summary = batch_summary(if args.strategy.starts_with("external:") { "external".to_string() } else { args.strategy.clone() });
+            let telemetry = telemetry::Telemetry::new(endpoint, &args.telemetry_queue);
+            telemetry.enqueue(&summary.to_json())?;
+            match telemetry.flush() {
+                Ok(sent) => println!("Telemetry: {sent} result(s) submitted"),
+                Err(e) => println!("Telemetry: {e:#}, results kept in {}", args.telemetry_queue.display()),
+            }
+        }
+    }
+
+    if let Some(path) = &args.ply_stats {
+        let mut stats = PlyStats::default();
+        for result in &valid_results {
+            stats.add_game(&result.plies);
+        }
+        stats.export(path)?;
+        println!("Per-move statistics written to {}", path.display());
+    }
+
+    if let Some(notifier) = &notifier {
+        let message = format!("{} games played, average score (#actions) {average_score:.2}", valid_results.len());
+        notifier.send("finished", &message, Some(&batch_summary(args.strategy.clone()).to_json()));
+    }
+
     Ok(())
 }
 
-/// Play a game with the given `timeout
-fn play(timeout: Duration) -> anyhow::Result<(f32, PlayableBoard)> {
+/// Options changing how the games are played, that a resumed run must share with the
+/// checkpointed one
+fn run_options(args: &Args) -> String {
+    let mut options = format!(
+        "strategy {}\nseed {}\ntimeout {}\ndepth {}\nadversarial-mix {}\nscore-weight {}\nextensions {}\ncache-mb {}\nhysteresis {}\ntiebreak {}\nrules {:?}\neval-plugin {}\n",
+        args.strategy,
+        args.seed,
+        args.timeout,
+        args.depth,
+        args.adversarial_mix,
+        args.score_weight,
+        args.extensions,
+        args.cache_mb,
+        args.hysteresis,
+        args.tiebreak,
+        rules::rules(),
+        plugin::active_id()
+    );
+    // (only when set, so that the checkpoints of random spawns are still resumed)
+    if let Some(depth) = args.hostile_spawns {
+        options.push_str(&format!("hostile-spawns {depth}\n"));
+    }
+    if let Some(ms) = args.move_time {
+        options.push_str(&format!("move-time {ms}\n"));
+    }
+    if args.spawn_preview {
+        options.push_str("spawn-preview\n");
+    }
+    if args.prob_cutoff > 0.0 {
+        options.push_str(&format!("prob-cutoff {}\n", args.prob_cutoff));
+    }
+    options
+}
+
+/// Seed of the spawns of the game of the given index in a run
+fn game_seed(seed: u64, index: u64) -> u64 {
+    // spread the seeds of the runs apart, so that consecutive run seeds do not share games
+    seed.wrapping_mul(0x9e37_79b9_7f4a_7c15).wrapping_add(index)
+}
+
+/// Play a game with the given `timeout` and strategy, the spawns being drawn from `seed`, or
+/// chosen by the hostile spawns searching with `hostile` after the first tile, the value
+/// of the next tile shown to the strategy with `preview`, each move chosen within `budget`,
+/// sampling the evaluation of the positions with the given `weights`
+fn play(timeout: Duration, mut strategy: Box<dyn Strategy>, seed: u64, hostile: Option<&SearchParams>, preview: bool, budget: Budget, weights: &Weights) -> anyhow::Result<GameResult> {
     // timestamp of when we started to play
     let start = Instant::now();
 
-    // count of the number of move played
-    let mut num_moves = 0;
-    let mut board = PlayableBoard::init();
+    let mut seeded = SeededSpawns::new(seed);
+    let mut game = Game::new(PlayableBoard::init_with(&mut seeded));
+    let mut spawns: Box<dyn SpawnStream> = match hostile {
+        Some(params) => Box::new(HostileSpawns::new(params)),
+        None => Box::new(seeded),
+    };
+    if preview {
+        spawns = Box::new(PreviewSpawns::new(spawns));
+    }
+    let mut plies = Vec::new();
+    crash::start_game(&game.board().board(), Some(seed), &strategy.name());
+    let summarize = |record: &GameRecord, strategy: &dyn Strategy, truncated_decisions: usize| {
+        GameSummary::of_record(record, Some(seed), "bench", &strategy.name(), start.elapsed(), truncated_decisions).ok()
+    };
 
     loop {
-        let Some(action) = crate::search::select_action(board) else {
+        let board = game.board();
+        // count of the number of move played
+        let num_moves = game.num_moves();
+        plies.push(PlySample::of(&board, weights));
+
+        if rules::rules().move_limit_reached(num_moves) {
+            println!("Move limit // num moves: {num_moves}");
+            let record = game.into_record();
+            let summary = summarize(&record, strategy.as_ref(), 0);
+            return Ok(GameResult { score: num_moves as f32, board, plies, record, summary, cache: strategy.cache_usage() });
+        }
+
+        strategy.preview(spawns.preview());
+        let choice = if game.is_over() { None } else { strategy.choose(board, budget) };
+        let Some(SearchResult { best: action, .. }) = choice else {
             println!("End game // num moves {num_moves}");
-            return Ok((num_moves as f32, board));
+            if let Some(report) = strategy.report() {
+                println!("{report}");
+            }
+            // a strategy giving up while a move is legal (e.g. a failing engine) ends the game
+            let truncated_decisions = usize::from(!game.is_over());
+            if !game.is_over() {
+                game.step(Input::Resign)?;
+            }
+            let record = game.into_record();
+            let summary = summarize(&record, strategy.as_ref(), truncated_decisions);
+            return Ok(GameResult { score: num_moves as f32, board, plies, record, summary, cache: strategy.cache_usage() });
         };
 
         if start.elapsed() > timeout {
             println!("Timeout // num moves: {num_moves}");
-            return Ok((num_moves as f32, board));
+            if let Some(report) = strategy.report() {
+                println!("{report}");
+            }
+            let mut record = game.into_record();
+            record.outcome = GameOutcome::Timeout;
+            let summary = summarize(&record, strategy.as_ref(), 0);
+            return Ok(GameResult { score: num_moves as f32, board, plies, record, summary, cache: strategy.cache_usage() });
         }
 
         //println!("GOT ========================> {action:?}");
-        num_moves += 1;
-        let played = board
-            .apply(action)
-            // This 'format!' call now works because PlayableBoard implements Display
-            .with_context(|| format!("Got inapplicable action {action:?} on board\n{board}"))?;
-        board = played.with_random_tile();
+        game.step(Input::Play(action)).context("Got an inapplicable action")?;
+        game.spawn_from(&mut spawns)?;
+        crash::record_move(action, &game.board().board());
+    }
+}
+
+/// Play a game with random moves, recording all the tile spawns
+fn audit_spawns() -> SpawnAudit {
+    let mut audit = SpawnAudit::default();
+    let mut board = PlayableBoard::init();
+    while let Some(action) = crate::search::select_action_randomly(board).map(|result| result.best) {
+        let played = board.apply(action).expect("random action is applicable");
+        let (next, spawn) = played.with_random_spawn();
+        audit.record(&played.board(), spawn);
+        board = next;
     }
+    audit
 }
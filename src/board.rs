@@ -1,48 +1,137 @@
+use arrayvec::ArrayVec;
 use colored::Colorize; // Import ONLY the trait to enable coloring methods on strings
 use std::fmt::{Debug, Display, Formatter};
-use macroquad::prelude::*; // Import Macroquad drawing functions (Color is now unambiguously from Macroquad)
-
-// CORRECTION: Explicitly import the Rng trait using absolute path to resolve ambiguity
-use ::rand::Rng as _;
+use std::sync::OnceLock;
+#[cfg(feature = "gui")]
+use macroquad::prelude::*; // Import Macroquad drawing functions
+#[cfg(feature = "gui")]
+use crate::batch::ShapeBatch;
+use crate::color::{Color, BLACK};
 
 // --- RENDERING CONSTANTS (MACROQUAD) ---
 // Dimensions and styles for the grid
+// (shared with the offscreen renderer of `render.rs`)
 pub const WINDOW_WIDTH: f32 = 600.0;
-const PADDING: f32 = 10.0;
-const UI_HEIGHT: f32 = 60.0; // Extra space for statistics
-const GRID_SIZE: f32 = WINDOW_WIDTH - 2.0 * PADDING;
-// Tile size calculation
-const TILE_SIZE: f32 = (GRID_SIZE - (N as f32 + 1.0) * PADDING) / N as f32;
+pub const PADDING: f32 = 10.0;
+pub const UI_HEIGHT: f32 = 60.0; // Extra space for statistics
+pub const GRID_SIZE: f32 = WINDOW_WIDTH - 2.0 * PADDING;
+// Size and position of the tiles: see `layout.rs`
+#[cfg(feature = "gui")]
 const FONT_SIZE: f32 = 40.0;
-const BORDER_COLOR: Color = Color::new(0.53, 0.49, 0.45, 1.0); // #bbada0
+pub const BORDER_COLOR: Color = Color::new(0.53, 0.49, 0.45, 1.0); // #bbada0
+pub const CELL_COLOR: Color = Color::new(0.8, 0.75, 0.69, 1.0); // #cdc1b4
 
 // A board on which the next thing to do is to play (Agent's turn - MAX Node).
+// The legal actions are computed once, when the board is built (bit `action as usize` of the
+// second field): the game loops and the search ask for them again and again.
 #[derive(Clone, Copy, Hash, PartialEq, Eq)]
-pub struct PlayableBoard(Board);
+pub struct PlayableBoard(Board, u8);
 
 impl PlayableBoard {
     /// Returns an initial board, with a single random tile.
     pub fn init() -> PlayableBoard {
-        let mut board = Board::EMPTY;
-        board.add_random();
-        PlayableBoard(board)
+        PlayableBoard::init_with(&mut crate::spawn::RandomSpawns)
+    }
+
+    /// Returns an initial board, with the first tile of `spawns`.
+    pub fn init_with(spawns: &mut impl crate::spawn::SpawnStream) -> PlayableBoard {
+        RandableBoard(Board::EMPTY).with_spawn_from(spawns).0
+    }
+
+    /// Wraps an arbitrary board, e.g. one imported from outside the game.
+    pub fn from_board(board: Board) -> PlayableBoard {
+        let legal = ALL_ACTIONS.into_iter().fold(0, |mask, action| mask | (u8::from(board.can_apply(action)) << action as usize));
+        PlayableBoard(board, legal)
+    }
+
+    /// Whether the action moves at least one tile.
+    pub fn can_apply(&self, action: Action) -> bool {
+        self.1 & (1 << action as usize) != 0
     }
 
     /// Applies an action and returns the next board state (RandableBoard), or None if the action is invalid.
     pub fn apply(&self, action: Action) -> Option<RandableBoard> {
-        match self.0.apply(action) {
-            Some(board) => Some(RandableBoard(board)),
-            None => None,
+        if !self.can_apply(action) {
+            return None;
         }
+        self.0.apply(action).map(RandableBoard)
+    }
+
+    /// Same as `apply` but also returns the points gained by the merges of the action.
+    pub fn apply_scored(&self, action: Action) -> Option<(RandableBoard, u32)> {
+        if !self.can_apply(action) {
+            return None;
+        }
+        self.0
+            .apply_scored(action)
+            .map(|(board, score)| (RandableBoard(board), score))
+    }
+
+    /// Same as `apply_scored` but also tells where every tile went and which tiles merged, for
+    /// the animations and the analyses of the games (the search sticks to `apply`).
+    pub fn apply_outcome(&self, action: Action) -> Option<MoveOutcome> {
+        let (board, score) = self.apply_scored(action)?;
+        let mut tiles: ArrayVec<TileMove, { N * N }> = ArrayVec::new();
+        for k in 0..N {
+            let line = line_cells(action, k);
+            let mut write = 0;
+            // index of the last tile written on the line, if it can still absorb the next one
+            let mut mergeable: Option<usize> = None;
+            for &(i, j) in &line {
+                let exponent = self.0.get(i, j);
+                if exponent == 0 {
+                    continue;
+                }
+                match mergeable {
+                    Some(last) if tiles[last].exponent == exponent && exponent < MAX_EXPONENT => {
+                        tiles[last].merged = true;
+                        tiles.push(TileMove { from: (i, j), to: tiles[last].to, exponent, merged: true });
+                        mergeable = None;
+                    }
+                    _ => {
+                        mergeable = Some(tiles.len());
+                        tiles.push(TileMove { from: (i, j), to: line[write], exponent, merged: false });
+                        write += 1;
+                    }
+                }
+            }
+        }
+        Some(MoveOutcome { board, score, tiles })
+    }
+
+    /// Returns the underlying board.
+    pub fn board(&self) -> Board {
+        self.0
+    }
+
+    /// The applicable actions, in the order of `ALL_ACTIONS` (empty when the game is lost).
+    pub fn legal_actions(&self) -> ArrayVec<Action, 4> {
+        ALL_ACTIONS.into_iter().filter(|&action| self.can_apply(action)).collect()
+    }
+
+    /// Whether each action of `ALL_ACTIONS` is applicable, in the same order.
+    pub fn action_mask(&self) -> [bool; 4] {
+        ALL_ACTIONS.map(|action| self.can_apply(action))
+    }
+
+    /// Evaluates the current board state with the given heuristic weights.
+    pub fn evaluate_with(&self, weights: &crate::eval::Weights) -> f32 {
+        crate::eval::eval_with(&self.0, weights)
+    }
+
+    /// Exponent of the largest tile of the board (0 if empty).
+    pub fn max_tile(&self) -> u8 {
+        self.0.max_tile()
     }
 
     /// Checks if the board contains at least a tile with the given exponent (i).
     pub fn has_at_least_tile(&self, i: u8) -> bool {
-        self.0.cells.iter().flatten().any(|tile| *tile >= i)
+        self.0.exponents().any(|tile| tile >= i)
     }
 
-    /// Draws the board onto the Macroquad window.
-    pub fn draw(&self, num_moves: u32, decision_time_ms: f64) {
+    /// Draws the board onto the Macroquad window, with the score of the game when it is known.
+    #[cfg(feature = "gui")]
+    pub fn draw(&self, num_moves: u32, score: Option<u32>, decision_time_ms: f64) {
         clear_background(Color::new(0.98, 0.97, 0.94, 1.0)); // Window background (#faf8ef)
 
         // Draw the main grid background
@@ -56,92 +145,105 @@ impl PlayableBoard {
 
         // Draw statistics (Text)
         draw_text(
-            &format!("Moves: {}", num_moves),
+            format!("Moves: {}", num_moves),
             PADDING,
             30.0,
             FONT_SIZE / 2.0,
             BLACK,
         );
+        if let Some(score) = score {
+            draw_text(format!("Score: {}", score), PADDING + 380.0, 30.0, FONT_SIZE / 2.0, BLACK);
+        }
         draw_text(
-            &format!("Dec. Time: {:.2}ms", decision_time_ms),
+            format!("Dec. Time: {:.2}ms", decision_time_ms),
             PADDING,
             55.0,
             FONT_SIZE / 2.0,
             BLACK,
         );
 
-        // Draw cells and tiles
+        // Draw cells and tiles: all the shapes in one batch, then the values (see `batch.rs`)
+        let layout = crate::layout::layout();
+        let tile_size = layout.tile_size();
+        let mut shapes = ShapeBatch::default();
         for i in 0..N {
             for j in 0..N {
-                let cell_value = self.0.cells[i][j];
-                let (x, y) = self.get_tile_position(j, i);
+                let cell_value = self.0.get(i, j);
+                let (x, y) = tile_position(j, i);
 
                 // Draw the empty cell background
-                draw_rectangle(
-                    x,
-                    y,
-                    TILE_SIZE,
-                    TILE_SIZE,
-                    Color::new(0.8, 0.75, 0.69, 1.0), // #cdc1b4
-                );
+                shapes.rounded_rect(x, y, tile_size, tile_size, layout.corner_radius, CELL_COLOR);
 
                 if cell_value != 0 {
-                    let value = 2u32.pow(cell_value as u32);
-                    let (bg_color, text_color) = self.get_tile_colors(value);
-
-                    // 1. Draw the tile background
-                    draw_rectangle(x, y, TILE_SIZE, TILE_SIZE, bg_color);
-
-                    // 2. Draw the tile value text
-                    let text = value.to_string();
-                    let font_size = if value > 1024 { FONT_SIZE * 0.7 } else { FONT_SIZE };
-
-                    let text_dim = measure_text(&text, None, font_size as u16, 1.0);
-
-                    // Center the text
-                    let text_x = x + (TILE_SIZE - text_dim.width) / 2.0;
-                    let text_y = y + (TILE_SIZE + text_dim.height) / 2.0;
+                    // Draw the tile background
+                    let (bg_color, _) = tile_colors(2u32.pow(cell_value as u32));
+                    shapes.rounded_rect(x, y, tile_size, tile_size, layout.corner_radius, bg_color);
+                }
+            }
+        }
+        shapes.draw();
 
-                    draw_text(
-                        &text,
-                        text_x,
-                        text_y,
-                        font_size,
-                        text_color,
-                    );
+        // Draw the tile value texts
+        for i in 0..N {
+            for j in 0..N {
+                let cell_value = self.0.get(i, j);
+                if cell_value != 0 {
+                    let value = 2u32.pow(cell_value as u32);
+                    let (_, text_color) = tile_colors(value);
+                    let (x, y) = tile_position(j, i);
+                    let font_size = if value > 1024 { FONT_SIZE * 0.7 } else { FONT_SIZE } * layout.font_scale;
+                    crate::fonts::draw_tile_value(value, x, y, tile_size, font_size, text_color);
                 }
             }
         }
     }
 
-    /// Helper function to calculate the screen position of a tile
-    fn get_tile_position(&self, col: usize, row: usize) -> (f32, f32) {
-        let x = PADDING + (col as f32 + 1.0) * PADDING + col as f32 * TILE_SIZE;
-        let y = PADDING + UI_HEIGHT + (row as f32 + 1.0) * PADDING + row as f32 * TILE_SIZE;
-        (x, y)
-    }
-
-    /// Helper function to get tile colors based on its value (exponent)
-    fn get_tile_colors(&self, value: u32) -> (Color, Color) {
-        let text_color = BLACK;
-        let bg_color = match value {
-            2 => Color::new(0.93, 0.90, 0.85, 1.0),   // #eee4da
-            4 => Color::new(0.92, 0.88, 0.78, 1.0),   // #ede0c8
-            8 => Color::new(0.95, 0.69, 0.47, 1.0),   // #f2b179
-            16 => Color::new(0.96, 0.58, 0.39, 1.0),  // #f59563
-            32 => Color::new(0.96, 0.49, 0.36, 1.0),  // #f67c5f
-            64 => Color::new(0.96, 0.37, 0.23, 1.0),  // #f65e3b
-            128 => Color::new(0.92, 0.81, 0.45, 1.0), // #edcf72
-            256 => Color::new(0.92, 0.80, 0.38, 1.0), // #edcc61
-            512 => Color::new(0.92, 0.78, 0.31, 1.0), // #edc850
-            1024 => Color::new(0.92, 0.76, 0.25, 1.0),// #edc53f
-            2048 => Color::new(0.92, 0.75, 0.18, 1.0),// #edc22e
-            _ => Color::new(0.92, 0.75, 0.18, 1.0),   // 4096+
-        };
-        (bg_color, text_color)
+    /// Developer overlay: outline and `row,col` index of every cell, raw exponent of every
+    /// tile and hash of the position, to refer to cells and positions unambiguously.
+    #[cfg(feature = "gui")]
+    pub fn draw_debug_overlay(&self) {
+        let color = Color::new(0.8, 0.0, 0.6, 1.0);
+        let tile_size = crate::layout::layout().tile_size();
+        for i in 0..N {
+            for j in 0..N {
+                let (x, y) = tile_position(j, i);
+                draw_rectangle_lines(x, y, tile_size, tile_size, 2.0, color);
+                draw_text(format!("{i},{j}"), x + 4.0, y + 14.0, 16.0, color);
+                let exponent = self.0.get(i, j);
+                if exponent != 0 {
+                    draw_text(format!("e={exponent}"), x + 4.0, y + tile_size - 6.0, 16.0, color);
+                }
+            }
+        }
+        draw_text(format!("hash {:016x}", self.0.hash64()), WINDOW_WIDTH - 230.0, 55.0, 20.0, color);
     }
 }
 
+/// Screen position of the top left corner of a tile, in the layout of the process
+pub fn tile_position(col: usize, row: usize) -> (f32, f32) {
+    crate::layout::layout().tile_position(col, row)
+}
+
+/// Background and text colors of a tile, based on its value
+pub fn tile_colors(value: u32) -> (Color, Color) {
+    let text_color = BLACK;
+    let bg_color = match value {
+        2 => Color::new(0.93, 0.90, 0.85, 1.0),   // #eee4da
+        4 => Color::new(0.92, 0.88, 0.78, 1.0),   // #ede0c8
+        8 => Color::new(0.95, 0.69, 0.47, 1.0),   // #f2b179
+        16 => Color::new(0.96, 0.58, 0.39, 1.0),  // #f59563
+        32 => Color::new(0.96, 0.49, 0.36, 1.0),  // #f67c5f
+        64 => Color::new(0.96, 0.37, 0.23, 1.0),  // #f65e3b
+        128 => Color::new(0.92, 0.81, 0.45, 1.0), // #edcf72
+        256 => Color::new(0.92, 0.80, 0.38, 1.0), // #edcc61
+        512 => Color::new(0.92, 0.78, 0.31, 1.0), // #edc850
+        1024 => Color::new(0.92, 0.76, 0.25, 1.0),// #edc53f
+        2048 => Color::new(0.92, 0.75, 0.18, 1.0),// #edc22e
+        _ => Color::new(0.92, 0.75, 0.18, 1.0),   // 4096+
+    };
+    (bg_color, text_color)
+}
+
 // Implement Display for PlayableBoard (needed for bench.rs console output)
 impl Display for PlayableBoard {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -154,11 +256,43 @@ impl Display for PlayableBoard {
 pub struct RandableBoard(Board);
 
 impl RandableBoard {
+    /// Wraps an arbitrary board on which a tile spawns next, e.g. one read back from a file.
+    pub fn from_board(board: Board) -> RandableBoard {
+        RandableBoard(board)
+    }
+
     /// Adds a random tile (2 or 4) to the board, returning the next PlayableBoard state.
     pub fn with_random_tile(&self) -> PlayableBoard {
+        self.with_random_spawn().0
+    }
+
+    /// Same as `with_random_tile` but also reports where and which tile was placed.
+    pub fn with_random_spawn(&self) -> (PlayableBoard, Spawn) {
+        self.with_spawn_from(&mut crate::spawn::RandomSpawns)
+    }
+
+    /// Places the next tile of `spawns`, returning the next PlayableBoard state and the tile.
+    pub fn with_spawn_from(&self, spawns: &mut impl crate::spawn::SpawnStream) -> (PlayableBoard, Spawn) {
+        let spawn = spawns.next_spawn(&self.0);
+        let board = self.with_spawn(spawn).expect("spawn streams place tiles on empty cells");
+        (board, spawn)
+    }
+
+    /// Places the given tile, returning None if its cell is not empty.
+    pub fn with_spawn(&self, spawn: Spawn) -> Option<PlayableBoard> {
         let mut board = self.0;
-        board.add_random();
-        PlayableBoard(board)
+        if board.get(spawn.row, spawn.col) != 0 {
+            return None;
+        }
+        board.set(spawn.row, spawn.col, spawn.exponent);
+        #[cfg(feature = "verify")]
+        crate::verify::check_spawn(&self.0, spawn, &board);
+        Some(PlayableBoard::from_board(board))
+    }
+
+    /// Returns the underlying board.
+    pub fn board(&self) -> Board {
+        self.0
     }
 
     /// Returns the list of possible successors after placing a random tile, along with their probabilities.
@@ -166,13 +300,18 @@ impl RandableBoard {
     pub fn successors(&self) -> impl Iterator<Item = (f32, PlayableBoard)> + '_ {
         self.0
             .random_successors()
-            .map(|(proba, board)| (proba, PlayableBoard(board)))
+            .map(|(proba, board)| (proba, PlayableBoard::from_board(board)))
     }
 
     /// Evaluates the current board state using the heuristic function from `eval.rs`.
     pub fn evaluate(&self) -> f32 {
         crate::eval::eval(&self.0)
     }
+
+    /// Evaluates the current board state with the given heuristic weights.
+    pub fn evaluate_with(&self, weights: &crate::eval::Weights) -> f32 {
+        crate::eval::eval_with(&self.0, weights)
+    }
 }
 
 // Implement Display for RandableBoard (needed for bench.rs console output)
@@ -185,91 +324,294 @@ impl Display for RandableBoard {
 /// Size of board
 pub const N: usize = 4;
 
-// A board is an NxN matrix where each entry represents a tile.
+/// Largest representable tile exponent (`2^15 = 32768`), so that a tile fits in 4 bits.
+/// Two tiles at this exponent do not merge: they behave as distinct tiles.
+pub const MAX_EXPONENT: u8 = 15;
+
+/// Number of values of the one-hot encoding of a board (`Board::encode_onehot`)
+pub const ONEHOT_LEN: usize = (MAX_EXPONENT as usize + 1) * N * N;
+
+// A board is an NxN matrix where each entry represents a tile, packed in a u64 on 4 bits per
+// cell, row by row with the first cell in the highest bits (hence `hash64`).
 //
-// A tile is encoded by an 8-bits unsigned int where:
+// A tile is encoded by its exponent where:
 //
 //  - 0 represents the empty tile
 //  - n > 0 represents the tile `2^n`
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Board {
-    pub cells: [[u8; N]; N],
+//
+// The actions move the rows with the precomputed tables of `row_moves`, the columns being the
+// rows of the transposed board.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Board(u64);
+
+// Lowest bit of the cell (row, col) in the packed board
+const fn cell_shift(row: usize, col: usize) -> u32 {
+    4 * (N * N - 1 - (row * N + col)) as u32
 }
 
+// Lowest bit of the row `row` in the packed board
+const fn row_shift(row: usize) -> u32 {
+    cell_shift(row, N - 1)
+}
+
+// The lowest bit of each cell
+const LOW_BITS: u64 = 0x1111_1111_1111_1111;
+
 impl Board {
     /// The completely empty board. Not the initial board.
-    const EMPTY: Board = Board { cells: [[0; N]; N] };
+    const EMPTY: Board = Board(0);
+
+    /// The board of the given exponents, `cells[row][col]` (0 for the empty cells).
+    pub fn from_cells(cells: [[u8; N]; N]) -> Board {
+        let mut board = Board::EMPTY;
+        for (i, row) in cells.iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate() {
+                board.set(i, j, cell);
+            }
+        }
+        board
+    }
+
+    /// The exponents of the cells, `cells()[row][col]` (0 for the empty cells).
+    pub fn cells(&self) -> [[u8; N]; N] {
+        std::array::from_fn(|i| std::array::from_fn(|j| self.get(i, j)))
+    }
+
+    /// The exponents of the cells in reading order (row by row), 0 for the empty cells.
+    pub fn exponents(&self) -> impl Iterator<Item = u8> + Clone {
+        let packed = self.0;
+        (0..N * N).map(move |k| (packed >> (4 * (N * N - 1 - k)) & 0xf) as u8)
+    }
+
+    /// Exponent of the tile of the cell (0 if empty).
+    pub fn get(&self, row: usize, col: usize) -> u8 {
+        (self.0 >> cell_shift(row, col) & 0xf) as u8
+    }
+
+    /// Places the tile of the given exponent on the cell (0 empties it).
+    pub fn set(&mut self, row: usize, col: usize, exponent: u8) {
+        debug_assert!(exponent <= MAX_EXPONENT, "invalid exponent {exponent}");
+        let shift = cell_shift(row, col);
+        self.0 = self.0 & !(0xf << shift) | u64::from(exponent & 0xf) << shift;
+    }
 
     /// Returns the board resulting from the action, or None if the action is not applicable (no tiles moved).
     pub fn apply(&self, action: Action) -> Option<Board> {
-        let mut next = self.clone();
-        // We only implement push_left, so we use symmetries (transpose/swap_lr)
-        // to map all actions to push_left and then revert the symmetries.
-        match action {
-            Action::Left => {
-                next.push_left();
-            }
+        self.apply_scored(action).map(|(board, _score)| board)
+    }
+
+    /// Same as `apply` but also returns the score gained by the action:
+    /// the sum of the values of the tiles created by merges, as in the original game.
+    pub fn apply_scored(&self, action: Action) -> Option<(Board, u32)> {
+        // The tables move the rows to the left or to the right: the columns are moved as the
+        // rows of the transposed board.
+        let moves = row_moves();
+        let (next, score) = match action {
+            Action::Left => self.move_rows(&moves.left),
+            Action::Right => self.move_rows(&moves.right),
             Action::Up => {
-                next.transpose();
-                next.push_left();
-                next.transpose();
+                let (next, score) = self.transposed().move_rows(&moves.left);
+                (next.transposed(), score)
             }
             Action::Down => {
-                next.transpose();
-                next.swap_lr();
-                next.push_left();
-                next.swap_lr();
-                next.transpose();
-            }
-            Action::Right => {
-                next.swap_lr();
-                next.push_left();
-                next.swap_lr();
+                let (next, score) = self.transposed().move_rows(&moves.right);
+                (next.transposed(), score)
             }
-        }
-        if *self != next {
+        };
+        let result = if *self != next {
             // The board has changed, the action is applicable
-            Some(next)
+            Some((next, score))
         } else {
             // Nothing changed, the action is not applicable
             None
-        }
+        };
+        #[cfg(feature = "verify")]
+        crate::verify::check_apply(self, action, result);
+        result
+    }
+
+    /// Whether the action moves at least one tile, i.e. `apply` would return a board, without
+    /// computing the resulting board: the table of the action changes one of the lines.
+    pub fn can_apply(&self, action: Action) -> bool {
+        let moves = row_moves();
+        let (board, table) = match action {
+            Action::Left => (*self, &moves.left),
+            Action::Right => (*self, &moves.right),
+            Action::Up => (self.transposed(), &moves.left),
+            Action::Down => (self.transposed(), &moves.right),
+        };
+        (0..N).any(|i| {
+            let row = (board.0 >> row_shift(i)) as u16;
+            table[row as usize].row != row
+        })
     }
 
-    /// Places a random tile (2 or 4) on an empty cell of the board
-    pub fn add_random(&mut self) {
+    /// Places a random tile (2 or 4) on an empty cell of the board and returns it
+    pub fn add_random(&mut self) -> Spawn {
+        // Use absolute path ::rand::rng() to resolve Macroquad ambiguity
+        self.add_random_with(&mut ::rand::rng())
+    }
+
+    /// Same as `add_random`, drawing from the given random number generator.
+    pub fn add_random_with(&mut self, rng: &mut impl ::rand::Rng) -> Spawn {
+        #[cfg(feature = "verify")]
+        let before = *self;
         // compute the number of empty cells
         let n = self.num_empty();
 
         // decide which empty cell to update in [0,n)
-        // Use absolute path ::rand::rng() to resolve Macroquad ambiguity
-        let picked = ::rand::rng().random_range(0..n);
-
-        // get a mutable reference of the cell
-        let picked = self
-            .cells
-            .iter_mut()
-            .map(|row| row.iter_mut())
-            .flatten()
-            .filter(|cell| **cell == 0)
+        let picked = rng.random_range(0..n);
+
+        // get the position of the cell
+        let (row, col) = (0..N)
+            .flat_map(|i| (0..N).map(move |j| (i, j)))
+            .filter(|&(i, j)| self.get(i, j) == 0)
             .nth(picked)
             .unwrap();
 
-        // decide which value to put in the cell (2^1 = 2 with probability 0.9, 2^2 = 4 with probability 0.1)
-        // Use absolute path ::rand::rng() to resolve Macroquad ambiguity
-        let value = if ::rand::rng().random_bool(0.9) { 1 } else { 2 };
+        // decide which value to put in the cell (2^1 = 2 with probability 0.9, 2^2 = 4 with
+        // probability 0.1 in the original rules, 2^3 = 8 only in the levels of a ramp)
+        let rules = crate::rules::rules();
+        let (four, eight) = rules.four_eight_probabilities(self);
+        let value = if rng.random_bool(rules.two_probability(self)) {
+            1
+        } else if eight == 0.0 || rng.random_bool(four / (four + eight)) {
+            2
+        } else {
+            3
+        };
 
         // update the board by setting the value to the selected empty cell
-        *picked = value;
+        self.set(row, col, value);
+        let spawn = Spawn { row, col, exponent: value };
+        #[cfg(feature = "verify")]
+        crate::verify::check_random_spawn(&before, spawn, self);
+        spawn
+    }
+
+    /// Compact textual representation of the board: one hexadecimal digit (the exponent) per cell,
+    /// rows separated by `/`, e.g. `0000/0120/0003/0001`.
+    pub fn compact(&self) -> String {
+        let rows: Vec<String> = self
+            .cells()
+            .iter()
+            .map(|row| row.iter().map(|&cell| format!("{cell:x}")).collect())
+            .collect();
+        rows.join("/")
+    }
+
+    /// Parses the compact representation produced by `compact` (separators and whitespace are optional).
+    pub fn from_compact(s: &str) -> anyhow::Result<Board> {
+        let digits: Vec<char> = s.chars().filter(|c| !c.is_whitespace() && *c != '/').collect();
+        anyhow::ensure!(
+            digits.len() == N * N,
+            "expected {} cells but got {}",
+            N * N,
+            digits.len()
+        );
+        let mut board = Board::EMPTY;
+        for (k, c) in digits.into_iter().enumerate() {
+            let exponent = c
+                .to_digit(16)
+                .ok_or_else(|| anyhow::anyhow!("invalid cell `{c}` (expected an hexadecimal digit)"))?;
+            board.set(k / N, k % N, exponent as u8);
+        }
+        anyhow::ensure!(board.num_empty() < N * N, "the board has no tile");
+        Ok(board)
+    }
+
+    /// Writes the exponents of the cells (0 for empty) in row-major order:
+    /// `out[row * N + col]`. `out` must have `N * N` elements.
+    pub fn encode_exponents(&self, out: &mut [f32]) {
+        assert_eq!(out.len(), N * N, "the exponent encoding has {} values", N * N);
+        for (value, cell) in out.iter_mut().zip(self.exponents()) {
+            *value = f32::from(cell);
+        }
+    }
+
+    /// Writes the one-hot encoding of the cells, one plane of `N * N` values (row-major) per
+    /// exponent from 0 (empty) to `MAX_EXPONENT`: `out[exponent * N * N + row * N + col]` is 1.0
+    /// if the cell holds this exponent and 0.0 otherwise. `out` must have `ONEHOT_LEN` elements.
+    pub fn encode_onehot(&self, out: &mut [f32]) {
+        assert_eq!(out.len(), ONEHOT_LEN, "the one-hot encoding has {ONEHOT_LEN} values");
+        out.fill(0.0);
+        for (k, cell) in self.exponents().enumerate() {
+            out[cell as usize * N * N + k] = 1.0;
+        }
+    }
+
+    /// Hash of the position, identical for equal boards: the exponents packed on 4 bits each,
+    /// row by row with the first cell in the highest bits. Written in hexadecimal, it is the
+    /// compact notation without the separators.
+    pub fn hash64(&self) -> u64 {
+        self.0
+    }
+
+    /// The board of the given `hash64`.
+    pub fn from_hash64(hash: u64) -> Board {
+        Board(hash)
+    }
+
+    /// Exponent of the largest tile of the board (0 if empty).
+    pub fn max_tile(&self) -> u8 {
+        self.exponents().max().unwrap_or(0)
+    }
+
+    /// Sum of the values of all the tiles of the board
+    pub fn tile_sum(&self) -> u32 {
+        self.exponents()
+            .filter(|&cell| cell != 0)
+            .map(|cell| 2u32.pow(cell as u32))
+            .sum()
+    }
+
+    /// Exponent of the largest tile the tiles of the board could be merged into without any new
+    /// tile: the largest power of two in the sum of their values, leaving out the trapped tiles
+    /// (all of whose neighbours are larger tiles), which cannot join the others until the
+    /// board is rebuilt around them.
+    pub fn potential(&self) -> u8 {
+        let cells = self.cells();
+        let trapped = |i: usize, j: usize| {
+            let neighbours = [(i.wrapping_sub(1), j), (i + 1, j), (i, j.wrapping_sub(1)), (i, j + 1)];
+            neighbours
+                .into_iter()
+                .filter(|&(k, l)| k < N && l < N)
+                .all(|(k, l)| cells[k][l] > cells[i][j])
+        };
+        let sum: u64 = (0..N)
+            .flat_map(|i| (0..N).map(move |j| (i, j)))
+            .filter(|&(i, j)| cells[i][j] != 0 && !trapped(i, j))
+            .map(|(i, j)| 1u64 << cells[i][j])
+            .sum();
+        if sum == 0 {
+            0
+        } else {
+            sum.ilog2() as u8
+        }
+    }
+
+    /// Rows and columns (in this order) that no applicable action can change: full lines
+    /// without any merge, held in place by the rest of the board. Lines with an empty cell are
+    /// never locked, and every line of a board without applicable action is.
+    pub fn locked_lines(&self) -> ([bool; N], [bool; N]) {
+        let mut rows: [bool; N] = std::array::from_fn(|i| (0..N).all(|k| self.get(i, k) != 0));
+        let mut cols: [bool; N] = std::array::from_fn(|i| (0..N).all(|k| self.get(k, i) != 0));
+        for next in ALL_ACTIONS.iter().filter_map(|&action| self.apply(action)) {
+            for i in 0..N {
+                rows[i] &= (0..N).all(|k| next.get(i, k) == self.get(i, k));
+                cols[i] &= (0..N).all(|k| next.get(k, i) == self.get(k, i));
+            }
+        }
+        (rows, cols)
     }
 
     /// Counts the number of empty tiles on the board
     pub fn num_empty(&self) -> usize {
-        self.cells
-            .iter()
-            .flatten()
-            .filter(|&&cell| cell == 0)
-            .count()
+        // fold the bits of each cell into its lowest bit, set for the non-empty cells
+        let mut folded = self.0;
+        folded |= (folded >> 2) & 0x3333_3333_3333_3333;
+        folded |= folded >> 1;
+        (!folded & LOW_BITS).count_ones() as usize
     }
 
     /// Given a board for which an action has already been applied, returns the list of possible successors as a result of placing a random tile (2 or 4) on an empty cell.
@@ -283,20 +625,26 @@ impl Board {
     /// // }
     /// ```
     pub fn random_successors(&self) -> impl Iterator<Item = (f32, Board)> + '_ {
+        #[cfg(feature = "verify")]
+        crate::verify::check_successors(self, self.spawn_successors());
+        self.spawn_successors()
+    }
+
+    // The successors of `random_successors`, without the verification
+    fn spawn_successors(&self) -> impl Iterator<Item = (f32, Board)> + '_ {
         let n = self.num_empty() as f32;
 
-        let empty_cells = self.cells.iter().enumerate().flat_map(|(i, row)| {
-            row.iter()
-                .enumerate()
-                .filter_map(move |(j, &cell)| if cell == 0 { Some((i, j)) } else { None })
-        });
+        let empty_cells = (0..N)
+            .flat_map(|i| (0..N).map(move |j| (i, j)))
+            .filter(|&(i, j)| self.get(i, j) == 0);
 
+        let spawns = crate::rules::rules().spawn_probabilities(self); // (value_exponent, probability)
         empty_cells.flat_map(move |(i, j)| {
-            [(1, 0.9), (2, 0.1)] // (value_exponent, probability)
+            spawns
                 .into_iter()
+                .filter(|&(_, proba)| proba > 0.0)
                 .map(move |(new_value, proba)| {
-                    let mut next = self.clone();
-                    next.cells[i][j] = new_value;
+                    let next = Board(self.0 | u64::from(new_value) << cell_shift(i, j));
                     // Probability is split evenly among all empty spots
                     (proba / n, next)
                 })
@@ -305,49 +653,121 @@ impl Board {
 
     /// Switches the matrix left/right
     fn swap_lr(&mut self) {
-        for row in &mut self.cells {
-            let mut i = 0;
-            let mut j = N - 1;
-            while i < j {
-                row.swap(i, j);
-                i += 1;
-                j -= 1;
-            }
-        }
+        // reverses the order of the 4 cells of each row
+        let x = self.0;
+        self.0 = (x & 0xf000_f000_f000_f000) >> 12
+            | (x & 0x0f00_0f00_0f00_0f00) >> 4
+            | (x & 0x00f0_00f0_00f0_00f0) << 4
+            | (x & 0x000f_000f_000f_000f) << 12;
+    }
+
+    /// Switches the matrix up/down
+    fn swap_ud(&mut self) {
+        // reverses the order of the 4 rows
+        let x = self.0.rotate_left(32);
+        self.0 = (x & 0xffff_0000_ffff_0000) >> 16 | (x & 0x0000_ffff_0000_ffff) << 16;
     }
 
     /// Transposes the matrix, inverting lines and columns
     fn transpose(&mut self) {
-        for i in 0..N {
-            for j in 0..i {
-                let tmp = self.cells[i][j];
-                self.cells[i][j] = self.cells[j][i];
-                self.cells[j][i] = tmp;
-            }
-        }
+        // swaps the cells across the diagonal within the 2x2 blocks, then the 2x2 blocks
+        let x = self.0;
+        let a = x & 0xf0f0_0f0f_f0f0_0f0f | (x & 0x0000_f0f0_0000_f0f0) << 12 | (x & 0x0f0f_0000_0f0f_0000) >> 12;
+        self.0 = a & 0xff00_ff00_00ff_00ff | (a & 0x00ff_00ff_0000_0000) >> 24 | (a & 0x0000_0000_ff00_ff00) << 24;
     }
 
     /// Builds an equivalent board where the lines and columns have been transposed
     pub fn transposed(&self) -> Board {
-        let mut transposed = self.clone();
+        let mut transposed = *self;
         transposed.transpose();
         transposed
     }
 
-    /// Applies the action of playing *Left* on all rows
-    fn push_left(&mut self) {
-        // apply the push left method on each line
-        for row in &mut self.cells {
-            push_left(row);
+    /// The image of the board by the symmetry `k` of the square (`0..NUM_SYMMETRIES`, 0 being
+    /// the identity): transposed if `k & 4`, then switched left/right if `k & 1` and up/down
+    /// if `k & 2`.
+    pub fn symmetric(&self, k: usize) -> Board {
+        let mut board = *self;
+        if k & 4 != 0 {
+            board.transpose();
+        }
+        if k & 1 != 0 {
+            board.swap_lr();
+        }
+        if k & 2 != 0 {
+            board.swap_ud();
+        }
+        board
+    }
+
+    /// Hash identical for the boards equal up to a symmetry: the smallest `hash64` of the images
+    pub fn canonical_hash64(&self) -> u64 {
+        (0..NUM_SYMMETRIES).map(|k| self.symmetric(k).hash64()).min().unwrap()
+    }
+
+    /// Moves every row with the `table` of the moves of the rows, returning the score of the merges
+    fn move_rows(&self, table: &[RowMove]) -> (Board, u32) {
+        let mut next = 0;
+        let mut score = 0;
+        for i in 0..N {
+            let moved = table[(self.0 >> row_shift(i)) as u16 as usize];
+            next |= u64::from(moved.row) << row_shift(i);
+            score += moved.score;
         }
+        (Board(next), score)
+    }
+}
+
+impl Debug for Board {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Board").field("cells", &self.cells()).finish()
     }
 }
 
+// A row after a move, with the score of its merges
+#[derive(Clone, Copy)]
+struct RowMove {
+    row: u16,
+    score: u32,
+}
+
+// The moves of all the rows to the left and to the right, indexed by the row packed on 16 bits
+// as in the boards
+struct RowMoves {
+    left: Box<[RowMove]>,
+    right: Box<[RowMove]>,
+}
+
+// The tables of the moves, computed at the first move with `push_left`
+fn row_moves() -> &'static RowMoves {
+    static ROW_MOVES: OnceLock<RowMoves> = OnceLock::new();
+    ROW_MOVES.get_or_init(|| {
+        let unpack = |bits: u16| -> [u8; N] { std::array::from_fn(|k| (bits >> (4 * (N - 1 - k)) & 0xf) as u8) };
+        let pack = |row: [u8; N]| row.iter().fold(0u16, |bits, &cell| bits << 4 | u16::from(cell));
+        let table = |reversed: bool| -> Box<[RowMove]> {
+            (0..=u16::MAX)
+                .map(|bits| {
+                    let mut row = unpack(bits);
+                    if reversed {
+                        row.reverse();
+                    }
+                    let score = push_left(&mut row);
+                    if reversed {
+                        row.reverse();
+                    }
+                    RowMove { row: pack(row), score }
+                })
+                .collect()
+        };
+        RowMoves { left: table(false), right: table(true) }
+    })
+}
+
 // Implement Display for Board
 impl Display for Board {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "{}", format!("╔═{}╗", "═".repeat(8 * N)).bold())?;
-        for row in &self.cells {
+        for row in &self.cells() {
             write!(f, "{}", "║ ".bold())?;
             for &cell in row {
                 if cell != 0 {
@@ -369,7 +789,7 @@ impl Display for Board {
                     };
                     write!(f, "{} ", colored)?;
                 } else {
-                    let formatted = format!("   .   ");
+                    let formatted = "   .   ".to_string();
                     let colored = formatted.black().on_truecolor(205, 193, 180); // #cdc1b4
                     write!(f, "{} ", colored)?;
                 }
@@ -381,8 +801,41 @@ impl Display for Board {
     }
 }
 
-/// The set of possible actions to apply on the board.
+/// A tile placed on the board by the chance player.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spawn {
+    pub row: usize,
+    pub col: usize,
+    /// Exponent of the placed tile (1 for a 2, 2 for a 4)
+    pub exponent: u8,
+}
+
+/// A move played on a board, with what happened to its tiles.
+#[derive(Clone, PartialEq, Eq)]
+pub struct MoveOutcome {
+    pub board: RandableBoard,
+    /// Points of the merges
+    pub score: u32,
+    /// Every tile of the board before the move, line by line in the direction of the move
+    pub tiles: ArrayVec<TileMove, { N * N }>,
+}
+
+/// Where a tile went during a move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileMove {
+    /// `(row, col)` of the tile before the move
+    pub from: (usize, usize),
+    /// `(row, col)` of the tile after the move (`from` when it did not move)
+    pub to: (usize, usize),
+    /// Exponent of the tile before the move
+    pub exponent: u8,
+    /// Whether the tile merged: the two tiles of a merge have the same `to`, where the merged
+    /// tile of exponent `exponent + 1` stands after the move
+    pub merged: bool,
+}
+
+/// The set of possible actions to apply on the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Action {
     Up,
     Down,
@@ -393,10 +846,54 @@ pub enum Action {
 /// An iterable list of all possible actions.
 pub const ALL_ACTIONS: [Action; 4] = [Action::Up, Action::Down, Action::Left, Action::Right];
 
-/// Applies the core logic of pushing tiles "left" on a single Row
-fn push_left(row: &mut [u8; N]) {
+/// Number of symmetries of the square board, see `Board::symmetric`
+pub const NUM_SYMMETRIES: usize = 8;
+
+/// The action on `board.symmetric(k)` equivalent to `action` on `board`
+pub fn symmetric_action(action: Action, k: usize) -> Action {
+    let mut action = action;
+    if k & 4 != 0 {
+        action = match action {
+            Action::Up => Action::Left,
+            Action::Left => Action::Up,
+            Action::Down => Action::Right,
+            Action::Right => Action::Down,
+        };
+    }
+    if k & 1 != 0 {
+        action = match action {
+            Action::Left => Action::Right,
+            Action::Right => Action::Left,
+            other => other,
+        };
+    }
+    if k & 2 != 0 {
+        action = match action {
+            Action::Up => Action::Down,
+            Action::Down => Action::Up,
+            other => other,
+        };
+    }
+    action
+}
+
+/// Cells of the `k`-th line moved by `action`, in the direction of the move: first the cell
+/// against which the tiles are pushed.
+pub fn line_cells(action: Action, k: usize) -> [(usize, usize); N] {
+    std::array::from_fn(|t| match action {
+        Action::Left => (k, t),
+        Action::Right => (k, N - 1 - t),
+        Action::Up => (t, k),
+        Action::Down => (N - 1 - t, k),
+    })
+}
+
+/// Applies the core logic of pushing tiles "left" on a single Row.
+/// Returns the score of the merges (sum of the values of the merged tiles).
+fn push_left(row: &mut [u8; N]) -> u32 {
     let mut write_index = 0; // Position to write next non-zero tile
     let mut read_index = 0; // Reading index
+    let mut score = 0;
 
     // Move non-zero tiles forward and merge adjacent ones
     while read_index < N {
@@ -413,8 +910,10 @@ fn push_left(row: &mut [u8; N]) {
             while read_index < N && row[read_index] == 0 {
                 read_index += 1; // Skip empty cell
             }
-            if read_index < N && row[read_index] == value {
+            // Tiles at MAX_EXPONENT cannot be merged any further
+            if read_index < N && row[read_index] == value && value < MAX_EXPONENT {
                 row[write_index] = value + 1;
+                score += 2u32.pow(value as u32 + 1);
                 read_index += 1; // Skip merged cell
             } else {
                 row[write_index] = value;
@@ -428,11 +927,14 @@ fn push_left(row: &mut [u8; N]) {
 
     // Fill the remaining cells with zero (empty)
     row[write_index..].fill(0);
+    score
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    // the random boards of the tests are drawn with the methods of this trait
+    use ::rand::Rng as _;
 
     #[test]
     fn test_push_left() {
@@ -450,17 +952,282 @@ mod tests {
         check([0, 0, 1, 1], [2, 0, 0, 0]);
         check([0, 1, 0, 1], [2, 0, 0, 0]);
         check([1, 2, 0, 1], [1, 2, 1, 0]);
+        // tiles at the maximum exponent never merge
+        check([15, 15, 0, 0], [15, 15, 0, 0]);
+        check([0, 15, 0, 15], [15, 15, 0, 0]);
+        check([14, 14, 15, 0], [15, 15, 0, 0]);
+    }
+
+    /// Straightforward reference implementation of `push_left`:
+    /// compact the tiles, merge equal neighbours from the left (below MAX_EXPONENT), compact again.
+    fn reference_push_left(row: [u8; N]) -> ([u8; N], u32) {
+        let tiles: Vec<u8> = row.iter().copied().filter(|&t| t != 0).collect();
+        let mut merged = Vec::new();
+        let mut score = 0;
+        let mut k = 0;
+        while k < tiles.len() {
+            if k + 1 < tiles.len() && tiles[k] == tiles[k + 1] && tiles[k] < MAX_EXPONENT {
+                merged.push(tiles[k] + 1);
+                score += 1 << (tiles[k] + 1);
+                k += 2;
+            } else {
+                merged.push(tiles[k]);
+                k += 1;
+            }
+        }
+        let mut result = [0; N];
+        result[..merged.len()].copy_from_slice(&merged);
+        (result, score)
+    }
+
+    #[test]
+    fn test_push_left_exhaustive() {
+        // all 65536 rows with exponents in 0..16 (one nibble per cell)
+        for bits in 0..=u16::MAX {
+            let row: [u8; N] = std::array::from_fn(|k| ((bits >> (4 * k)) & 0xF) as u8);
+            let mut pushed = row;
+            let score = push_left(&mut pushed);
+            assert_eq!((pushed, score), reference_push_left(row), "push_left({row:?})");
+        }
+    }
+
+    #[test]
+    fn test_apply_outcome() {
+        let board = PlayableBoard::from_board(Board::from_compact("0000/0000/0000/1101").unwrap());
+        let outcome = board.apply_outcome(Action::Left).unwrap();
+        assert_eq!((outcome.board.board().compact(), outcome.score), ("0000/0000/0000/2100".to_string(), 4));
+        let tiles: Vec<_> = outcome.tiles.iter().map(|tile| (tile.from, tile.to, tile.merged)).collect();
+        assert_eq!(tiles, [((3, 0), (3, 0), true), ((3, 1), (3, 0), true), ((3, 3), (3, 1), false)]);
+        assert!(board.apply_outcome(Action::Down).is_none());
+
+        // the tiles moved where they say give the board after the move
+        let mut rng = <::rand::rngs::StdRng as ::rand::SeedableRng>::seed_from_u64(755);
+        for _ in 0..200 {
+            let board = PlayableBoard::from_board(Board::random_with(rng.random_range(0..=N * N), 4, &mut rng));
+            for action in board.legal_actions() {
+                let outcome = board.apply_outcome(action).unwrap();
+                let mut moved = Board::from_cells([[0; N]; N]);
+                for tile in &outcome.tiles {
+                    let (i, j) = tile.to;
+                    moved.set(i, j, tile.exponent + u8::from(tile.merged));
+                }
+                assert_eq!(moved, outcome.board.board(), "{action:?} on\n{board}");
+                assert_eq!(outcome.tiles.len(), N * N - board.board().num_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_apply_scored() {
+        let board = Board::from_cells([[1, 1, 2, 2], [3, 0, 3, 0], [1, 2, 3, 4], [0; N]]);
+        // 4 + 8 on the first row, 16 on the second one
+        let (_, score) = board.apply_scored(Action::Left).unwrap();
+        assert_eq!(score, 4 + 8 + 16);
+        // only the two 8s of the third column merge vertically
+        let (_, score) = board.apply_scored(Action::Down).unwrap();
+        assert_eq!(score, 16);
+    }
+
+    #[test]
+    fn test_max_exponent_not_applicable() {
+        let board = Board::from_cells([[15, 15, 0, 0], [0; N], [0; N], [0; N]]);
+        assert_eq!(board.apply(Action::Left), None);
+        assert!(board.apply(Action::Right).is_some());
     }
 
     #[test]
     fn test_actions() {
-        let board = Board {
-            cells: [[1, 2, 1, 0], [4, 1, 0, 0], [3, 0, 0, 0], [0, 0, 0, 0]],
-        };
-        let target = Board {
-            cells: [[0, 0, 0, 0], [1, 0, 0, 0], [4, 2, 0, 0], [3, 1, 1, 0]],
-        };
-        // The test checks the Down action (which requires transpose, swap_lr, push_left, swap_lr, transpose)
+        let board = Board::from_cells([[1, 2, 1, 0], [4, 1, 0, 0], [3, 0, 0, 0], [0, 0, 0, 0]]);
+        let target = Board::from_cells([[0, 0, 0, 0], [1, 0, 0, 0], [4, 2, 0, 0], [3, 1, 1, 0]]);
+        // The test checks the Down action (which moves the rows of the transposed board to the right)
         assert_eq!(board.apply(Action::Down), Some(target));
     }
+
+    #[test]
+    fn test_packed_board() {
+        // the moves of the tables against `push_left` on the lines of the unpacked cells
+        let mut rng = <::rand::rngs::StdRng as ::rand::SeedableRng>::seed_from_u64(752);
+        for _ in 0..1000 {
+            let board = Board::random_with(rng.random_range(1..=N * N), MAX_EXPONENT, &mut rng);
+            let cells = board.cells();
+            assert_eq!(Board::from_cells(cells), board);
+            assert_eq!(board.num_empty(), cells.iter().flatten().filter(|&&cell| cell == 0).count());
+            for action in ALL_ACTIONS {
+                let expected = reference_move(cells, action).map(|(cells, score)| (Board::from_cells(cells), score));
+                assert_eq!(board.apply_scored(action), expected, "{action:?} on\n{board}");
+            }
+        }
+    }
+
+    // The reference move: `push_left` on each line of the unpacked cells, with the score of the
+    // merges, `None` when no tile moves
+    fn reference_move(cells: [[u8; N]; N], action: Action) -> Option<([[u8; N]; N], u32)> {
+        let mut moved = cells;
+        let mut score = 0;
+        for k in 0..N {
+            let line = line_cells(action, k);
+            let mut pushed = line.map(|(i, j)| cells[i][j]);
+            score += push_left(&mut pushed);
+            for (&(i, j), &cell) in line.iter().zip(&pushed) {
+                moved[i][j] = cell;
+            }
+        }
+        (moved != cells).then_some((moved, score))
+    }
+
+    // Plays `count` random sequences of moves, until no action is legal, with both the reference
+    // moves on the cells and the packed board, spawning the same tiles on both. Asserts the same
+    // legal actions, boards and scores after every move.
+    fn differential_sequences(count: u64, seed: u64) {
+        let mut rng = <::rand::rngs::StdRng as ::rand::SeedableRng>::seed_from_u64(seed);
+        for sequence in 0..count {
+            // half of the sequences start from a crowded position, up to the largest tiles
+            let mut board = if rng.random_bool(0.5) {
+                Board::random_with(rng.random_range(1..=N * N), MAX_EXPONENT, &mut rng)
+            } else {
+                let mut board = Board::EMPTY;
+                board.add_random_with(&mut rng);
+                board
+            };
+            let mut cells = board.cells();
+            let (mut score, mut reference_score) = (0, 0);
+            for ply in 0.. {
+                let context = |board: Board| format!("sequence {sequence}, ply {ply} on\n{board}");
+                let legal: ArrayVec<Action, 4> =
+                    ALL_ACTIONS.into_iter().filter(|&action| reference_move(cells, action).is_some()).collect();
+                assert_eq!(PlayableBoard::from_board(board).legal_actions(), legal, "{}", context(board));
+                for action in ALL_ACTIONS {
+                    assert_eq!(board.can_apply(action), legal.contains(&action), "{action:?}, {}", context(board));
+                }
+                if legal.is_empty() {
+                    break;
+                }
+                let action = legal[rng.random_range(0..legal.len())];
+                let (moved, gained) = board.apply_scored(action).unwrap();
+                let (reference, reference_gained) = reference_move(cells, action).unwrap();
+                assert_eq!(moved.cells(), reference, "{action:?}, {}", context(board));
+                (score, reference_score) = (score + gained, reference_score + reference_gained);
+                assert_eq!(score, reference_score, "{action:?}, {}", context(board));
+                (board, cells) = (moved, reference);
+                let spawn = board.add_random_with(&mut rng);
+                cells[spawn.row][spawn.col] = spawn.exponent;
+                assert_eq!(board.cells(), cells, "{spawn:?}, {}", context(board));
+                assert_eq!(board.num_empty(), cells.iter().flatten().filter(|&&cell| cell == 0).count());
+            }
+        }
+    }
+
+    #[test]
+    fn test_differential_sequences() {
+        differential_sequences(500, 670);
+    }
+
+    // The exhaustive run, over a million sequences by default (`DIFFERENTIAL_SEQUENCES` sets
+    // their number): `cargo test --release -- --ignored test_differential_exhaustive`
+    #[test]
+    #[ignore]
+    fn test_differential_exhaustive() {
+        let count = std::env::var("DIFFERENTIAL_SEQUENCES").map_or(1_000_000, |count| count.parse().unwrap());
+        differential_sequences(count, 6700);
+    }
+
+    #[test]
+    fn test_locked_lines() {
+        // only the merge of the top row is possible
+        let board = Board::from_compact("1123/2345/3456/4567").unwrap();
+        assert_eq!(board.locked_lines(), ([false, true, true, true], [false; N]));
+        let board = Board::from_compact("1234/2345/3456/4567").unwrap();
+        assert_eq!(board.locked_lines(), ([true; N], [true; N]));
+        // lines with empty cells are not walls, even when no action changes them
+        let board = Board::from_compact("1000/0000/0000/0000").unwrap();
+        assert_eq!(board.locked_lines(), ([false; N], [false; N]));
+        // nor are partly filled ones
+        let board = Board::from_compact("0000/0000/1000/2345").unwrap();
+        assert_eq!(board.locked_lines(), ([false; N], [false; N]));
+    }
+
+    #[test]
+    fn test_legal_actions() {
+        let board = PlayableBoard::from_board(Board::from_compact("1000/0000/0000/0000").unwrap());
+        assert_eq!(board.legal_actions().as_slice(), &[Action::Down, Action::Right]);
+        assert_eq!(board.action_mask(), [false, true, false, true]);
+        // tiles at the maximum exponent do not merge
+        let board = Board::from_compact("ff12/2121/1212/2121").unwrap();
+        assert!(ALL_ACTIONS.iter().all(|&action| !board.can_apply(action)));
+
+        let mut rng = <::rand::rngs::StdRng as ::rand::SeedableRng>::seed_from_u64(705);
+        for _ in 0..1000 {
+            let board = Board::random_with(rng.random_range(1..=N * N), 4, &mut rng);
+            for action in ALL_ACTIONS {
+                assert_eq!(board.can_apply(action), board.apply(action).is_some(), "{action:?} on\n{board}");
+                // the legal actions cached by the wrapper
+                let playable = PlayableBoard::from_board(board);
+                assert_eq!(playable.apply_scored(action).map(|(b, score)| (b.board(), score)), board.apply_scored(action));
+            }
+        }
+    }
+
+    #[test]
+    fn test_encodings() {
+        let board = Board::from_compact("1000/0000/0030/000f").unwrap();
+        let mut exponents = [-1.0; N * N];
+        board.encode_exponents(&mut exponents);
+        assert_eq!(exponents, [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 3.0, 0.0, 0.0, 0.0, 0.0, 15.0]);
+
+        let mut onehot = [-1.0; ONEHOT_LEN];
+        board.encode_onehot(&mut onehot);
+        let ones: Vec<usize> = (0..ONEHOT_LEN).filter(|&i| onehot[i] == 1.0).collect();
+        // 13 empty cells in plane 0, then the 2 at cell 0, the 8 at cell 10 and the 32768 at cell 15
+        let mut expected: Vec<usize> = (1..N * N).filter(|&k| k != 10 && k != 15).collect();
+        expected.extend([16, 3 * 16 + 10, 15 * 16 + 15]);
+        assert_eq!(ones, expected);
+        assert!(onehot.iter().all(|&value| value == 0.0 || value == 1.0));
+    }
+
+    #[test]
+    fn test_symmetries() {
+        let board = Board::from_compact("1210/4100/3000/000b").unwrap();
+        let images: Vec<Board> = (0..NUM_SYMMETRIES).map(|k| board.symmetric(k)).collect();
+        assert_eq!(images[0], board);
+        assert_eq!(images[4], board.transposed());
+        assert_eq!(images[3].compact(), "b000/0003/0014/0121");
+        for (k, image) in images.iter().enumerate() {
+            assert_eq!(image.canonical_hash64(), board.canonical_hash64());
+            for action in ALL_ACTIONS {
+                let moved = board.apply(action).map(|moved| moved.symmetric(k));
+                assert_eq!(image.apply(symmetric_action(action, k)), moved, "{k} {action:?}");
+            }
+        }
+        assert_ne!(Board::from_compact("1100/0000/0000/0000").unwrap().canonical_hash64(), board.canonical_hash64());
+    }
+
+    #[test]
+    fn test_potential() {
+        let potential = |compact: &str| 1u32 << Board::from_compact(compact).unwrap().potential();
+        assert_eq!(potential("0000/0000/0000/1100"), 4);
+        // a full chain, one 2 short of the next tile
+        assert_eq!(potential("0000/0000/1234/ba98"), 2048);
+        // the two 512s complete it
+        assert_eq!(potential("0000/0001/1234/ba99"), 4096);
+        // a 512 walled in by 1024s does not count, a free one does
+        assert_eq!(potential("a9a0/0a00/0000/0000"), 2048);
+        assert_eq!(potential("a9a0/0a00/0000/0009"), 2048);
+        assert_eq!(potential("a0a0/0a00/0000/0099"), 4096);
+        // an empty corner is not trapped, even between large tiles
+        assert_eq!(potential("0b00/b000/0000/0000"), 4096);
+        assert_eq!(Board::EMPTY.potential(), 0);
+    }
+
+    #[test]
+    fn test_compact() {
+        let board = Board::from_cells([[1, 2, 1, 0], [4, 1, 0, 0], [3, 0, 0, 0], [0, 0, 0, 11]]);
+        assert_eq!(board.compact(), "1210/4100/3000/000b");
+        assert_eq!(board.hash64(), 0x1210_4100_3000_000b);
+        assert_eq!(Board::from_hash64(board.hash64()), board);
+        assert_eq!(Board::from_compact("1210/4100/3000/000b").unwrap(), board);
+        assert_eq!(Board::from_compact(" 1210 4100 3000 000B ").unwrap(), board);
+        assert!(Board::from_compact("1210/4100/3000").is_err());
+        assert!(Board::from_compact("1210/4100/3000/000z").is_err());
+        assert!(Board::from_compact("0000/0000/0000/0000").is_err());
+    }
 }
@@ -0,0 +1,202 @@
+//! Bookmarks of interesting positions, with a note, kept across the sessions in
+//! `BOOKMARKS_FILE`. `K` bookmarks the current position during a game or an analysis; the
+//! bookmarks are browsed from the start menu, and any of them opened in the analysis mode or
+//! played from as a practice game.
+//!
+//! The file has one bookmark per line: the position in the compact notation of the boards, the
+//! number of moves played before it and the note, separated by tabs.
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Context;
+use macroquad::prelude::*;
+
+use crate::board::*;
+
+/// File of the bookmarks, in the directory of the profile (see `profile.rs`)
+pub const BOOKMARKS_FILE: &str = "bookmarks.txt";
+// Longest note, in characters
+const MAX_NOTE_LEN: usize = 60;
+
+/// A bookmarked position
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bookmark {
+    pub board: Board,
+    /// Moves played in the game before the position
+    pub move_number: usize,
+    pub note: String,
+}
+
+impl Bookmark {
+    fn to_line(&self) -> String {
+        format!("{}\t{}\t{}", self.board.compact(), self.move_number, self.note)
+    }
+
+    fn parse(line: &str) -> anyhow::Result<Bookmark> {
+        let mut fields = line.splitn(3, '\t');
+        let board = Board::from_compact(fields.next().unwrap_or_default())?;
+        let move_number = fields.next().context("missing move number")?.parse().context("invalid move number")?;
+        let note = fields.next().unwrap_or_default().to_string();
+        Ok(Bookmark { board, move_number, note })
+    }
+}
+
+/// The bookmarks saved in `path`, oldest first; none if the file does not exist yet.
+pub fn load(path: &Path) -> anyhow::Result<Vec<Bookmark>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = std::fs::read_to_string(path).with_context(|| format!("Could not read the bookmarks of {}", path.display()))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| Bookmark::parse(line).with_context(|| format!("{}:{}: invalid bookmark", path.display(), i + 1)))
+        .collect()
+}
+
+/// Adds a bookmark at the end of the file `path`.
+pub fn append(path: &Path, bookmark: &Bookmark) -> anyhow::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Could not open {}", path.display()))?;
+    writeln!(file, "{}", bookmark.to_line())?;
+    Ok(())
+}
+
+/// Replaces the bookmarks of the file `path`, e.g. after a removal.
+pub fn save(path: &Path, bookmarks: &[Bookmark]) -> anyhow::Result<()> {
+    let text: String = bookmarks.iter().map(|bookmark| bookmark.to_line() + "\n").collect();
+    std::fs::write(path, text).with_context(|| format!("Could not write the bookmarks to {}", path.display()))
+}
+
+/// Where the note of a bookmark stands
+pub enum NoteEntry {
+    Typing,
+    /// Enter was pressed: the bookmark is saved with the note
+    Done(String),
+    /// Escape was pressed: nothing is saved
+    Cancelled,
+}
+
+/// The note of a new bookmark being typed, capturing the keyboard
+#[derive(Default, Hash)]
+pub struct NoteInput {
+    text: String,
+}
+
+impl NoteInput {
+    /// A note to type, the key that opened it being dropped
+    pub fn open() -> NoteInput {
+        while get_char_pressed().is_some() {}
+        NoteInput::default()
+    }
+
+    /// Processes the keyboard input of the current frame.
+    pub fn update(&mut self) -> NoteEntry {
+        while let Some(c) = get_char_pressed() {
+            if !c.is_control() && self.text.chars().count() < MAX_NOTE_LEN {
+                self.text.push(c);
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.text.pop();
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            return NoteEntry::Cancelled;
+        }
+        if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) {
+            return NoteEntry::Done(self.text.trim().to_string());
+        }
+        NoteEntry::Typing
+    }
+
+    pub fn draw(&self) {
+        let top = screen_height() - 70.0;
+        draw_rectangle(0.0, top, screen_width(), 70.0, Color::new(0.0, 0.0, 0.0, 0.8));
+        draw_text("Bookmark note (Enter: save, Escape: cancel)", 10.0, top + 24.0, 20.0, LIGHTGRAY);
+        draw_text(format!("> {}_", self.text), 10.0, top + 52.0, 20.0, YELLOW);
+    }
+}
+
+/// What to do with the bookmark chosen in the browser
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Jump {
+    Analysis,
+    Practice,
+}
+
+// Browses the bookmarks of the BOOKMARKS_FILE of the profile (ASYNC): Left/Right go through them, A opens the
+// shown one in the analysis mode, P plays a practice game from it, Delete removes it and
+// Escape goes back. Returns the chosen position, `None` when going back.
+pub async fn browse() -> anyhow::Result<Option<(PlayableBoard, Jump)>> {
+    let path = &crate::profile::path(BOOKMARKS_FILE);
+    let mut bookmarks = load(path)?;
+    // starting with the most recent one
+    let mut index = bookmarks.len().saturating_sub(1);
+    loop {
+        if is_key_pressed(KeyCode::Escape) || is_quit_requested() {
+            return Ok(None);
+        }
+        if is_key_pressed(KeyCode::Left) {
+            index = index.saturating_sub(1);
+        }
+        if is_key_pressed(KeyCode::Right) {
+            index = (index + 1).min(bookmarks.len().saturating_sub(1));
+        }
+        if let Some(bookmark) = bookmarks.get(index) {
+            let board = PlayableBoard::from_board(bookmark.board);
+            if is_key_pressed(KeyCode::A) {
+                return Ok(Some((board, Jump::Analysis)));
+            }
+            if is_key_pressed(KeyCode::P) {
+                return Ok(Some((board, Jump::Practice)));
+            }
+            if is_key_pressed(KeyCode::Delete) {
+                bookmarks.remove(index);
+                save(path, &bookmarks)?;
+                index = index.min(bookmarks.len().saturating_sub(1));
+                continue;
+            }
+        }
+
+        clear_background(Color::new(0.98, 0.97, 0.94, 1.0));
+        let x0 = 230.0;
+        match bookmarks.get(index) {
+            Some(bookmark) => {
+                PlayableBoard::from_board(bookmark.board).draw(bookmark.move_number as u32, None, 0.0);
+                draw_text(format!("Bookmark {}/{}", index + 1, bookmarks.len()), x0, 28.0, 20.0, BLACK);
+                draw_text(&bookmark.note, x0, 50.0, 18.0, DARKGRAY);
+            }
+            None => {
+                draw_text(format!("No bookmark yet (K during a game saves one to {BOOKMARKS_FILE})"), PADDING, 40.0, 20.0, BLACK);
+            }
+        }
+        let help = "Left/Right: browse, A: analyze, P: practice, Delete: remove, Escape: back";
+        draw_text(help, PADDING, screen_height() - 20.0, 16.0, DARKGRAY);
+        next_frame().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bookmarks() {
+        let path = std::env::temp_dir().join(format!("2048-bookmarks-{}.txt", std::process::id()));
+        assert!(load(&path).unwrap().is_empty());
+        let bookmark = |note: &str| Bookmark { board: Board::from_compact("1200/0000/0030/000b").unwrap(), move_number: 42, note: note.to_string() };
+        append(&path, &bookmark("corner lost\tafter a 4")).unwrap();
+        append(&path, &bookmark("")).unwrap();
+        assert_eq!(load(&path).unwrap(), [bookmark("corner lost\tafter a 4"), bookmark("")]);
+
+        save(&path, &[bookmark("kept")]).unwrap();
+        assert_eq!(load(&path).unwrap(), [bookmark("kept")]);
+        std::fs::write(&path, "1200/0000/0030/000b\tmany\tnote\n").unwrap();
+        assert!(load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,297 @@
+//! Bounded cache evicting the least recently used entries, for the values of the search.
+
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use hashbrown::HashMap;
+
+// Index of the absent node at the ends of the list
+const NIL: usize = usize::MAX;
+
+// Number of trims of the caches requested so far
+static TRIM_REQUESTS: AtomicU64 = AtomicU64::new(0);
+
+/// Asks the searches of the process to trim their caches, e.g. when the process runs short of
+/// memory. Each one does it before its next search (see `Searcher`).
+pub fn request_trim() {
+    TRIM_REQUESTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of trims requested so far
+pub fn trim_requests() -> u64 {
+    TRIM_REQUESTS.load(Ordering::Relaxed)
+}
+
+/// Capacity of a cache, as a number of entries or as an approximate memory size
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CacheCapacity {
+    Entries(usize),
+    Megabytes(usize),
+}
+
+impl CacheCapacity {
+    /// One of `parts` equal shares of the capacity, e.g. for caches searched in parallel
+    pub fn split(self, parts: usize) -> CacheCapacity {
+        match self {
+            CacheCapacity::Entries(entries) => CacheCapacity::Entries(entries / parts),
+            CacheCapacity::Megabytes(mb) => CacheCapacity::Megabytes(mb / parts),
+        }
+    }
+}
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    /// Neighbours in the recency list: `prev` was used more recently, `next` less recently
+    prev: usize,
+    next: usize,
+}
+
+/// A map holding at most `capacity` entries: inserting in a full cache evicts the least
+/// recently used (read or written) entry.
+pub struct LruCache<K, V> {
+    map: HashMap<K, usize>,
+    /// Entries, linked in order of use
+    nodes: Vec<Node<K, V>>,
+    /// Most recently used node
+    head: usize,
+    /// Least recently used node, the next to be evicted
+    tail: usize,
+    capacity: usize,
+    evictions: u64,
+}
+
+impl<K: Copy + Hash + Eq, V> LruCache<K, V> {
+    pub fn new(capacity: CacheCapacity) -> LruCache<K, V> {
+        let capacity = match capacity {
+            CacheCapacity::Entries(entries) => entries,
+            // each entry is stored in the list and in the map (key, index and control byte)
+            CacheCapacity::Megabytes(mb) => {
+                let entry_size = std::mem::size_of::<Node<K, V>>() + std::mem::size_of::<(K, usize)>() + 1;
+                mb * 1024 * 1024 / entry_size
+            }
+        };
+        LruCache {
+            map: HashMap::new(),
+            nodes: Vec::new(),
+            head: NIL,
+            tail: NIL,
+            capacity: capacity.max(1),
+            evictions: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Maximum number of entries
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of entries evicted to make room for new ones since the creation of the cache
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// The entries, from the most recently used to the least recently used
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        std::iter::successors((self.head != NIL).then_some(self.head), |&index| {
+            let next = self.nodes[index].next;
+            (next != NIL).then_some(next)
+        })
+        .map(|index| (&self.nodes[index].key, &self.nodes[index].value))
+    }
+
+    /// Returns the value of the key, marking it as the most recently used.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let index = *self.map.get(key)?;
+        self.move_to_front(index);
+        Some(&self.nodes[index].value)
+    }
+
+    /// Returns the value of the key, without marking it as used.
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        self.map.get(key).map(|&index| &self.nodes[index].value)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        if let Some(&index) = self.map.get(&key) {
+            self.nodes[index].value = value;
+            self.move_to_front(index);
+            return;
+        }
+        let index = if self.nodes.len() < self.capacity {
+            self.nodes.push(Node { key, value, prev: NIL, next: NIL });
+            self.nodes.len() - 1
+        } else {
+            // reuse the node of the least recently used entry
+            let index = self.tail;
+            self.unlink(index);
+            self.map.remove(&self.nodes[index].key);
+            self.nodes[index].key = key;
+            self.nodes[index].value = value;
+            self.evictions += 1;
+            index
+        };
+        self.map.insert(key, index);
+        self.push_front(index);
+    }
+
+    /// Keeps only the entries satisfying the predicate, in the same order of use. The entries
+    /// are visited from the least recently used, and removed in place: only the removed ones
+    /// cost more than the visit.
+    pub fn retain(&mut self, mut keep: impl FnMut(&K, &V) -> bool) {
+        let mut index = self.tail;
+        while index != NIL {
+            let prev = self.nodes[index].prev;
+            if keep(&self.nodes[index].key, &self.nodes[index].value) {
+                index = prev;
+            } else {
+                let moved = self.remove_node(index);
+                // the next node to visit may be the one moved in place of the removed one
+                index = if prev == moved { index } else { prev };
+            }
+        }
+    }
+
+    /// Evicts the least recently used entries until at most `len` are left, and releases the
+    /// memory they used.
+    pub fn trim(&mut self, len: usize) {
+        let excess = self.len().saturating_sub(len);
+        // (`retain` visits the entries from the least recently used)
+        let mut visited = 0;
+        self.retain(|_, _| {
+            visited += 1;
+            visited > excess
+        });
+        self.evictions += excess as u64;
+        self.nodes.shrink_to_fit();
+        self.map.shrink_to_fit();
+    }
+
+    /// Removes all the entries (the count of evictions is kept).
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.nodes.clear();
+        self.head = NIL;
+        self.tail = NIL;
+    }
+
+    // Removes the node at `index`, moving the last node of `nodes` in its place. Returns the
+    // former index of the moved node.
+    fn remove_node(&mut self, index: usize) -> usize {
+        self.unlink(index);
+        self.map.remove(&self.nodes[index].key);
+        let last = self.nodes.len() - 1;
+        self.nodes.swap_remove(index);
+        if index != last {
+            let (prev, next) = (self.nodes[index].prev, self.nodes[index].next);
+            match prev {
+                NIL => self.head = index,
+                prev => self.nodes[prev].next = index,
+            }
+            match next {
+                NIL => self.tail = index,
+                next => self.nodes[next].prev = index,
+            }
+            *self.map.get_mut(&self.nodes[index].key).unwrap() = index;
+        }
+        last
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let (prev, next) = (self.nodes[index].prev, self.nodes[index].next);
+        match prev {
+            NIL => self.head = next,
+            prev => self.nodes[prev].next = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            next => self.nodes[next].prev = prev,
+        }
+    }
+
+    fn push_front(&mut self, index: usize) {
+        self.nodes[index].prev = NIL;
+        self.nodes[index].next = self.head;
+        match self.head {
+            NIL => self.tail = index,
+            head => self.nodes[head].prev = index,
+        }
+        self.head = index;
+    }
+
+    fn move_to_front(&mut self, index: usize) {
+        if self.head != index {
+            self.unlink(index);
+            self.push_front(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut cache = LruCache::new(CacheCapacity::Entries(2));
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        // 1 becomes the most recently used, 2 is evicted
+        assert_eq!(cache.get(&1), Some(&"a"));
+        cache.insert(3, "c");
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&1), Some(&"a"));
+        assert_eq!(cache.get(&3), Some(&"c"));
+        assert_eq!(cache.evictions(), 1);
+        assert_eq!(cache.iter().map(|(&key, _)| key).collect::<Vec<_>>(), [3, 1]);
+
+        cache.insert(4, "d");
+        cache.retain(|&key, _| key != 4);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&3), Some(&"c"));
+        cache.insert(5, "e");
+        cache.insert(6, "f");
+        assert_eq!(cache.get(&3), None);
+
+        // the most recently used entries are kept
+        cache.get(&5);
+        cache.trim(1);
+        assert_eq!(cache.iter().map(|(&key, _)| key).collect::<Vec<_>>(), [5]);
+        assert_eq!(cache.evictions(), 4);
+        cache.trim(4);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut cache = LruCache::new(CacheCapacity::Entries(20));
+        for key in 0..20 {
+            cache.insert(key, key * 10);
+        }
+        cache.get(&3);
+        cache.get(&0);
+        // removing in place keeps the order of use and the lookups
+        cache.retain(|&key, _| key % 3 == 0);
+        assert_eq!(cache.iter().map(|(&key, _)| key).collect::<Vec<_>>(), [0, 3, 18, 15, 12, 9, 6]);
+        for key in [0, 3, 6, 9, 12, 15, 18] {
+            assert_eq!(cache.peek(&key), Some(&(key * 10)));
+        }
+        assert_eq!(cache.peek(&7), None);
+        // and the least recently used entries are still evicted first
+        for key in 100..114 {
+            cache.insert(key, 0);
+        }
+        assert_eq!((cache.len(), cache.evictions()), (20, 1));
+        assert_eq!(cache.peek(&6), None);
+        cache.retain(|_, _| false);
+        assert!(cache.is_empty() && cache.iter().next().is_none());
+    }
+}
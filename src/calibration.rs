@@ -0,0 +1,137 @@
+//! Diagnostic of the quality of the evaluation: the value the search predicts for each move is
+//! compared with what is realized once the game has gone as far as the search looked ahead.
+//!
+//! For a move searched at depth `k`, the search predicts `score_weight * score gained` over the
+//! next `k` moves plus the heuristic of the position after the `k`-th move (before its spawn).
+//! The realized outcome is the same quantity measured on the moves actually played, so a well
+//! calibrated evaluation has no bias and a correlation close to 1.
+
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+
+// A prediction waiting for its outcome
+struct Pending {
+    predicted: f32,
+    horizon: usize,
+    score_weight: f32,
+    /// Moves played and score gained since the prediction (the predicted move included)
+    moves: usize,
+    gained: u32,
+}
+
+/// Predictions of a game and their realized outcomes
+#[derive(Default)]
+pub struct Calibration {
+    pending: VecDeque<Pending>,
+    /// Number of predictions whose outcome is known
+    n: usize,
+    sum_predicted: f64,
+    sum_realized: f64,
+    sum_predicted_sq: f64,
+    sum_realized_sq: f64,
+    sum_product: f64,
+    sum_abs_error: f64,
+}
+
+impl Calibration {
+    /// Records the value `predicted` for the move about to be played, by a search looking
+    /// `horizon` moves ahead.
+    pub fn predict(&mut self, predicted: f32, horizon: usize, score_weight: f32) {
+        self.pending.push_back(Pending { predicted, horizon: horizon.max(1), score_weight, moves: 0, gained: 0 });
+    }
+
+    /// Records the move just played: the score it gained and the evaluation of the position
+    /// after it (before the spawn). Resolves the predictions whose horizon is reached.
+    pub fn observe(&mut self, gained: u32, evaluation: f32) {
+        for pending in &mut self.pending {
+            pending.moves += 1;
+            pending.gained += gained;
+        }
+        while let Some(pending) = self.pending.front().filter(|pending| pending.moves >= pending.horizon) {
+            let realized = pending.score_weight * pending.gained as f32 + evaluation;
+            self.add(pending.predicted as f64, realized as f64);
+            self.pending.pop_front();
+        }
+    }
+
+    /// Drops the predictions still waiting for their outcome, when the game continues with
+    /// moves that are not observed (e.g. played by a human).
+    pub fn interrupt(&mut self) {
+        self.pending.clear();
+    }
+
+    fn add(&mut self, predicted: f64, realized: f64) {
+        self.n += 1;
+        self.sum_predicted += predicted;
+        self.sum_realized += realized;
+        self.sum_predicted_sq += predicted * predicted;
+        self.sum_realized_sq += realized * realized;
+        self.sum_product += predicted * realized;
+        self.sum_abs_error += (predicted - realized).abs();
+    }
+
+    /// Number of predictions whose outcome is known
+    pub fn num_outcomes(&self) -> usize {
+        self.n
+    }
+
+    /// Mean of `predicted - realized`: positive when the evaluation is optimistic
+    pub fn bias(&self) -> f64 {
+        (self.sum_predicted - self.sum_realized) / self.n.max(1) as f64
+    }
+
+    pub fn mean_abs_error(&self) -> f64 {
+        self.sum_abs_error / self.n.max(1) as f64
+    }
+
+    /// Pearson correlation between the predictions and the outcomes, `None` when undefined
+    /// (fewer than two outcomes or constant values)
+    pub fn correlation(&self) -> Option<f64> {
+        let n = self.n as f64;
+        let covariance = n * self.sum_product - self.sum_predicted * self.sum_realized;
+        let var_predicted = n * self.sum_predicted_sq - self.sum_predicted * self.sum_predicted;
+        let var_realized = n * self.sum_realized_sq - self.sum_realized * self.sum_realized;
+        let denominator = (var_predicted * var_realized).sqrt();
+        (self.n >= 2 && denominator > 0.0).then(|| covariance / denominator)
+    }
+}
+
+impl Display for Calibration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "calibration over {} predictions: bias {:+.1}, mean abs error {:.1}", self.n, self.bias(), self.mean_abs_error())?;
+        if let Some(correlation) = self.correlation() {
+            write!(f, ", correlation {correlation:.3}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calibration() {
+        let mut calibration = Calibration::default();
+        // predictions two moves ahead, resolved when the second move is observed
+        calibration.predict(110.0, 2, 1.0);
+        calibration.observe(4, 50.0);
+        assert_eq!(calibration.num_outcomes(), 0);
+        calibration.predict(120.0, 2, 1.0);
+        calibration.observe(8, 100.0);
+        assert_eq!(calibration.num_outcomes(), 1);
+        calibration.predict(130.0, 2, 1.0);
+        calibration.observe(0, 110.0);
+        assert_eq!(calibration.num_outcomes(), 2);
+        // outcomes 112 and 118
+        assert!((calibration.bias() - 0.0).abs() < 1e-9);
+        assert!((calibration.mean_abs_error() - 2.0).abs() < 1e-9);
+        assert!((calibration.correlation().unwrap() - 1.0).abs() < 1e-9);
+
+        // the pending prediction is dropped, not resolved by unrelated moves
+        calibration.interrupt();
+        calibration.observe(0, 0.0);
+        calibration.observe(0, 0.0);
+        assert_eq!(calibration.num_outcomes(), 2);
+    }
+}
@@ -0,0 +1,58 @@
+//! Live chart of the score and of the number of empty cells over the last moves of a game,
+//! drawn in a strip below the grid: the pressure building up before a loss shows as the empty
+//! cells dropping while the score stalls.
+
+use std::collections::VecDeque;
+
+use macroquad::prelude::*;
+
+use crate::board::*;
+
+/// Height of the strip of the chart, below the grid
+pub const CHART_HEIGHT: f32 = 90.0;
+// Number of moves shown
+const CAPACITY: usize = 300;
+const SCORE_COLOR: Color = Color::new(0.96, 0.49, 0.37, 1.0); // #f67c5f
+const EMPTY_COLOR: Color = Color::new(0.2, 0.45, 0.8, 1.0);
+
+/// Score and number of empty cells after each of the last moves
+#[derive(Default)]
+pub struct MoveChart {
+    samples: VecDeque<(u32, usize)>,
+}
+
+impl MoveChart {
+    pub fn push(&mut self, score: u32, board: &Board) {
+        if self.samples.len() == CAPACITY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((score, board.num_empty()));
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Draws the chart in the strip below the grid: the score scaled between its smallest
+    /// and largest values over the window, the empty cells between 0 and `N * N`.
+    pub fn draw(&self) {
+        let top = PADDING + UI_HEIGHT + GRID_SIZE + PADDING;
+        let (left, width, height) = (PADDING, GRID_SIZE, CHART_HEIGHT - 2.0 * PADDING);
+        draw_rectangle(left, top, width, height, Color::new(0.93, 0.89, 0.85, 1.0));
+        let Some(&(score, empty)) = self.samples.back() else {
+            return;
+        };
+        let (min, max) = self.samples.iter().fold((u32::MAX, 0), |(min, max), &(s, _)| (min.min(s), max.max(s)));
+        let x = |i: usize| left + width * i as f32 / (CAPACITY - 1) as f32;
+        let y = |fraction: f32| top + height * (1.0 - fraction);
+        for (i, (a, b)) in self.samples.iter().zip(self.samples.iter().skip(1)).enumerate() {
+            let scaled = |s: u32| if max > min { (s - min) as f32 / (max - min) as f32 } else { 0.5 };
+            draw_line(x(i), y(scaled(a.0)), x(i + 1), y(scaled(b.0)), 2.0, SCORE_COLOR);
+            let filled = |e: usize| e as f32 / (N * N) as f32;
+            draw_line(x(i), y(filled(a.1)), x(i + 1), y(filled(b.1)), 2.0, EMPTY_COLOR);
+        }
+        let score = crate::layout::layout().numbers.format(score as u64);
+        draw_text(format!("Score: {score}"), left + 6.0, top + 16.0, 18.0, SCORE_COLOR);
+        draw_text(format!("Empty: {empty}"), left + 6.0, top + 34.0, 18.0, EMPTY_COLOR);
+    }
+}
@@ -0,0 +1,108 @@
+//! Checkpoints of batch runs, so that an interrupted run can be resumed instead of playing all
+//! its games again.
+//!
+//! A checkpoint is a directory holding the options of the run (`run.txt`, after the
+//! `compat::ENGINE_VERSION` of the run) and every finished game in the game notation
+//! (`game-<index>.2gn`), written as soon as the game ends. All the statistics of the run are
+//! computed from the games, so they need no separate saving.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+
+use crate::compat::{self, ENGINE_VERSION};
+use crate::notation::{GameRecord, NOTATION_EXTENSION};
+
+// File holding the options of the run
+const OPTIONS_FILE: &str = "run.txt";
+
+/// Directory where the games of a run are saved as they finish
+pub struct Checkpoint {
+    dir: PathBuf,
+}
+
+impl Checkpoint {
+    /// Starts the checkpoint of a run with the given `options` in `dir`, refusing to overwrite
+    /// the checkpoint of another run.
+    pub fn create(dir: &Path, options: &str) -> anyhow::Result<Checkpoint> {
+        let path = dir.join(OPTIONS_FILE);
+        if path.exists() {
+            bail!("{} already holds a checkpoint, resume it with `--resume`", dir.display());
+        }
+        std::fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+        std::fs::write(&path, format!("engine {ENGINE_VERSION}\n{options}")).with_context(|| format!("Could not write {}", path.display()))?;
+        Ok(Checkpoint { dir: dir.to_path_buf() })
+    }
+
+    /// Reopens the checkpoint in `dir`, which must have been created with the same `options`,
+    /// returning the games already finished by index.
+    pub fn resume(dir: &Path, options: &str) -> anyhow::Result<(Checkpoint, BTreeMap<u64, GameRecord>)> {
+        let path = dir.join(OPTIONS_FILE);
+        let saved = std::fs::read_to_string(&path).with_context(|| format!("Could not read {}", path.display()))?;
+        // the checkpoints older than the versioning of the engine were all run by the first one
+        let (engine, saved) = match saved.strip_prefix("engine ").and_then(|rest| rest.split_once('\n')) {
+            Some((engine, saved)) => (engine.trim().parse().with_context(|| format!("Invalid engine version in {}", path.display()))?, saved),
+            None => (1, saved.as_str()),
+        };
+        compat::check_engine(engine).with_context(|| format!("The run in {} cannot be resumed", dir.display()))?;
+        if saved != options {
+            bail!("the run in {} used other options:\n{saved}", dir.display());
+        }
+
+        let mut games = BTreeMap::new();
+        for entry in std::fs::read_dir(dir).with_context(|| format!("Could not read {}", dir.display()))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == NOTATION_EXTENSION) {
+                let index = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str()?.strip_prefix("game-")?.parse().ok())
+                    .with_context(|| format!("Unexpected game file {}", path.display()))?;
+                games.insert(index, GameRecord::load(&path)?);
+            }
+        }
+        Ok((Checkpoint { dir: dir.to_path_buf() }, games))
+    }
+
+    /// Saves the finished game of the given index.
+    pub fn save_game(&self, index: u64, record: &GameRecord) -> anyhow::Result<()> {
+        // written under another name first, an interruption must not leave a truncated game
+        let path = self.dir.join(format!("game-{index:04}.{NOTATION_EXTENSION}"));
+        let partial = path.with_extension("partial");
+        record.save(&partial)?;
+        std::fs::rename(&partial, &path).with_context(|| format!("Could not write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::*;
+
+    #[test]
+    fn test_resume() {
+        let dir = std::env::temp_dir().join(format!("2048-checkpoint-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let checkpoint = Checkpoint::create(&dir, "depth 3\n").unwrap();
+        let mut record = GameRecord::new(Board::from_compact("0000/0000/0010/0000").unwrap());
+        record.push(Action::Left, Spawn { row: 3, col: 1, exponent: 1 });
+        checkpoint.save_game(7, &record).unwrap();
+
+        // a second run cannot reuse the directory, and a resumed run must have the same options
+        assert!(Checkpoint::create(&dir, "depth 3\n").is_err());
+        assert!(Checkpoint::resume(&dir, "depth 4\n").is_err());
+        let (_, games) = Checkpoint::resume(&dir, "depth 3\n").unwrap();
+        assert_eq!(games, BTreeMap::from([(7, record)]));
+
+        // a run of another engine is refused, one older than the engine versions is resumed
+        let options = dir.join(OPTIONS_FILE);
+        std::fs::write(&options, format!("engine {}\ndepth 3\n", ENGINE_VERSION + 1)).unwrap();
+        let error = Checkpoint::resume(&dir, "depth 3\n").err().unwrap();
+        assert!(format!("{error:#}").contains("newer engine"), "{error:#}");
+        std::fs::write(&options, "depth 3\n").unwrap();
+        assert!(Checkpoint::resume(&dir, "depth 3\n").is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,35 @@
+//! Colors of the theme. With the `gui` feature they are the colors of macroquad, without it
+//! a plain replacement with the same API, so that the theme and the offscreen renderer
+//! (`render.rs`) do not depend on the window.
+
+#[cfg(feature = "gui")]
+pub use macroquad::prelude::{Color, BLACK};
+
+#[cfg(not(feature = "gui"))]
+pub use plain::*;
+
+#[cfg(not(feature = "gui"))]
+mod plain {
+    /// RGBA color with components in `[0, 1]`, as in macroquad
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct Color {
+        pub r: f32,
+        pub g: f32,
+        pub b: f32,
+        pub a: f32,
+    }
+
+    impl Color {
+        pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Color {
+            Color { r, g, b, a }
+        }
+    }
+
+    impl From<Color> for [u8; 4] {
+        fn from(color: Color) -> [u8; 4] {
+            [color.r, color.g, color.b, color.a].map(|c| (c * 255.0) as u8)
+        }
+    }
+
+    pub const BLACK: Color = Color::new(0.0, 0.0, 0.0, 1.0);
+}
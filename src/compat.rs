@@ -0,0 +1,55 @@
+//! Versioning of the engine in the saved files, so that a change of the rules of the engine
+//! (how tiles move and merge, how the score is counted, how tiles spawn) is detected when
+//! reading files written before it, instead of replaying them wrongly.
+//!
+//! Every saved file records the `ENGINE_VERSION` it was written with: games in the notation
+//! (saves, replays, checkpoints, exports), the options of the checkpoints and the warm caches.
+//! Files with a checksum (`checksum`) are also checked for edits and truncation.
+//!
+//! When the engine changes, `ENGINE_VERSION` is bumped and `check_engine` decides what
+//! happens to the files of the previous versions: accepted as they are, migrated by their
+//! reader, or rejected with an explicit error.
+
+use anyhow::bail;
+
+/// Version of the rules of the engine, bumped whenever a saved game would replay differently
+pub const ENGINE_VERSION: u32 = 1;
+
+/// Checks that a file written by the engine `version` can be read by this one.
+pub fn check_engine(version: u32) -> anyhow::Result<()> {
+    match version {
+        // (the files of older engines that can be migrated are accepted here)
+        ENGINE_VERSION => Ok(()),
+        0 => bail!("invalid engine version 0"),
+        version if version > ENGINE_VERSION => {
+            bail!("written by a newer engine (version {version}, this build has version {ENGINE_VERSION}), update to read it")
+        }
+        version => bail!("written by the engine version {version}, which the engine version {ENGINE_VERSION} cannot read"),
+    }
+}
+
+/// 64-bit FNV-1a hash of `bytes`, the same on all platforms and versions
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+/// Checksum of the content of a file, written as 8 hexadecimal digits
+pub fn checksum(bytes: &[u8]) -> u32 {
+    fnv1a(bytes) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_engine() {
+        assert!(check_engine(ENGINE_VERSION).is_ok());
+        let newer = check_engine(ENGINE_VERSION + 1).unwrap_err().to_string();
+        assert!(newer.contains("newer engine"), "{newer}");
+        assert!(check_engine(0).is_err());
+        // the reference value of FNV-1a: checksums must never change
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(checksum(b"move L 3,1=1"), checksum(b"move L 3,1=2"));
+    }
+}
@@ -0,0 +1,201 @@
+use macroquad::prelude::*;
+
+use crate::board::*;
+use crate::cache::CacheCapacity;
+use crate::search::{SearchParams, Tiebreak};
+
+// Number of log lines kept (and displayed) by the console
+const MAX_LOG_LINES: usize = 8;
+const CONSOLE_FONT_SIZE: f32 = 20.0;
+// Largest depth and number of extensions accepted, beyond which a single move takes seconds
+// and the window stops responding
+const MAX_DEPTH: usize = 8;
+const MAX_EXTENSIONS: usize = 4;
+const LINE_HEIGHT: f32 = 22.0;
+
+/// In-game developer console, toggled with `~`, to tweak the running agent live.
+///
+/// Supported commands:
+///  - `set depth <n>`
+///  - `set mix <lambda>`
+///  - `set score-weight <value>`
+///  - `set extensions <n>`
+///  - `set cache-mb <n>`
+///  - `set hysteresis <margin>`
+///  - `set cutoff <probability>`
+///  - `set tiebreak <fixed|heuristic|seeded:<seed>>`
+///  - `set weight <monotonicity|empty|adjacent|sum|smoothness|locked|potential> <value>`
+///  - `dump board`
+///  - `eval`
+///  - `help`
+#[derive(Default, Hash)]
+pub struct Console {
+    /// Whether the console is currently shown (and capturing the keyboard)
+    pub open: bool,
+    /// Command line being typed
+    input: String,
+    /// Last output lines
+    log: Vec<String>,
+}
+
+impl Console {
+    /// Processes the keyboard input of the current frame.
+    /// Commands are applied directly to the given search parameters.
+    pub fn update(&mut self, params: &mut SearchParams, board: &PlayableBoard) {
+        if is_key_pressed(KeyCode::GraveAccent) {
+            self.open = !self.open;
+            // drop the `~` character that toggled the console
+            while get_char_pressed().is_some() {}
+            return;
+        }
+        if !self.open {
+            return;
+        }
+
+        while let Some(c) = get_char_pressed() {
+            if !c.is_control() && c != '`' && c != '~' {
+                self.input.push(c);
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.input.pop();
+        }
+        if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) {
+            let line = std::mem::take(&mut self.input);
+            self.push(format!("> {line}"));
+            match self.execute(&line, params, board) {
+                Ok(output) => output.lines().for_each(|l| self.push(l.to_string())),
+                Err(e) => self.push(format!("error: {e}")),
+            }
+        }
+    }
+
+    /// Runs a single command and returns its textual output.
+    pub fn execute(&self, line: &str, params: &mut SearchParams, board: &PlayableBoard) -> anyhow::Result<String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["set", "depth", n] => {
+                let depth: usize = n.parse()?;
+                anyhow::ensure!((1..=MAX_DEPTH).contains(&depth), "depth must be in [1, {MAX_DEPTH}]");
+                params.depth = depth;
+                Ok(format!("depth = {depth}"))
+            }
+            ["set", "mix", x] => {
+                let mix: f32 = x.parse()?;
+                anyhow::ensure!((0.0..=1.0).contains(&mix), "mix must be in [0, 1]");
+                params.adversarial_mix = mix;
+                Ok(format!("mix = {mix}"))
+            }
+            ["set", "score-weight", x] => {
+                let weight: f32 = x.parse()?;
+                // (a negative weight would reward the search for losing points, as in bench)
+                anyhow::ensure!(weight.is_finite() && weight >= 0.0, "score-weight must be a non-negative number");
+                params.score_weight = weight;
+                Ok(format!("score-weight = {weight}"))
+            }
+            ["set", "extensions", n] => {
+                let extensions: usize = n.parse()?;
+                anyhow::ensure!(extensions <= MAX_EXTENSIONS, "extensions must be at most {MAX_EXTENSIONS}");
+                params.extensions = extensions;
+                Ok(format!("extensions = {extensions}"))
+            }
+            ["set", "cache-mb", n] => {
+                let mb: usize = n.parse()?;
+                params.cache_capacity = CacheCapacity::Megabytes(mb);
+                Ok(format!("cache = {mb} MB"))
+            }
+            ["set", "hysteresis", x] => {
+                let margin: f32 = x.parse()?;
+                anyhow::ensure!(margin.is_finite() && margin >= 0.0, "hysteresis must be a non-negative number");
+                params.hysteresis = margin;
+                Ok(format!("hysteresis = {margin}"))
+            }
+            ["set", "cutoff", x] => {
+                let cutoff: f32 = x.parse()?;
+                anyhow::ensure!((0.0..=1.0).contains(&cutoff), "cutoff must be in [0, 1]");
+                params.probability_cutoff = cutoff;
+                Ok(format!("cutoff = {cutoff}"))
+            }
+            ["set", "tiebreak", policy] => {
+                params.tiebreak = Tiebreak::parse(policy)?;
+                Ok(format!("tiebreak = {}", params.tiebreak))
+            }
+            ["set", "weight", name, x] => {
+                let value: f32 = x.parse()?;
+                params.weights.set(name, value)?;
+                Ok(format!("weight {name} = {value}"))
+            }
+            ["dump", "board"] => {
+                let rows: Vec<String> = board
+                    .board()
+                    .cells()
+                    .iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(|&cell| if cell == 0 { ".".to_string() } else { 2u32.pow(cell as u32).to_string() })
+                            .map(|s| format!("{s:>6}"))
+                            .collect()
+                    })
+                    .collect();
+                Ok(rows.join("\n"))
+            }
+            ["eval"] => Ok(format!("eval = {:.1}", board.evaluate_with(&params.weights))),
+            ["help"] => Ok("set depth <n> | set mix <x> | set score-weight <x> | set extensions <n> | set cache-mb <n> | set hysteresis <x> | set cutoff <p> | set tiebreak <policy> | set weight <name> <x> | dump board | eval".to_string()),
+            [] => Ok(String::new()),
+            _ => anyhow::bail!("unknown command, try `help`"),
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        self.log.push(line);
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.remove(0);
+        }
+    }
+
+    /// Draws the console over the bottom of the window, if open.
+    pub fn draw(&self) {
+        if !self.open {
+            return;
+        }
+        let height = LINE_HEIGHT * (MAX_LOG_LINES as f32 + 1.0) + 10.0;
+        let top = screen_height() - height;
+        draw_rectangle(0.0, top, screen_width(), height, Color::new(0.0, 0.0, 0.0, 0.8));
+        for (i, line) in self.log.iter().enumerate() {
+            draw_text(line, 10.0, top + LINE_HEIGHT * (i as f32 + 1.0), CONSOLE_FONT_SIZE, WHITE);
+        }
+        draw_text(
+            format!("> {}_", self.input),
+            10.0,
+            top + LINE_HEIGHT * (MAX_LOG_LINES as f32 + 1.0),
+            CONSOLE_FONT_SIZE,
+            YELLOW,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execute_rejects_bad_values() {
+        let console = Console::default();
+        let board = PlayableBoard::from_board(Board::from_compact("0000/0000/1000/9800").unwrap());
+        let mut params = SearchParams::default();
+        for line in ["set depth 0", "set depth 20", "set extensions 100", "set score-weight NaN", "set score-weight -1e30", "set hysteresis inf", "set weight empty inf", "set weight sum NaN"] {
+            assert!(console.execute(line, &mut params, &board).is_err(), "{line}");
+        }
+        assert_eq!(params, SearchParams::default());
+        assert_eq!(console.execute("set depth 5", &mut params, &board).unwrap(), "depth = 5");
+        assert_eq!(console.execute("set weight empty -10", &mut params, &board).unwrap(), "weight empty = -10");
+        assert_eq!((params.depth, params.weights.empty), (5, -10.0));
+
+        // extreme finite weights are accepted, and the agent still finds a move
+        for line in ["set weight sum 1e6", "set weight monotonicity -1e30"] {
+            let mut params = SearchParams { depth: 2, ..SearchParams::default() };
+            console.execute(line, &mut params, &board).unwrap();
+            assert!(crate::search::select_action_with(board, &params).is_some(), "{line}");
+        }
+    }
+}
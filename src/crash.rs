@@ -0,0 +1,128 @@
+//! Crash reports: on a panic, a diagnostic bundle with the last known state of the game is
+//! written to `CRASH_DIR`, so that bug reports contain enough to reproduce the crash.
+//!
+//! The state is kept per thread (the benchmark plays its games on a thread pool), by calling
+//! `start_game` and `record_move` from the loop playing the game.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::board::*;
+
+/// Directory of the crash reports
+pub const CRASH_DIR: &str = "crashes";
+// Number of actions kept in the reports
+const NUM_ACTIONS: usize = 20;
+
+// Last known state of the game played by a thread
+#[derive(Default)]
+struct GameContext {
+    board: Option<Board>,
+    seed: Option<u64>,
+    strategy: String,
+    num_moves: usize,
+    last_actions: VecDeque<Action>,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<GameContext> = RefCell::new(GameContext::default());
+}
+
+/// Installs the panic hook writing the crash reports, after the usual panic message.
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        let report = report(&info.to_string(), &backtrace.to_string());
+        match write_report(&report) {
+            Ok(path) => eprintln!("A crash report was written to {}, please attach it to the bug report.", path.display()),
+            Err(e) => eprintln!("Could not write the crash report: {e}"),
+        }
+    }));
+}
+
+/// Starts tracking a new game on the current thread, with the `seed` of its spawns if any and
+/// the `strategy` playing it (name and parameters).
+pub fn start_game(board: &Board, seed: Option<u64>, strategy: &str) {
+    CONTEXT.with_borrow_mut(|context| {
+        *context = GameContext { board: Some(*board), seed, strategy: strategy.to_string(), ..Default::default() }
+    });
+}
+
+/// Records a change of the strategy playing the game of the current thread.
+pub fn set_strategy(strategy: &str) {
+    CONTEXT.with_borrow_mut(|context| context.strategy = strategy.to_string());
+}
+
+/// Records that `action` was played, leading to `board` (after the spawn).
+pub fn record_move(action: Action, board: &Board) {
+    CONTEXT.with_borrow_mut(|context| {
+        context.board = Some(*board);
+        context.num_moves += 1;
+        if context.last_actions.len() == NUM_ACTIONS {
+            context.last_actions.pop_front();
+        }
+        context.last_actions.push_back(action);
+    });
+}
+
+// Contents of the crash report of the current thread
+fn report(panic: &str, backtrace: &str) -> String {
+    let mut report = format!(
+        "{} {} (engine {}) crash report\n\n{panic}\n\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        crate::compat::ENGINE_VERSION
+    );
+    CONTEXT.with_borrow(|context| match context.board {
+        Some(board) => {
+            let seed = context.seed.map_or("none (random spawns)".to_string(), |seed| seed.to_string());
+            let actions: Vec<String> = context.last_actions.iter().map(|action| format!("{action:?}")).collect();
+            writeln!(report, "position: {} after {} moves", board.compact(), context.num_moves).unwrap();
+            writeln!(report, "seed: {seed}").unwrap();
+            writeln!(report, "strategy: {}", context.strategy).unwrap();
+            writeln!(report, "last actions: {}", actions.join(" ")).unwrap();
+        }
+        None => report.push_str("no game in progress on this thread\n"),
+    });
+    write!(report, "\nbacktrace:\n{backtrace}").unwrap();
+    report
+}
+
+// Writes a report in `CRASH_DIR`, returning its path
+fn write_report(report: &str) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(CRASH_DIR)?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let path = PathBuf::from(CRASH_DIR).join(format!("crash-{timestamp}-{}.txt", std::process::id()));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        assert!(report("boom", "").contains("no game in progress"));
+
+        let board = Board::from_compact("0000/0000/0000/1100").unwrap();
+        start_game(&board, Some(42), "greedy");
+        for (i, action) in ALL_ACTIONS.into_iter().cycle().take(25).enumerate() {
+            record_move(action, &Board::from_compact(&format!("0000/0000/0000/{:x}000", i % 15 + 1)).unwrap());
+        }
+        let report = report("boom", "frame 0");
+        assert!(report.contains("boom"), "{report}");
+        assert!(report.contains("position: 0000/0000/0000/a000 after 25 moves"), "{report}");
+        assert!(report.contains("seed: 42\nstrategy: greedy\n"), "{report}");
+        // only the last actions
+        let actions = report.lines().find_map(|line| line.strip_prefix("last actions: ")).unwrap();
+        assert_eq!(actions.split(' ').count(), NUM_ACTIONS);
+        assert!(actions.starts_with("Down Left Right Up"), "{actions}");
+        assert!(report.ends_with("frame 0"));
+    }
+}
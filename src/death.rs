@@ -0,0 +1,123 @@
+//! Classification of the reason why a game was lost, from simple patterns of the final board.
+
+use crate::board::*;
+
+/// Main reason why a game was lost
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DeathCause {
+    /// A 4 (or an 8, in the levels of a ramp) spawned in the last free cell where a 2 would have
+    /// left a legal move
+    UnluckyFour,
+    /// The largest tile is not in a corner anymore
+    CornerLost,
+    /// Small tiles are stranded between larger ones and cannot be merged
+    StrandedTiles,
+    /// Equal tiles that would start a chain of merges are separated by another tile
+    BlockedChain,
+    /// None of the patterns above
+    Other,
+}
+
+pub const ALL_CAUSES: [DeathCause; 5] = [
+    DeathCause::UnluckyFour,
+    DeathCause::CornerLost,
+    DeathCause::StrandedTiles,
+    DeathCause::BlockedChain,
+    DeathCause::Other,
+];
+
+// A tile at least 2^STRANDED_GAP times smaller than the largest one is considered small
+const STRANDED_GAP: u8 = 4;
+// Number of stranded small tiles from which the board is considered jammed by them
+const MIN_STRANDED: usize = 2;
+
+impl DeathCause {
+    /// Name used in the game notation and in reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeathCause::UnluckyFour => "unlucky-four",
+            DeathCause::CornerLost => "corner-lost",
+            DeathCause::StrandedTiles => "stranded-tiles",
+            DeathCause::BlockedChain => "blocked-chain",
+            DeathCause::Other => "other",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<DeathCause> {
+        ALL_CAUSES.into_iter().find(|cause| cause.name() == name)
+    }
+}
+
+/// Classifies why the game ended on `board`, a position without any legal move.
+/// `last_spawn` is the tile that spawned just before, if any.
+pub fn classify(board: &Board, last_spawn: Option<Spawn>) -> DeathCause {
+    let cells = board.cells();
+    if let Some(spawn) = last_spawn.filter(|spawn| spawn.exponent >= 2) {
+        let mut with_two = *board;
+        with_two.set(spawn.row, spawn.col, 1);
+        if ALL_ACTIONS.iter().any(|&action| with_two.apply(action).is_some()) {
+            return DeathCause::UnluckyFour;
+        }
+    }
+
+    let max = board.max_tile();
+    if ![cells[0][0], cells[0][N - 1], cells[N - 1][0], cells[N - 1][N - 1]].contains(&max) {
+        return DeathCause::CornerLost;
+    }
+
+    let neighbours = |i: usize, j: usize| {
+        [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)]
+            .into_iter()
+            .filter_map(move |(di, dj): (isize, isize)| {
+                let (ni, nj) = (i.checked_add_signed(di)?, j.checked_add_signed(dj)?);
+                (ni < N && nj < N).then_some((ni, nj, di != 0 && dj != 0))
+            })
+    };
+    let mut stranded = 0;
+    let mut blocked_chain = false;
+    for i in 0..N {
+        for j in 0..N {
+            let tile = cells[i][j];
+            // orthogonal neighbours all larger
+            if tile + STRANDED_GAP <= max
+                && neighbours(i, j).filter(|&(.., diagonal)| !diagonal).all(|(ni, nj, _)| cells[ni][nj] > tile)
+            {
+                stranded += 1;
+            }
+            // an equal tile diagonally or two cells away on the same line
+            let diagonal_pair = neighbours(i, j).any(|(ni, nj, diagonal)| diagonal && cells[ni][nj] == tile);
+            let line_pair = (i + 2 < N && cells[i + 2][j] == tile) || (j + 2 < N && cells[i][j + 2] == tile);
+            blocked_chain |= tile != 0 && (diagonal_pair || line_pair);
+        }
+    }
+    if stranded >= MIN_STRANDED {
+        DeathCause::StrandedTiles
+    } else if blocked_chain {
+        DeathCause::BlockedChain
+    } else {
+        DeathCause::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cause(compact: &str, last_spawn: Option<Spawn>) -> DeathCause {
+        classify(&Board::from_compact(compact).unwrap(), last_spawn)
+    }
+
+    #[test]
+    fn test_classify() {
+        // a 2 in the top left corner would have merged with its neighbour
+        let spawn = Spawn { row: 0, col: 0, exponent: 2 };
+        assert_eq!(cause("2121/1212/2121/121b", Some(spawn)), DeathCause::UnluckyFour);
+        assert_eq!(cause("2121/1212/21b1/1212", None), DeathCause::CornerLost);
+        assert_eq!(cause("1575/5656/6567/567b", None), DeathCause::StrandedTiles);
+        assert_eq!(cause("2345/3456/4567/789b", None), DeathCause::BlockedChain);
+        assert_eq!(cause("1234/8765/9abc/fedf", None), DeathCause::Other);
+        for cause in ALL_CAUSES {
+            assert_eq!(DeathCause::from_name(cause.name()), Some(cause));
+        }
+    }
+}
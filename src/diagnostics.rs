@@ -0,0 +1,211 @@
+//! Diagnostics of long benchmark runs (`--diagnostics <games>`): every few games, the memory of
+//! the process (resident set, live heap, number of allocations), the caches of the searches and
+//! the histories kept for the final statistics are reported, so that a slow growth shows up
+//! long before a run of several hours degrades or runs out of memory.
+//!
+//! Hard caps on the resident set and on the live heap are checked every second; above one of
+//! them, the caches of the searches are trimmed (`cache::request_trim`). The allocator may keep
+//! the memory freed by a trim, so the resident set can stay high after it: the cap on the live
+//! heap, counted by `CountingAllocator`, is the reliable one.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::cache;
+
+// Interval between two checks of the caps
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, counting the allocations and the bytes allocated and not yet freed
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            LIVE_BYTES.fetch_add(new_size, Ordering::Relaxed);
+            LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+/// Number of allocations since the start of the process (with `CountingAllocator`)
+pub fn allocations() -> u64 {
+    ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+/// Bytes allocated and not yet freed (with `CountingAllocator`)
+pub fn live_bytes() -> u64 {
+    LIVE_BYTES.load(Ordering::Relaxed) as u64
+}
+
+/// Resident set size of the process, where the system tells it (Linux)
+pub fn rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{} MB", bytes / (1024 * 1024))
+}
+
+/// Memory above which the caches are trimmed, in megabytes
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Caps {
+    pub max_rss_mb: Option<u64>,
+    pub max_heap_mb: Option<u64>,
+}
+
+impl Caps {
+    // The measure above its cap, if any, as its name, its value and the cap
+    fn exceeded(&self) -> Option<(&'static str, u64, u64)> {
+        let rss = self.max_rss_mb.zip(rss_bytes()).map(|(cap, rss)| ("resident set", rss, cap));
+        let heap = self.max_heap_mb.map(|cap| ("live heap", live_bytes(), cap));
+        [rss, heap].into_iter().flatten().find(|&(_, bytes, cap)| bytes > cap * 1024 * 1024)
+    }
+
+    /// Checks the caps every second on a background thread for the rest of the process,
+    /// requesting a trim of the caches at every check above a cap.
+    pub fn watch(self) {
+        if self.max_rss_mb.is_none() && self.max_heap_mb.is_none() {
+            return;
+        }
+        std::thread::spawn(move || {
+            let mut above = false;
+            loop {
+                std::thread::sleep(CHECK_INTERVAL);
+                let exceeded = self.exceeded();
+                if let Some((name, bytes, cap)) = exceeded {
+                    // (reported once per crossing, the trims go on while above)
+                    if !above {
+                        println!("[Diagnostics] {name} of {} above the cap of {cap} MB: trimming the caches", megabytes(bytes));
+                    }
+                    cache::request_trim();
+                }
+                above = exceeded.is_some();
+            }
+        });
+    }
+}
+
+// What the finished games left behind
+#[derive(Default)]
+struct Tally {
+    games: u64,
+    /// Positions and moves of the games, kept until the end of the run
+    plies: usize,
+    moves: usize,
+    /// Largest cache at the end of a game since the last report: entries and capacity
+    largest_cache: Option<(usize, usize)>,
+    evictions: u64,
+    /// Allocations at the last report
+    allocations: u64,
+}
+
+/// Reports of the memory every `every` games
+pub struct Diagnostics {
+    every: u64,
+    start: Instant,
+    tally: Mutex<Tally>,
+}
+
+impl Diagnostics {
+    pub fn new(every: u64) -> Diagnostics {
+        Diagnostics { every: every.max(1), start: Instant::now(), tally: Mutex::new(Tally::default()) }
+    }
+
+    /// Counts a finished game, with the numbers of positions and moves kept for the
+    /// statistics and the usage of the cache of its search, reporting every `every` games.
+    pub fn game_finished(&self, plies: usize, moves: usize, cache: Option<(usize, usize, u64)>) {
+        let mut tally = self.tally.lock().unwrap();
+        tally.games += 1;
+        tally.plies += plies;
+        tally.moves += moves;
+        if let Some((len, capacity, evictions)) = cache {
+            if tally.largest_cache.is_none_or(|(largest, _)| len > largest) {
+                tally.largest_cache = Some((len, capacity));
+            }
+            tally.evictions += evictions;
+        }
+        if tally.games.is_multiple_of(self.every) {
+            println!("{}", self.report(&tally));
+            tally.largest_cache = None;
+            tally.evictions = 0;
+            tally.allocations = allocations();
+        }
+    }
+
+    fn report(&self, tally: &Tally) -> String {
+        let rss = rss_bytes().map_or("unknown".to_string(), megabytes);
+        let allocations = allocations();
+        let cache = match tally.largest_cache {
+            Some((len, capacity)) => format!("largest cache {len}/{capacity} entries, {} evictions", tally.evictions),
+            None => "no cache".to_string(),
+        };
+        format!(
+            "[Diagnostics] {} games in {:.0?}: resident set {rss}, live heap {}, {allocations} allocations (+{}), {cache}, {} positions and {} moves kept, {} cache trims",
+            tally.games,
+            self.start.elapsed(),
+            megabytes(live_bytes()),
+            allocations - tally.allocations,
+            tally.plies,
+            tally.moves,
+            cache::trim_requests()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostics() {
+        let diagnostics = Diagnostics::new(2);
+        diagnostics.game_finished(10, 9, Some((100, 1000, 0)));
+        diagnostics.game_finished(20, 19, Some((50, 1000, 5)));
+        diagnostics.game_finished(5, 4, None);
+        let tally = diagnostics.tally.lock().unwrap();
+        assert_eq!((tally.games, tally.plies, tally.moves), (3, 35, 32));
+        // reset by the report of the second game
+        assert_eq!((tally.largest_cache, tally.evictions), (None, 0));
+        let report = diagnostics.report(&tally);
+        assert!(report.contains("3 games") && report.contains("35 positions and 32 moves kept"), "{report}");
+
+        // no cap, nothing exceeded
+        assert!(Caps::default().exceeded().is_none());
+        assert_eq!(Caps { max_heap_mb: Some(0), ..Default::default() }.exceeded().is_some(), live_bytes() > 0);
+    }
+}
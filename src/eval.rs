@@ -1,38 +1,147 @@
 use crate::board::*;
+use crate::plugin;
 
 /// One line/column of the board
-type Row = [u8; N];
+pub type Row = [u8; N];
 
 pub fn eval(board: &Board) -> f32 {
+    eval_with(board, &Weights::default())
+}
+
+/// Evaluates the board with the given heuristic weights.
+/// The evaluation plugin of the process, if any, replaces the heuristic (see `plugin.rs`).
+pub fn eval_with(board: &Board, weights: &Weights) -> f32 {
+    if let Some(plugin) = plugin::active() {
+        return plugin.evaluate(board);
+    }
     let mut sum = 0.0;
-    for row in board.cells.iter() {
-        sum += eval_row(row);
+    for row in board.cells().iter() {
+        sum += eval_row(row, weights);
+    }
+    for col in board.transposed().cells().iter() {
+        sum += eval_row(col, weights);
+    }
+    // finding the locked lines requires playing all the actions, only done when weighted
+    if weights.locked != 0.0 {
+        sum += locked_of_board(board) * weights.locked;
     }
-    for col in board.transposed().cells.iter() {
-        sum += eval_row(col);
+    if weights.potential != 0.0 {
+        sum += potential_of_board(board) * weights.potential;
     }
     sum
 }
 
+/// Number of rows and columns that no action can change (see `Board::locked_lines`).
+/// Such walls of tiles stay where they are: a stability bonus when they hold the large tiles,
+/// but also lines that cannot be used for merges anymore.
+pub fn locked_of_board(board: &Board) -> f32 {
+    let (rows, cols) = board.locked_lines();
+    rows.iter().chain(&cols).filter(|&&locked| locked).count() as f32
+}
+
+/// Exponent of the largest tile the board could build without new tiles (see
+/// `Board::potential`): rewards keeping the tiles mergeable rather than walling small ones in.
+pub fn potential_of_board(board: &Board) -> f32 {
+    board.potential() as f32
+}
+
 const NOT_LOST: f32 = 200_000f32;
 const MONOTONICITY_WEIGHT: f32 = 47.0;
 const EMPTY_WEIGHT: f32 = 270.0;
 const ADJACENT_WEIGHT: f32 = 700.0;
 const SUM_WEIGHT: f32 = 11.0;
+const SMOOTHNESS_WEIGHT: f32 = 0.0;
+const LOCKED_WEIGHT: f32 = 0.0;
+const POTENTIAL_WEIGHT: f32 = 0.0;
+
+/// Weights of each component of the heuristic, tunable at runtime.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weights {
+    pub monotonicity: f32,
+    pub empty: f32,
+    pub adjacent: f32,
+    pub sum: f32,
+    pub smoothness: f32,
+    /// Weight of the locked lines, the only component that is not computed line by line
+    pub locked: f32,
+    /// Weight of the potential of the board (`Board::potential`), not computed line by line
+    /// either
+    pub potential: f32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            monotonicity: MONOTONICITY_WEIGHT,
+            empty: EMPTY_WEIGHT,
+            adjacent: ADJACENT_WEIGHT,
+            sum: SUM_WEIGHT,
+            smoothness: SMOOTHNESS_WEIGHT,
+            locked: LOCKED_WEIGHT,
+            potential: POTENTIAL_WEIGHT,
+        }
+    }
+}
+
+impl Weights {
+    /// Sets the weight of the component with the given name, failing on unknown names and on
+    /// infinite or NaN values (which would make all the values of the search NaN).
+    pub fn set(&mut self, name: &str, value: f32) -> anyhow::Result<()> {
+        anyhow::ensure!(value.is_finite(), "invalid weight {value} for `{name}`");
+        match name {
+            "monotonicity" => self.monotonicity = value,
+            "empty" => self.empty = value,
+            "adjacent" => self.adjacent = value,
+            "sum" => self.sum = value,
+            "smoothness" => self.smoothness = value,
+            "locked" => self.locked = value,
+            "potential" => self.potential = value,
+            _ => anyhow::bail!("unknown weight `{name}` (expected monotonicity, empty, adjacent, sum, smoothness, locked or potential)"),
+        }
+        Ok(())
+    }
+
+    /// Parses weights written one per line, `name = value` with the names of `set`; blank lines
+    /// and `#` comments are ignored, the weights not given keep their default value.
+    pub fn parse(text: &str) -> anyhow::Result<Weights> {
+        let mut weights = Weights::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let parsed = line
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("expected `name = value`"))
+                .and_then(|(name, value)| {
+                    let value: f32 = value.trim().parse().map_err(|_| anyhow::anyhow!("invalid value `{}`", value.trim()))?;
+                    weights.set(name.trim(), value)
+                });
+            parsed.map_err(|e| anyhow::anyhow!("line {}: {e}", i + 1))?;
+        }
+        Ok(weights)
+    }
+}
 
-fn eval_row(row: &Row) -> f32 {
+/// Heuristic value of a single row (or column), before summing over all lines of the board.
+pub fn eval_row(row: &Row, weights: &Weights) -> f32 {
     NOT_LOST
-        + monotonicity(row) * MONOTONICITY_WEIGHT
-        + empty(row) * EMPTY_WEIGHT
-        + adjacent(row) * ADJACENT_WEIGHT
-        + sum(row) * SUM_WEIGHT
+        + monotonicity_of_row(row) * weights.monotonicity
+        + empty_of_row(row) * weights.empty
+        + adjacent_of_row(row) * weights.adjacent
+        + sum_of_row(row) * weights.sum
+        + smoothness_of_row(row) * weights.smoothness
 }
 
-fn empty(row: &Row) -> f32 {
+/// Number of empty cells of the row.
+pub fn empty_of_row(row: &Row) -> f32 {
     row.iter().filter(|&&cell| cell == 0).count() as f32
 }
 
-fn monotonicity(row: &Row) -> f32 {
+/// Penalty (<= 0) for rows that are not monotonic: the smallest of the total increase and the
+/// total decrease along the row, measured on the 4th power of the exponents.
+/// A row sorted in either direction has a monotonicity of 0.
+pub fn monotonicity_of_row(row: &Row) -> f32 {
     let mut left = 0;
     let mut right = 0;
 
@@ -49,7 +158,10 @@ fn monotonicity(row: &Row) -> f32 {
     -left.min(right) as f32
 }
 
-fn adjacent(row: &Row) -> f32 {
+/// Number of merges available in the row: pairs of equal neighbouring tiles, each tile
+/// being counted in at most one pair (`[2, 2, 2, 0]` has a single merge).
+/// Empty cells are not skipped: `[2, 0, 2, 0]` has no adjacent merge.
+pub fn adjacent_of_row(row: &Row) -> f32 {
     let mut adjacent_count = 0;
     let mut i = 0;
 
@@ -65,12 +177,80 @@ fn adjacent(row: &Row) -> f32 {
     adjacent_count as f32
 }
 
-fn sum(row: &Row) -> f32 {
+/// Penalty (<= 0) for large tiles: `-sum(exponent^3.5)`, so that merging tiles is always rewarded.
+pub fn sum_of_row(row: &Row) -> f32 {
     -row.iter().map(|&v| POW_3_5_LOOKUP[v as usize]).sum::<f32>()
 }
 
+/// Penalty (<= 0) for rough rows: minus the sum of the exponent differences between
+/// consecutive tiles, ignoring the empty cells.
+pub fn smoothness_of_row(row: &Row) -> f32 {
+    let mut roughness = 0;
+    let mut previous: Option<i32> = None;
+    for &cell in row.iter().filter(|&&cell| cell != 0) {
+        if let Some(previous) = previous {
+            roughness += (i32::from(cell) - previous).abs();
+        }
+        previous = Some(i32::from(cell));
+    }
+    -roughness as f32
+}
+
 /// lookup table: `POW_3_5_LOOKUP[i]` is equal to `i^3.5` but faster to compute
 const POW_3_5_LOOKUP: [f32; 18] = [
     0.0, 1.0, 11.313708, 46.765373, 128.0, 279.50848, 529.0898, 907.4927, 1448.1547, 2187.0,
     3162.2776, 4414.4277, 5985.968, 7921.396, 10267.107, 13071.318, 16384.0, 20256.818,
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_of_row() {
+        assert_eq!(empty_of_row(&[0, 0, 0, 0]), 4.0);
+        assert_eq!(empty_of_row(&[1, 0, 3, 0]), 2.0);
+        assert_eq!(empty_of_row(&[1, 2, 3, 4]), 0.0);
+    }
+
+    #[test]
+    fn test_monotonicity_of_row() {
+        assert_eq!(monotonicity_of_row(&[1, 2, 3, 4]), 0.0);
+        assert_eq!(monotonicity_of_row(&[4, 3, 0, 0]), 0.0);
+        // increase of 2^4 - 1^4 = 15 then decrease of 2^4 - 0 = 16
+        assert_eq!(monotonicity_of_row(&[1, 2, 0, 0]), -15.0);
+    }
+
+    #[test]
+    fn test_adjacent_of_row() {
+        assert_eq!(adjacent_of_row(&[1, 1, 2, 2]), 2.0);
+        assert_eq!(adjacent_of_row(&[1, 1, 1, 0]), 1.0);
+        assert_eq!(adjacent_of_row(&[1, 0, 1, 0]), 0.0);
+        assert_eq!(adjacent_of_row(&[0, 0, 0, 0]), 0.0);
+    }
+
+    #[test]
+    fn test_sum_of_row() {
+        assert_eq!(sum_of_row(&[0, 0, 0, 0]), 0.0);
+        assert_eq!(sum_of_row(&[1, 4, 0, 0]), -(1.0 + 128.0));
+    }
+
+    #[test]
+    fn test_parse_weights() {
+        let weights = Weights::parse("# tuning run 3\nempty = 300\n\n  sum=0.5  # halved\n").unwrap();
+        assert_eq!(weights, Weights { empty: 300.0, sum: 0.5, ..Weights::default() });
+        assert_eq!(Weights::parse("").unwrap(), Weights::default());
+        assert!(Weights::parse("empty 300").is_err());
+        assert!(Weights::parse("empty = lots").is_err());
+        assert!(Weights::parse("emptiness = 1").is_err());
+        assert!(Weights::parse("empty = NaN").is_err());
+        assert!(Weights::parse("sum = inf").is_err());
+    }
+
+    #[test]
+    fn test_smoothness_of_row() {
+        assert_eq!(smoothness_of_row(&[3, 3, 3, 3]), 0.0);
+        assert_eq!(smoothness_of_row(&[1, 0, 3, 2]), -3.0);
+        assert_eq!(smoothness_of_row(&[0, 5, 0, 0]), 0.0);
+    }
+}
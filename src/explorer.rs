@@ -0,0 +1,225 @@
+//! "What if" explorer of the analysis mode: an interactive browser of the expectimax tree. From
+//! the analyzed position, the user plays an action, chooses the tile that spawns (or takes the
+//! most probable one), plays again, and so on, with the values of the search shown at every
+//! node. The path followed is kept as breadcrumbs to walk back up.
+//!
+//! Decision nodes carry the value of every action, as in the analysis mode. Chance nodes carry
+//! the value of every spawn, i.e. the value of the best action once the tile is placed,
+//! searched at the same depth. The values of a node are computed when it is entered and kept
+//! along the path, so walking back up is immediate.
+
+use crate::board::*;
+use crate::search::{SearchParams, Searcher};
+
+/// A tile that can spawn at a chance node, with its value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpawnValue {
+    pub spawn: Spawn,
+    pub probability: f32,
+    /// Value of the best action once the tile is placed, 0 when no action is left
+    pub value: f32,
+}
+
+/// A node of the tree, with the values computed when it was entered
+#[derive(Clone)]
+pub enum Node {
+    /// The player moves: the values of `ALL_ACTIONS`, `None` for the illegal ones
+    Decision { board: PlayableBoard, values: [Option<f32>; 4] },
+    /// A tile spawns: every possible tile, empty cells row by row
+    Chance { board: RandableBoard, spawns: Vec<SpawnValue> },
+}
+
+impl Node {
+    fn decision(board: PlayableBoard, searcher: &mut Searcher, params: &SearchParams) -> Node {
+        Node::Decision { board, values: searcher.action_values(board, params) }
+    }
+
+    fn chance(board: RandableBoard, searcher: &mut Searcher, params: &SearchParams) -> Node {
+        let num_empty = board.board().num_empty() as f32;
+        let mut spawns = Vec::new();
+        for row in 0..N {
+            for col in 0..N {
+                for (exponent, proba) in crate::rules::rules().spawn_probabilities(&board.board()) {
+                    let spawn = Spawn { row, col, exponent };
+                    let Some(succ) = board.with_spawn(spawn).filter(|_| proba > 0.0) else {
+                        continue;
+                    };
+                    let value = searcher.action_values(succ, params).into_iter().flatten().fold(0.0, f32::max);
+                    spawns.push(SpawnValue { spawn, probability: proba / num_empty, value });
+                }
+            }
+        }
+        Node::Chance { board, spawns }
+    }
+
+    /// The board of the node
+    pub fn board(&self) -> Board {
+        match self {
+            Node::Decision { board, .. } => board.board(),
+            Node::Chance { board, .. } => board.board(),
+        }
+    }
+
+    /// Value of the node: the one of its best action, or the expectation of its spawns.
+    /// `None` when the game is lost.
+    pub fn value(&self) -> Option<f32> {
+        match self {
+            Node::Decision { values, .. } => values.iter().flatten().copied().reduce(f32::max),
+            Node::Chance { spawns, .. } => Some(spawns.iter().map(|s| s.probability * s.value).sum()),
+        }
+    }
+
+    /// The action of best value of a decision node
+    pub fn best_action(&self) -> Option<Action> {
+        let Node::Decision { values, .. } = self else {
+            return None;
+        };
+        ALL_ACTIONS
+            .into_iter()
+            .zip(values)
+            .filter_map(|(action, value)| value.map(|v| (action, v)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(action, _)| action)
+    }
+
+    /// The most probable spawn of a chance node; among equally probable ones, the worst for
+    /// the player, the one worth looking at.
+    pub fn most_probable_spawn(&self) -> Option<Spawn> {
+        let Node::Chance { spawns, .. } = self else {
+            return None;
+        };
+        spawns
+            .iter()
+            .max_by(|a, b| a.probability.total_cmp(&b.probability).then(b.value.total_cmp(&a.value)))
+            .map(|s| s.spawn)
+    }
+}
+
+/// An edge of the path followed in the tree
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    Play(Action),
+    Spawn(Spawn),
+}
+
+/// The path followed from the analyzed position: `nodes[0]` is the position, `steps[i]` leads
+/// from `nodes[i]` to `nodes[i + 1]`.
+pub struct Explorer {
+    nodes: Vec<Node>,
+    steps: Vec<Step>,
+}
+
+impl Explorer {
+    pub fn new(board: PlayableBoard, searcher: &mut Searcher, params: &SearchParams) -> Explorer {
+        Explorer { nodes: vec![Node::decision(board, searcher, params)], steps: Vec::new() }
+    }
+
+    /// The current node, the end of the path
+    pub fn node(&self) -> &Node {
+        self.nodes.last().expect("the path starts at the analyzed position")
+    }
+
+    /// Number of steps from the analyzed position
+    pub fn depth(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Number of actions played from the analyzed position
+    pub fn num_moves(&self) -> usize {
+        self.steps.iter().filter(|step| matches!(step, Step::Play(_))).count()
+    }
+
+    /// Descends into the position after `action`, if the current node is a decision node where
+    /// it is legal. Returns whether it did.
+    pub fn play(&mut self, action: Action, searcher: &mut Searcher, params: &SearchParams) -> bool {
+        let Node::Decision { board, .. } = self.node() else {
+            return false;
+        };
+        let Some(succ) = board.apply(action) else {
+            return false;
+        };
+        self.nodes.push(Node::chance(succ, searcher, params));
+        self.steps.push(Step::Play(action));
+        true
+    }
+
+    /// Descends into the position after `spawn`, if the current node is a chance node where
+    /// it can happen. Returns whether it did.
+    pub fn spawn(&mut self, spawn: Spawn, searcher: &mut Searcher, params: &SearchParams) -> bool {
+        let Node::Chance { board, spawns } = self.node() else {
+            return false;
+        };
+        if !spawns.iter().any(|s| s.spawn == spawn) {
+            return false;
+        }
+        let succ = board.with_spawn(spawn).expect("the spawns of the node are on empty cells");
+        self.nodes.push(Node::decision(succ, searcher, params));
+        self.steps.push(Step::Spawn(spawn));
+        true
+    }
+
+    /// Walks back up one step, returning false at the analyzed position.
+    pub fn back(&mut self) -> bool {
+        if self.steps.is_empty() {
+            return false;
+        }
+        self.back_to(self.depth() - 1);
+        true
+    }
+
+    /// Walks back up to the node at `depth` steps from the analyzed position.
+    pub fn back_to(&mut self, depth: usize) {
+        self.nodes.truncate(depth + 1);
+        self.steps.truncate(depth);
+    }
+
+    /// Labels of the nodes of the path, from the analyzed position
+    pub fn breadcrumbs(&self) -> Vec<String> {
+        let steps = self.steps.iter().map(|step| match step {
+            Step::Play(action) => format!("{action:?}"),
+            Step::Spawn(spawn) => format!("{} at {},{}", 2u32.pow(spawn.exponent as u32), spawn.row, spawn.col),
+        });
+        std::iter::once("Start".to_string()).chain(steps).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explore() {
+        let params = SearchParams { depth: 1, ..Default::default() };
+        let mut searcher = Searcher::default();
+        let board = PlayableBoard::from_board(Board::from_compact("1100/0000/0000/0002").unwrap());
+        let mut explorer = Explorer::new(board, &mut searcher, &params);
+        let Node::Decision { values, .. } = explorer.node() else { panic!("the analyzed position is a decision node") };
+        let best = explorer.node().best_action().unwrap();
+        assert_eq!(values[ALL_ACTIONS.iter().position(|&a| a == best).unwrap()], explorer.node().value());
+        // nothing spawns at a decision node
+        assert!(!explorer.spawn(Spawn { row: 1, col: 1, exponent: 1 }, &mut searcher, &params));
+
+        assert!(explorer.play(Action::Left, &mut searcher, &params));
+        let Node::Chance { spawns, .. } = explorer.node() else { panic!("a tile spawns after a move") };
+        // 2 tiles on each of the 14 empty cells of "2000/0000/0000/2000"
+        assert_eq!(spawns.len(), 28);
+        assert!((spawns.iter().map(|s| s.probability).sum::<f32>() - 1.0).abs() < 1e-5);
+        let spawn = explorer.node().most_probable_spawn().unwrap();
+        assert_eq!(spawn.exponent, 1);
+        // the value of a spawn is the value of the best action after it
+        let value = spawns.iter().find(|s| s.spawn == spawn).unwrap().value;
+        assert!(!explorer.play(Action::Right, &mut searcher, &params));
+        assert!(!explorer.spawn(Spawn { row: 0, col: 0, exponent: 1 }, &mut searcher, &params));
+        assert!(explorer.spawn(spawn, &mut searcher, &params));
+        assert_eq!(explorer.node().value(), Some(value));
+
+        assert!(explorer.play(Action::Up, &mut searcher, &params));
+        assert_eq!(explorer.num_moves(), 2);
+        assert_eq!(explorer.breadcrumbs()[..3], ["Start", "Left", &format!("2 at {},{}", spawn.row, spawn.col)]);
+        assert!(explorer.back());
+        assert_eq!((explorer.depth(), explorer.node().value()), (2, Some(value)));
+        explorer.back_to(0);
+        assert_eq!(explorer.node().board(), board.board());
+        assert!(!explorer.back());
+    }
+}
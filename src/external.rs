@@ -0,0 +1,165 @@
+//! A strategy delegating the moves to an external engine, a separate process speaking a text
+//! protocol on its standard input and output, so that bots written in any language can play.
+//!
+//! Protocol (one command per line):
+//!
+//! ```text
+//! > 2048 1                      protocol name and version, sent once at start
+//! < ready                       the engine is ready
+//! > position 1210/4100/3000/000b    position to play, in the compact board notation
+//! > go movetime 500             search the position, with an optional time limit in ms
+//! < bestmove L                  the chosen action (U, D, L or R), `none` if no move is legal
+//! > quit                        the engine must exit
+//! ```
+//!
+//! Lines sent by the engine that are not part of the protocol (e.g. `info ...`) are ignored.
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+
+use crate::board::*;
+use crate::search::SearchResult;
+use crate::strategy::{Budget, Strategy};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+// Time allowed to the engine to start and to answer when the budget has no time limit
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+// Extra time allowed over the budget to receive the answer
+const TIMEOUT_MARGIN: Duration = Duration::from_millis(200);
+
+/// An engine running in a child process
+pub struct ExternalStrategy {
+    command: String,
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines printed by the engine, read on a separate thread to be able to time out
+    lines: Receiver<String>,
+}
+
+impl ExternalStrategy {
+    /// Starts the engine with the given shell command and waits for it to be ready.
+    pub fn spawn(command: &str) -> anyhow::Result<ExternalStrategy> {
+        let shell = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+        let mut child = Command::new(shell.0)
+            .args([shell.1, command])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Could not start the engine `{command}`"))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = ExternalStrategy { command: command.to_string(), child, stdin, lines };
+        engine.send(&format!("2048 {PROTOCOL_VERSION}"))?;
+        engine.expect("ready", DEFAULT_TIMEOUT)?;
+        Ok(engine)
+    }
+
+    fn send(&mut self, line: &str) -> anyhow::Result<()> {
+        writeln!(self.stdin, "{line}")
+            .and_then(|_| self.stdin.flush())
+            .context("the engine closed its input")
+    }
+
+    /// Waits for a line starting with `keyword`, returning the rest of the line.
+    fn expect(&mut self, keyword: &str, timeout: Duration) -> anyhow::Result<String> {
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(std::time::Instant::now());
+            let line = self
+                .lines
+                .recv_timeout(left)
+                .with_context(|| format!("no `{keyword}` from the engine within {timeout:?}"))?;
+            if let Some(rest) = line.trim().strip_prefix(keyword) {
+                return Ok(rest.trim().to_string());
+            }
+        }
+    }
+
+    fn best_move(&mut self, board: PlayableBoard, budget: Budget) -> anyhow::Result<Option<Action>> {
+        self.send(&format!("position {}", board.board().compact()))?;
+        match budget.time {
+            Some(time) => self.send(&format!("go movetime {}", time.as_millis()))?,
+            None => self.send("go")?,
+        }
+        let timeout = budget.time.map_or(DEFAULT_TIMEOUT, |time| time + TIMEOUT_MARGIN);
+        let action = match self.expect("bestmove", timeout)?.as_str() {
+            "none" => return Ok(None),
+            "U" => Action::Up,
+            "D" => Action::Down,
+            "L" => Action::Left,
+            "R" => Action::Right,
+            other => bail!("invalid move `{other}`"),
+        };
+        if board.apply(action).is_none() {
+            bail!("illegal move {action:?} on\n{board}");
+        }
+        Ok(Some(action))
+    }
+}
+
+impl Strategy for ExternalStrategy {
+    fn name(&self) -> String {
+        format!("external ({})", self.command)
+    }
+
+    /// An engine that fails (crash, timeout, illegal move) forfeits the game.
+    fn choose(&mut self, board: PlayableBoard, budget: Budget) -> Option<SearchResult> {
+        let start = Instant::now();
+        let action = self.best_move(board, budget).unwrap_or_else(|e| {
+            println!("Engine `{}` forfeits: {e:#}", self.command);
+            None
+        })?;
+        Some(SearchResult::unsearched(action, start.elapsed()))
+    }
+}
+
+impl Drop for ExternalStrategy {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // give the engine a moment to exit by itself before killing it
+        for _ in 0..10 {
+            if matches!(self.child.try_wait(), Ok(Some(_))) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_external_engine() {
+        // an engine always pushing to the left
+        let script = r#"while read cmd arg; do
+            case "$cmd" in
+                2048) echo ready ;;
+                go) echo "info thinking"; echo "bestmove L" ;;
+                quit) exit 0 ;;
+            esac
+        done"#;
+        let mut engine = ExternalStrategy::spawn(script).unwrap();
+        let board = PlayableBoard::from_board(Board::from_compact("0000/0000/0000/0011").unwrap());
+        assert_eq!(engine.choose(board, Budget::default()).map(|result| result.best), Some(Action::Left));
+        // pushing left is not legal here: the engine forfeits
+        let board = PlayableBoard::from_board(Board::from_compact("1000/0000/0000/0000").unwrap());
+        assert_eq!(engine.choose(board, Budget::default()), None);
+    }
+}
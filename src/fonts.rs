@@ -0,0 +1,67 @@
+//! Text of the tiles, drawn with a bundled TTF font (`assets/`) rasterized at the exact size
+//! it is displayed at: the default bitmap font of macroquad is blurry once scaled up.
+//!
+//! The size of the text depends on the value of the tile, so that large values (5 digits and
+//! more) fit in the tile. Sizes and measurements are computed once per value.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use macroquad::prelude::*;
+
+// DejaVu Sans Mono Bold, see `assets/DejaVu-LICENSE.txt`
+const TILE_FONT: &[u8] = include_bytes!("../assets/DejaVuSansMono-Bold.ttf");
+// Largest part of the width of a tile taken by its text
+const MAX_TEXT_WIDTH: f32 = 0.8;
+
+thread_local! {
+    // `None` if the font could not be loaded, the default font is used instead
+    static FONT: Option<Font> = load_ttf_font_from_bytes(TILE_FONT)
+        .inspect_err(|e| eprintln!("Could not load the font of the tiles: {e}"))
+        .ok();
+    // Font size and dimensions of the text of each value, for the current tile size
+    static MEASURES: RefCell<HashMap<(u32, u32), (u16, TextDimensions)>> = RefCell::new(HashMap::new());
+}
+
+/// Largest font size, at most `base`, whose text fits in `max_width`, given the `width` of the
+/// text at the size `base`
+pub fn fit_font_size(base: f32, width: f32, max_width: f32) -> u16 {
+    let size = if width > max_width { base * max_width / width } else { base };
+    size.floor().max(1.0) as u16
+}
+
+/// Draws the value of a tile centered in the tile, whose top left corner is at `(x, y)`.
+/// `base_size` is the font size for the values that fit.
+pub fn draw_tile_value(value: u32, x: f32, y: f32, tile_size: f32, base_size: f32, color: Color) {
+    FONT.with(|font| {
+        let text = crate::layout::layout().numbers.format(value as u64);
+        let (font_size, dimensions) = MEASURES.with_borrow_mut(|measures| {
+            *measures.entry((value, tile_size.to_bits())).or_insert_with(|| {
+                let base = measure_text(&text, font.as_ref(), base_size as u16, 1.0);
+                let font_size = fit_font_size(base_size, base.width, tile_size * MAX_TEXT_WIDTH);
+                (font_size, measure_text(&text, font.as_ref(), font_size, 1.0))
+            })
+        });
+        // the text is placed by its baseline: centered on the height of the glyphs
+        let text_x = x + (tile_size - dimensions.width) / 2.0;
+        let text_y = y + (tile_size - dimensions.height) / 2.0 + dimensions.offset_y;
+        draw_text_ex(
+            &text,
+            text_x.round(),
+            text_y.round(),
+            TextParams { font: font.as_ref(), font_size, font_scale: 1.0, color, ..Default::default() },
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_font_size() {
+        assert_eq!(fit_font_size(40.0, 60.0, 100.0), 40);
+        assert_eq!(fit_font_size(40.0, 200.0, 100.0), 20);
+        assert_eq!(fit_font_size(40.0, 10_000.0, 1.0), 1);
+    }
+}
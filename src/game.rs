@@ -0,0 +1,272 @@
+//! The model of a game, independent of who chooses the moves and of how the game is shown. The
+//! window and the benchmark drive the same state machine, feeding it the moves of the player and
+//! the tiles of the chance player, so that the rules (score, decay, target tile, move limit, end
+//! of the game) and the record of the game are applied in one place.
+
+use anyhow::{bail, Context};
+
+use crate::board::*;
+use crate::notation::{GameOutcome, GameRecord};
+use crate::spawn::SpawnStream;
+
+/// Where the game stands
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    /// Waiting for the move of the player
+    Playing,
+    /// A move was played, waiting for the tile spawned after it
+    ChancePending,
+    /// The target tile of the rules was just reached; the player may keep going
+    Won,
+    /// No legal move is left, or the move limit of the rules is reached
+    Lost,
+    /// Nothing is played until the game is resumed
+    Paused,
+}
+
+/// What drives the game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    /// The move of the player
+    Play(Action),
+    /// The tile of the chance player
+    Spawn(Spawn),
+    Pause,
+    Resume,
+    /// The player gives up while moves are left: the game is lost
+    Resign,
+}
+
+/// What a step changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The move was played, gaining `gained` points
+    Played { action: Action, gained: u32 },
+    /// The tile spawned, then the rules removed the tile at `decayed`, if any. The game may be
+    /// won or lost from there.
+    Spawned { spawn: Spawn, decayed: Option<(usize, usize)> },
+    Paused,
+    Resumed,
+    Resigned,
+}
+
+/// A game being played
+pub struct Game {
+    state: State,
+    /// State left by the pause
+    resumed: State,
+    /// The position of the last move of the player
+    board: PlayableBoard,
+    /// The move waiting for its spawn, with the position after it
+    pending: Option<(Action, RandableBoard)>,
+    num_moves: usize,
+    score: u32,
+    /// Set once the target tile is reached, the game going on
+    won: bool,
+    record: GameRecord,
+}
+
+impl Game {
+    pub fn new(init: PlayableBoard) -> Game {
+        let mut game = Game {
+            state: State::Playing,
+            resumed: State::Playing,
+            board: init,
+            pending: None,
+            num_moves: 0,
+            score: 0,
+            won: false,
+            record: GameRecord::new(init.board()),
+        };
+        game.settle();
+        game
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// The position the player moves from (the one before the pending spawn, if any)
+    pub fn board(&self) -> PlayableBoard {
+        self.board
+    }
+
+    pub fn num_moves(&self) -> usize {
+        self.num_moves
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    /// Whether the target tile of the rules has been reached
+    pub fn has_won(&self) -> bool {
+        self.won
+    }
+
+    /// Whether the game is lost, paused or not
+    pub fn is_over(&self) -> bool {
+        self.state == State::Lost || (self.state == State::Paused && self.resumed == State::Lost)
+    }
+
+    pub fn record(&self) -> &GameRecord {
+        &self.record
+    }
+
+    /// The record, e.g. to log a switch of strategy or a timeout
+    pub fn record_mut(&mut self) -> &mut GameRecord {
+        &mut self.record
+    }
+
+    pub fn into_record(self) -> GameRecord {
+        self.record
+    }
+
+    /// Applies `input`, failing without any change when it is not expected in the current state
+    /// (e.g. a move before the pending spawn) or not legal (a move that moves nothing, a tile on
+    /// an occupied cell).
+    pub fn step(&mut self, input: Input) -> anyhow::Result<Event> {
+        match (self.state, input) {
+            (State::Playing | State::Won, Input::Play(action)) => {
+                let (played, gained) = self
+                    .board
+                    .apply_scored(action)
+                    .with_context(|| format!("{action:?} is not legal on\n{}", self.board))?;
+                self.num_moves += 1;
+                self.score += gained;
+                self.pending = Some((action, played));
+                self.state = State::ChancePending;
+                Ok(Event::Played { action, gained })
+            }
+            (State::ChancePending, Input::Spawn(spawn)) => {
+                let (action, played) = self.pending.expect("a move is pending");
+                let next = played.with_spawn(spawn).with_context(|| format!("the cell {},{} is not empty", spawn.row, spawn.col))?;
+                self.pending = None;
+                self.record.push(action, spawn);
+                let mut next = next.board();
+                let decayed = crate::rules::rules().decay(&mut next, self.num_moves);
+                self.board = PlayableBoard::from_board(next);
+                self.state = State::Playing;
+                self.settle();
+                Ok(Event::Spawned { spawn, decayed })
+            }
+            (State::Paused, Input::Resume) => {
+                self.state = self.resumed;
+                Ok(Event::Resumed)
+            }
+            (state, Input::Pause) if state != State::Paused => {
+                self.resumed = self.state;
+                self.state = State::Paused;
+                Ok(Event::Paused)
+            }
+            (State::Playing | State::Won, Input::Resign) => {
+                self.record.lose(&self.board.board());
+                self.state = State::Lost;
+                Ok(Event::Resigned)
+            }
+            (state, input) => bail!("{input:?} is not expected while the game is {state:?}"),
+        }
+    }
+
+    /// Spawns the next tile of `spawns` after the pending move.
+    pub fn spawn_from(&mut self, spawns: &mut impl SpawnStream) -> anyhow::Result<Event> {
+        let Some((_, played)) = self.pending else {
+            bail!("no tile spawns while the game is {:?}", self.state);
+        };
+        self.step(Input::Spawn(spawns.next_spawn(&played.board())))
+    }
+
+    // Ends the game, or marks it won, in the position reached
+    fn settle(&mut self) {
+        let rules = crate::rules::rules();
+        let reached = !self.won && rules.is_won(&self.board.board());
+        self.won |= reached;
+        if self.board.legal_actions().is_empty() {
+            self.record.lose(&self.board.board());
+            self.state = State::Lost;
+        } else if rules.move_limit_reached(self.num_moves) {
+            // the game ends on the rules, not on the board: there is no cause of death
+            self.record.outcome = GameOutcome::Lost;
+            self.state = State::Lost;
+        } else if reached {
+            self.state = State::Won;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn::ScriptedSpawns;
+
+    fn game(compact: &str) -> Game {
+        Game::new(PlayableBoard::from_board(Board::from_compact(compact).unwrap()))
+    }
+
+    #[test]
+    fn test_game() {
+        let mut game = game("0000/0000/0000/0011");
+        assert_eq!(game.state(), State::Playing);
+        // nothing spawns before a move, and illegal moves change nothing
+        assert!(game.step(Input::Spawn(Spawn { row: 0, col: 0, exponent: 1 })).is_err());
+        assert!(game.step(Input::Play(Action::Down)).is_err());
+        assert_eq!(game.step(Input::Play(Action::Left)).unwrap(), Event::Played { action: Action::Left, gained: 4 });
+        assert_eq!(game.state(), State::ChancePending);
+        // no other move until the tile spawns, on an empty cell, even across a pause
+        assert!(game.step(Input::Play(Action::Right)).is_err());
+        game.step(Input::Pause).unwrap();
+        assert!(game.step(Input::Pause).is_err());
+        game.step(Input::Resume).unwrap();
+        assert_eq!(game.state(), State::ChancePending);
+        assert!(game.step(Input::Spawn(Spawn { row: 3, col: 0, exponent: 1 })).is_err());
+        let spawn = Spawn { row: 0, col: 0, exponent: 1 };
+        assert_eq!(game.step(Input::Spawn(spawn)).unwrap(), Event::Spawned { spawn, decayed: None });
+        assert_eq!((game.state(), game.num_moves(), game.score()), (State::Playing, 1, 4));
+        assert_eq!(game.board().board().compact(), "1000/0000/0000/2000");
+        assert_eq!(game.record().moves, [(Action::Left, spawn)]);
+
+        // paused, nothing is played until resumed
+        game.step(Input::Pause).unwrap();
+        assert!(game.step(Input::Play(Action::Down)).is_err());
+        game.step(Input::Resume).unwrap();
+        game.step(Input::Play(Action::Down)).unwrap();
+        let mut spawns = ScriptedSpawns::new([Spawn { row: 0, col: 3, exponent: 2 }]);
+        game.spawn_from(&mut spawns).unwrap();
+        assert_eq!(game.board().board().compact(), "0002/0000/1000/2000");
+        assert!(game.spawn_from(&mut spawns).is_err());
+
+        game.step(Input::Resign).unwrap();
+        assert!(game.is_over());
+        assert_eq!(game.record().outcome, GameOutcome::Lost);
+        assert!(game.step(Input::Play(Action::Up)).is_err());
+    }
+
+    #[test]
+    fn test_game_ends() {
+        // the target tile is reached, the game goes on
+        let mut game = game("0000/0000/0000/00aa");
+        game.step(Input::Play(Action::Left)).unwrap();
+        game.step(Input::Spawn(Spawn { row: 0, col: 0, exponent: 1 })).unwrap();
+        assert_eq!(game.state(), State::Won);
+        assert!(game.has_won());
+        game.step(Input::Play(Action::Right)).unwrap();
+        game.step(Input::Spawn(Spawn { row: 0, col: 0, exponent: 1 })).unwrap();
+        assert_eq!(game.state(), State::Playing);
+
+        // no legal move left after the spawn
+        let mut game = game_lost_after_spawn();
+        assert_eq!(game.state(), State::Lost);
+        assert!(game.record().cause.is_some());
+        game.step(Input::Pause).unwrap();
+        assert!(game.is_over());
+        game.step(Input::Resume).unwrap();
+        assert_eq!(game.state(), State::Lost);
+    }
+
+    fn game_lost_after_spawn() -> Game {
+        let mut game = game("1212/2121/1212/0212");
+        game.step(Input::Play(Action::Left)).unwrap();
+        game.step(Input::Spawn(Spawn { row: 3, col: 3, exponent: 3 })).unwrap();
+        game
+    }
+}
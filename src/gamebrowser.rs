@@ -0,0 +1,178 @@
+//! Browser of the saved games for the replay mode: a page of small previews of the last
+//! position of each game (the final one, or the current one of an unfinished game), drawn with
+//! the offscreen renderer (`render.rs`), with the number of moves, the largest tile and how the
+//! game ended, rather than a file name typed in the terminal.
+
+use std::path::{Path, PathBuf};
+
+use macroquad::prelude::*;
+
+use crate::board::*;
+use crate::notation::{GameOutcome, GameRecord, NOTATION_EXTENSION};
+use crate::render;
+
+// Previews per row and rows per page
+const COLUMNS: usize = 3;
+const ROWS: usize = 3;
+// Scale of the previews (1.0 is the size of the grid of the game window)
+const PREVIEW_SCALE: f32 = 0.25;
+// Top of the first row of previews, and height of a row (preview and metadata)
+const TOP: f32 = 40.0;
+const ROW_HEIGHT: f32 = 215.0;
+
+/// What was picked in the browser
+pub enum Pick {
+    Game(Box<GameRecord>),
+    /// A game outside the directory, whose path is asked in the terminal
+    Other,
+}
+
+// A saved game, with its preview once its page has been shown
+struct Entry {
+    path: PathBuf,
+    preview: Option<Result<Preview, String>>,
+}
+
+struct Preview {
+    texture: Texture2D,
+    record: GameRecord,
+    num_moves: usize,
+    max_tile: u8,
+}
+
+impl Preview {
+    fn load(path: &Path) -> anyhow::Result<Preview> {
+        let record = GameRecord::load(path)?;
+        let last = *record.positions()?.last().expect("a game has an initial position");
+        let image = render::render_board(&last.board(), PREVIEW_SCALE);
+        let rgba: Vec<u8> = image.pixels.iter().flat_map(|&[r, g, b]| [r, g, b, 255]).collect();
+        let texture = Texture2D::from_rgba8(image.width as u16, image.height as u16, &rgba);
+        Ok(Preview { texture, num_moves: record.moves.len(), max_tile: last.max_tile(), record })
+    }
+
+    // Metadata lines shown below the preview
+    fn lines(&self) -> [String; 2] {
+        let ending = match (self.record.outcome, self.record.cause) {
+            (GameOutcome::Lost, Some(cause)) => format!("lost: {}", cause.name()),
+            (outcome, _) => outcome.name().to_string(),
+        };
+        [format!("{} moves, {}", self.num_moves, 1u32 << self.max_tile), ending]
+    }
+}
+
+// The games saved in `dir`, most recent first (their names contain the time they were saved)
+fn saved_games(dir: &Path) -> Vec<Entry> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == NOTATION_EXTENSION))
+        .collect();
+    paths.sort();
+    paths.into_iter().rev().map(|path| Entry { path, preview: None }).collect()
+}
+
+// Browses the games saved in `dir` (ASYNC): the arrows move the selection, PageUp/PageDown
+// turn the pages, Enter replays the selected game, O asks for another file in the terminal and
+// Escape goes back. Returns `None` when going back.
+pub async fn browse(dir: &Path) -> Option<Pick> {
+    let mut games = saved_games(dir);
+    let page_len = COLUMNS * ROWS;
+    let mut selected: usize = 0;
+    loop {
+        if is_key_pressed(KeyCode::Escape) || is_quit_requested() {
+            return None;
+        }
+        if is_key_pressed(KeyCode::O) {
+            return Some(Pick::Other);
+        }
+        let last = games.len().saturating_sub(1);
+        if is_key_pressed(KeyCode::Left) {
+            selected = selected.saturating_sub(1);
+        }
+        if is_key_pressed(KeyCode::Right) {
+            selected = (selected + 1).min(last);
+        }
+        if is_key_pressed(KeyCode::Up) {
+            selected = selected.saturating_sub(COLUMNS);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            selected = (selected + COLUMNS).min(last);
+        }
+        if is_key_pressed(KeyCode::PageUp) {
+            selected = selected.saturating_sub(page_len);
+        }
+        if is_key_pressed(KeyCode::PageDown) {
+            selected = (selected + page_len).min(last);
+        }
+
+        let page = selected / page_len * page_len;
+        let shown = page..(page + page_len).min(games.len());
+        for entry in &mut games[shown.clone()] {
+            if entry.preview.is_none() {
+                entry.preview = Some(Preview::load(&entry.path).map_err(|e| format!("{e:#}")));
+            }
+        }
+        if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) {
+            if let Some(Entry { preview: Some(Ok(preview)), .. }) = games.get(selected) {
+                return Some(Pick::Game(Box::new(preview.record.clone())));
+            }
+        }
+
+        clear_background(Color::new(0.98, 0.97, 0.94, 1.0));
+        if games.is_empty() {
+            draw_text(format!("No game saved in {} yet", dir.display()), PADDING, TOP, 20.0, BLACK);
+        } else {
+            let num_pages = games.len().div_ceil(page_len);
+            let title = format!("Saved games, page {}/{}", page / page_len + 1, num_pages);
+            draw_text(title, PADDING, 26.0, 20.0, BLACK);
+        }
+        let cell_width = screen_width() / COLUMNS as f32;
+        let size = GRID_SIZE * PREVIEW_SCALE;
+        for (k, entry) in games[shown].iter().enumerate() {
+            let x = (k % COLUMNS) as f32 * cell_width + (cell_width - size) / 2.0;
+            let y = TOP + (k / COLUMNS) as f32 * ROW_HEIGHT;
+            if page + k == selected {
+                draw_rectangle_lines(x - 4.0, y - 4.0, size + 8.0, size + 8.0, 4.0, ORANGE);
+            }
+            let name = entry.path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+            draw_text(&name, x, y + size + 18.0, 16.0, BLACK);
+            match &entry.preview {
+                Some(Ok(preview)) => {
+                    draw_texture(&preview.texture, x, y, WHITE);
+                    for (i, line) in preview.lines().iter().enumerate() {
+                        draw_text(line, x, y + size + 36.0 + 16.0 * i as f32, 16.0, DARKGRAY);
+                    }
+                }
+                Some(Err(e)) => {
+                    draw_rectangle(x, y, size, size, LIGHTGRAY);
+                    draw_text("invalid game", x + 4.0, y + size / 2.0, 16.0, RED);
+                    draw_text(e.chars().take(24).collect::<String>(), x, y + size + 36.0, 14.0, RED);
+                }
+                None => {}
+            }
+        }
+        let help = "Arrows: select, PageUp/PageDown: pages, Enter: replay, O: other file (terminal), Escape: back";
+        draw_text(help, PADDING, screen_height() - 20.0, 14.0, DARKGRAY);
+        next_frame().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saved_games() {
+        let dir = std::env::temp_dir().join(format!("2048-games-{}", std::process::id()));
+        assert!(saved_games(&dir).is_empty());
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["game-100.2gn", "game-300.2gn", "notes.txt", "game-200.2gn"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let names: Vec<_> = saved_games(&dir).iter().map(|entry| entry.path.file_name().unwrap().to_owned()).collect();
+        assert_eq!(names, ["game-300.2gn", "game-200.2gn", "game-100.2gn"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
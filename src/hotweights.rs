@@ -0,0 +1,69 @@
+//! Weights of the evaluation read from a file and reloaded live while the agent plays, so that
+//! the effect of a change of the weights is seen at once, without restarting the game.
+//!
+//! The file, given by `WEIGHTS_VAR`, has one weight per line in the format of
+//! `Weights::parse`. Its modification time is checked between the decisions of the agent; a
+//! file that does not parse is reported and the previous weights are kept.
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use anyhow::Context;
+
+use crate::eval::Weights;
+
+/// Environment variable giving the file of the weights of the agent
+pub const WEIGHTS_VAR: &str = "AI2048_WEIGHTS";
+
+/// The file of the weights, with the modification time of the version last read
+pub struct WeightsFile {
+    pub path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl WeightsFile {
+    /// The file of `WEIGHTS_VAR`, if set
+    pub fn from_env() -> Option<WeightsFile> {
+        std::env::var_os(WEIGHTS_VAR).map(|path| WeightsFile::new(PathBuf::from(path)))
+    }
+
+    pub fn new(path: PathBuf) -> WeightsFile {
+        WeightsFile { path, modified: None }
+    }
+
+    /// The weights of the file if it changed since it was last read (the first call reads it),
+    /// `None` otherwise. A file missing for a moment, e.g. while an editor saves it, is not
+    /// a change.
+    pub fn poll(&mut self) -> Option<anyhow::Result<Weights>> {
+        let modified = std::fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok()?;
+        if self.modified == Some(modified) {
+            return None;
+        }
+        self.modified = Some(modified);
+        let text = std::fs::read_to_string(&self.path).with_context(|| format!("Could not read {}", self.path.display()));
+        Some(text.and_then(|text| Weights::parse(&text).with_context(|| format!("Invalid weights in {}", self.path.display()))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poll() {
+        let path = std::env::temp_dir().join(format!("2048-weights-{}.txt", std::process::id()));
+        let mut file = WeightsFile::new(path.clone());
+        assert!(file.poll().is_none());
+        std::fs::write(&path, "empty = 300\n").unwrap();
+        assert_eq!(file.poll().unwrap().unwrap().empty, 300.0);
+        assert!(file.poll().is_none());
+
+        // a later modification time, as after an edit
+        std::fs::write(&path, "empty = oops\n").unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        assert!(file.poll().unwrap().is_err());
+        assert!(file.poll().is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
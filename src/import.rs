@@ -0,0 +1,186 @@
+//! Import of a board from a screenshot of a 2048 game (requires the `screenshot` feature).
+//!
+//! The grid is located as the largest region having the grid background color,
+//! and each tile is recognized by the color of its background. The original game draws all
+//! the tiles above 2048 with the same color: their value cannot be told, and a screenshot
+//! showing one is rejected rather than read wrong.
+
+use std::collections::VecDeque;
+use std::path::Path;
+
+use anyhow::Context;
+use image::RgbImage;
+
+use crate::board::*;
+
+/// Color of the grid background and of the gaps between tiles (#bbada0)
+const GRID_RGB: [u8; 3] = [187, 173, 160];
+/// Maximum per-channel difference for a pixel to be considered part of the grid
+const GRID_TOLERANCE: i32 = 12;
+/// Maximum (squared) distance between a tile color and its closest known color
+const MAX_COLOR_DISTANCE: i32 = 40 * 40;
+
+/// Known tile background colors, with the exponent they represent (0 is the empty cell).
+const TILE_COLORS: [(u8, [u8; 3]); 13] = [
+    (0, [205, 193, 180]),  // #cdc1b4
+    (1, [238, 228, 218]),  // #eee4da
+    (2, [237, 224, 200]),  // #ede0c8
+    (3, [242, 177, 121]),  // #f2b179
+    (4, [245, 149, 99]),   // #f59563
+    (5, [246, 124, 95]),   // #f67c5f
+    (6, [246, 94, 59]),    // #f65e3b
+    (7, [237, 207, 114]),  // #edcf72
+    (8, [237, 204, 97]),   // #edcc61
+    (9, [237, 200, 80]),   // #edc850
+    (10, [237, 197, 63]),  // #edc53f
+    (11, [237, 194, 46]),  // #edc22e
+    (ABOVE_2048, [60, 58, 50]), // #3c3a32, used by the original game for every tile above 2048
+];
+
+/// Exponent standing for the tiles above 2048 in `TILE_COLORS`, whose value is unknown
+const ABOVE_2048: u8 = 12;
+
+/// Loads a screenshot and reads the board it shows.
+pub fn board_from_screenshot(path: &Path) -> anyhow::Result<Board> {
+    let image = image::open(path)
+        .with_context(|| format!("Could not open screenshot {}", path.display()))?
+        .to_rgb8();
+    board_from_image(&image)
+}
+
+/// Reads the board shown in an image.
+pub fn board_from_image(image: &RgbImage) -> anyhow::Result<Board> {
+    let (x0, y0, x1, y1) = find_grid(image).context("No 2048 grid found in the image")?;
+    let cell_w = (x1 - x0 + 1) as f32 / N as f32;
+    let cell_h = (y1 - y0 + 1) as f32 / N as f32;
+
+    let mut board = Board::from_cells([[0; N]; N]);
+    for i in 0..N {
+        for j in 0..N {
+            // sample close to the top-left corner of the tile, away from the centered text
+            let x = x0 as f32 + (j as f32 + 0.2) * cell_w;
+            let y = y0 as f32 + (i as f32 + 0.2) * cell_h;
+            let color = average_color(image, x as u32, y as u32);
+            let exponent = closest_tile(color)
+                .with_context(|| format!("Unrecognized tile color {color:?} at row {i}, column {j}"))?;
+            anyhow::ensure!(
+                exponent != ABOVE_2048,
+                "The tile at row {i}, column {j} is above 2048: 4096, 8192 and the larger tiles look alike, enter this position by hand"
+            );
+            board.set(i, j, exponent);
+        }
+    }
+    Ok(board)
+}
+
+/// Returns the bounding box `(x0, y0, x1, y1)` of the largest connected region having the grid color.
+fn find_grid(image: &RgbImage) -> Option<(u32, u32, u32, u32)> {
+    let (w, h) = image.dimensions();
+    let is_grid = |x: u32, y: u32| {
+        let p = image.get_pixel(x, y).0;
+        (0..3).all(|c| (p[c] as i32 - GRID_RGB[c] as i32).abs() <= GRID_TOLERANCE)
+    };
+
+    let mut visited = vec![false; (w * h) as usize];
+    let mut best: Option<(usize, (u32, u32, u32, u32))> = None;
+    for start_y in 0..h {
+        for start_x in 0..w {
+            if visited[(start_y * w + start_x) as usize] || !is_grid(start_x, start_y) {
+                continue;
+            }
+            // flood fill the region containing the starting pixel
+            let mut size = 0;
+            let mut bbox = (start_x, start_y, start_x, start_y);
+            let mut queue = VecDeque::from([(start_x, start_y)]);
+            visited[(start_y * w + start_x) as usize] = true;
+            while let Some((x, y)) = queue.pop_front() {
+                size += 1;
+                bbox = (bbox.0.min(x), bbox.1.min(y), bbox.2.max(x), bbox.3.max(y));
+                let neighbours = [
+                    (x.wrapping_sub(1), y),
+                    (x + 1, y),
+                    (x, y.wrapping_sub(1)),
+                    (x, y + 1),
+                ];
+                for (nx, ny) in neighbours {
+                    if nx < w && ny < h && !visited[(ny * w + nx) as usize] && is_grid(nx, ny) {
+                        visited[(ny * w + nx) as usize] = true;
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+            if best.is_none_or(|(best_size, _)| size > best_size) {
+                best = Some((size, bbox));
+            }
+        }
+    }
+    best.map(|(_, bbox)| bbox)
+}
+
+/// Average color of a small patch of pixels around `(x, y)`
+fn average_color(image: &RgbImage, x: u32, y: u32) -> [u8; 3] {
+    let (w, h) = image.dimensions();
+    let mut sum = [0u32; 3];
+    let mut count = 0;
+    for py in y.saturating_sub(2)..(y + 3).min(h) {
+        for px in x.saturating_sub(2)..(x + 3).min(w) {
+            let p = image.get_pixel(px, py).0;
+            for c in 0..3 {
+                sum[c] += p[c] as u32;
+            }
+            count += 1;
+        }
+    }
+    sum.map(|s| (s / count.max(1)) as u8)
+}
+
+/// Exponent of the known tile color closest to `color`, if close enough.
+fn closest_tile(color: [u8; 3]) -> Option<u8> {
+    let distance = |known: &[u8; 3]| -> i32 { (0..3).map(|c| (color[c] as i32 - known[c] as i32).pow(2)).sum() };
+    TILE_COLORS
+        .iter()
+        .min_by_key(|(_, known)| distance(known))
+        .filter(|(_, known)| distance(known) <= MAX_COLOR_DISTANCE)
+        .map(|(exponent, _)| *exponent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A synthetic 2048 screenshot of the board: white margin, grid background, tiles (the
+    // tiles above 2048 in their common color)
+    fn screenshot(cells: [[u8; N]; N]) -> RgbImage {
+        let (margin, gap, tile) = (30, 12, 100);
+        let side = 2 * margin + N as u32 * tile + (N as u32 + 1) * gap;
+        let mut image = RgbImage::from_pixel(side, side, image::Rgb([250, 248, 239]));
+        for y in margin..side - margin {
+            for x in margin..side - margin {
+                image.put_pixel(x, y, image::Rgb(GRID_RGB));
+            }
+        }
+        for (i, row) in cells.iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate() {
+                let color = TILE_COLORS[cell.min(ABOVE_2048) as usize].1;
+                let x0 = margin + gap + j as u32 * (tile + gap);
+                let y0 = margin + gap + i as u32 * (tile + gap);
+                for y in y0..y0 + tile {
+                    for x in x0..x0 + tile {
+                        image.put_pixel(x, y, image::Rgb(color));
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn test_board_from_image() {
+        let cells = [[0, 1, 2, 3], [4, 5, 6, 7], [8, 9, 10, 11], [11, 0, 1, 0]];
+        assert_eq!(board_from_image(&screenshot(cells)).unwrap(), Board::from_cells(cells));
+        // an 8192 cannot be told from a 4096
+        let cells = [[0, 1, 2, 3], [4, 5, 6, 7], [8, 9, 10, 11], [0, 13, 1, 0]];
+        let error = board_from_image(&screenshot(cells)).unwrap_err().to_string();
+        assert!(error.contains("row 3, column 1 is above 2048"), "{error}");
+    }
+}
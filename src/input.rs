@@ -0,0 +1,144 @@
+//! Keyboard input of the moves: the keys of the actions (WASD or arrows), and the repetition
+//! of the move while a key is held, so that the early game can be played quickly without
+//! hammering the keys.
+
+use macroquad::prelude::*;
+
+use crate::board::*;
+
+// Keys of each action, in the order of `ALL_ACTIONS`
+const ACTION_KEYS: [(Action, [KeyCode; 2]); 4] = [
+    (Action::Up, [KeyCode::W, KeyCode::Up]),
+    (Action::Down, [KeyCode::S, KeyCode::Down]),
+    (Action::Left, [KeyCode::A, KeyCode::Left]),
+    (Action::Right, [KeyCode::D, KeyCode::Right]),
+];
+
+// The last action of `ACTION_KEYS` one of whose keys is `in_state`
+fn action_key(in_state: impl Fn(KeyCode) -> bool) -> Option<Action> {
+    ACTION_KEYS.iter().rev().find(|(_, keys)| keys.iter().any(|&key| in_state(key))).map(|&(action, _)| action)
+}
+
+/// Reads the action selected with the keyboard this frame (WASD or arrows), if any
+pub fn read_action_key() -> Option<Action> {
+    action_key(is_key_pressed)
+}
+
+/// The action whose key is held down, if any
+pub fn held_action_key() -> Option<Action> {
+    action_key(is_key_down)
+}
+
+/// Rate at which the move of a held key is repeated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyRepeat {
+    /// Time the key is held before the first repetition
+    pub delay_ms: u32,
+    /// Time between two repetitions, 0 to never repeat
+    pub interval_ms: u32,
+}
+
+impl Default for KeyRepeat {
+    fn default() -> Self {
+        KeyRepeat { delay_ms: 300, interval_ms: 0 }
+    }
+}
+
+/// Turns the keys pressed and held at each frame into moves, repeating the move of a held key
+/// at the rate of its `KeyRepeat`
+pub struct ActionKeys {
+    repeat: KeyRepeat,
+    // the action repeated and the time of its next repetition, in seconds
+    held: Option<(Action, f64)>,
+}
+
+impl ActionKeys {
+    pub fn new(repeat: KeyRepeat) -> ActionKeys {
+        ActionKeys { repeat, held: None }
+    }
+
+    /// The move of this frame
+    pub fn read(&mut self) -> Option<Action> {
+        self.update(get_time(), read_action_key(), held_action_key())
+    }
+
+    // The move at time `now` given the action key pressed this frame and the one held down.
+    // The interval is counted from the last repetition: after a slow frame, the repetitions
+    // missed are not caught up.
+    fn update(&mut self, now: f64, pressed: Option<Action>, held: Option<Action>) -> Option<Action> {
+        if let Some(action) = pressed {
+            self.held = Some((action, now + self.repeat.delay_ms as f64 / 1000.0));
+            return Some(action);
+        }
+        if self.held.is_some_and(|(action, _)| held != Some(action)) {
+            // released
+            self.held = None;
+        }
+        let (action, next) = self.held.filter(|_| self.repeat.interval_ms > 0)?;
+        if now < next {
+            return None;
+        }
+        self.held = Some((action, now + self.repeat.interval_ms as f64 / 1000.0));
+        Some(action)
+    }
+}
+
+// Keys the settings can bind, found back by their name when the settings are loaded
+const BINDABLE_KEYS: [KeyCode; 58] = [
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G, KeyCode::H,
+    KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P,
+    KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X,
+    KeyCode::Y, KeyCode::Z, KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+    KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9, KeyCode::F1, KeyCode::F2,
+    KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6, KeyCode::F7, KeyCode::F8, KeyCode::F9,
+    KeyCode::F10, KeyCode::F11, KeyCode::F12, KeyCode::Tab, KeyCode::Space, KeyCode::Enter,
+    KeyCode::Backspace, KeyCode::Insert, KeyCode::Home, KeyCode::End, KeyCode::PageUp,
+    KeyCode::PageDown, KeyCode::Delete,
+];
+
+/// The key of the given name (as shown in the menus), if it is one the settings can bind
+pub fn key_by_name(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS.into_iter().find(|key| format!("{key:?}") == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_repeat() {
+        let mut keys = ActionKeys::new(KeyRepeat { delay_ms: 300, interval_ms: 100 });
+        let up = Some(Action::Up);
+        assert_eq!(keys.update(0.0, up, up), up);
+        // nothing before the delay, then one move per interval
+        assert_eq!(keys.update(0.2, None, up), None);
+        assert_eq!(keys.update(0.31, None, up), up);
+        assert_eq!(keys.update(0.35, None, up), None);
+        assert_eq!(keys.update(0.42, None, up), up);
+        // a long frame does not release a burst of moves
+        assert_eq!(keys.update(2.0, None, up), up);
+        assert_eq!(keys.update(2.01, None, up), None);
+        // released, then held again: the delay starts over
+        assert_eq!(keys.update(2.5, None, None), None);
+        assert_eq!(keys.update(2.6, None, up), None);
+        assert_eq!(keys.update(3.0, up, up), up);
+        assert_eq!(keys.update(3.1, None, up), None);
+        // another key pressed while the first one is still held takes over
+        assert_eq!(keys.update(3.2, Some(Action::Left), up), Some(Action::Left));
+        assert_eq!(keys.update(3.6, None, up), None);
+
+        // no repetition by default
+        let mut keys = ActionKeys::new(KeyRepeat::default());
+        assert_eq!(keys.update(0.0, up, up), up);
+        assert_eq!(keys.update(5.0, None, up), None);
+    }
+
+    #[test]
+    fn test_key_by_name() {
+        assert_eq!(key_by_name("Tab"), Some(KeyCode::Tab));
+        assert_eq!(key_by_name("Key7"), Some(KeyCode::Key7));
+        assert_eq!(key_by_name("F12"), Some(KeyCode::F12));
+        assert_eq!(key_by_name("tab"), None);
+        assert_eq!(key_by_name("Escape"), None);
+    }
+}
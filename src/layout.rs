@@ -0,0 +1,180 @@
+//! Layout of the grid: gap between the tiles, radius of their corners, scale of their text,
+//! format of the numbers and orientation of the board, adjustable for recordings or small
+//! screens.
+//!
+//! The layout is read once from the `LAYOUT_VAR` environment variable, e.g.
+//! `AI2048_LAYOUT="gap=4,radius=12,font=0.8,numbers=short,anchor=on"`; missing parameters keep
+//! their default value.
+
+use std::sync::OnceLock;
+
+use anyhow::{bail, Context};
+
+use crate::board::{GRID_SIZE, N, PADDING, UI_HEIGHT};
+
+/// Environment variable overriding the default layout
+pub const LAYOUT_VAR: &str = "AI2048_LAYOUT";
+
+/// Parameters of the layout of the grid
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    /// Space between the tiles, and between the tiles and the border of the grid
+    pub gap: f32,
+    /// Radius of the rounded corners of the tiles (0 for square tiles)
+    pub corner_radius: f32,
+    /// Scale of the values written on the tiles
+    pub font_scale: f32,
+    /// Format of the values of the tiles and of the scores
+    pub numbers: NumberFormat,
+    /// Whether the games start with the board re-oriented to show the largest tile bottom-left
+    /// (see `orientation.rs`)
+    pub anchored: bool,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout { gap: PADDING, corner_radius: 6.0, font_scale: 1.0, numbers: NumberFormat::Plain, anchored: false }
+    }
+}
+
+/// How the values of the tiles and the scores are written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// `16384`
+    Plain,
+    /// `16 384`: groups of three digits
+    Spaced,
+    /// `16k`: thousands (`k`) and millions (`M`) from 5 digits on
+    Short,
+    /// `2^14` for the powers of two (the tiles), plain for the other numbers
+    Power,
+}
+
+impl NumberFormat {
+    pub fn parse(name: &str) -> anyhow::Result<NumberFormat> {
+        Ok(match name {
+            "plain" => NumberFormat::Plain,
+            "spaced" => NumberFormat::Spaced,
+            "short" => NumberFormat::Short,
+            "power" => NumberFormat::Power,
+            _ => bail!("unknown number format `{name}` (expected plain, spaced, short or power)"),
+        })
+    }
+
+    pub fn format(&self, value: u64) -> String {
+        match self {
+            NumberFormat::Plain => value.to_string(),
+            NumberFormat::Spaced => {
+                let digits = value.to_string();
+                let mut out = String::new();
+                for (i, digit) in digits.chars().enumerate() {
+                    if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                        out.push(' ');
+                    }
+                    out.push(digit);
+                }
+                out
+            }
+            NumberFormat::Short => match value {
+                0..10_000 => value.to_string(),
+                10_000..10_000_000 => format!("{}k", value / 1000),
+                _ => format!("{}M", value / 1_000_000),
+            },
+            NumberFormat::Power if value.is_power_of_two() && value > 1 => format!("2^{}", value.trailing_zeros()),
+            NumberFormat::Power => value.to_string(),
+        }
+    }
+}
+
+impl Layout {
+    /// Parses `name=value` pairs separated by commas (`gap`, `radius`, `font`, `numbers`,
+    /// `anchor`), starting from the default layout.
+    pub fn parse(text: &str) -> anyhow::Result<Layout> {
+        let mut layout = Layout::default();
+        for pair in text.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').with_context(|| format!("expected `name=value`, got `{pair}`"))?;
+            let parse_value = || -> anyhow::Result<f32> { value.trim().parse().with_context(|| format!("invalid value of `{name}`")) };
+            match name.trim() {
+                "gap" => layout.gap = parse_value()?,
+                "radius" => layout.corner_radius = parse_value()?,
+                "font" => layout.font_scale = parse_value()?,
+                "numbers" => layout.numbers = NumberFormat::parse(value.trim())?,
+                "anchor" => {
+                    layout.anchored = match value.trim() {
+                        "on" => true,
+                        "off" => false,
+                        value => bail!("invalid value of `anchor`: `{value}` (expected on or off)"),
+                    }
+                }
+                name => bail!("unknown layout parameter `{name}` (expected gap, radius, font, numbers or anchor)"),
+            }
+        }
+        if layout.gap < 0.0 || layout.tile_size() < 1.0 {
+            bail!("the gap must be between 0 and {}", (GRID_SIZE - N as f32) / (N as f32 + 1.0));
+        }
+        if layout.corner_radius < 0.0 || layout.font_scale <= 0.0 {
+            bail!("the radius cannot be negative and the font scale must be positive");
+        }
+        Ok(layout)
+    }
+
+    /// Side of a tile
+    pub fn tile_size(&self) -> f32 {
+        (GRID_SIZE - (N as f32 + 1.0) * self.gap) / N as f32
+    }
+
+    /// Screen position of the top left corner of a tile
+    pub fn tile_position(&self, col: usize, row: usize) -> (f32, f32) {
+        let x = PADDING + (col as f32 + 1.0) * self.gap + col as f32 * self.tile_size();
+        let y = PADDING + UI_HEIGHT + (row as f32 + 1.0) * self.gap + row as f32 * self.tile_size();
+        (x, y)
+    }
+}
+
+/// The layout of the process: the one of `LAYOUT_VAR` if set and valid, the default otherwise.
+pub fn layout() -> &'static Layout {
+    static LAYOUT: OnceLock<Layout> = OnceLock::new();
+    LAYOUT.get_or_init(|| match std::env::var(LAYOUT_VAR) {
+        Ok(text) => Layout::parse(&text).unwrap_or_else(|e| {
+            eprintln!("Ignoring {LAYOUT_VAR}: {e:#}");
+            Layout::default()
+        }),
+        Err(_) => Layout::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Layout::parse("").unwrap(), Layout::default());
+        let layout = Layout::parse("gap=4, radius=12,font=0.8").unwrap();
+        assert_eq!(layout, Layout { gap: 4.0, corner_radius: 12.0, font_scale: 0.8, numbers: NumberFormat::Plain, anchored: false });
+        // the tiles fill the grid whatever the gap
+        let (x, _) = layout.tile_position(N - 1, 0);
+        assert_eq!(x + layout.tile_size() + layout.gap, PADDING + GRID_SIZE);
+
+        assert!(Layout::parse("gap=200").is_err());
+        assert!(Layout::parse("margin=3").is_err());
+        assert!(Layout::parse("font=0").is_err());
+        assert!(Layout::parse("gap").is_err());
+        assert_eq!(Layout::parse("numbers=power").unwrap().numbers, NumberFormat::Power);
+        assert!(Layout::parse("numbers=roman").is_err());
+        assert!(Layout::parse("anchor=on").unwrap().anchored);
+        assert!(Layout::parse("anchor=yes").is_err());
+    }
+
+    #[test]
+    fn test_number_format() {
+        let formats = |value: u64| {
+            [NumberFormat::Plain, NumberFormat::Spaced, NumberFormat::Short, NumberFormat::Power].map(|format| format.format(value))
+        };
+        assert_eq!(formats(16384), ["16384", "16 384", "16k", "2^14"]);
+        assert_eq!(formats(2048), ["2048", "2 048", "2048", "2^11"]);
+        assert_eq!(formats(1234567), ["1234567", "1 234 567", "1234k", "1234567"]);
+        assert_eq!(formats(12_345_678)[2], "12M");
+        assert_eq!(formats(0), ["0", "0", "0", "0"]);
+    }
+}
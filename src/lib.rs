@@ -0,0 +1,39 @@
+//! The 2048 engine: the boards and their rules, the evaluation of the positions, the
+//! expectimax search and the strategies of the agent, the games and their notation, shared by
+//! the `main` (game window) and `bench` binaries and usable by other projects to embed the agent.
+//!
+//! Without the default `gui` feature (`default-features = false`), the engine does not depend on
+//! macroquad: the boards are drawn only by the game window.
+//!
+//! ```
+//! use ai_2048::board::PlayableBoard;
+//! use ai_2048::search::{select_action_with, SearchParams};
+//!
+//! let board = PlayableBoard::init();
+//! let result = select_action_with(board, &SearchParams { depth: 1, ..SearchParams::default() });
+//! assert!(result.is_some());
+//! ```
+
+#[cfg(feature = "gui")]
+pub mod batch;
+pub mod board;
+pub mod cache;
+pub mod calibration;
+pub mod color;
+pub mod compat;
+pub mod death;
+pub mod eval;
+pub mod external;
+#[cfg(feature = "gui")]
+pub mod fonts;
+pub mod game;
+pub mod layout;
+pub mod notation;
+pub mod plugin;
+pub mod rules;
+pub mod search;
+pub mod spawn;
+pub mod strategy;
+pub mod testing;
+#[cfg(feature = "verify")]
+pub mod verify;
@@ -1,177 +1,1550 @@
 #![allow(unused)]
 
-pub mod board;
-pub mod eval;
-pub mod search;
+// The engine, from the library crate (see `lib.rs`)
+use ai_2048::{batch, board, cache, calibration, color, compat, death, eval, external, fonts, game, layout, notation, plugin, rules, search, spawn, strategy, testing};
+#[cfg(feature = "verify")]
+use ai_2048::verify;
+
+pub mod bookmarks;
+pub mod chart;
+pub mod console;
+pub mod crash;
+pub mod explorer;
+pub mod gamebrowser;
+pub mod hotweights;
+#[cfg(feature = "screenshot")]
+pub mod import;
+pub mod input;
+pub mod menu;
+pub mod odds;
+pub mod orientation;
+pub mod pacing;
+pub mod profile;
+pub mod provenance;
+pub mod reachability;
+pub mod redraw;
+pub mod render;
+pub mod replay;
+pub mod similar;
+pub mod split;
+pub mod summary;
+pub mod tips;
+pub mod tutorial;
+pub mod valuechart;
+pub mod warmcache;
+pub mod window;
 
 use std::{
     time::{Instant, Duration},
     io::{self, Write},
 };
 
+use anyhow::Context;
 use board::*;
+use bookmarks::{Bookmark, Jump, NoteEntry, NoteInput};
+use chart::MoveChart;
+use console::Console;
+use explorer::{Explorer, Node, SpawnValue, Step};
+use game::{Event, Game, Input, State};
+use gamebrowser::Pick;
+use input::{read_action_key, ActionKeys, KeyRepeat};
+use menu::{Menu, MenuInput};
+use notation::{GameOutcome, GameRecord, NOTATION_EXTENSION};
+use orientation::Orientation;
+use provenance::Provenance;
+use redraw::{FrameCache, FrameKey};
 use macroquad::prelude::*; 
+use search::SearchParams;
+use spawn::{HostileSpawns, PreviewSpawns, RandomSpawns, SpawnStream};
+use split::{Resolution, SplitDecision};
+use strategy::{Budget, Strategy};
+use summary::{Destination, GameSummary};
+use window::window_conf;
 
 // Constant for the window dimension
 const WINDOW_DIM: f32 = 600.0;
-// Slowdown factor for the agent, to make the game visible
-const AGENT_DELAY_MS: u64 = 100;
 
 // The main function for Macroquad must be ASYNCHRONOUS
-#[macroquad::main("2048 Expectimax")]
+// (the window size, title and icon are configured in `window.rs`)
+#[macroquad::main(window_conf)]
 async fn main() {
-    // Set the window size
-    request_new_screen_size(WINDOW_DIM, WINDOW_DIM + 60.0); // +60px for the UI
-
-    // Mode Selection Logic 
+    crash::install();
+    match rules::init() {
+        Ok(ruleset) if *ruleset != rules::Ruleset::default() => println!("Rules: {ruleset:?}"),
+        Ok(_) => {}
+        Err(e) => {
+            println!("{e:#}");
+            return;
+        }
+    }
+    match plugin::init() {
+        Ok(Some(plugin)) => println!("Evaluation plugin: {}", plugin.id()),
+        Ok(None) => {}
+        Err(e) => {
+            println!("{e:#}");
+            return;
+        }
+    }
+    match choose_profile().await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(e) => {
+            println!("{e:#}");
+            return;
+        }
+    }
     println!("Welcome to 2048!");
-    println!("Choose the game mode:");
-    println!("  [A] - Agent Mode "); // Expectimax
-    println!("  [P] - Human Mode "); // Keyboard
+    if tutorial::is_first_run() {
+        println!("First time here? Follow the tutorial in the game window (Esc to skip).");
+        tutorial::play_tutorial().await;
+    }
 
-    let mut choice = String::new();
-    io::stdin().read_line(&mut choice).expect("Failed to read line");
-    let choice = choice.trim().to_uppercase();
+    println!("(During a game, press Tab to hand the board over between you and the agent)");
+    println!("(F3 toggles a debug overlay with the cell indices, exponents and position hash)");
+    println!("(B shows what the agent would have played instead of your last move)");
+    println!("(V makes the expectimax agent answer at once and verify its move in the background)");
+    println!("(O turns the board so that the largest tile stays bottom-left)");
+    println!("(K bookmarks the position with a note, browse the bookmarks from the menu)");
 
+    // Mode selection, in the window
+    let mut settings = Settings::load();
+    let Some(mode) = start_menu(&mut settings).await else {
+        return;
+    };
     let init = PlayableBoard::init();
 
-    match choice.as_str() {
-        "A" => {
-            println!("\nStarting game in Agent Mode. (Popup Window)");
+    match mode {
+        Mode::Agent(index) => {
+            let strategy = match index {
+                Some(index) => (strategy::STRATEGIES[index].build)(&settings.params()),
+                None => {
+                    show_terminal_notice().await;
+                    choose_strategy(&settings.params()).unwrap_or_else(|e| {
+                        println!("{e:#}\nUsing the default strategy instead.");
+                        strategy::default_strategy(&settings.params())
+                    })
+                }
+            };
+            println!("\nStarting game in Agent Mode with {}.", strategy.name());
             // Execute the agent's asynchronous game loop
-            play_agent(init).await;
+            play_agent(init, strategy, &settings).await;
         }
-        "P" => {
-            println!("\nStarting game in Human Mode. (Popup Window)");
+        Mode::Human => {
+            println!("\nStarting game in Human Mode.");
             // Execute the human player's asynchronous game loop
-            play_person(init).await;
+            play_person(init, &settings).await;
         }
-        _ => {
-            println!("Invalid option. Closing...");
-            // If the option is invalid, show the window briefly before closing
-            while !is_key_pressed(KeyCode::Escape) {
-                clear_background(RED);
-                draw_text("Invalid option. Press ESC.", 50.0, 300.0, 50.0, BLACK);
-                next_frame().await;
+        Mode::Tutorial => {
+            tutorial::play_tutorial().await;
+            println!("Tutorial finished, starting a game in Human Mode.");
+            play_person(init, &settings).await;
+        }
+        Mode::Analysis => {
+            show_terminal_notice().await;
+            let start = analysis_start().unwrap_or_else(|e| {
+                println!("{e:#}\nStarting from a new game instead.");
+                init
+            });
+            println!("\nStarting Analysis Mode.");
+            println!("F: look for the position in the saved games");
+            println!("X: explore what follows (click the actions and the cells, Backspace: back up)");
+            play_analysis(start).await;
+        }
+        Mode::Replay => {
+            let picked = match gamebrowser::browse(&profile::path(GAMES_DIR)).await {
+                Some(Pick::Game(record)) => Ok(*record),
+                Some(Pick::Other) => {
+                    show_terminal_notice().await;
+                    replay_start()
+                }
+                None => return,
+            };
+            let result = match picked {
+                Ok(record) => {
+                    println!("\nStarting Replay Mode.");
+                    println!("Left/Right: step, PageUp/PageDown: jump, Home/End: start/end, Space: play");
+                    replay::play_replay(record).await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                println!("{e:#}");
+            }
+        }
+        Mode::Bookmarks => match bookmarks::browse().await {
+            Ok(Some((board, Jump::Analysis))) => {
+                println!("\nStarting Analysis Mode on the bookmarked position.");
+                play_analysis(board).await;
+            }
+            Ok(Some((board, Jump::Practice))) => {
+                println!("\nPracticing the bookmarked position in Human Mode.");
+                play_person(board, &settings).await;
+            }
+            Ok(None) => {}
+            Err(e) => println!("{e:#}"),
+        },
+    }
+}
+
+/// Options of the games, changed in the settings of the start menu
+#[derive(Clone, Copy, Debug)]
+pub struct Settings {
+    /// Depth of the search of the agent
+    pub depth: usize,
+    /// Ticks the agent waits before each move
+    pub agent_pause_ticks: u32,
+    /// Whether the board is turned to show the largest tile bottom-left (see `orientation.rs`)
+    pub anchored: bool,
+    /// Key handing the board over between the human and the agent
+    pub hand_over_key: KeyCode,
+    /// Repetition of the moves while an action key is held
+    pub key_repeat: KeyRepeat,
+    /// Strength of the hints (`H`) and of the reviews of the moves (`B`)
+    pub assistant: tips::Assistant,
+    /// Whether the tiles spawn adversarially (see `spawn::HostileSpawns`) instead of randomly
+    pub hostile_spawns: bool,
+    /// Whether the value of the next tile is shown before each move (see `spawn::PreviewSpawns`)
+    pub spawn_preview: bool,
+    /// Whether the pause of the agent follows how close its decisions are (see `pacing.rs`)
+    pub adaptive_pause: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            depth: SearchParams::default().depth,
+            agent_pause_ticks: AGENT_PAUSE_TICKS,
+            anchored: layout::layout().anchored,
+            hand_over_key: KeyCode::Tab,
+            key_repeat: KeyRepeat::default(),
+            assistant: tips::Assistant::default(),
+            hostile_spawns: false,
+            spawn_preview: false,
+            adaptive_pause: true,
+        }
+    }
+}
+
+// File of the settings of a profile (see `profile.rs`)
+const SETTINGS_FILE: &str = "settings.txt";
+
+impl Settings {
+    fn params(&self) -> SearchParams {
+        SearchParams { depth: self.depth, ..SearchParams::default() }
+    }
+
+    // The settings saved in the profile, one `key = value` per line; the missing or invalid
+    // ones keep their default value
+    fn load() -> Settings {
+        let mut settings = Settings::default();
+        let path = profile::path(SETTINGS_FILE);
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            return settings;
+        };
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let parsed = line.split_once('=').and_then(|(key, value)| {
+                let value = value.trim();
+                match key.trim() {
+                    "depth" => settings.depth = value.parse().ok().filter(|depth| (1..=MAX_MENU_DEPTH).contains(depth))?,
+                    "agent_pause_ticks" => settings.agent_pause_ticks = value.parse().ok().filter(|&ticks| ticks <= MAX_AGENT_PAUSE_TICKS)?,
+                    "anchored" => settings.anchored = value.parse().ok()?,
+                    "hand_over_key" => settings.hand_over_key = input::key_by_name(value)?,
+                    "key_repeat_ms" => settings.key_repeat.interval_ms = value.parse().ok()?,
+                    "key_repeat_delay_ms" => settings.key_repeat.delay_ms = value.parse().ok()?,
+                    "assistant" => settings.assistant = tips::ALL_ASSISTANTS.into_iter().find(|a| a.name() == value)?,
+                    "hostile_spawns" => settings.hostile_spawns = value.parse().ok()?,
+                    "spawn_preview" => settings.spawn_preview = value.parse().ok()?,
+                    "adaptive_pause" => settings.adaptive_pause = value.parse().ok()?,
+                    _ => return None,
+                }
+                Some(())
+            });
+            if parsed.is_none() {
+                println!("Ignoring the setting `{}` of {}", line.trim(), path.display());
+            }
+        }
+        settings
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let path = profile::path(SETTINGS_FILE);
+        let text = format!(
+            "depth = {}\nagent_pause_ticks = {}\nanchored = {}\nhand_over_key = {:?}\nkey_repeat_ms = {}\nkey_repeat_delay_ms = {}\nassistant = {}\nhostile_spawns = {}\nspawn_preview = {}\nadaptive_pause = {}\n",
+            self.depth,
+            self.agent_pause_ticks,
+            self.anchored,
+            self.hand_over_key,
+            self.key_repeat.interval_ms,
+            self.key_repeat.delay_ms,
+            self.assistant.name(),
+            self.hostile_spawns,
+            self.spawn_preview,
+            self.adaptive_pause,
+        );
+        std::fs::write(&path, text).with_context(|| format!("Could not save the settings to {}", path.display()))
+    }
+}
+
+// Modes of the start menu
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// With the strategy of the registry at this index, `None` for one typed in the terminal
+    Agent(Option<usize>),
+    Human,
+    Analysis,
+    Replay,
+    Tutorial,
+    Bookmarks,
+}
+
+// Search depths offered by the menus
+const MAX_MENU_DEPTH: usize = 6;
+
+// Shows the start menu until a mode is chosen (`None` to quit). The settings are changed in
+// its submenu.
+async fn start_menu(settings: &mut Settings) -> Option<Mode> {
+    let mut strategies: Vec<&str> = strategy::STRATEGIES.iter().map(|entry| entry.name).collect();
+    strategies.push("other (terminal)");
+    let mut strategy_index = 0;
+    let mut menu = Menu::default();
+    let mut settings_menu: Option<Menu> = None;
+    loop {
+        clear_background(Color::new(0.98, 0.97, 0.94, 1.0));
+        let input = MenuInput::read();
+        let mut mode = None;
+        if let Some(submenu) = &mut settings_menu {
+            let mut ui = submenu.frame(input);
+            let mut depth = settings.depth;
+            ui.slider("Search depth", &mut depth, 1..=MAX_MENU_DEPTH, 1, |depth| depth.to_string());
+            settings.depth = depth;
+            let mut ticks = settings.agent_pause_ticks as usize;
+            ui.slider("Agent pause", &mut ticks, 0..=MAX_AGENT_PAUSE_TICKS as usize, 2, pause_text);
+            settings.agent_pause_ticks = ticks as u32;
+            ui.toggle("Adaptive pause", &mut settings.adaptive_pause);
+            ui.toggle("Largest tile bottom-left", &mut settings.anchored);
+            ui.key_capture("Hand-over key", &mut settings.hand_over_key);
+            let mut interval = settings.key_repeat.interval_ms as usize;
+            ui.slider("Key repeat", &mut interval, 0..=MAX_KEY_REPEAT_MS, 25, repeat_text);
+            settings.key_repeat.interval_ms = interval as u32;
+            let mut delay = settings.key_repeat.delay_ms as usize;
+            ui.slider("Key repeat delay", &mut delay, 100..=MAX_KEY_REPEAT_MS, 50, |ms| format!("{ms}ms"));
+            settings.key_repeat.delay_ms = delay as u32;
+            let assistants = tips::ALL_ASSISTANTS.map(tips::Assistant::name);
+            let mut assistant = tips::ALL_ASSISTANTS.iter().position(|&a| a == settings.assistant).unwrap_or(0);
+            ui.list("Assistant", &assistants, &mut assistant);
+            settings.assistant = tips::ALL_ASSISTANTS[assistant];
+            ui.toggle("Hostile spawns", &mut settings.hostile_spawns);
+            ui.toggle("Next tile preview", &mut settings.spawn_preview);
+            let done = ui.button("Back") || ui.back();
+            ui.show("SETTINGS");
+            if done {
+                settings_menu = None;
+                if let Err(e) = settings.save() {
+                    println!("{e:#}");
+                }
+            }
+        } else {
+            let mut ui = menu.frame(input);
+            let agent = strategy_index < strategy::STRATEGIES.len();
+            if ui.button("Agent mode") {
+                mode = Some(Mode::Agent(agent.then_some(strategy_index)));
+            }
+            ui.list("Agent strategy", &strategies, &mut strategy_index);
+            if ui.button("Human mode") {
+                mode = Some(Mode::Human);
+            }
+            if ui.button("Analysis mode") {
+                mode = Some(Mode::Analysis);
             }
+            if ui.button("Replay mode") {
+                mode = Some(Mode::Replay);
+            }
+            if ui.button("Tutorial") {
+                mode = Some(Mode::Tutorial);
+            }
+            if ui.button("Bookmarks") {
+                mode = Some(Mode::Bookmarks);
+            }
+            if ui.button("Settings") {
+                settings_menu = Some(Menu::default());
+            }
+            let quit = ui.button("Quit") || ui.back();
+            match profile::current() {
+                Some(name) => ui.show(&format!("2048 - {name}")),
+                None => ui.show("2048"),
+            }
+            if quit {
+                return None;
+            }
+        }
+        next_frame().await;
+        // (the key choosing the mode is released before the mode starts)
+        if mode.is_some() {
+            return mode;
         }
     }
 }
 
+// Agent pause in milliseconds, for the sliders
+fn pause_text(ticks: usize) -> String {
+    format!("{:.0}ms", ticks as f64 * TICK_S * 1000.0)
+}
+
+// Slowest repetition of a held key offered by the settings, and longest delay before it
+const MAX_KEY_REPEAT_MS: usize = 1000;
+
+// Interval of the repetition of a held key, for the slider
+fn repeat_text(ms: usize) -> String {
+    if ms == 0 { "off".to_string() } else { format!("every {ms}ms") }
+}
+
+// Tells in the window that the next question is asked in the terminal (which blocks the window)
+async fn show_terminal_notice() {
+    clear_background(Color::new(0.98, 0.97, 0.94, 1.0));
+    draw_text("Please answer in the terminal", 60.0, WINDOW_DIM / 2.0, 36.0, BLACK);
+    next_frame().await;
+}
+
 // Function for the Agent game mode (ASYNC)
-pub async fn play_agent(init: PlayableBoard) {
-    let mut num_moves = 0;
-    let mut cur = init;
+pub async fn play_agent(init: PlayableBoard, strategy: Box<dyn Strategy>, settings: &Settings) {
+    play(init, Controller::Agent, strategy, settings).await;
+}
+
+// Function for the Human player game mode (ASYNC)
+pub async fn play_person(init: PlayableBoard, settings: &Settings) {
+    // the agent that takes over on `Tab`
+    play(init, Controller::Human, strategy::default_strategy(&settings.params()), settings).await;
+}
+
+// Duration of one step of the game logic, whatever the frame rate
+const TICK_S: f64 = 1.0 / 60.0;
+// Maximum number of logic steps caught up in a single frame
+const MAX_TICKS_PER_FRAME: u32 = 10;
+// Number of ticks the agent waits before each move, to make the game visible
+// (10 ticks is ~166ms pause). Adjustable with +/- during the game.
+const AGENT_PAUSE_TICKS: u32 = 10;
+const MAX_AGENT_PAUSE_TICKS: u32 = 60;
+// Ticks the quick move of a split decision waits for its verification before being played
+// unverified (~500ms)
+const VERIFY_TICKS: u32 = 30;
+
+/// Who is currently choosing the moves
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Controller {
+    Agent,
+    Human,
+}
+
+impl Controller {
+    /// Name in the record of the game (`control` entries of the notation)
+    fn name(self) -> &'static str {
+        match self {
+            Controller::Agent => "agent",
+            Controller::Human => "human",
+        }
+    }
+
+    fn other(self) -> Controller {
+        match self {
+            Controller::Agent => Controller::Human,
+            Controller::Human => Controller::Agent,
+        }
+    }
+}
+
+/// Number of moves played by each controller and of hand-overs during a game
+#[derive(Default, Debug)]
+struct ControlStats {
+    agent_moves: u32,
+    human_moves: u32,
+    switches: u32,
+}
+
+/// Category under which a finished game is recorded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameCategory {
+    Agent,
+    Human,
+    /// Both the human and the agent played moves (e.g. the bot finished a human game)
+    Mixed,
+}
+
+impl ControlStats {
+    fn category(&self) -> GameCategory {
+        match (self.agent_moves, self.human_moves) {
+            (_, 0) => GameCategory::Agent,
+            (0, _) => GameCategory::Human,
+            _ => GameCategory::Mixed,
+        }
+    }
+}
+
+// Game loop shared by the Agent and Human modes (ASYNC).
+// The hand-over key of the settings (`Tab` by default) hands the board over to the other
+// controller at any time, `P` opens the pause menu (where the strategy of the agent can be
+// switched, logged in the record of the game) and `+`/`-` change the speed of the agent. `F3`
+// toggles the debug overlay. `H` suggests a move, at the strength of the assistant of the
+// settings, which also reviews the moves of the human (`B`). `K` bookmarks the position.
+// Escape or closing the window asks for a confirmation while the game is live, with the option
+// to save it before quitting.
+pub async fn play(init: PlayableBoard, mut controller: Controller, mut strategy: Box<dyn Strategy>, settings: &Settings) {
+    // The board, moves, score and record of the game
+    let mut game = Game::new(init);
+    game.record_mut().take_control(controller.name().to_string());
     let mut decision_time_ms = 0.0;
+    // Set once the end of the game has been reported
     let mut game_over = false;
+    let mut status = StatusMessage::default();
+    let mut control_stats = ControlStats::default();
+    // Search parameters, tweakable live from the developer console (`~`)
+    let mut params = settings.params();
+    let mut console = Console::default();
+    // File of the weights of the agent, reloaded when it changes (see `hotweights.rs`)
+    let mut weights_file = hotweights::WeightsFile::from_env();
+    // The random tiles, or the worst ones for the player according to the evaluation of the
+    // agent, their values shown in advance if enabled
+    let mut spawns: Box<dyn SpawnStream> = match settings.hostile_spawns {
+        true => Box::new(HostileSpawns::new(&SearchParams { depth: spawn::DEFAULT_HOSTILE_DEPTH, ..params })),
+        false => Box::new(RandomSpawns),
+    };
+    if settings.spawn_preview {
+        spawns = Box::new(PreviewSpawns::new(spawns));
+    }
+    // Ticks left before the agent plays its next move
+    let mut agent_pause_ticks = settings.agent_pause_ticks;
+    let mut agent_pause = agent_pause_ticks;
+    let mut pause_menu = Menu::default();
+    // Set while the confirmation of quitting is shown
+    let mut quitting = false;
+    let mut quit_menu = Menu::default();
+    prevent_quit();
+    // The last frame, presented again while nothing changes
+    let mut frames = FrameCache::default();
+    // Cell indices, exponents and position hash drawn over the board
+    let mut debug_overlay = false;
+    // Set once the human asked the bot to finish the game
+    let mut auto_finish = false;
+    let mut throttle = IdleThrottle::new();
+    // Move typed by the human, played at the next tick
+    let mut pending_human_action: Option<Action> = None;
+    let mut action_keys = ActionKeys::new(settings.key_repeat);
+    // Time not yet simulated by the fixed-timestep update
+    let mut accumulator = 0.0;
+    // Positions and actions played by the human, analyzed after the game
+    let mut human_history: Vec<(PlayableBoard, Action)> = Vec::new();
+    let mut tips_analysis: Option<std::thread::JoinHandle<Vec<tips::Blunder>>> = None;
+    // What the agent would have played instead of each human move, shown on demand with `B`
+    let mut live_review = tips::LiveReview::new(settings.assistant);
+    let mut blunders: Vec<tips::Blunder> = Vec::new();
+    crash::start_game(&init.board(), None, &strategy.name());
+    // Chart of the score, with the empty cells, over the last moves
+    let mut chart = MoveChart::default();
+    chart.push(0, &init.board());
+    // Position of the strategy in the registry, for the strategy list of the pause menu (an
+    // external engine is shown as the last one, and switches to the first one with Right)
+    let mut strategy_index = strategy::STRATEGIES
+        .iter()
+        .position(|entry| strategy.name().starts_with(entry.name))
+        .unwrap_or(strategy::STRATEGIES.len() - 1);
+    // How every tile was built, shown in the debug overlay
+    let mut provenance = Provenance::new(&init.board());
+    // Where the JSON summary of the game goes, if anywhere, with what it needs besides the record
+    let summaries = Destination::from_env();
+    let mut game_start = Instant::now();
+    let mut truncated_decisions = 0;
+    // Quick answers verified in the background (`V`), the decision waiting for its verification
+    // with the ticks left, and the number of quick moves replaced by the verification
+    let mut split_mode = false;
+    let mut split: Option<(SplitDecision, u32)> = None;
+    let mut corrections = 0;
+    // Orientation of the displayed board when it follows the largest tile (`O`)
+    let mut orientation = settings.anchored.then(Orientation::default);
+    // Note of the bookmark being saved (`K`), the game waiting meanwhile
+    let mut note: Option<NoteInput> = None;
 
-    // Main Macroquad loop
+    // Main Macroquad loop: input, fixed-timestep update, then rendering
     loop {
-        // Rendering 
-        cur.draw(num_moves, decision_time_ms);
-        if game_over {
-            draw_text("GAME OVER!", WINDOW_DIM/2.0 - 150.0, WINDOW_DIM/2.0 + 30.0, 80.0, RED);
-            next_frame().await;
-            continue;
-        }
-        
-        // Use a frame loop to implement a non-blocking PAUSE for visibility.
-        // This replaces the blocking thread::sleep.
-        for _ in 0..10 { // 10 frames at 60 FPS is ~166ms pause
-            cur.draw(num_moves, decision_time_ms);
-            next_frame().await;
-        }
-
-        // Start action selection time measurement
-        let start_action_selection = Instant::now();
-        let action = match search::select_action(cur) {
-            Some(action) => action,
-            None => {
-                // Game Over: No possible moves left
-                println!("GAME OVER! Num moves: {num_moves}");
+        let paused = game.state() == State::Paused;
+        // Nothing happens on screen without input unless the agent is playing
+        throttle.update(paused || quitting || game_over || controller == Controller::Human);
+
+        // --- Quit (Escape or closing the window), confirmed unless nothing would be lost ---
+        let quit_requested = is_quit_requested() || (is_key_pressed(KeyCode::Escape) && !paused && !console.open && note.is_none());
+        // (the Escape opening the confirmation does not close it right away)
+        let quit_opened = quit_requested && !quitting;
+        if quit_opened {
+            if game_over || game.num_moves() == 0 {
+                return;
+            }
+            quitting = true;
+            quit_menu = Menu::default();
+        }
+
+        // --- Input (once per frame) ---
+        console.update(&mut params, &game.board());
+        if quitting {
+            // (the confirmation is handled with the rendering)
+        } else if let Some(input) = &mut note {
+            match input.update() {
+                NoteEntry::Typing => {}
+                NoteEntry::Done(text) => {
+                    bookmark(&game.board().board(), game.num_moves(), text, &mut status);
+                    note = None;
+                }
+                NoteEntry::Cancelled => note = None,
+            }
+        } else if paused {
+            // (the pause menu is handled with the rendering)
+            if is_key_pressed(KeyCode::P) {
+                let _ = game.step(Input::Resume);
+            }
+        } else if !console.open {
+            if is_key_pressed(KeyCode::P) {
+                let _ = game.step(Input::Pause);
+            }
+            if is_key_pressed(KeyCode::F3) {
+                debug_overlay = !debug_overlay;
+            }
+            if is_key_pressed(KeyCode::O) {
+                orientation = match orientation {
+                    Some(_) => None,
+                    None => Some(Orientation::default()),
+                };
+                status.show(format!("Largest tile bottom-left: {}", if orientation.is_some() { "on" } else { "off" }), WHITE);
+            }
+            if is_key_pressed(KeyCode::H) {
+                let cur = game.board();
+                match settings.assistant.hint(cur, &params) {
+                    Some(action) => {
+                        // in the directions of the board as displayed
+                        let symmetry = shown_orientation(orientation, debug_overlay).map_or(0, |orientation| orientation.symmetry());
+                        status.show(format!("Hint ({}): {:?}", settings.assistant.name(), symmetric_action(action, symmetry)), WHITE);
+                    }
+                    None => status.show("No move left".to_string(), WHITE),
+                }
+            }
+            if is_key_pressed(KeyCode::K) {
+                note = Some(NoteInput::open());
+            }
+            if is_key_pressed(KeyCode::B) {
+                match live_review.reviews().last() {
+                    Some(review) => status.show(review.to_string(), WHITE),
+                    None => status.show("No move of yours reviewed yet".to_string(), WHITE),
+                }
+            }
+            // (Ctrl+V pastes a position)
+            if is_key_pressed(KeyCode::V) && !ctrl_down() {
+                if split_mode || strategy.name().starts_with("expectimax") {
+                    split_mode = !split_mode;
+                    split = None;
+                    status.show(format!("Verified quick answers: {}", if split_mode { "on" } else { "off" }), WHITE);
+                } else {
+                    status.show("Only the expectimax agent verifies its moves".to_string(), ORANGE);
+                }
+            }
+            if is_key_pressed(KeyCode::Equal) || is_key_pressed(KeyCode::KpAdd) {
+                agent_pause_ticks = agent_pause_ticks.saturating_sub(2);
+                status.show(format!("Agent pause: {:.0}ms", agent_pause_ticks as f64 * TICK_S * 1000.0), WHITE);
+            }
+            if is_key_pressed(KeyCode::Minus) || is_key_pressed(KeyCode::KpSubtract) {
+                agent_pause_ticks = (agent_pause_ticks + 2).min(MAX_AGENT_PAUSE_TICKS);
+                status.show(format!("Agent pause: {:.0}ms", agent_pause_ticks as f64 * TICK_S * 1000.0), WHITE);
+            }
+            // once the bot finishes the game, the control cannot be taken back
+            if is_key_pressed(settings.hand_over_key) && !auto_finish {
+                controller = controller.other();
+                control_stats.switches += 1;
+                game.record_mut().take_control(controller.name().to_string());
+                agent_pause = agent_pause_ticks;
+                pending_human_action = None;
+                split = None;
+                println!("[Control] {controller:?} takes over after {} moves", game.num_moves());
+                status.show(format!("{controller:?} takes over"), WHITE);
+            }
+
+            // Positions can be copied and pasted at any time to practice them
+            if let Some(pasted) = handle_clipboard(&game.board(), &mut status) {
+                game = Game::new(pasted);
+                game.record_mut().take_control(controller.name().to_string());
+                game_over = false;
+                human_history.clear();
+                blunders.clear();
+                live_review = tips::LiveReview::new(settings.assistant);
+                provenance = Provenance::new(&pasted.board());
+                crash::start_game(&pasted.board(), None, &strategy.name());
+                chart.clear();
+                chart.push(0, &pasted.board());
+                game_start = Instant::now();
+                truncated_decisions = 0;
+                split = None;
+            }
+
+            if controller == Controller::Human {
+                if let Some(action) = action_keys.read() {
+                    // the keys move the tiles of the board as displayed
+                    pending_human_action = Some(match shown_orientation(orientation, debug_overlay) {
+                        Some(orientation) => orientation.from_display(action),
+                        None => action,
+                    });
+                }
+            }
+        }
+
+        // --- Update (fixed timestep, independent of the frame rate) ---
+        accumulator += get_frame_time() as f64;
+        let mut ticks = 0;
+        while accumulator >= TICK_S && ticks < MAX_TICKS_PER_FRAME {
+            accumulator -= TICK_S;
+            ticks += 1;
+            if game.state() == State::Paused || quitting || game_over || console.open || note.is_some() {
+                continue;
+            }
+            let cur = game.board();
+
+            // 0. Game Over check
+            if game.is_over() {
+                println!("GAME OVER! Number of moves: {}", game.num_moves());
+                println!(
+                    "Moves by the agent: {}, by the human: {}, hand-overs: {}",
+                    control_stats.agent_moves, control_stats.human_moves, control_stats.switches
+                );
+                println!("Category: {:?}", control_stats.category());
+                if let Some(report) = strategy.report() {
+                    println!("Agent {report}");
+                }
+                if corrections > 0 {
+                    println!("Quick moves corrected by the verification: {corrections}");
+                }
+                println!("Frames drawn: {}, presented again unchanged: {}", frames.drawn, frames.skipped);
+                if !live_review.reviews().is_empty() {
+                    println!("Your moves against the agent: {}", tips::grade_counts(live_review.reviews()));
+                }
                 game_over = true;
+                if !cur.legal_actions().is_empty() {
+                    println!("Move limit of the rules reached");
+                }
+                if let Some(cause) = game.record().cause {
+                    println!("Cause of death: {}", cause.name());
+                }
+                match save_game(game.record()) {
+                    Ok(path) => println!("Game saved to {}", path.display()),
+                    Err(e) => println!("Could not save the game: {e:#}"),
+                }
+                if let Some(destination) = &summaries {
+                    let mode = format!("{:?}", control_stats.category()).to_lowercase();
+                    let summary = GameSummary::of_record(game.record(), None, &mode, &strategy.name(), game_start.elapsed(), truncated_decisions);
+                    if let Err(e) = summary.and_then(|summary| destination.write(&summary)) {
+                        println!("{e:#}");
+                    }
+                }
+                if !human_history.is_empty() {
+                    // analyze the human moves with a deeper search, in the background
+                    println!("Looking for your biggest mistakes...");
+                    let history = std::mem::take(&mut human_history);
+                    let deep = SearchParams { depth: params.depth + 1, ..params };
+                    tips_analysis = Some(std::thread::spawn(move || tips::find_blunders(&history, &deep, NUM_TIPS)));
+                }
                 continue;
             }
+
+            // 1. Get the action of the current controller, with the weights of the file as they
+            // are now
+            if controller == Controller::Agent && agent_pause == 0 {
+                if let Some(reloaded) = weights_file.as_mut().and_then(hotweights::WeightsFile::poll) {
+                    match reloaded {
+                        Ok(weights) => {
+                            params.weights = weights;
+                            println!("[Agent] Weights loaded from {}: {weights:?}", weights_file.as_ref().unwrap().path.display());
+                            status.show("Weights reloaded".to_string(), WHITE);
+                        }
+                        Err(e) => {
+                            println!("{e:#}");
+                            status.show("Invalid weights file, the previous weights are kept".to_string(), ORANGE);
+                        }
+                    }
+                }
+            }
+            let action = match controller {
+                Controller::Human => pending_human_action.take(),
+                Controller::Agent if agent_pause > 0 => {
+                    agent_pause -= 1;
+                    None
+                }
+                Controller::Agent if split_mode => match split.take() {
+                    None => {
+                        let start_action_selection = Instant::now();
+                        let verify_time = Duration::from_secs_f64(VERIFY_TICKS as f64 * TICK_S);
+                        split = SplitDecision::start(cur, &params, verify_time).map(|decision| (decision, VERIFY_TICKS));
+                        decision_time_ms = start_action_selection.elapsed().as_secs_f64() * 1000.0;
+                        None
+                    }
+                    Some((decision, ticks_left)) if ticks_left > 0 && !decision.is_verified() => {
+                        split = Some((decision, ticks_left - 1));
+                        None
+                    }
+                    Some((decision, _)) => {
+                        agent_pause = agent_pause_ticks;
+                        let resolution = decision.resolve();
+                        match resolution {
+                            Resolution::Corrected(quick, deep) => {
+                                corrections += 1;
+                                println!("[Agent] The verification replaces {quick:?} with {deep:?}");
+                            }
+                            Resolution::Unverified(quick) => println!("[Agent] No verification in time, playing {quick:?}"),
+                            Resolution::Confirmed(_) => {}
+                        }
+                        Some(resolution.action())
+                    }
+                },
+                Controller::Agent => {
+                    agent_pause = agent_pause_ticks;
+                    // Start action selection time measurement
+                    let start_action_selection = Instant::now();
+                    strategy.configure(&params);
+                    strategy.preview(spawns.preview());
+                    let result = strategy.choose(cur, Budget::default());
+                    if let Some(result) = result.as_ref().filter(|_| settings.adaptive_pause) {
+                        agent_pause = pacing::agent_pause(cur, result, agent_pause_ticks, MAX_AGENT_PAUSE_TICKS);
+                    }
+                    let action = result.map(|result| result.best);
+                    // Calculate decision time
+                    decision_time_ms = start_action_selection.elapsed().as_secs_f64() * 1000.0;
+                    // the game is not over here: no action means the strategy gave up (e.g. a
+                    // failing engine), it tries again at the next move
+                    if action.is_none() {
+                        truncated_decisions += 1;
+                    }
+                    action
+                }
+            };
+
+            // 2. Play the action if it is applicable (legal move)
+            if let Some(act) = action.filter(|&act| game.step(Input::Play(act)).is_ok()) {
+                match controller {
+                    Controller::Agent => {
+                        control_stats.agent_moves += 1;
+                        println!("\n[Agent | {:.2}ms] Playing action {act:?}", decision_time_ms);
+                    }
+                    Controller::Human => {
+                        control_stats.human_moves += 1;
+                        human_history.push((cur, act));
+                        live_review.submit(game.num_moves(), cur, act, &params);
+                        println!("[Player] Playing action {act:?}");
+                    }
+                }
+
+                // CHANCE turn: Add a random tile, or the worst one
+                let spawned = game.spawn_from(&mut spawns).expect("a tile spawns after a legal move");
+                if let Event::Spawned { spawn, decayed } = spawned {
+                    provenance.play(&cur.board(), act, spawn);
+                    if let Some((row, col)) = decayed {
+                        println!("[Rules] The tile at {row},{col} decays");
+                    }
+                }
+                let level = rules::rules().level(&game.board().board());
+                if level > rules::rules().level(&cur.board()) {
+                    println!("[Rules] Level {level} after {} moves", game.num_moves());
+                    status.show(format!("Level {level}: the tiles spawn larger"), GOLD);
+                }
+                if game.state() == State::Won {
+                    println!("Target tile {} reached after {} moves!", rules::rules().target_tile, game.num_moves());
+                    status.show(format!("{} reached, you win! Keep going?", rules::rules().target_tile), GOLD);
+                }
+                crash::record_move(act, &game.board().board());
+                chart.push(game.score(), &game.board().board());
+            }
+        }
+        if tips_analysis.as_ref().is_some_and(|analysis| analysis.is_finished()) {
+            blunders = tips_analysis.take().unwrap().join().unwrap_or_default();
+            println!("\n===== Your biggest mistakes =====");
+            for blunder in &blunders {
+                println!("{blunder}");
+            }
+        }
+        if ticks == MAX_TICKS_PER_FRAME {
+            // too far behind (e.g. after a long search): drop the backlog instead of catching up
+            accumulator = 0.0;
+        }
+
+        // --- Confirmation of quitting, with what is at stake ---
+        let quit_rows = if quitting {
+            let mut ui = quit_menu.frame(if quit_opened { MenuInput::default() } else { MenuInput::read() });
+            if ui.button("Keep playing") || ui.back() {
+                quitting = false;
+            }
+            if ui.button("Save and quit") {
+                match save_game(game.record()) {
+                    Ok(path) => println!("Unfinished game saved to {}", path.display()),
+                    Err(e) => println!("Could not save the game: {e:#}"),
+                }
+                return;
+            }
+            if ui.button("Quit without saving") {
+                return;
+            }
+            Some(ui.end())
+        } else {
+            None
         };
-        // Calculate decision time
-        decision_time_ms = start_action_selection.elapsed().as_secs_f64() * 1000.0;
-        println!("\n[Agent | {:.2}ms] Playing action {action:?}", decision_time_ms);
 
-        // Apply the move
-        let played = cur.apply(action).expect("invalid action");
-        num_moves += 1;
+        // --- Pause menu (its input is read with the rendering of the rows) ---
+        let pause_rows = if game.state() == State::Paused && !quitting {
+            let mut ui = pause_menu.frame(MenuInput::read());
+            if ui.button("Resume") || ui.back() {
+                let _ = game.step(Input::Resume);
+            }
+            if !game_over && !auto_finish && ui.button("Let the bot finish") {
+                let _ = game.step(Input::Resume);
+                auto_finish = true;
+                if controller == Controller::Human {
+                    control_stats.switches += 1;
+                    game.record_mut().take_control(Controller::Agent.name().to_string());
+                }
+                controller = Controller::Agent;
+                println!("[Control] The agent finishes the game after {} moves", game.num_moves());
+                status.show("The bot finishes the game".to_string(), WHITE);
+            }
+            let names: Vec<&str> = strategy::STRATEGIES.iter().map(|entry| entry.name).collect();
+            let switched = ui.list("Strategy", &names, &mut strategy_index);
+            if switched {
+                // with the current search parameters
+                strategy = (strategy::STRATEGIES[strategy_index].build)(&params);
+            }
+            let mut depth = params.depth;
+            if ui.slider("Search depth", &mut depth, 1..=MAX_MENU_DEPTH, 1, |depth| depth.to_string()) || switched {
+                params.depth = depth;
+                strategy.configure(&params);
+                let name = strategy.name();
+                println!("[Control] Switching to {name} after {} moves", game.num_moves());
+                status.show(format!("Agent: {name}"), WHITE);
+                crash::set_strategy(&name);
+                game.record_mut().switch_strategy(name);
+            }
+            let mut ticks = agent_pause_ticks as usize;
+            ui.slider("Agent pause", &mut ticks, 0..=MAX_AGENT_PAUSE_TICKS as usize, 2, pause_text);
+            agent_pause_ticks = ticks as u32;
+            let mut anchored = orientation.is_some();
+            if ui.toggle("Largest tile bottom-left", &mut anchored) {
+                orientation = anchored.then(Orientation::default);
+            }
+            Some(ui.end())
+        } else {
+            None
+        };
 
-        // CHANCE turn: Add a random tile
-        cur = played.with_random_tile();
+        // --- Rendering (skipped when nothing changed since the last frame) ---
+        let (cur, num_moves, score) = (game.board(), game.num_moves() as u32, game.score());
+        if let Some(orientation) = &mut orientation {
+            orientation.update(&cur.board());
+        }
+        let shown = shown_orientation(orientation, debug_overlay);
+        let quick = split.as_ref().map(|(decision, _)| decision.quick());
+        let next_spawn = spawns.preview();
+        let mut key = FrameKey::default();
+        key.add(cur.board().hash64()).add(num_moves).add(score).add(decision_time_ms.to_bits()).add(controller).add(next_spawn);
+        key.add(quick).add(shown).add(debug_overlay).add(game_over).add(game.record().cause.map(|cause| cause.name()));
+        key.add(blunders.len()).add(tips_analysis.is_some()).add(status.visible_text()).add(&pause_rows).add(&quit_rows).add(&console).add(&note);
+        frames.present(key.finish(), || {
+            match shown {
+                Some(orientation) => PlayableBoard::from_board(orientation.display(&cur.board())).draw(num_moves, Some(score), decision_time_ms),
+                None => cur.draw(num_moves, Some(score), decision_time_ms),
+            }
+            draw_text(format!("{controller:?}"), WINDOW_DIM - 80.0, 30.0, 20.0, DARKGRAY);
+            let potential = layout::layout().numbers.format(1 << cur.board().potential());
+            draw_text(format!("Potential: {potential}"), PADDING + 200.0, 30.0, 20.0, DARKGRAY);
+            if !rules::rules().ramp.levels().is_empty() {
+                draw_text(format!("Level {}", rules::rules().level(&cur.board())), PADDING + 200.0, 52.0, 20.0, DARKBLUE);
+            }
+            if let Some(next) = next_spawn {
+                draw_text(format!("Next: {}", 1u32 << next), PADDING + 300.0, 52.0, 20.0, DARKGREEN);
+            }
+            if let Some(quick) = quick {
+                let symmetry = shown.map_or(0, |orientation| orientation.symmetry());
+                draw_text(format!("{:?}, verifying...", symmetric_action(quick, symmetry)), WINDOW_DIM - 200.0, 50.0, 18.0, DARKGRAY);
+            }
+            chart.draw();
+            if debug_overlay {
+                cur.draw_debug_overlay();
+                provenance.draw();
+            }
+            if game_over {
+                draw_text("GAME OVER!", WINDOW_DIM/2.0 - 150.0, WINDOW_DIM/2.0 + 30.0, 80.0, RED);
+                if let Some(cause) = game.record().cause {
+                    draw_text(format!("Cause: {}", cause.name()), WINDOW_DIM/2.0 - 150.0, WINDOW_DIM/2.0 + 60.0, 24.0, RED);
+                }
+                draw_tips(&blunders, tips_analysis.is_some());
+            }
+            status.draw();
+            if let Some(rows) = &quit_rows {
+                menu::draw("QUIT?", rows);
+                let best = 2u32.pow(cur.max_tile() as u32);
+                let stakes = format!("Score {score} after {num_moves} moves, best tile {best}");
+                draw_text(stakes, 60.0, 185.0, 24.0, WHITE);
+            }
+            if let Some(rows) = &pause_rows {
+                menu::draw("PAUSED", rows);
+                draw_text(format!("Agent: {}", strategy.name()), 20.0, screen_height() - 60.0, 20.0, WHITE);
+            }
+            console.draw();
+            if let Some(note) = &note {
+                note.draw();
+            }
+        });
 
-        // Wait for the next Macroquad frame
+        // Wait for the next frame
         next_frame().await;
     }
 }
 
-// Function for the Human player game mode (ASYNC)
-pub async fn play_person(init: PlayableBoard) {
+// The orientation the board is displayed with: none while the debug overlay shows the real
+// cells
+fn shown_orientation(orientation: Option<Orientation>, debug_overlay: bool) -> Option<Orientation> {
+    orientation.filter(|_| !debug_overlay)
+}
+
+// Directory of the profile where finished games are saved in the game notation
+const GAMES_DIR: &str = "games";
+
+// Bookmarks the position with the given note, telling how it went in the status
+fn bookmark(board: &Board, move_number: usize, note: String, status: &mut StatusMessage) {
+    let bookmark = Bookmark { board: *board, move_number, note };
+    match bookmarks::append(&profile::path(bookmarks::BOOKMARKS_FILE), &bookmark) {
+        Ok(()) => status.show("Position bookmarked".to_string(), WHITE),
+        Err(e) => status.show(format!("{e:#}"), ORANGE),
+    }
+}
+
+// Selects the profile of the session (ASYNC): the one of PROFILE_VAR, created if needed, or
+// else one chosen in the window among the existing ones. `false` when the player quits instead.
+async fn choose_profile() -> anyhow::Result<bool> {
+    let name = match std::env::var(profile::PROFILE_VAR) {
+        Ok(name) => Some(name),
+        Err(_) => {
+            let names = profile::names();
+            if names.is_empty() {
+                None
+            } else {
+                let Some(name) = profile::choose(&names).await else {
+                    return Ok(false);
+                };
+                name
+            }
+        }
+    };
+    profile::select(name.clone())?;
+    if let Some(name) = name {
+        println!("Profile: {name}");
+    }
+    Ok(true)
+}
+
+// Saves a finished game under a new file of GAMES_DIR, returning its path
+fn save_game(record: &GameRecord) -> anyhow::Result<std::path::PathBuf> {
+    let dir = profile::path(GAMES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+    let path = dir.join(format!("game-{timestamp}.{NOTATION_EXTENSION}"));
+    record.save(&path)?;
+    Ok(path)
+}
+
+// Asks for the strategy of the agent, the default one if none is given
+fn choose_strategy(params: &SearchParams) -> anyhow::Result<Box<dyn Strategy>> {
+    println!("Strategies:");
+    for entry in strategy::STRATEGIES {
+        println!("  {:<12} {}", entry.name, entry.description);
+    }
+    println!("  external:<command> an engine speaking the protocol of `external.rs`");
+    print!("Strategy (empty for {}): ", strategy::STRATEGIES[0].name);
+    io::stdout().flush()?;
+    let mut name = String::new();
+    io::stdin().read_line(&mut name)?;
+    match name.trim() {
+        "" => Ok(strategy::default_strategy(params)),
+        name => strategy::by_name(name, params),
+    }
+}
+
+// Asks in the terminal for the game to replay (a file outside the browser of GAMES_DIR), the
+// most recent one of GAMES_DIR by default
+fn replay_start() -> anyhow::Result<GameRecord> {
+    print!("Game to replay (empty for the last saved game): ");
+    io::stdout().flush()?;
+    let mut path = String::new();
+    io::stdin().read_line(&mut path)?;
+    let path = match path.trim() {
+        "" => {
+            // file names contain the timestamp of the game
+            let dir = profile::path(GAMES_DIR);
+            let mut games: Vec<_> = std::fs::read_dir(&dir)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == NOTATION_EXTENSION))
+                .collect();
+            games.sort();
+            games.pop().ok_or_else(|| anyhow::anyhow!("No game saved in {}", dir.display()))?
+        }
+        path => std::path::PathBuf::from(path),
+    };
+    println!("Replaying {}", path.display());
+    GameRecord::load(&path)
+}
+
+// Number of mistakes reported after a human game
+const NUM_TIPS: usize = 3;
+
+// Draws the biggest mistakes of the human below the game over message
+fn draw_tips(blunders: &[tips::Blunder], analyzing: bool) {
+    let top = WINDOW_DIM / 2.0 + 70.0;
+    if analyzing {
+        draw_text("Analyzing your moves...", 60.0, top, 26.0, BLACK);
+        return;
+    }
+    for (i, blunder) in blunders.iter().enumerate() {
+        let text = format!(
+            "Move {}: {:?} instead of {:?}",
+            blunder.move_number, blunder.played, blunder.best
+        );
+        draw_text(text, 60.0, top + 30.0 * i as f32, 26.0, BLACK);
+    }
+    if !blunders.is_empty() {
+        draw_text("(before/after boards in the terminal)", 60.0, top + 30.0 * blunders.len() as f32, 20.0, DARKGRAY);
+    }
+}
+
+// Asks for the position to analyze (a screenshot when the `screenshot` feature is enabled)
+#[cfg(feature = "screenshot")]
+fn analysis_start() -> anyhow::Result<PlayableBoard> {
+    print!("Screenshot to analyze (empty for a new game): ");
+    io::stdout().flush()?;
+    let mut path = String::new();
+    io::stdin().read_line(&mut path)?;
+    let path = path.trim();
+    if path.is_empty() {
+        return Ok(PlayableBoard::init());
+    }
+    let board = import::board_from_screenshot(std::path::Path::new(path))?;
+    println!("Imported board:\n{board}");
+    if let Some(reason) = reachability::unreachable_reason(&board) {
+        println!("Warning: this position cannot appear in a game ({reason})");
+    }
+    Ok(PlayableBoard::from_board(board))
+}
+
+#[cfg(not(feature = "screenshot"))]
+fn analysis_start() -> anyhow::Result<PlayableBoard> {
+    Ok(PlayableBoard::init())
+}
+
+// Tiles whose probability of being reached is estimated in the analysis mode (2048 and 4096)
+const ODDS_TARGETS: [u8; 2] = [11, 12];
+// Number of games played to estimate these probabilities, with a shallow search
+const ODDS_ROLLOUTS: u64 = 32;
+const ODDS_DEPTH: usize = 1;
+
+// Function for the Analysis game mode (ASYNC): shows the agent's evaluation of every action
+// on the current position, the user plays the moves. `F` looks for the position in the games
+// saved in GAMES_DIR (indexed at the first search), `K` bookmarks the position. With
+// `WARM_CACHE_VAR` set, the cache of the search is loaded from that file and saved back when
+// leaving (Escape or closing the window).
+pub async fn play_analysis(init: PlayableBoard) {
+    let params = SearchParams::default();
+    let warm_cache = std::env::var_os(warmcache::WARM_CACHE_VAR).map(std::path::PathBuf::from);
+    let mut searcher = match &warm_cache {
+        Some(path) if path.exists() => warmcache::load(path, &params).unwrap_or_else(|e| {
+            println!("{e:#}, starting with an empty cache");
+            search::Searcher::default()
+        }),
+        _ => search::Searcher::default(),
+    };
+    if warm_cache.is_some() {
+        println!("Warm cache: {} values loaded", searcher.cached_values().count());
+        prevent_quit();
+    }
     let mut num_moves = 0;
+    // points of the moves played in the analysis
+    let mut score = 0;
     let mut cur = init;
-    let decision_time_ms = 0.0; // Time is always 0.0 in human mode
-    let mut game_over = false;
+    let mut values = searcher.action_values(cur, &params);
+    let mut status = StatusMessage::default();
+    let mut throttle = IdleThrottle::new();
+    // the estimates take a while: they are computed in the background, and also logged to the
+    // terminal for every analyzed position
+    let mut odds: Option<Vec<f32>> = None;
+    let mut odds_estimate = Some(estimate_odds(cur, &params));
+    let mut recorded_positions: Option<similar::PositionIndex> = None;
+    // the "what if" explorer, started from the current position with X
+    let mut explorer: Option<Explorer> = None;
+    // note of the bookmark being saved, capturing the keyboard
+    let mut note: Option<NoteInput> = None;
 
-    // Main Macroquad loop
     loop {
-        // --- Rendering ---
-        cur.draw(num_moves, decision_time_ms);
-        if game_over {
-            draw_text("GAME OVER!", WINDOW_DIM/2.0 - 150.0, WINDOW_DIM/2.0 + 30.0, 80.0, RED);
-            next_frame().await;
-            continue;
-        }
-
-        // 0. Game Over check
-        let mut is_game_over = true;
-        for action in ALL_ACTIONS {
-            if cur.apply(action).is_some() {
-                is_game_over = false;
-                break;
-            }
-        }
-
-        if is_game_over {
-            println!("GAME OVER! Number of moves: {num_moves}");
-            game_over = true;
-            next_frame().await;
-            continue;
-        }
-
-        // 1. Get user action (Macroquad keyboard input)
-        let mut action: Option<Action> = None;
-        if is_key_pressed(KeyCode::W) || is_key_pressed(KeyCode::Up) { action = Some(Action::Up); }
-        if is_key_pressed(KeyCode::S) || is_key_pressed(KeyCode::Down) { action = Some(Action::Down); }
-        if is_key_pressed(KeyCode::A) || is_key_pressed(KeyCode::Left) { action = Some(Action::Left); }
-        if is_key_pressed(KeyCode::D) || is_key_pressed(KeyCode::Right) { action = Some(Action::Right); }
-
-        if let Some(act) = action {
-            // 2. Check if the action is applicable (legal move)
-            if cur.apply(act).is_some() {
-                // Valid action: apply move and proceed to CHANCE turn
-                num_moves += 1;
-                println!("[Player] Playing action {act:?}");
-
-                // Apply the move
-                let played = cur.apply(act).unwrap();
-
-                // CHANCE turn: Add a random tile
-                cur = played.with_random_tile();
-
-                // Draw the new state before waiting for the next input
-                cur.draw(num_moves, decision_time_ms);
-                // Wait one frame to register the change
-                next_frame().await;
-            } else {
-                // Invalid move (no change)
+        throttle.update(odds_estimate.is_none());
+        let typing = note.is_some();
+        if let Some(input) = &mut note {
+            match input.update() {
+                NoteEntry::Typing => {}
+                NoteEntry::Done(text) => {
+                    bookmark(&cur.board(), num_moves as usize, text, &mut status);
+                    note = None;
+                }
+                NoteEntry::Cancelled => note = None,
             }
+        } else if explorer.is_none() && is_key_pressed(KeyCode::K) {
+            note = Some(NoteInput::open());
+        }
+        if is_key_pressed(KeyCode::X) && !typing {
+            explorer = match explorer {
+                Some(_) => None,
+                None => Some(Explorer::new(cur, &mut searcher, &params)),
+            };
+        }
+        if let Some(explorer) = &mut explorer {
+            explore(explorer, &mut searcher, &params);
+        } else {
+            cur.draw(num_moves, Some(score), 0.0);
+            draw_action_values(&values);
+            draw_odds(odds.as_deref());
+        }
+        status.draw();
+        if let Some(note) = &note {
+            note.draw();
+        }
+
+        let mut moved = false;
+        if explorer.is_none() && !typing {
+            if let Some(pasted) = handle_clipboard(&cur, &mut status) {
+                cur = pasted;
+                num_moves = 0;
+                score = 0;
+                moved = true;
+            }
+
+            if let Some(act) = read_action_key() {
+                if let Some((played, gained)) = cur.apply_scored(act) {
+                    num_moves += 1;
+                    score += gained;
+                    cur = played.with_random_tile();
+                    moved = true;
+                }
+            }
+        }
+
+        if is_key_pressed(KeyCode::F) && !typing {
+            if recorded_positions.is_none() {
+                let dir = profile::path(GAMES_DIR);
+                match similar::PositionIndex::of_dir(&dir) {
+                    Ok(index) => {
+                        println!("Indexed the positions of {} games of {}", index.num_games(), dir.display());
+                        recorded_positions = Some(index);
+                    }
+                    Err(e) => status.show(format!("{e:#}"), ORANGE),
+                }
+            }
+            if let Some(index) = &recorded_positions {
+                let shown = explorer.as_ref().map_or(cur.board(), |explorer| explorer.node().board());
+                let matches = index.query(&shown);
+                println!("\n===== {} in the recorded games =====", shown.compact());
+                for m in &matches {
+                    println!("{m}");
+                }
+                status.show(similar::summarize(&matches), WHITE);
+            }
+        }
+
+        if moved {
+            values = searcher.action_values(cur, &params);
+            odds = None;
+            // an estimate of the previous position is left to finish in the background
+            odds_estimate = Some(estimate_odds(cur, &params));
+        }
+        if odds_estimate.as_ref().is_some_and(|estimate| estimate.is_finished()) {
+            odds = odds_estimate.take().unwrap().join().ok();
+            if let Some(odds) = &odds {
+                let odds: Vec<String> = odds.iter().map(|p| format!("{p:.3}")).collect();
+                println!("odds move {num_moves} {} {}", cur.board().compact(), odds.join(" "));
+            }
+        }
+
+        if let Some(path) = warm_cache.as_ref().filter(|_| is_quit_requested() || (is_key_pressed(KeyCode::Escape) && !typing)) {
+            match warmcache::save(&searcher, path) {
+                Ok(saved) => println!("Warm cache: {saved} values saved to {}", path.display()),
+                Err(e) => println!("Could not save the cache: {e:#}"),
+            }
+            return;
         }
 
-        // Wait for the next frame
         next_frame().await;
     }
 }
+
+// Starts estimating the probabilities of reaching the `ODDS_TARGETS` from `board`
+fn estimate_odds(board: PlayableBoard, params: &SearchParams) -> std::thread::JoinHandle<Vec<f32>> {
+    let params = SearchParams { depth: ODDS_DEPTH, ..*params };
+    std::thread::spawn(move || {
+        odds::reach_probabilities(board, &ODDS_TARGETS, ODDS_ROLLOUTS, || {
+            Box::new(strategy::ExpectimaxStrategy::new(params))
+        })
+    })
+}
+
+// Draws the estimated probabilities of reaching the `ODDS_TARGETS` in the UI bar
+fn draw_odds(odds: Option<&[f32]>) {
+    let text = match odds {
+        Some(odds) => ODDS_TARGETS
+            .iter()
+            .zip(odds)
+            .map(|(&target, p)| format!("{}: {:.0}%", 2u32.pow(target as u32), p * 100.0))
+            .collect::<Vec<String>>()
+            .join("  "),
+        None => "estimating...".to_string(),
+    };
+    draw_text(text, PADDING + 120.0, 30.0, 18.0, DARKBLUE);
+}
+
+// Where the value of the action `ALL_ACTIONS[i]` is drawn in the UI bar (clicking it plays the
+// action in the explorer)
+fn action_label_rect(i: usize) -> Rect {
+    Rect::new(WINDOW_DIM / 2.0 - 100.0 + 100.0 * i as f32, 40.0, 100.0, 20.0)
+}
+
+// Draws the suggested action and the value loss of the other actions in the UI bar
+fn draw_action_values(values: &[Option<f32>; 4]) {
+    let best = ALL_ACTIONS
+        .into_iter()
+        .zip(values.iter())
+        .filter_map(|(action, value)| value.map(|v| (action, v)))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    let Some((best_action, best_value)) = best else {
+        draw_text("No legal move", WINDOW_DIM / 2.0, 30.0, 20.0, RED);
+        return;
+    };
+    draw_text(format!("Suggested: {best_action:?}"), WINDOW_DIM / 2.0, 30.0, 20.0, DARKGREEN);
+    for (i, (action, value)) in ALL_ACTIONS.into_iter().zip(values.iter()).enumerate() {
+        let loss = match value {
+            Some(v) => format!("{action:?} {:+.0}", v - best_value),
+            None => format!("{action:?} -"),
+        };
+        draw_text(loss, action_label_rect(i).x, 55.0, 18.0, BLACK);
+    }
+}
+
+// Draws the current node of the "what if" explorer and handles its input: the arrows or a
+// click on an action play it, a click on an empty cell spawns a tile there (left button: the
+// most probable tile, right button: the other one), Enter follows the suggested action or the
+// most probable spawn, Backspace or a click on a breadcrumb walks back up.
+fn explore(explorer: &mut Explorer, searcher: &mut search::Searcher, params: &SearchParams) {
+    let click = |button| is_mouse_button_pressed(button).then(|| Vec2::from(mouse_position()));
+    let (left_click, right_click) = (click(MouseButton::Left), click(MouseButton::Right));
+    let enter = is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter);
+    let crumbs = explorer.breadcrumbs();
+    let crumb_rects = breadcrumb_rects(&crumbs);
+
+    let step = match explorer.node() {
+        node @ Node::Decision { .. } => read_action_key()
+            .or_else(|| node.best_action().filter(|_| enter))
+            .or_else(|| (0..ALL_ACTIONS.len()).find(|&i| left_click.is_some_and(|p| action_label_rect(i).contains(p))).map(|i| ALL_ACTIONS[i]))
+            .map(Step::Play),
+        node @ Node::Chance { spawns, .. } => {
+            let clicked = [left_click, right_click].into_iter().enumerate().find_map(|(rank, point)| {
+                let (row, col) = cell_at(point?)?;
+                cell_spawns(spawns, row, col).get(rank).map(|s| s.spawn)
+            });
+            node.most_probable_spawn().filter(|_| enter).or(clicked).map(Step::Spawn)
+        }
+    };
+    match step {
+        Some(Step::Play(action)) => explorer.play(action, searcher, params),
+        Some(Step::Spawn(spawn)) => explorer.spawn(spawn, searcher, params),
+        None => false,
+    };
+    if is_key_pressed(KeyCode::Backspace) {
+        explorer.back();
+    }
+    if let Some(&(depth, _)) = crumb_rects.iter().find(|(_, rect)| left_click.is_some_and(|p| rect.contains(p))) {
+        explorer.back_to(depth);
+    }
+
+    let node = explorer.node();
+    PlayableBoard::from_board(node.board()).draw(explorer.num_moves() as u32, None, 0.0);
+    match node {
+        Node::Decision { values, .. } => draw_action_values(values),
+        Node::Chance { spawns, .. } => draw_spawn_values(spawns, node.value().unwrap_or(0.0), node.most_probable_spawn()),
+    }
+    // the path may have changed above
+    let crumbs = explorer.breadcrumbs();
+    draw_breadcrumbs(&crumbs, &breadcrumb_rects(&crumbs));
+}
+
+// The cell of the grid under `point`, as (row, col)
+fn cell_at(point: Vec2) -> Option<(usize, usize)> {
+    let size = layout::layout().tile_size();
+    (0..N).flat_map(|row| (0..N).map(move |col| (row, col))).find(|&(row, col)| {
+        let (x, y) = tile_position(col, row);
+        Rect::new(x, y, size, size).contains(point)
+    })
+}
+
+// The tiles that can spawn on a cell, the most probable first
+fn cell_spawns(spawns: &[SpawnValue], row: usize, col: usize) -> Vec<&SpawnValue> {
+    let mut on_cell: Vec<&SpawnValue> = spawns.iter().filter(|s| (s.spawn.row, s.spawn.col) == (row, col)).collect();
+    on_cell.sort_by(|a, b| b.probability.total_cmp(&a.probability));
+    on_cell
+}
+
+// Draws the value of every spawn of a chance node on its cell, relative to the expected value
+fn draw_spawn_values(spawns: &[SpawnValue], expected: f32, most_probable: Option<Spawn>) {
+    draw_text(format!("Expected: {expected:.0}"), WINDOW_DIM / 2.0, 30.0, 20.0, DARKGREEN);
+    draw_text("Click: spawn (right: rarer tile)  Enter: most probable", WINDOW_DIM / 2.0 - 100.0, 55.0, 16.0, BLACK);
+    let size = layout::layout().tile_size();
+    for row in 0..N {
+        for col in 0..N {
+            let (x, y) = tile_position(col, row);
+            for (k, s) in cell_spawns(spawns, row, col).into_iter().enumerate() {
+                let text = format!("{}: {:+.0}", 2u32.pow(s.spawn.exponent as u32), s.value - expected);
+                draw_text(text, x + 8.0, y + 24.0 + 22.0 * k as f32, 20.0, DARKGRAY);
+            }
+            if most_probable.is_some_and(|s| (s.row, s.col) == (row, col)) {
+                draw_rectangle_lines(x, y, size, size, 4.0, GOLD);
+            }
+        }
+    }
+}
+
+const CRUMB_SIZE: f32 = 20.0;
+const CRUMB_SEPARATOR: &str = " > ";
+const CRUMB_ELLIPSIS: &str = "... > ";
+
+// Where the breadcrumbs of the explorer are drawn, with the depth of their node, in the space
+// of the chart below the grid. Only the last ones are shown when the path is too long.
+fn breadcrumb_rects(crumbs: &[String]) -> Vec<(usize, Rect)> {
+    let top = PADDING + UI_HEIGHT + GRID_SIZE + PADDING + 10.0;
+    let width = |text: &str| measure_text(text, None, CRUMB_SIZE as u16, 1.0).width;
+    let total = |crumbs: &[String]| crumbs.iter().map(|crumb| width(crumb) + width(CRUMB_SEPARATOR)).sum::<f32>();
+    let mut first = 0;
+    while first + 1 < crumbs.len() && width(CRUMB_ELLIPSIS) + total(&crumbs[first..]) > GRID_SIZE {
+        first += 1;
+    }
+    let mut x = PADDING + if first > 0 { width(CRUMB_ELLIPSIS) } else { 0.0 };
+    let mut rects = Vec::new();
+    for (depth, crumb) in crumbs.iter().enumerate().skip(first) {
+        rects.push((depth, Rect::new(x, top, width(crumb), CRUMB_SIZE)));
+        x += width(crumb) + width(CRUMB_SEPARATOR);
+    }
+    rects
+}
+
+// Draws the breadcrumbs of the explorer, the current node last
+fn draw_breadcrumbs(crumbs: &[String], rects: &[(usize, Rect)]) {
+    let top = PADDING + UI_HEIGHT + GRID_SIZE + PADDING;
+    draw_rectangle(PADDING, top, GRID_SIZE, chart::CHART_HEIGHT - 2.0 * PADDING, Color::new(0.93, 0.89, 0.85, 1.0));
+    let baseline = |rect: &Rect| rect.y + CRUMB_SIZE - 4.0;
+    if let Some((_, rect)) = rects.first().filter(|(depth, _)| *depth > 0) {
+        draw_text(CRUMB_ELLIPSIS, PADDING, baseline(rect), CRUMB_SIZE, DARKGRAY);
+    }
+    for (i, (depth, rect)) in rects.iter().enumerate() {
+        let last = i + 1 == rects.len();
+        draw_text(&crumbs[*depth], rect.x, baseline(rect), CRUMB_SIZE, if last { DARKBLUE } else { DARKGRAY });
+        if !last {
+            draw_text(CRUMB_SEPARATOR, rect.x + rect.w, baseline(rect), CRUMB_SIZE, DARKGRAY);
+        }
+    }
+    draw_text("Backspace or click: back up  X: leave", PADDING, top + 55.0, 16.0, GRAY);
+}
+
+// After this long without any input, the render loop drops to a low frame rate
+const IDLE_AFTER_S: f64 = 5.0;
+// Duration of a frame when idle (~10 FPS)
+const IDLE_FRAME_MS: u64 = 100;
+
+// Lowers the frame rate when the user has not touched the keyboard or mouse for a while,
+// to save battery. The full rate is restored on the first input.
+struct IdleThrottle {
+    last_input: f64,
+}
+
+impl IdleThrottle {
+    fn new() -> Self {
+        IdleThrottle { last_input: get_time() }
+    }
+
+    // To be called once per frame; `can_idle` is false while something animates on its own
+    fn update(&mut self, can_idle: bool) {
+        let input = get_last_key_pressed().is_some()
+            || !get_keys_down().is_empty()
+            || mouse_delta_position() != Vec2::ZERO;
+        if input || !can_idle {
+            self.last_input = get_time();
+        } else if get_time() - self.last_input > IDLE_AFTER_S {
+            std::thread::sleep(Duration::from_millis(IDLE_FRAME_MS));
+        }
+    }
+}
+
+// How long a status message stays on screen
+const STATUS_DURATION_S: f64 = 3.0;
+
+// A short message shown over the bottom of the grid for a few seconds
+#[derive(Default)]
+struct StatusMessage {
+    text: String,
+    color: Color,
+    shown_at: f64,
+}
+
+impl StatusMessage {
+    fn show(&mut self, text: String, color: Color) {
+        self.text = text;
+        self.color = color;
+        self.shown_at = get_time();
+    }
+
+    // The text shown, if any
+    fn visible_text(&self) -> Option<&str> {
+        (!self.text.is_empty() && get_time() - self.shown_at <= STATUS_DURATION_S).then_some(self.text.as_str())
+    }
+
+    fn draw(&self) {
+        if self.visible_text().is_none() {
+            return;
+        }
+        let y = WINDOW_DIM + 20.0;
+        draw_rectangle(0.0, y - 25.0, WINDOW_DIM, 35.0, Color::new(0.0, 0.0, 0.0, 0.7));
+        draw_text(&self.text, 15.0, y, 24.0, self.color);
+    }
+}
+
+// Whether Ctrl (or Cmd) is held, for the shortcuts
+fn ctrl_down() -> bool {
+    is_key_down(KeyCode::LeftControl)
+        || is_key_down(KeyCode::RightControl)
+        || is_key_down(KeyCode::LeftSuper)
+        || is_key_down(KeyCode::RightSuper)
+}
+
+// Handles Ctrl+C (copy the current position) and Ctrl+V (paste a position) with the
+// compact board notation. Returns the pasted position, if any.
+fn handle_clipboard(cur: &PlayableBoard, status: &mut StatusMessage) -> Option<PlayableBoard> {
+    if !ctrl_down() {
+        return None;
+    }
+    if is_key_pressed(KeyCode::C) {
+        let compact = cur.board().compact();
+        miniquad::window::clipboard_set(&compact);
+        status.show(format!("Copied {compact}"), WHITE);
+    }
+    if is_key_pressed(KeyCode::V) {
+        let text = miniquad::window::clipboard_get().unwrap_or_default();
+        match Board::from_compact(&text) {
+            Ok(board) => {
+                // unrealistic positions are still loaded, with a warning
+                match reachability::unreachable_reason(&board) {
+                    Some(reason) => status.show(format!("Loaded, but unreachable: {reason}"), ORANGE),
+                    None => status.show(format!("Loaded {}", board.compact()), WHITE),
+                }
+                return Some(PlayableBoard::from_board(board));
+            }
+            Err(e) => status.show(format!("Invalid position: {e}"), ORANGE),
+        }
+    }
+    None
+}
@@ -0,0 +1,299 @@
+//! Immediate-mode menu widgets for the keyboard: buttons, list selections, sliders, toggles and
+//! key-capture fields, laid out one per row.
+//!
+//! A menu is declared again at every frame, as the rest of the UI:
+//!
+//! ```ignore
+//! let mut ui = menu.frame(MenuInput::read());
+//! if ui.button("Resume") { paused = false; }
+//! ui.slider("Depth", &mut depth, 1..=6, 1, |depth| depth.to_string());
+//! ui.show("PAUSED");
+//! ```
+//!
+//! Up/Down (or W/S) select a row, Left/Right (or A/D) change its value, Enter or Space activate
+//! it and Escape or Backspace go back. Nothing needs the mouse. The widgets only see the
+//! `MenuInput` of the frame, so gamepads mapped to these keys work as well.
+
+use std::ops::RangeInclusive;
+
+use macroquad::prelude::*;
+
+/// The navigation keys pressed during a frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MenuInput {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub activate: bool,
+    pub back: bool,
+    /// Any key pressed, for the key-capture fields
+    pub key: Option<KeyCode>,
+}
+
+impl MenuInput {
+    /// The keys pressed this frame
+    pub fn read() -> MenuInput {
+        let pressed = |keys: &[KeyCode]| keys.iter().any(|&key| is_key_pressed(key));
+        MenuInput {
+            up: pressed(&[KeyCode::Up, KeyCode::W]),
+            down: pressed(&[KeyCode::Down, KeyCode::S]),
+            left: pressed(&[KeyCode::Left, KeyCode::A]),
+            right: pressed(&[KeyCode::Right, KeyCode::D]),
+            activate: pressed(&[KeyCode::Enter, KeyCode::KpEnter, KeyCode::Space]),
+            back: pressed(&[KeyCode::Escape, KeyCode::Backspace]),
+            key: get_last_key_pressed(),
+        }
+    }
+}
+
+/// State of a menu kept between the frames: the selected row
+#[derive(Default)]
+pub struct Menu {
+    selected: usize,
+    num_rows: usize,
+    // the key-capture field of the selected row waits for a key
+    capturing: bool,
+}
+
+/// A row of a menu, as drawn
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Row {
+    pub label: String,
+    /// Current value of the widget, empty for the buttons
+    pub value: String,
+    pub selected: bool,
+}
+
+/// The widgets of a menu during one frame
+pub struct Frame<'a> {
+    menu: &'a mut Menu,
+    input: MenuInput,
+    rows: Vec<Row>,
+    // Escape cancels a key capture rather than leaving the menu
+    back: bool,
+}
+
+impl Menu {
+    /// Starts declaring the widgets of this frame. The selection moves first, so the widgets
+    /// see the input on the newly selected row.
+    pub fn frame(&mut self, input: MenuInput) -> Frame<'_> {
+        let mut input = input;
+        let back = input.back && !self.capturing;
+        if self.capturing {
+            // the key goes to the key-capture field only
+            input = MenuInput { key: input.key, back: input.back, ..MenuInput::default() };
+        } else if self.num_rows > 0 {
+            if input.up {
+                self.selected = (self.selected + self.num_rows - 1) % self.num_rows;
+            }
+            if input.down {
+                self.selected = (self.selected + 1) % self.num_rows;
+            }
+        }
+        Frame { menu: self, input, rows: Vec::new(), back }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+}
+
+impl Frame<'_> {
+    // Adds a row, returning whether it is the selected one
+    fn row(&mut self, label: &str, value: String) -> bool {
+        let selected = self.rows.len() == self.menu.selected;
+        self.rows.push(Row { label: label.to_string(), value, selected });
+        selected
+    }
+
+    /// Whether the menu was left this frame (Escape or Backspace, outside a key capture)
+    pub fn back(&self) -> bool {
+        self.back
+    }
+
+    /// A button, returning whether it was activated
+    pub fn button(&mut self, label: &str) -> bool {
+        self.row(label, String::new()) && self.input.activate
+    }
+
+    /// A boolean switched with Enter, Space, Left or Right. Returns whether it changed.
+    pub fn toggle(&mut self, label: &str, value: &mut bool) -> bool {
+        let text = if *value { "on" } else { "off" }.to_string();
+        let input = self.input;
+        if self.row(label, text) && (input.activate || input.left || input.right) {
+            *value = !*value;
+            return true;
+        }
+        false
+    }
+
+    /// A choice among `options`, `index` being the chosen one, cycled with Left and Right.
+    /// Returns whether it changed.
+    pub fn list(&mut self, label: &str, options: &[&str], index: &mut usize) -> bool {
+        let text = format!("< {} >", options.get(*index).copied().unwrap_or(""));
+        let input = self.input;
+        if !self.row(label, text) || options.is_empty() {
+            return false;
+        }
+        let before = *index;
+        if input.left {
+            *index = (*index + options.len() - 1) % options.len();
+        }
+        if input.right {
+            *index = (*index + 1) % options.len();
+        }
+        *index != before
+    }
+
+    /// A number of `range`, changed by `step` with Left and Right and shown with `text`.
+    /// Returns whether it changed.
+    pub fn slider(
+        &mut self,
+        label: &str,
+        value: &mut usize,
+        range: RangeInclusive<usize>,
+        step: usize,
+        text: impl Fn(usize) -> String,
+    ) -> bool {
+        let (min, max) = (*range.start(), *range.end());
+        let filled = if max > min { (*value - min) * 10 / (max - min) } else { 10 };
+        let bar = format!("[{}{}] {}", "=".repeat(filled), " ".repeat(10 - filled), text(*value));
+        let input = self.input;
+        if !self.row(label, bar) {
+            return false;
+        }
+        let before = *value;
+        if input.left {
+            *value = value.saturating_sub(step).max(min);
+        }
+        if input.right {
+            *value = (*value + step).min(max);
+        }
+        *value != before
+    }
+
+    /// A key binding: Enter waits for the next key, which replaces `key` (Escape cancels).
+    /// Returns whether it changed.
+    pub fn key_capture(&mut self, label: &str, key: &mut KeyCode) -> bool {
+        let selected = self.rows.len() == self.menu.selected;
+        let capturing = selected && self.menu.capturing;
+        let text = if capturing { "press a key...".to_string() } else { format!("{key:?}") };
+        self.row(label, text);
+        let input = self.input;
+        if !capturing {
+            if selected && input.activate {
+                self.menu.capturing = true;
+            }
+            return false;
+        }
+        if input.back {
+            self.menu.capturing = false;
+            return false;
+        }
+        match input.key {
+            Some(pressed) => {
+                self.menu.capturing = false;
+                *key = pressed;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Ends the frame, returning the rows to draw.
+    pub fn end(self) -> Vec<Row> {
+        self.menu.num_rows = self.rows.len();
+        self.menu.selected = self.menu.selected.min(self.rows.len().saturating_sub(1));
+        self.rows
+    }
+
+    /// Ends the frame and draws the menu over the whole window.
+    pub fn show(self, title: &str) {
+        draw(title, &self.end());
+    }
+}
+
+const TITLE_SIZE: f32 = 56.0;
+const ROW_SIZE: f32 = 28.0;
+const ROW_HEIGHT: f32 = 38.0;
+
+/// Draws the rows of a menu under its title, over a dark veil
+pub fn draw(title: &str, rows: &[Row]) {
+    draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.75));
+    let left = 60.0;
+    let top = 150.0;
+    draw_text(title, left, top, TITLE_SIZE, WHITE);
+    for (i, row) in rows.iter().enumerate() {
+        let y = top + 60.0 + ROW_HEIGHT * i as f32;
+        let color = if row.selected { GOLD } else { WHITE };
+        if row.selected {
+            draw_text(">", left - 30.0, y, ROW_SIZE, color);
+        }
+        draw_text(&row.label, left, y, ROW_SIZE, color);
+        draw_text(&row.value, left + 260.0, y, ROW_SIZE, color);
+    }
+    draw_text("Arrows: choose  Enter: select  Esc: back", left, screen_height() - 30.0, 20.0, LIGHTGRAY);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Settings {
+        sound: bool,
+        depth: usize,
+        strategy: usize,
+        key: KeyCode,
+    }
+
+    // Declares the menu of the test for one frame, returning whether `Quit` was activated
+    fn frame(menu: &mut Menu, settings: &mut Settings, input: MenuInput) -> (bool, Vec<Row>) {
+        let mut ui = menu.frame(input);
+        let quit = ui.button("Quit");
+        ui.toggle("Sound", &mut settings.sound);
+        ui.slider("Depth", &mut settings.depth, 1..=6, 1, |depth| depth.to_string());
+        ui.list("Strategy", &["a", "b", "c"], &mut settings.strategy);
+        ui.key_capture("Key", &mut settings.key);
+        (quit && !ui.back(), ui.end())
+    }
+
+    #[test]
+    fn test_navigation_and_widgets() {
+        let mut menu = Menu::default();
+        let mut settings = Settings { sound: false, depth: 3, strategy: 0, key: KeyCode::Tab };
+        let mut press = |menu: &mut Menu, input: MenuInput| frame(menu, &mut settings, input);
+
+        let (quit, rows) = press(&mut menu, MenuInput::default());
+        assert!(!quit && rows[0].selected && rows.len() == 5);
+        assert_eq!(rows[2].value, "[====      ] 3");
+        // the selection wraps around
+        let (_, rows) = press(&mut menu, MenuInput { up: true, ..Default::default() });
+        assert!(rows[4].selected);
+        press(&mut menu, MenuInput { up: true, left: true, ..Default::default() });
+        press(&mut menu, MenuInput { up: true, right: true, ..Default::default() });
+        // the slider stops at the end of its range
+        for _ in 0..4 {
+            press(&mut menu, MenuInput { right: true, ..Default::default() });
+        }
+        press(&mut menu, MenuInput { up: true, activate: true, ..Default::default() });
+
+        // a key capture takes the next key, even a navigation one, and Escape only cancels it
+        for _ in 0..3 {
+            press(&mut menu, MenuInput { down: true, ..Default::default() });
+        }
+        press(&mut menu, MenuInput { activate: true, key: Some(KeyCode::Enter), ..Default::default() });
+        let (_, rows) = press(&mut menu, MenuInput::default());
+        assert_eq!(rows[4].value, "press a key...");
+        let (quit, _) = press(&mut menu, MenuInput { back: true, key: Some(KeyCode::Escape), ..Default::default() });
+        assert!(!quit && menu.selected() == 4);
+        press(&mut menu, MenuInput { activate: true, ..Default::default() });
+        press(&mut menu, MenuInput { down: true, key: Some(KeyCode::Down), ..Default::default() });
+        assert_eq!(menu.selected(), 4);
+
+        let (quit, _) = press(&mut menu, MenuInput { down: true, activate: true, ..Default::default() });
+        assert!(quit);
+        assert!(settings.sound);
+        assert_eq!((settings.depth, settings.strategy, settings.key), (6, 2, KeyCode::Down));
+    }
+}
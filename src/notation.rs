@@ -0,0 +1,420 @@
+//! 2048 game notation: a portable text format for complete games.
+//!
+//! ```text
+//! # comments start with '#'
+//! version 6
+//! engine 1
+//! rules target_tile = 4096, four_probability = 0.1, move_limit = 0, decay_interval = 0
+//! initial 0000/0000/0010/0000
+//! control human
+//! move L 3,1=1
+//! control agent
+//! strategy expectimax (depth 4, adversarial mix 0, score weight 0)
+//! move U 0,0=2
+//! result lost
+//! cause stranded-tiles
+//! checksum 6cad69cf
+//! ```
+//!
+//! - `version`: version of the notation, must come first
+//! - `engine` (since version 5, required from then on): the `compat::ENGINE_VERSION` the game
+//!   was played with, engine 1 for the older versions
+//! - `rules` (since version 4, optional): the rules of the variant played (`Ruleset`), the
+//!   original game when absent
+//! - `initial`: the starting position, in the compact board notation (`Board::compact`)
+//! - `move <U|D|L|R> <row>,<col>=<exponent>`: an action followed by the tile spawned after it
+//! - `strategy <name>` (since version 3, optional): the agent was switched to this strategy
+//!   before the next move
+//! - `control <human|agent>` (since version 6, optional): the human or the agent took over the
+//!   board before the next move
+//! - `result`: `lost`, `timeout` or `unfinished`
+//! - `cause` (since version 2, optional): why a lost game ended, see `DeathCause::name`
+//! - `checksum` (since version 5, optional, must come last): `compat::checksum` of the other
+//!   lines, without the comments and the blank lines, joined with newlines. Files written by
+//!   hand may omit it.
+//!
+//! Readers reject files with a version newer than the one they implement, played by another
+//! engine or whose checksum does not match.
+
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use anyhow::{bail, Context};
+
+use crate::board::*;
+use crate::compat::{self, ENGINE_VERSION};
+use crate::death::{self, DeathCause};
+use crate::rules::{self, Ruleset};
+
+/// Current version of the notation
+pub const NOTATION_VERSION: u32 = 6;
+/// Extension of the files in this notation
+pub const NOTATION_EXTENSION: &str = "2gn";
+
+/// How a recorded game ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameOutcome {
+    /// No more legal moves
+    Lost,
+    /// The time allowed for the game ran out
+    Timeout,
+    /// The game was interrupted
+    Unfinished,
+}
+
+impl GameOutcome {
+    /// Name used in the notation (`result` line)
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameOutcome::Lost => "lost",
+            GameOutcome::Timeout => "timeout",
+            GameOutcome::Unfinished => "unfinished",
+        }
+    }
+}
+
+/// A complete game: the initial position and every action with the tile spawned after it
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRecord {
+    pub initial: Board,
+    pub moves: Vec<(Action, Spawn)>,
+    pub outcome: GameOutcome,
+    /// Why the game was lost, if known
+    pub cause: Option<DeathCause>,
+    /// Strategies the agent was switched to during the game, with the number of moves played
+    /// before the switch
+    pub switches: Vec<(usize, String)>,
+    /// Who took over the board (`human` or `agent`) during the game, with the number of moves
+    /// played before the hand-over
+    pub controls: Vec<(usize, String)>,
+    /// Rules the game is played with
+    pub ruleset: Ruleset,
+}
+
+impl GameRecord {
+    /// A game starting from `initial`, played with the rules of the process
+    pub fn new(initial: Board) -> GameRecord {
+        GameRecord {
+            initial,
+            moves: Vec::new(),
+            outcome: GameOutcome::Unfinished,
+            cause: None,
+            switches: Vec::new(),
+            controls: Vec::new(),
+            ruleset: *rules::rules(),
+        }
+    }
+
+    /// Marks the game as lost on `board` (the final position) and classifies why.
+    pub fn lose(&mut self, board: &Board) {
+        self.outcome = GameOutcome::Lost;
+        self.cause = Some(death::classify(board, self.moves.last().map(|&(_, spawn)| spawn)));
+    }
+
+    pub fn push(&mut self, action: Action, spawn: Spawn) {
+        self.moves.push((action, spawn));
+    }
+
+    /// Records that the agent plays the next moves with the strategy `name`.
+    pub fn switch_strategy(&mut self, name: String) {
+        self.switches.push((self.moves.len(), name));
+    }
+
+    /// Name of the last strategy switched to before the move of the given index, if any
+    pub fn strategy_at(&self, index: usize) -> Option<&str> {
+        self.switches.iter().rev().find(|(at, _)| *at <= index).map(|(_, name)| name.as_str())
+    }
+
+    /// Records that `controller` (`human` or `agent`) plays the next moves.
+    pub fn take_control(&mut self, controller: String) {
+        self.controls.push((self.moves.len(), controller));
+    }
+
+    /// Who had taken over the board last before the move of the given index, if recorded
+    pub fn controller_at(&self, index: usize) -> Option<&str> {
+        self.controls.iter().rev().find(|(at, _)| *at <= index).map(|(_, name)| name.as_str())
+    }
+
+    /// Replays the game, returning the position before each move followed by the final position,
+    /// with the decay of the rules of the game. Fails if an action is not applicable or a spawn is not on an empty cell.
+    pub fn positions(&self) -> anyhow::Result<Vec<PlayableBoard>> {
+        let mut cur = PlayableBoard::from_board(self.initial);
+        let mut positions = vec![cur];
+        for (i, &(action, spawn)) in self.moves.iter().enumerate() {
+            let played = cur
+                .apply(action)
+                .with_context(|| format!("move {}: {action:?} is not applicable", i + 1))?;
+            cur = played
+                .with_spawn(spawn)
+                .with_context(|| format!("move {}: cannot spawn on occupied cell {},{}", i + 1, spawn.row, spawn.col))?;
+            let mut board = cur.board();
+            if self.ruleset.decay(&mut board, i + 1).is_some() {
+                cur = PlayableBoard::from_board(board);
+            }
+            positions.push(cur);
+        }
+        Ok(positions)
+    }
+
+    /// Replays the game, returning the score (points of the merges) before each move followed by
+    /// the final score. Fails like `positions`.
+    pub fn scores(&self) -> anyhow::Result<Vec<u32>> {
+        let positions = self.positions()?;
+        let mut scores = vec![0];
+        for (board, &(action, _)) in positions.iter().zip(&self.moves) {
+            let (_, gained) = board.apply_scored(action).expect("the moves of the positions are applicable");
+            scores.push(scores.last().unwrap() + gained);
+        }
+        Ok(scores)
+    }
+
+    /// Parses a game in the notation described in the module documentation.
+    pub fn parse(text: &str) -> anyhow::Result<GameRecord> {
+        let mut lines: Vec<(usize, &str)> = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        if let Some(&(number, last)) = lines.last() {
+            if let Some(expected) = last.strip_prefix("checksum ") {
+                lines.pop();
+                let expected = u32::from_str_radix(expected.trim(), 16).with_context(|| format!("line {number}: invalid checksum"))?;
+                if content_checksum(lines.iter().map(|&(_, line)| line)) != expected {
+                    bail!("checksum mismatch: the file was modified or truncated");
+                }
+            }
+        }
+        let mut lines = lines.into_iter();
+
+        let Some((_, first)) = lines.next() else {
+            bail!("empty game notation");
+        };
+        let version: u32 = first
+            .strip_prefix("version ")
+            .context("the notation must start with a `version` line")?
+            .trim()
+            .parse()
+            .context("invalid version")?;
+        if version > NOTATION_VERSION {
+            bail!("notation version {version} is newer than the supported version {NOTATION_VERSION}");
+        }
+
+        // the files older than the versioning of the engine were all played by the first one
+        let mut engine = (version < 5).then_some(1);
+        let mut initial = None;
+        let mut record = GameRecord::new(Board::from_cells([[0; N]; N]));
+        record.ruleset = Ruleset::default();
+        for (number, line) in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "engine" => engine = Some(value.trim().parse().with_context(|| format!("line {number}: invalid engine version"))?),
+                "initial" => initial = Some(Board::from_compact(value).with_context(|| format!("line {number}"))?),
+                "move" => {
+                    let (action, spawn) = parse_move(value).with_context(|| format!("line {number}: invalid move `{value}`"))?;
+                    record.push(action, spawn);
+                }
+                "result" => {
+                    record.outcome = match value.trim() {
+                        "lost" => GameOutcome::Lost,
+                        "timeout" => GameOutcome::Timeout,
+                        "unfinished" => GameOutcome::Unfinished,
+                        other => bail!("line {number}: unknown result `{other}`"),
+                    }
+                }
+                "strategy" => record.switch_strategy(value.trim().to_string()),
+                "control" => match value.trim() {
+                    controller @ ("human" | "agent") => record.take_control(controller.to_string()),
+                    other => bail!("line {number}: unknown controller `{other}`"),
+                },
+                "rules" => record.ruleset = Ruleset::parse_inline(value).with_context(|| format!("line {number}: invalid rules"))?,
+                "cause" => {
+                    record.cause = Some(
+                        DeathCause::from_name(value.trim()).with_context(|| format!("line {number}: unknown cause `{value}`"))?,
+                    )
+                }
+                other => bail!("line {number}: unknown entry `{other}`"),
+            }
+        }
+        let engine = engine.context("missing `engine` version")?;
+        compat::check_engine(engine).context("the game cannot be replayed")?;
+        record.initial = initial.context("missing `initial` position")?;
+        Ok(record)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, self.to_string()).with_context(|| format!("Could not write {}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<GameRecord> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+        GameRecord::parse(&text).with_context(|| format!("Invalid game notation in {}", path.display()))
+    }
+}
+
+fn action_letter(action: Action) -> char {
+    match action {
+        Action::Up => 'U',
+        Action::Down => 'D',
+        Action::Left => 'L',
+        Action::Right => 'R',
+    }
+}
+
+// Parses `<U|D|L|R> <row>,<col>=<exponent>`
+fn parse_move(text: &str) -> anyhow::Result<(Action, Spawn)> {
+    let (letter, spawn) = text.trim().split_once(' ').context("expected an action and a spawn")?;
+    let action = match letter {
+        "U" => Action::Up,
+        "D" => Action::Down,
+        "L" => Action::Left,
+        "R" => Action::Right,
+        _ => bail!("unknown action `{letter}`"),
+    };
+    let (position, exponent) = spawn.split_once('=').context("expected `<row>,<col>=<exponent>`")?;
+    let (row, col) = position.split_once(',').context("expected `<row>,<col>`")?;
+    let spawn = Spawn {
+        row: row.trim().parse()?,
+        col: col.trim().parse()?,
+        exponent: exponent.trim().parse()?,
+    };
+    if spawn.row >= N || spawn.col >= N {
+        bail!("spawn position out of the board");
+    }
+    Ok((action, spawn))
+}
+
+// Checksum of the significant lines of a file, trimmed
+fn content_checksum<'a>(lines: impl Iterator<Item = &'a str>) -> u32 {
+    compat::checksum(lines.map(str::trim).collect::<Vec<&str>>().join("\n").as_bytes())
+}
+
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let body = self.body()?;
+        writeln!(f, "{body}checksum {:08x}", content_checksum(body.lines()))
+    }
+}
+
+impl GameRecord {
+    // All the lines of the notation but the checksum
+    fn body(&self) -> Result<String, std::fmt::Error> {
+        use std::fmt::Write as _;
+        let mut f = String::new();
+        writeln!(f, "version {NOTATION_VERSION}")?;
+        writeln!(f, "engine {ENGINE_VERSION}")?;
+        if self.ruleset != Ruleset::default() {
+            writeln!(f, "rules {}", self.ruleset)?;
+        }
+        writeln!(f, "initial {}", self.initial.compact())?;
+        let mut controls = self.controls.iter().peekable();
+        let mut switches = self.switches.iter().peekable();
+        for (i, (action, spawn)) in self.moves.iter().enumerate() {
+            while let Some((_, controller)) = controls.next_if(|(at, _)| *at <= i) {
+                writeln!(f, "control {controller}")?;
+            }
+            while let Some((_, name)) = switches.next_if(|(at, _)| *at <= i) {
+                writeln!(f, "strategy {name}")?;
+            }
+            writeln!(f, "move {} {},{}={}", action_letter(*action), spawn.row, spawn.col, spawn.exponent)?;
+        }
+        for (_, controller) in controls {
+            writeln!(f, "control {controller}")?;
+        }
+        for (_, name) in switches {
+            writeln!(f, "strategy {name}")?;
+        }
+        writeln!(f, "result {}", self.outcome.name())?;
+        if let Some(cause) = self.cause {
+            writeln!(f, "cause {}", cause.name())?;
+        }
+        Ok(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> GameRecord {
+        let mut record = GameRecord::new(Board::from_compact("0000/0000/0010/0000").unwrap());
+        record.take_control("human".to_string());
+        record.push(Action::Left, Spawn { row: 3, col: 1, exponent: 1 });
+        record.take_control("agent".to_string());
+        record.switch_strategy("greedy".to_string());
+        record.push(Action::Up, Spawn { row: 3, col: 3, exponent: 2 });
+        record.outcome = GameOutcome::Lost;
+        record.cause = Some(DeathCause::CornerLost);
+        record
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let record = sample();
+        let text = record.to_string();
+        assert_eq!(GameRecord::parse(&text).unwrap(), record);
+        assert!(text.contains("control human\nmove L 3,1=1\ncontrol agent\nstrategy greedy\nmove U"), "{text}");
+        assert_eq!(record.strategy_at(0), None);
+        assert_eq!(record.strategy_at(1), Some("greedy"));
+        assert_eq!(record.controller_at(0), Some("human"));
+        assert_eq!(record.controller_at(1), Some("agent"));
+        // a hand-over after the last move is kept too
+        let mut handed_back = record.clone();
+        handed_back.take_control("human".to_string());
+        assert_eq!(GameRecord::parse(&handed_back.to_string()).unwrap(), handed_back);
+        // version 1 files have no cause, strategy, control, engine nor checksum
+        let checksum = text.lines().last().unwrap();
+        assert!(checksum.starts_with("checksum "));
+        let v1 = text
+            .replace(&format!("version {NOTATION_VERSION}"), "version 1")
+            .replace(&format!("engine {ENGINE_VERSION}\n"), "")
+            .replace("cause corner-lost\n", "")
+            .replace("strategy greedy\n", "")
+            .replace("control human\n", "")
+            .replace("control agent\n", "")
+            .replace(checksum, "");
+        let v1 = GameRecord::parse(&v1).unwrap();
+        assert_eq!((v1.cause, v1.switches.len(), v1.controls.len()), (None, 0, 0));
+
+        // comments and blank lines may be added, not moves changed
+        assert_eq!(GameRecord::parse(&format!("# a game\n\n{text}")).unwrap(), record);
+        let edited = GameRecord::parse(&text.replace("move U 3,3=2", "move U 3,3=1"));
+        assert!(edited.unwrap_err().to_string().contains("checksum mismatch"));
+        // a game played by another engine is refused rather than replayed wrongly
+        let other_engine = text.replace(checksum, "").replace(&format!("engine {ENGINE_VERSION}"), &format!("engine {}", ENGINE_VERSION + 1));
+        assert!(GameRecord::parse(&other_engine).is_err());
+
+        // the rules of a variant are kept, the original ones are implied
+        assert!(!text.contains("rules"));
+        let variant = GameRecord { ruleset: Ruleset { four_probability: 0.5, ..Default::default() }, ..record };
+        let text = variant.to_string();
+        assert!(text.contains("\nrules target_tile = 2048, four_probability = 0.5,"), "{text}");
+        assert_eq!(GameRecord::parse(&text).unwrap(), variant);
+    }
+
+    #[test]
+    fn test_positions() {
+        let positions = sample().positions().unwrap();
+        assert_eq!(positions.len(), 3);
+        assert_eq!(positions[2].board().compact(), "1100/0000/0000/0002");
+        assert_eq!(sample().scores().unwrap(), [0, 0, 0]);
+
+        let mut record = GameRecord::new(Board::from_compact("0000/0000/0000/1100").unwrap());
+        record.push(Action::Left, Spawn { row: 0, col: 0, exponent: 1 });
+        record.push(Action::Down, Spawn { row: 0, col: 0, exponent: 1 });
+        assert_eq!(record.scores().unwrap(), [0, 4, 4]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(GameRecord::parse(&format!("version {}\ninitial 0000/0000/0010/0000\n", NOTATION_VERSION + 1)).is_err());
+        assert!(GameRecord::parse(&format!("version {NOTATION_VERSION}\ninitial 0000/0000/0010/0000\n")).is_err());
+        assert!(GameRecord::parse("initial 0000/0000/0010/0000\n").is_err());
+        assert!(GameRecord::parse("version 1\ninitial 0000/0000/0010/0000\nmove X 0,0=1\n").is_err());
+        assert!(GameRecord::parse("version 6\nengine 1\ninitial 0000/0000/0010/0000\ncontrol nobody\n").is_err());
+        // the spawn lands on the tile moved to the left
+        let mut record = sample();
+        record.moves[0].1 = Spawn { row: 2, col: 0, exponent: 1 };
+        assert!(record.positions().is_err());
+    }
+}
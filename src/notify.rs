@@ -0,0 +1,129 @@
+//! Notifications of the end (and of the milestones) of long headless runs, so that nobody has
+//! to watch the terminal: a desktop notification and/or a JSON payload posted to a webhook.
+//!
+//! Nothing is notified unless asked on the command line. A notification that cannot be
+//! delivered is only reported, it never fails the run.
+
+use std::process::Command;
+use std::sync::Mutex;
+
+use anyhow::{bail, ensure, Context};
+
+use crate::summary::json_string;
+
+/// Where the notifications of a run go
+pub struct Notifier {
+    desktop: bool,
+    /// `http://host[:port]/path` receiving the payloads
+    webhook: Option<String>,
+    /// Best score of the games finished so far
+    best: Mutex<Option<u32>>,
+}
+
+impl Notifier {
+    pub fn new(desktop: bool, webhook: Option<String>) -> Notifier {
+        Notifier { desktop, webhook, best: Mutex::new(None) }
+    }
+
+    /// Notifies `event` (e.g. `finished`, `best`) with a short `message` and the JSON `summary`
+    /// of the run or of the game, if any.
+    pub fn send(&self, event: &str, message: &str, summary: Option<&str>) {
+        if self.desktop {
+            if let Err(e) = desktop_notification(&format!("2048 bench: {event}"), message) {
+                println!("Desktop notification failed: {e:#}");
+            }
+        }
+        if let Some(webhook) = &self.webhook {
+            if let Err(e) = crate::telemetry::post(webhook, &payload(event, message, summary)) {
+                println!("Webhook notification to {webhook} failed: {e:#}");
+            }
+        }
+    }
+
+    /// Records the score of a finished game, notifying when it beats all the games finished
+    /// before it (the first game sets the bar silently).
+    pub fn game_finished(&self, score: u32, summary: &str) {
+        let new_best = {
+            let mut best = self.best.lock().unwrap();
+            let previous = *best;
+            *best = Some(previous.map_or(score, |previous| previous.max(score)));
+            previous.is_some_and(|previous| score > previous)
+        };
+        if new_best {
+            self.send("best", &format!("New best score: {score}"), Some(summary));
+        }
+    }
+}
+
+// JSON object posted to the webhook
+fn payload(event: &str, message: &str, summary: Option<&str>) -> String {
+    format!(
+        "{{\"event\": {}, \"message\": {}, \"summary\": {}}}",
+        json_string(event),
+        json_string(message),
+        summary.map_or("null", str::trim)
+    )
+}
+
+// Shows a notification on the desktop, with the notifier of the platform
+fn desktop_notification(title: &str, message: &str) -> anyhow::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let script = format!("display notification {} with title {}", json_string(message), json_string(title));
+        let mut command = Command::new("osascript");
+        command.args(["-e", &script]);
+        command
+    } else if cfg!(unix) {
+        let mut command = Command::new("notify-send");
+        command.args([title, message]);
+        command
+    } else {
+        bail!("desktop notifications are not supported on this platform");
+    };
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command.status().with_context(|| format!("could not run `{program}`"))?;
+    ensure!(status.success(), "`{program}` failed ({status})");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_webhook() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let webhook = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+            String::from_utf8(body).unwrap()
+        });
+
+        let notifier = Notifier::new(false, Some(webhook));
+        // only the game beating a previous one is notified
+        notifier.game_finished(500, "{\"score\": 500}");
+        notifier.game_finished(300, "{\"score\": 300}");
+        notifier.game_finished(800, "{\"score\": 800}\n");
+        assert_eq!(
+            server.join().unwrap(),
+            "{\"event\": \"best\", \"message\": \"New best score: 800\", \"summary\": {\"score\": 800}}"
+        );
+        assert_eq!(payload("finished", "done", None), "{\"event\": \"finished\", \"message\": \"done\", \"summary\": null}");
+    }
+}
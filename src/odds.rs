@@ -0,0 +1,67 @@
+//! Estimates of the probability of eventually reaching a tile (e.g. 2048) from a position, by
+//! playing the game to the end many times with a strategy (rollouts).
+//!
+//! The spawns of rollout `i` are drawn from the seed `i`, so that the estimates of a position
+//! are reproducible and the estimates of consecutive positions are comparable.
+
+use rayon::prelude::*;
+
+use crate::board::*;
+use crate::search::SearchResult;
+use crate::spawn::SeededSpawns;
+use crate::strategy::{Budget, Strategy};
+
+/// Fraction of `rollouts` games, played from `board` by the strategies built by `strategy`,
+/// in which each of the `targets` exponents is reached. Rollouts stop as soon as the largest
+/// target is reached.
+pub fn reach_probabilities(
+    board: PlayableBoard,
+    targets: &[u8],
+    rollouts: u64,
+    strategy: impl Fn() -> Box<dyn Strategy> + Sync,
+) -> Vec<f32> {
+    let Some(&highest) = targets.iter().max() else {
+        return Vec::new();
+    };
+    let max_tiles: Vec<u8> = (0..rollouts)
+        .into_par_iter()
+        .map(|seed| {
+            let mut strategy = strategy();
+            let mut spawns = SeededSpawns::new(seed);
+            let mut cur = board;
+            while cur.max_tile() < highest {
+                let Some(SearchResult { best: action, .. }) = strategy.choose(cur, Budget::default()) else {
+                    break;
+                };
+                // strategies only return applicable actions, except misbehaving external engines
+                let Some(played) = cur.apply(action) else {
+                    break;
+                };
+                cur = played.with_spawn_from(&mut spawns).0;
+            }
+            cur.max_tile()
+        })
+        .collect();
+    targets
+        .iter()
+        .map(|&target| max_tiles.iter().filter(|&&max| max >= target).count() as f32 / rollouts.max(1) as f32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::{GreedyStrategy, RandomStrategy};
+
+    #[test]
+    fn test_reach_probabilities() {
+        let board = PlayableBoard::from_board(Board::from_compact("0000/0000/0000/0aa0").unwrap());
+        // the two 1024 can always be merged
+        assert_eq!(reach_probabilities(board, &[11], 8, || Box::new(GreedyStrategy)), vec![1.0]);
+
+        let start = PlayableBoard::from_board(Board::from_compact("0000/0000/0000/1001").unwrap());
+        let odds = reach_probabilities(start, &[6, 11], 16, || Box::new(RandomStrategy));
+        assert!(odds[0] > 0.0 && odds[1] == 0.0, "{odds:?}");
+        assert!(reach_probabilities(start, &[], 16, || Box::new(RandomStrategy)).is_empty());
+    }
+}
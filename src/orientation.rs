@@ -0,0 +1,77 @@
+//! Re-orientation of the displayed board, so that the corner where the agent keeps its largest
+//! tile always appears bottom-left: agents anchor it in any corner, and a spectator of a long
+//! session follows the game more easily when it does not jump from one corner to another.
+//!
+//! The transform is purely visual: the game is played on the real board, only its image by one
+//! of the symmetries of `Board::symmetric` is drawn, and the moves typed on the displayed board
+//! are translated back.
+
+use crate::board::*;
+
+/// Symmetry of the displayed board
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Orientation {
+    symmetry: usize,
+}
+
+impl Orientation {
+    /// Follows the largest tile of `board`: the symmetry only changes when the current one no
+    /// longer shows it bottom-left and another one can, so the display stays still while the
+    /// tile is out of the corners. Among the symmetries showing it bottom-left, the one putting
+    /// the largest tiles on the bottom row is chosen.
+    pub fn update(&mut self, board: &Board) {
+        let max = board.max_tile();
+        let anchored = |k: usize| board.symmetric(k).get(N - 1, 0) == max;
+        if anchored(self.symmetry) {
+            return;
+        }
+        let bottom_row = |k: usize| board.symmetric(k).cells()[N - 1].iter().map(|&e| e as u32).sum::<u32>();
+        if let Some(k) = (0..NUM_SYMMETRIES).filter(|&k| anchored(k)).max_by_key(|&k| (bottom_row(k), usize::MAX - k)) {
+            self.symmetry = k;
+        }
+    }
+
+    pub fn symmetry(&self) -> usize {
+        self.symmetry
+    }
+
+    /// The board as displayed
+    pub fn display(&self, board: &Board) -> Board {
+        board.symmetric(self.symmetry)
+    }
+
+    /// The action on the real board of an action typed on the displayed board
+    pub fn from_display(&self, action: Action) -> Action {
+        ALL_ACTIONS
+            .into_iter()
+            .find(|&real| symmetric_action(real, self.symmetry) == action)
+            .expect("the symmetries of the square permute the actions")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anchor_bottom_left() {
+        let mut orientation = Orientation::default();
+        // largest tile top right, next ones on the right column
+        let board = Board::from_compact("0005/0004/0003/0001").unwrap();
+        orientation.update(&board);
+        assert_eq!(orientation.display(&board).compact(), "0000/0000/0000/5431");
+        // typing Left on the display moves the tiles towards the real top
+        assert_eq!(orientation.from_display(Action::Left), Action::Up);
+        assert_eq!(symmetric_action(orientation.from_display(Action::Down), orientation.symmetry()), Action::Down);
+
+        // the tile leaves the corner: the display does not move
+        let k = orientation.symmetry();
+        orientation.update(&Board::from_compact("0050/0004/0003/0001").unwrap());
+        assert_eq!(orientation.symmetry(), k);
+
+        // the identity is kept as long as it anchors the largest tile
+        let mut orientation = Orientation::default();
+        orientation.update(&Board::from_compact("0000/0000/1000/5000").unwrap());
+        assert_eq!(orientation.symmetry(), 0);
+    }
+}
@@ -0,0 +1,53 @@
+//! Pace of the agent in the game window: the pause after each move follows how interesting the
+//! decision was, so that long games stay watchable without touching the speed. Forced moves are
+//! played at once, obvious ones quickly, and the close calls are left on screen longer.
+
+use crate::board::*;
+use crate::search::SearchResult;
+
+// Relative lead of the best action over the second one above which the move is obvious, and
+// below which the decision is a close call
+const OBVIOUS_MARGIN: f32 = 0.05;
+const CLOSE_MARGIN: f32 = 0.005;
+
+/// Ticks to wait after the move chosen by `result` on `board`, for a pause of `base` ticks
+/// (at most `max` ticks). Results without values (e.g. random moves) keep the base pause.
+pub fn agent_pause(board: PlayableBoard, result: &SearchResult, base: u32, max: u32) -> u32 {
+    if board.legal_actions().len() <= 1 {
+        return 0;
+    }
+    let mut values: Vec<f32> = result.values.iter().flatten().copied().collect();
+    values.sort_by(|a, b| b.total_cmp(a));
+    let [best, second, ..] = values[..] else {
+        return base;
+    };
+    let lead = (best - second) / best.abs().max(f32::MIN_POSITIVE);
+    if lead >= OBVIOUS_MARGIN {
+        base / 4
+    } else if lead <= CLOSE_MARGIN {
+        (base * 2).min(max).max(base)
+    } else {
+        base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_agent_pause() {
+        let board = PlayableBoard::from_board(Board::from_compact("0000/0000/1000/9800").unwrap());
+        let result = |values: [Option<f32>; 4]| SearchResult { values, ..SearchResult::unsearched(Action::Up, Duration::ZERO) };
+        assert_eq!(agent_pause(board, &result([Some(100.0), Some(50.0), None, None]), 12, 60), 3);
+        assert_eq!(agent_pause(board, &result([Some(100.0), Some(97.0), None, None]), 12, 60), 12);
+        assert_eq!(agent_pause(board, &result([Some(100.0), None, None, Some(99.9)]), 12, 60), 24);
+        assert_eq!(agent_pause(board, &result([Some(100.0), None, None, Some(99.9)]), 40, 60), 60);
+        assert_eq!(agent_pause(board, &result([None; 4]), 12, 60), 12);
+        // a single legal move (Right) is played at once
+        let forced = PlayableBoard::from_board(Board::from_compact("1230/2310/1230/2310").unwrap());
+        assert_eq!(forced.legal_actions().len(), 1);
+        assert_eq!(agent_pause(forced, &result([None, None, None, Some(100.0)]), 12, 60), 0);
+    }
+}
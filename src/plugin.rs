@@ -0,0 +1,171 @@
+//! Evaluation functions written by the users, compiled to WebAssembly in any language, used by
+//! the search instead of the built-in heuristic (`eval.rs`), to experiment without changing the
+//! search. Needs the `wasm` feature.
+//!
+//! The plugin of the process is loaded once at startup from the file named by `PLUGIN_VAR`. The
+//! module must export a function `evaluate(board: i64) -> f32`, receiving the packed board
+//! (`Board::hash64`: 4 bits per cell, the exponent of the tile, row by row from the top left
+//! cell in the high bits) and returning its value, larger being better. As for the built-in
+//! heuristic, the values must be positive: 0 is the value of a lost game, and an agent seeing
+//! only lost games gives up. The module cannot import anything. A trap of the plugin stops
+//! the process, its values would be meaningless.
+//!
+//! For instance, in Rust (built for `wasm32-unknown-unknown` as a `cdylib`):
+//!
+//! ```ignore
+//! #[no_mangle]
+//! pub extern "C" fn evaluate(board: i64) -> f32 {
+//!     // one more than the number of empty cells
+//!     (0..16).filter(|i| (board >> (4 * i)) & 0xf == 0).count() as f32 + 1.0
+//! }
+//! ```
+
+use std::path::Path;
+use std::sync::OnceLock;
+
+use anyhow::Context;
+
+use crate::board::*;
+
+/// Environment variable giving the WebAssembly module of the evaluation
+pub const PLUGIN_VAR: &str = "AI2048_EVAL_PLUGIN";
+
+/// A loaded evaluation module
+#[cfg(feature = "wasm")]
+pub struct Plugin {
+    engine: wasmi::Engine,
+    module: wasmi::Module,
+    id: u32,
+}
+
+/// A loaded evaluation module (none without the `wasm` feature)
+#[cfg(not(feature = "wasm"))]
+pub enum Plugin {}
+
+#[cfg(feature = "wasm")]
+mod instance {
+    use std::cell::RefCell;
+
+    use wasmi::{Linker, Store, TypedFunc};
+
+    use super::Plugin;
+
+    // Instance of the plugin of the given id, one per thread since instances are not shared
+    type Instance = (u32, Store<()>, TypedFunc<i64, f32>);
+
+    thread_local! {
+        static INSTANCE: RefCell<Option<Instance>> = const { RefCell::new(None) };
+    }
+
+    pub fn instantiate(plugin: &Plugin) -> anyhow::Result<(Store<()>, TypedFunc<i64, f32>)> {
+        let mut store = Store::new(&plugin.engine, ());
+        let instance = <Linker<()>>::new(&plugin.engine).instantiate(&mut store, &plugin.module)?.start(&mut store)?;
+        let evaluate = instance.get_typed_func::<i64, f32>(&store, "evaluate")?;
+        Ok((store, evaluate))
+    }
+
+    pub fn call(plugin: &Plugin, board: i64) -> f32 {
+        INSTANCE.with_borrow_mut(|instance| {
+            if instance.as_ref().is_none_or(|(id, _, _)| *id != plugin.id) {
+                let (store, evaluate) = instantiate(plugin).expect("the plugin was instantiated when loaded");
+                *instance = Some((plugin.id, store, evaluate));
+            }
+            let (_, store, evaluate) = instance.as_mut().unwrap();
+            evaluate.call(store, board).unwrap_or_else(|e| panic!("the evaluation plugin failed: {e}"))
+        })
+    }
+}
+
+impl Plugin {
+    /// Loads a module, checking that it exports a valid `evaluate` function.
+    #[cfg(feature = "wasm")]
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Plugin> {
+        let engine = wasmi::Engine::default();
+        let module = wasmi::Module::new(&engine, bytes)?;
+        let plugin = Plugin { engine, module, id: crate::compat::checksum(bytes) };
+        instance::instantiate(&plugin).context("the module must export `evaluate(i64) -> f32` and import nothing")?;
+        Ok(plugin)
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn from_bytes(_bytes: &[u8]) -> anyhow::Result<Plugin> {
+        anyhow::bail!("evaluation plugins need the `wasm` feature (cargo build --features wasm)")
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Plugin> {
+        let bytes = std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+        Plugin::from_bytes(&bytes).with_context(|| format!("Invalid evaluation plugin {}", path.display()))
+    }
+
+    /// Value of `board` according to the plugin
+    #[cfg_attr(not(feature = "wasm"), allow(unused_variables))]
+    pub fn evaluate(&self, board: &Board) -> f32 {
+        #[cfg(feature = "wasm")]
+        return instance::call(self, board.hash64() as i64);
+        #[cfg(not(feature = "wasm"))]
+        match *self {}
+    }
+
+    /// Short identifier of the module (8 hexadecimal digits), to tell the values of different
+    /// plugins apart
+    pub fn id(&self) -> String {
+        #[cfg(feature = "wasm")]
+        return format!("{:08x}", self.id);
+        #[cfg(not(feature = "wasm"))]
+        match *self {}
+    }
+}
+
+static PLUGIN: OnceLock<Option<Plugin>> = OnceLock::new();
+
+/// Loads the plugin of `PLUGIN_VAR`, if set, as the evaluation of the process. Called once at
+/// startup, so that an invalid plugin is reported before any game.
+pub fn init() -> anyhow::Result<Option<&'static Plugin>> {
+    let plugin = match std::env::var_os(PLUGIN_VAR) {
+        Some(path) => Some(Plugin::load(Path::new(&path))?),
+        None => None,
+    };
+    Ok(PLUGIN.get_or_init(|| plugin).as_ref())
+}
+
+/// The evaluation plugin of the process, if `init` loaded one
+pub fn active() -> Option<&'static Plugin> {
+    PLUGIN.get_or_init(|| None).as_ref()
+}
+
+/// `Plugin::id` of the plugin of the process, `none` without plugin
+pub fn active_id() -> String {
+    active().map_or("none".to_string(), Plugin::id)
+}
+
+#[cfg(all(test, feature = "wasm"))]
+mod tests {
+    use super::*;
+
+    // A module whose `evaluate` returns the exponent of the bottom right tile:
+    // `f32.convert_i64_u(i64.and(board, 15))`
+    const BOTTOM_RIGHT: [u8; 46] = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x06, 0x01, 0x60, 0x01, 0x7e, 0x01, 0x7d, // type 0: (i64) -> f32
+        0x03, 0x02, 0x01, 0x00, // function 0 of type 0
+        0x07, 0x0c, 0x01, 0x08, b'e', b'v', b'a', b'l', b'u', b'a', b't', b'e', 0x00, 0x00, // export
+        0x0a, 0x0a, 0x01, 0x08, 0x00, 0x20, 0x00, 0x42, 0x0f, 0x83, 0xb5, 0x0b, // code
+    ];
+
+    #[test]
+    fn test_plugin() {
+        let plugin = Plugin::from_bytes(&BOTTOM_RIGHT).unwrap();
+        assert_eq!(plugin.evaluate(&Board::from_compact("1000/0000/0000/000b").unwrap()), 11.0);
+        // from another thread, with its own instance
+        let board = Board::from_compact("0000/0000/0000/0003").unwrap();
+        assert_eq!(std::thread::scope(|scope| scope.spawn(|| plugin.evaluate(&board)).join().unwrap()), 3.0);
+        assert_eq!(plugin.id().len(), 8);
+
+        // truncated
+        assert!(Plugin::from_bytes(&BOTTOM_RIGHT[..40]).is_err());
+        // the function has another name
+        let mut renamed = BOTTOM_RIGHT;
+        renamed[24] = b'E';
+        assert!(Plugin::from_bytes(&renamed).is_err());
+    }
+}
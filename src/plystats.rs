@@ -0,0 +1,97 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::board::*;
+use crate::eval::Weights;
+
+/// State of the board at a given move of a game
+#[derive(Clone, Copy, Debug)]
+pub struct PlySample {
+    pub num_empty: usize,
+    /// Value of the largest tile (e.g. 2048)
+    pub max_tile: u32,
+    pub eval: f32,
+}
+
+impl PlySample {
+    pub fn of(board: &PlayableBoard, weights: &Weights) -> PlySample {
+        PlySample {
+            num_empty: board.board().num_empty(),
+            max_tile: 2u32.pow(board.max_tile() as u32),
+            eval: board.evaluate_with(weights),
+        }
+    }
+}
+
+/// Sums of the samples of many games, indexed by move number
+#[derive(Default)]
+pub struct PlyStats {
+    /// Number of games that reached each move number
+    num_games: Vec<u64>,
+    sum_empty: Vec<f64>,
+    sum_max_tile: Vec<f64>,
+    sum_eval: Vec<f64>,
+}
+
+impl PlyStats {
+    /// Adds the samples of one game (one sample per move)
+    pub fn add_game(&mut self, samples: &[PlySample]) {
+        if samples.len() > self.num_games.len() {
+            self.num_games.resize(samples.len(), 0);
+            self.sum_empty.resize(samples.len(), 0.0);
+            self.sum_max_tile.resize(samples.len(), 0.0);
+            self.sum_eval.resize(samples.len(), 0.0);
+        }
+        for (ply, sample) in samples.iter().enumerate() {
+            self.num_games[ply] += 1;
+            self.sum_empty[ply] += sample.num_empty as f64;
+            self.sum_max_tile[ply] += sample.max_tile as f64;
+            self.sum_eval[ply] += sample.eval as f64;
+        }
+    }
+
+    /// Averages per move: `(ply, num_games, avg_empty, avg_max_tile, avg_eval)`
+    fn averages(&self) -> impl Iterator<Item = (usize, u64, f64, f64, f64)> + '_ {
+        (0..self.num_games.len()).map(|ply| {
+            let n = self.num_games[ply] as f64;
+            (
+                ply,
+                self.num_games[ply],
+                self.sum_empty[ply] / n,
+                self.sum_max_tile[ply] / n,
+                self.sum_eval[ply] / n,
+            )
+        })
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("ply,games,avg_empty,avg_max_tile,avg_eval\n");
+        for (ply, games, empty, max_tile, eval) in self.averages() {
+            writeln!(out, "{ply},{games},{empty:.4},{max_tile:.2},{eval:.2}").unwrap();
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let rows: Vec<String> = self
+            .averages()
+            .map(|(ply, games, empty, max_tile, eval)| {
+                format!(
+                    "  {{\"ply\": {ply}, \"games\": {games}, \"avg_empty\": {empty:.4}, \"avg_max_tile\": {max_tile:.2}, \"avg_eval\": {eval:.2}}}"
+                )
+            })
+            .collect();
+        format!("[\n{}\n]\n", rows.join(",\n"))
+    }
+
+    /// Writes the averaged curves to `path`, as JSON if the extension is `.json` and as CSV otherwise.
+    pub fn export(&self, path: &Path) -> anyhow::Result<()> {
+        let content = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => self.to_json(),
+            _ => self.to_csv(),
+        };
+        std::fs::write(path, content).with_context(|| format!("Could not write {}", path.display()))
+    }
+}
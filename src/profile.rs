@@ -0,0 +1,116 @@
+//! Named local profiles, so that the players of a shared machine, or the arms of an A/B
+//! experiment, keep their own persistent state: saved games (the statistics of the replays and
+//! analyses), bookmarks, settings and progress in the tutorial.
+//!
+//! The profile of the process is chosen once at startup, from `PROFILE_VAR` (which creates it
+//! if needed) or, when profiles exist, in a menu. The files of a profile live in its directory
+//! of `PROFILES_DIR`; without a profile, they stay in the working directory as before.
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use anyhow::ensure;
+use macroquad::prelude::*;
+
+use crate::menu::{Menu, MenuInput};
+
+/// Environment variable naming the profile of the process
+pub const PROFILE_VAR: &str = "AI2048_PROFILE";
+/// Directory of the profiles, in the working directory
+pub const PROFILES_DIR: &str = "profiles";
+// Longest name of a profile
+const MAX_NAME_LEN: usize = 32;
+// Name shown for the state kept in the working directory
+const DEFAULT_NAME: &str = "(default)";
+
+static PROFILE: OnceLock<Option<String>> = OnceLock::new();
+
+/// Checks that `name` can name a profile: letters, digits, `-` and `_`, so that it is a plain
+/// directory name on every platform.
+pub fn check_name(name: &str) -> anyhow::Result<()> {
+    ensure!(
+        !name.is_empty() && name.len() <= MAX_NAME_LEN && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        "invalid profile name `{name}`: 1 to {MAX_NAME_LEN} letters, digits, `-` or `_`"
+    );
+    Ok(())
+}
+
+/// Names of the existing profiles, sorted
+pub fn names() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(PROFILES_DIR) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| check_name(name).is_ok())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Makes `name` the profile of the process (`None` for the working directory), creating its
+/// directory if needed. Only the first call has an effect.
+pub fn select(name: Option<String>) -> anyhow::Result<()> {
+    if let Some(name) = &name {
+        check_name(name)?;
+        std::fs::create_dir_all(PathBuf::from(PROFILES_DIR).join(name))?;
+    }
+    PROFILE.get_or_init(|| name);
+    Ok(())
+}
+
+/// The profile of the process, if any
+pub fn current() -> Option<&'static str> {
+    PROFILE.get().and_then(|name| name.as_deref())
+}
+
+/// Where the file or directory `name` of the current profile is
+pub fn path(name: &str) -> PathBuf {
+    match current() {
+        Some(profile) => PathBuf::from(PROFILES_DIR).join(profile).join(name),
+        None => PathBuf::from(name),
+    }
+}
+
+// Lets the player choose among the existing profiles (ASYNC), `None` for the default one.
+// Escape or closing the window quits.
+pub async fn choose(names: &[String]) -> Option<Option<String>> {
+    let mut options: Vec<&str> = vec![DEFAULT_NAME];
+    options.extend(names.iter().map(String::as_str));
+    let mut index = 0;
+    let mut menu = Menu::default();
+    loop {
+        clear_background(Color::new(0.98, 0.97, 0.94, 1.0));
+        let mut ui = menu.frame(MenuInput::read());
+        ui.list("Profile", &options, &mut index);
+        let chosen = ui.button("Continue");
+        let quit = ui.button("Quit") || ui.back() || is_quit_requested();
+        ui.show("WHO IS PLAYING?");
+        draw_text(format!("(new profiles: {PROFILE_VAR}=<name>)"), 20.0, screen_height() - 20.0, 18.0, DARKGRAY);
+        next_frame().await;
+        if quit {
+            return None;
+        }
+        if chosen {
+            return Some(names.get(index.wrapping_sub(1)).cloned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert!(check_name("alice").is_ok() && check_name("arm-B_2").is_ok());
+        assert!(check_name("").is_err());
+        assert!(check_name("../games").is_err());
+        assert!(check_name("a b").is_err());
+        assert!(check_name(&"x".repeat(MAX_NAME_LEN + 1)).is_err());
+        // (no profile selected in the tests)
+        assert_eq!(path("games"), PathBuf::from("games"));
+    }
+}
@@ -0,0 +1,150 @@
+//! Optional tracking of how every tile of a game was built: the spawned tiles merged into it,
+//! where they spawned and since when the tile has its value.
+//!
+//! The board itself only stores exponents, so the metadata lives in a separate grid that must
+//! be updated with every move (`Provenance::play`). Only the game window tracks it, the search
+//! does not pay for it.
+
+use crate::board::*;
+
+/// How a tile was built
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileInfo {
+    /// Number of spawned tiles merged into this one (1 for a tile that was just spawned)
+    pub spawns: u32,
+    /// Cells where these tiles spawned, one bit per cell (`row * N + col`)
+    pub origins: u16,
+    /// Move number at which the tile got its value, by spawning or merging
+    pub since: u32,
+}
+
+impl TileInfo {
+    fn spawned(row: usize, col: usize, moves: u32) -> TileInfo {
+        TileInfo { spawns: 1, origins: 1 << (row * N + col), since: moves }
+    }
+
+    /// Whether all the tiles merged into this one spawned in the same corner quadrant (2x2 cells)
+    pub fn from_single_corner(&self) -> bool {
+        const TOP_LEFT: u16 = 0b0000_0000_0011_0011;
+        [TOP_LEFT, TOP_LEFT << 2, TOP_LEFT << 8, TOP_LEFT << 10]
+            .iter()
+            .any(|&quadrant| self.origins & !quadrant == 0)
+    }
+}
+
+/// Metadata of every tile of a board during a game
+pub struct Provenance {
+    tiles: [[Option<TileInfo>; N]; N],
+    /// Number of moves played since the tracking started
+    moves: u32,
+}
+
+impl Provenance {
+    /// Starts tracking on `board`, its tiles being considered as spawned where they stand.
+    pub fn new(board: &Board) -> Provenance {
+        let mut tiles = [[None; N]; N];
+        for (i, row) in board.cells().iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate() {
+                if cell != 0 {
+                    tiles[i][j] = Some(TileInfo::spawned(i, j, 0));
+                }
+            }
+        }
+        Provenance { tiles, moves: 0 }
+    }
+
+    pub fn tile(&self, row: usize, col: usize) -> Option<TileInfo> {
+        self.tiles[row][col]
+    }
+
+    /// Number of moves since the tile got its value
+    pub fn age(&self, info: &TileInfo) -> u32 {
+        self.moves - info.since
+    }
+
+    /// Follows the tiles when `action` is played on `board` (the position before the move),
+    /// followed by `spawn`, as told by `PlayableBoard::apply_outcome`.
+    pub fn play(&mut self, board: &Board, action: Action, spawn: Spawn) {
+        self.moves += 1;
+        if let Some(outcome) = PlayableBoard::from_board(*board).apply_outcome(action) {
+            let mut next: [[Option<TileInfo>; N]; N] = [[None; N]; N];
+            for tile in &outcome.tiles {
+                let (i, j) = tile.from;
+                let info = self.tiles[i][j].unwrap_or_else(|| TileInfo::spawned(i, j, self.moves - 1));
+                let (ti, tj) = tile.to;
+                next[ti][tj] = Some(match next[ti][tj] {
+                    // the second tile of a merge
+                    Some(absorbing) => TileInfo {
+                        spawns: absorbing.spawns + info.spawns,
+                        origins: absorbing.origins | info.origins,
+                        since: self.moves,
+                    },
+                    None => info,
+                });
+            }
+            self.tiles = next;
+        }
+        self.tiles[spawn.row][spawn.col] = Some(TileInfo::spawned(spawn.row, spawn.col, self.moves));
+    }
+
+    /// Draws the number of spawned tiles (`n`) and the age (`a`) of every tile, for the debug
+    /// overlay.
+    #[cfg(feature = "gui")]
+    pub fn draw(&self) {
+        use macroquad::prelude::{draw_text, Color};
+        let color = Color::new(0.0, 0.4, 0.8, 1.0);
+        let tile_size = crate::layout::layout().tile_size();
+        for i in 0..N {
+            for j in 0..N {
+                if let Some(info) = &self.tiles[i][j] {
+                    let (x, y) = tile_position(j, i);
+                    draw_text(format!("n={} a={}", info.spawns, self.age(info)), x + 4.0, y + tile_size - 24.0, 16.0, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn::RandomSpawns;
+
+    #[test]
+    fn test_merge_provenance() {
+        let board = Board::from_compact("0000/0000/0000/1101").unwrap();
+        let mut provenance = Provenance::new(&board);
+        provenance.play(&board, Action::Left, Spawn { row: 0, col: 0, exponent: 1 });
+
+        let merged = provenance.tile(3, 0).unwrap();
+        assert_eq!(merged, TileInfo { spawns: 2, origins: 0b0011 << 12, since: 1 });
+        assert_eq!(provenance.tile(3, 1).unwrap().since, 0);
+        assert_eq!(provenance.age(&provenance.tile(3, 1).unwrap()), 1);
+        assert!(provenance.tile(3, 2).is_none());
+        assert!(merged.from_single_corner());
+        assert!(!TileInfo { origins: merged.origins | 1 << 15, ..merged }.from_single_corner());
+    }
+
+    #[test]
+    fn test_provenance_follows_the_board() {
+        let mut board = PlayableBoard::init();
+        let mut provenance = Provenance::new(&board.board());
+        while let Some(action) = crate::search::select_action_randomly(board).map(|result| result.best) {
+            let played = board.apply(action).unwrap();
+            let (next, spawn) = played.with_spawn_from(&mut RandomSpawns);
+            provenance.play(&board.board(), action, spawn);
+            board = next;
+            for (i, row) in board.board().cells().iter().enumerate() {
+                for (j, &exponent) in row.iter().enumerate() {
+                    let info = provenance.tile(i, j);
+                    assert_eq!(info.is_some(), exponent != 0);
+                    // made of 2s and 4s
+                    if let Some(info) = info {
+                        let value = 2u32.pow(exponent as u32);
+                        assert!((value / 4..=value / 2).contains(&info.spawns), "{value} from {} tiles", info.spawns);
+                    }
+                }
+            }
+        }
+    }
+}
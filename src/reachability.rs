@@ -0,0 +1,77 @@
+//! Checks that a constructed position (pasted, imported from a screenshot) could appear in
+//! normal play, so that the analyses and puzzles built on it stay realistic.
+//!
+//! The check is a reverse search of one move: a position where the player is to move was
+//! either the start of the game (two tiles of 2 or 4) or produced by a move followed by a spawn.
+//! Positions failing it are certainly unreachable; passing it does not prove the opposite.
+
+use crate::board::*;
+
+/// Why `board` cannot appear in a game, if it certainly cannot.
+pub fn unreachable_reason(board: &Board) -> Option<&'static str> {
+    let tiles: Vec<(usize, usize)> = (0..N)
+        .flat_map(|i| (0..N).map(move |j| (i, j)))
+        .filter(|&(i, j)| board.get(i, j) != 0)
+        .collect();
+    let is_spawn = |&(i, j): &(usize, usize)| board.get(i, j) <= 2;
+
+    if tiles.len() < 2 {
+        return Some("fewer than two tiles");
+    }
+    if !tiles.iter().any(is_spawn) {
+        // the tile spawned after the last move is missing
+        return Some("no 2 or 4 spawned");
+    }
+    if tiles.len() == 2 && tiles.iter().all(is_spawn) {
+        // the starting position
+        return None;
+    }
+    let after_move = tiles.iter().filter(|cell| is_spawn(cell)).any(|&(i, j)| {
+        let mut before_spawn = *board;
+        before_spawn.set(i, j, 0);
+        ALL_ACTIONS.into_iter().any(|action| is_pushed(&before_spawn, action))
+    });
+    if !after_move {
+        // without the spawned tile, the tiles would be pushed against a side
+        return Some("no previous move");
+    }
+    None
+}
+
+// Whether all the tiles are pushed in the direction of `action`, as after playing it
+fn is_pushed(board: &Board, action: Action) -> bool {
+    (0..N).all(|k| {
+        let line = line_cells(action, k).map(|(i, j)| board.get(i, j));
+        let count = line.iter().filter(|&&cell| cell != 0).count();
+        line[..count].iter().all(|&cell| cell != 0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(compact: &str) -> Option<&'static str> {
+        unreachable_reason(&Board::from_compact(compact).unwrap())
+    }
+
+    #[test]
+    fn test_unreachable_reason() {
+        // a lone 2048
+        assert!(reason("0000/0000/0000/000b").is_some());
+        // nothing spawned
+        assert!(reason("0000/0000/0030/000b").is_some());
+        // scattered tiles, with a 2 that could not have been spawned last
+        assert!(reason("3000/0030/0003/0301").is_some());
+        // starting positions
+        assert_eq!(reason("0100/0000/0020/0000"), None);
+        // after a move to the right then a spawn
+        assert_eq!(reason("0100/0000/0023/000b"), None);
+
+        let mut board = PlayableBoard::init();
+        while let Some(action) = crate::search::select_action_randomly(board).map(|result| result.best) {
+            board = board.apply(action).unwrap().with_random_tile();
+            assert_eq!(unreachable_reason(&board.board()), None, "\n{board}");
+        }
+    }
+}
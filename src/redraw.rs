@@ -0,0 +1,89 @@
+//! Skipping of the redundant redraws: the game window is drawn into an offscreen image, which is
+//! presented again as it is while nothing on screen changes, e.g. during the pauses of the agent
+//! or while a human thinks. A frame is described by a `FrameKey` of everything it shows; the
+//! scene is only drawn again when the key changes.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use macroquad::prelude::*;
+
+/// Fingerprint of what a frame shows
+#[derive(Default)]
+pub struct FrameKey(DefaultHasher);
+
+impl FrameKey {
+    /// Adds something shown by the frame
+    pub fn add(&mut self, value: impl Hash) -> &mut FrameKey {
+        value.hash(&mut self.0);
+        self
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+}
+
+/// The last frame drawn, with its key
+#[derive(Default)]
+pub struct FrameCache {
+    target: Option<RenderTarget>,
+    // size of the window the image was drawn for, and key of the image
+    size: (u32, u32),
+    key: Option<u64>,
+    /// Number of frames drawn and of frames presented from the cache
+    pub drawn: u64,
+    pub skipped: u64,
+}
+
+impl FrameCache {
+    /// Presents the frame of `key`: drawn by `draw` (in window coordinates) if the key or the
+    /// size of the window changed since the last frame, the cached image otherwise. Returns
+    /// whether `draw` was called.
+    pub fn present(&mut self, key: u64, draw: impl FnOnce()) -> bool {
+        let (width, height) = (screen_width(), screen_height());
+        let scale = screen_dpi_scale();
+        let size = ((width * scale) as u32, (height * scale) as u32);
+        if self.target.is_none() || size != self.size {
+            let target = render_target(size.0.max(1), size.1.max(1));
+            target.texture.set_filter(FilterMode::Linear);
+            self.target = Some(target);
+            self.size = size;
+            self.key = None;
+        }
+        let target = self.target.as_ref().expect("created above");
+        let redraw = self.key != Some(key);
+        if redraw {
+            let mut camera = Camera2D::from_display_rect(Rect::new(0.0, 0.0, width, height));
+            camera.render_target = Some(target.clone());
+            set_camera(&camera);
+            draw();
+            set_default_camera();
+            self.key = Some(key);
+            self.drawn += 1;
+        } else {
+            self.skipped += 1;
+        }
+        draw_texture_ex(
+            &target.texture,
+            0.0,
+            0.0,
+            WHITE,
+            // the images of the render targets are upside down
+            DrawTextureParams { dest_size: Some(vec2(width, height)), flip_y: true, ..Default::default() },
+        );
+        redraw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_key() {
+        let key = |moves: u32, status: &str| FrameKey::default().add(moves).add(status).finish();
+        assert_eq!(key(3, "Copied"), key(3, "Copied"));
+        assert_ne!(key(3, "Copied"), key(4, "Copied"));
+        assert_ne!(key(3, "Copied"), key(3, ""));
+    }
+}
@@ -0,0 +1,295 @@
+//! Offscreen rendering of boards to PNG images, without any window, for the generated reports.
+//!
+//! The grid uses the layout and colors of the game window (`board.rs`); tile values are drawn
+//! with a small bitmap font since the fonts of macroquad need a graphics context.
+
+use crate::color::Color;
+
+use crate::board::*;
+
+/// An RGB image, row-major
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[u8; 3]>,
+}
+
+// Glyphs of the bitmap font, 3 pixels wide and 5 high, one bit per pixel (row-major):
+// the digits, then the other characters of the number formats (`layout::NumberFormat`)
+const DIGITS: [u16; 10] = [
+    0b111_101_101_101_111, // 0
+    0b010_110_010_010_111, // 1
+    0b111_001_111_100_111, // 2
+    0b111_001_111_001_111, // 3
+    0b101_101_111_001_001, // 4
+    0b111_100_111_001_111, // 5
+    0b111_100_111_101_111, // 6
+    0b111_001_001_001_001, // 7
+    0b111_101_111_101_111, // 8
+    0b111_101_111_001_111, // 9
+];
+
+const OTHER_GLYPHS: [(u8, u16); 4] = [
+    (b' ', 0),
+    (b'k', 0b100_101_110_101_101),
+    (b'M', 0b101_111_111_101_101),
+    (b'^', 0b010_101_000_000_000),
+];
+
+// Glyph of a character of the bitmap font (blank for the unknown characters)
+fn glyph(c: u8) -> u16 {
+    match c {
+        b'0'..=b'9' => DIGITS[(c - b'0') as usize],
+        _ => OTHER_GLYPHS.iter().find(|&&(other, _)| other == c).map_or(0, |&(_, bits)| bits),
+    }
+}
+
+fn rgb(color: Color) -> [u8; 3] {
+    let [r, g, b, _] = color.into();
+    [r, g, b]
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, color: [u8; 3]) -> Image {
+        Image { width, height, pixels: vec![color; width * height] }
+    }
+
+    pub fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, color: [u8; 3]) {
+        for row in y..(y + h).min(self.height) {
+            for col in x..(x + w).min(self.width) {
+                self.pixels[row * self.width + col] = color;
+            }
+        }
+    }
+
+    /// Draws `text` (digits, spaces, `k`, `M` and `^`) centered in the given square
+    pub fn draw_number(&mut self, text: &str, x: usize, y: usize, size: usize, color: [u8; 3]) {
+        let len = text.len();
+        // a digit and its spacing is 4 units wide, the text fits in 70% of the width and 45% of the height
+        let unit = ((size * 7 / 10) / (4 * len - 1)).min(size * 45 / 100 / 5).max(1);
+        let x0 = x + (size - unit * (4 * len - 1)) / 2;
+        let y0 = y + (size - unit * 5) / 2;
+        for (k, digit) in text.bytes().enumerate() {
+            let bits = glyph(digit);
+            for py in 0..5 {
+                for px in 0..3 {
+                    if bits & (1 << (14 - 3 * py - px)) != 0 {
+                        self.fill_rect(x0 + (4 * k + px) * unit, y0 + py * unit, unit, unit, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Renders the grid of the board (without the statistics bar) at the given scale
+/// (1.0 is the size of the game window).
+pub fn render_board(board: &Board, scale: f32) -> Image {
+    let px = |v: f32| (v * scale).round() as usize;
+    let mut image = Image::new(px(GRID_SIZE), px(GRID_SIZE), rgb(BORDER_COLOR));
+    let tile = px(crate::layout::layout().tile_size());
+    for i in 0..N {
+        for j in 0..N {
+            let (x, y) = tile_position(j, i);
+            // positions relative to the top left corner of the grid
+            let (x, y) = (px(x - PADDING), px(y - PADDING - UI_HEIGHT));
+            let exponent = board.get(i, j);
+            if exponent == 0 {
+                image.fill_rect(x, y, tile, tile, rgb(CELL_COLOR));
+            } else {
+                let value = 2u32.pow(exponent as u32);
+                let (bg_color, text_color) = tile_colors(value);
+                image.fill_rect(x, y, tile, tile, rgb(bg_color));
+                let text = crate::layout::layout().numbers.format(value as u64);
+                image.draw_number(&text, x, y, tile, rgb(text_color));
+            }
+        }
+    }
+    image
+}
+
+/// Renders a grid of values (`values[row][col]`) as a heatmap of square cells of `cell` pixels,
+/// from dark blue for the smallest value to yellow for the largest, each cell showing its value
+/// rounded to an integer.
+pub fn render_heatmap(values: &[Vec<f32>], cell: usize) -> Image {
+    let width = values.iter().map(Vec::len).max().unwrap_or(0);
+    let mut image = Image::new(width * cell, values.len() * cell, [0, 0, 0]);
+    let (min, max) = values
+        .iter()
+        .flatten()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &v| (min.min(v), max.max(v)));
+    for (i, row) in values.iter().enumerate() {
+        for (j, &value) in row.iter().enumerate() {
+            let t = if max > min { (value - min) / (max - min) } else { 1.0 };
+            let mix = |low: f32, high: f32| (low + (high - low) * t).round() as u8;
+            let color = [mix(40.0, 250.0), mix(30.0, 220.0), mix(120.0, 40.0)];
+            image.fill_rect(j * cell, i * cell, cell, cell, color);
+            let text = if t > 0.5 { [0, 0, 0] } else { [255, 255, 255] };
+            image.draw_number(&format!("{:.0}", value.max(0.0)), j * cell, i * cell, cell, text);
+        }
+    }
+    image
+}
+
+/// Encodes the image as a PNG file
+pub fn encode_png(image: &Image) -> Vec<u8> {
+    // scanlines: filter `Up` (0x02) when the line repeats the previous one, `Sub` (0x01) otherwise,
+    // so that the flat areas of the grid become runs of zeros
+    let stride = image.width * 3;
+    let raw: Vec<u8> = image.pixels.iter().flatten().copied().collect();
+    let mut filtered = Vec::with_capacity((stride + 1) * image.height);
+    for y in 0..image.height {
+        let line = &raw[y * stride..(y + 1) * stride];
+        if y > 0 && line == &raw[(y - 1) * stride..y * stride] {
+            filtered.push(2);
+            filtered.extend(std::iter::repeat_n(0, stride));
+        } else {
+            filtered.push(1);
+            filtered.extend((0..stride).map(|i| line[i].wrapping_sub(if i >= 3 { line[i - 3] } else { 0 })));
+        }
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend((image.width as u32).to_be_bytes());
+    ihdr.extend((image.height as u32).to_be_bytes());
+    ihdr.extend([8, 2, 0, 0, 0]); // 8 bits per channel, RGB, no interlacing
+
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &zlib_compress(&filtered));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(kind.iter().chain(data));
+    png.extend(crc.to_be_bytes());
+}
+
+fn crc32<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+// Base length and number of extra bits of the length codes 257..=285 of DEFLATE
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+
+/// Bit writer in the order of DEFLATE: least significant bit first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit: u32,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: u32, count: u32) {
+        for k in 0..count {
+            if self.bit == 0 {
+                self.bytes.push(0);
+            }
+            *self.bytes.last_mut().unwrap() |= (((value >> k) & 1) as u8) << self.bit;
+            self.bit = (self.bit + 1) % 8;
+        }
+    }
+
+    /// Huffman codes are written most significant bit first
+    fn code(&mut self, code: u32, len: u32) {
+        self.bits(code.reverse_bits() >> (32 - len), len);
+    }
+
+    /// Literal or length symbol with the fixed Huffman code
+    fn symbol(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xc0 + symbol - 280, 8),
+        }
+    }
+}
+
+/// zlib stream with a single DEFLATE block using the fixed Huffman codes. The only matches
+/// searched for are runs of the previous byte (distance 1), which is enough for filtered images
+/// made of flat areas.
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = BitWriter::default();
+    out.bits(1, 1); // last block
+    out.bits(1, 2); // fixed Huffman codes
+    let mut i = 0;
+    while i < data.len() {
+        let run = if i == 0 { 0 } else { data[i..].iter().take(258).take_while(|&&b| b == data[i - 1]).count() };
+        if run >= 3 {
+            let code = LENGTH_BASE.iter().rposition(|&base| base as usize <= run).unwrap();
+            out.symbol(257 + code as u32);
+            out.bits((run - LENGTH_BASE[code] as usize) as u32, LENGTH_EXTRA[code] as u32);
+            out.code(0, 5); // distance 1
+            i += run;
+        } else {
+            out.symbol(data[i] as u32);
+            i += 1;
+        }
+    }
+    out.symbol(256); // end of block
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    let mut zlib = vec![0x78, 0x01];
+    zlib.extend(out.bytes);
+    zlib.extend(((b << 16) | a).to_be_bytes());
+    zlib
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_board() {
+        let board = Board::from_compact("1000/0000/0000/000b").unwrap();
+        let image = render_board(&board, 0.5);
+        assert_eq!(image.width, 290);
+        // background of the 2 tile (#eee4da) and of an empty cell
+        let (x, y) = tile_position(0, 0);
+        let at = |x: f32, y: f32| image.pixels[((y - PADDING - UI_HEIGHT) * 0.5) as usize * image.width + ((x - PADDING) * 0.5) as usize + 1];
+        assert_eq!(at(x, y), rgb(tile_colors(2).0));
+        let (x, y) = tile_position(1, 0);
+        assert_eq!(at(x, y), rgb(CELL_COLOR));
+
+        let png = encode_png(&image);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    }
+
+    #[test]
+    fn test_draw_formatted_numbers() {
+        let white = [255, 255, 255];
+        let inked = |text: &str| {
+            let mut image = Image::new(80, 80, [0, 0, 0]);
+            image.draw_number(text, 0, 0, 80, white);
+            image.pixels.iter().filter(|&&pixel| pixel == white).count()
+        };
+        assert_eq!(inked(" "), 0);
+        // every character of the formats has a glyph
+        for c in "0123456789kM^".bytes() {
+            assert_ne!(glyph(c), 0, "{}", c as char);
+        }
+        // the space only separates the groups of digits
+        assert_eq!(inked("16 384"), inked("16:384"));
+    }
+}
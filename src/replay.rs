@@ -0,0 +1,148 @@
+use std::sync::mpsc;
+
+use macroquad::prelude::*;
+use rayon::prelude::*;
+
+use crate::board::*;
+use crate::notation::GameRecord;
+use crate::search::{self, SearchParams};
+use crate::tips::{move_loss, MoveGrade};
+use crate::valuechart::{ValueChart, NUM_DROPS};
+
+// Number of moves shown on each side of the current one in the history ticker
+const TICKER_RADIUS: usize = 10;
+const TICKER_CELL: f32 = 17.0;
+// Number of moves skipped by PageUp / PageDown
+const PAGE: usize = 10;
+// Frames between two moves when the replay plays by itself
+const AUTOPLAY_FRAMES: u32 = 20;
+
+// Color of a move in the history ticker
+fn grade_color(grade: Option<MoveGrade>) -> Color {
+    match grade {
+        None => LIGHTGRAY, // not graded yet
+        Some(MoveGrade::Best) => DARKGREEN,
+        Some(MoveGrade::Good) => GREEN,
+        Some(MoveGrade::Inaccuracy) => ORANGE,
+        Some(MoveGrade::Blunder) => RED,
+    }
+}
+
+fn action_letter(action: Action) -> &'static str {
+    match action {
+        Action::Up => "U",
+        Action::Down => "D",
+        Action::Left => "L",
+        Action::Right => "R",
+    }
+}
+
+// Plays back a recorded game (ASYNC). Left/Right step through the moves, PageUp/PageDown jump
+// by 10 moves, Home/End go to the start/end, Space plays the game by itself and D jumps to the
+// next of the steepest drops of the value.
+// Every position is analyzed with a deeper search in the background: the history ticker fills
+// with the colors of the grades of the moves, and the chart below the grid with the values.
+pub async fn play_replay(record: GameRecord) -> anyhow::Result<()> {
+    let positions = record.positions()?;
+    let scores = record.scores()?;
+    let mut index = 0;
+    let mut autoplay = false;
+    let mut frames = 0;
+
+    // best action and grade of each move, and values of the positions, as computed by the
+    // background analysis
+    let mut grades: Vec<Option<(Action, MoveGrade)>> = vec![None; record.moves.len()];
+    let mut chart = ValueChart::new(record.moves.len());
+    let (sender, receiver) = mpsc::channel();
+    let deep = SearchParams { depth: SearchParams::default().depth + 1, ..Default::default() };
+    let analyzed = positions.clone();
+    std::thread::spawn(move || {
+        analyzed.par_iter().enumerate().for_each_with(sender, |sender, (i, &position)| {
+            // the receiver is gone once the replay is closed
+            let _ = sender.send((i, search::action_values(position, &deep)));
+        });
+    });
+
+    loop {
+        for (i, values) in receiver.try_iter() {
+            let played = record.moves.get(i).map(|&(action, _)| action);
+            if let Some((best, best_value, loss)) = played.and_then(|played| move_loss(&values, played)) {
+                grades[i] = Some((best, MoveGrade::of(best_value, loss)));
+            }
+            chart.analyzed(i, &values, played);
+        }
+
+        let last = record.moves.len();
+        if is_key_pressed(KeyCode::Escape) {
+            return Ok(());
+        }
+        if is_key_pressed(KeyCode::Space) {
+            autoplay = !autoplay;
+        }
+        if is_key_pressed(KeyCode::Right) {
+            index = (index + 1).min(last);
+        }
+        if is_key_pressed(KeyCode::Left) {
+            index = index.saturating_sub(1);
+        }
+        if is_key_pressed(KeyCode::PageDown) {
+            index = (index + PAGE).min(last);
+        }
+        if is_key_pressed(KeyCode::PageUp) {
+            index = index.saturating_sub(PAGE);
+        }
+        if is_key_pressed(KeyCode::Home) {
+            index = 0;
+        }
+        if is_key_pressed(KeyCode::End) {
+            index = last;
+        }
+        if is_key_pressed(KeyCode::D) {
+            // (to the steepest one after the last)
+            let mut drops: Vec<usize> = chart.steepest_drops(NUM_DROPS).iter().map(|drop| drop.move_index).collect();
+            drops.sort_unstable();
+            if let Some(&next) = drops.iter().find(|&&i| i > index).or(drops.first()) {
+                index = next;
+            }
+        }
+        if autoplay {
+            frames += 1;
+            if frames >= AUTOPLAY_FRAMES {
+                frames = 0;
+                index = (index + 1).min(last);
+                autoplay = index < last;
+            }
+        }
+
+        positions[index].draw(index as u32, Some(scores[index]), 0.0);
+        draw_ticker(&record, &grades, index);
+        chart.draw(index);
+        next_frame().await;
+    }
+}
+
+// Draws the moves around the current one, colored by grade, and the grade of the next move
+fn draw_ticker(record: &GameRecord, grades: &[Option<(Action, MoveGrade)>], index: usize) {
+    let x0 = 230.0;
+    let first = index.saturating_sub(TICKER_RADIUS);
+    for (k, i) in (first..(index + TICKER_RADIUS).min(record.moves.len())).enumerate() {
+        let x = x0 + k as f32 * TICKER_CELL;
+        let grade = grades[i].map(|(_, grade)| grade);
+        draw_rectangle(x, 38.0, TICKER_CELL - 2.0, TICKER_CELL, grade_color(grade));
+        if i == index {
+            draw_rectangle_lines(x - 1.0, 37.0, TICKER_CELL, TICKER_CELL + 2.0, 2.0, BLACK);
+        }
+        draw_text(action_letter(record.moves[i].0), x + 3.0, 51.0, 16.0, WHITE);
+    }
+
+    let text = match (record.moves.get(index), grades.get(index).copied().flatten()) {
+        (None, _) => "End of the game".to_string(),
+        (Some(&(played, _)), None) => format!("Next: {played:?} (analyzing...)"),
+        (Some(&(played, _)), Some((_, MoveGrade::Best))) => format!("Next: {played:?} - Best"),
+        (Some(&(played, _)), Some((best, grade))) => format!("Next: {played:?} - {grade:?} (best: {best:?})"),
+    };
+    draw_text(text, x0, 28.0, 20.0, BLACK);
+    if let Some(strategy) = record.strategy_at(index) {
+        draw_text(format!("Agent: {strategy}"), x0, 70.0, 14.0, DARKGRAY);
+    }
+}
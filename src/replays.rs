@@ -0,0 +1,215 @@
+//! Aggregate analysis of a folder of recorded games (`bench analyze-replays <dir>`).
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Context;
+use hashbrown::HashMap;
+use rayon::prelude::*;
+
+use crate::board::*;
+use crate::death::{self, DeathCause};
+use crate::render;
+use crate::notation::{GameOutcome, GameRecord, NOTATION_EXTENSION};
+use crate::search::{self, SearchParams};
+use crate::tips::{move_loss, MoveGrade, ALL_GRADES};
+
+// Number of misplayed positions listed in the report
+const NUM_MISPLAYED: usize = 5;
+
+/// Statistics accumulated over one or more recorded games
+#[derive(Default)]
+pub struct ReplayAnalysis {
+    num_games: usize,
+    num_moves: usize,
+    /// Number of moves of each grade, indexed like `ALL_GRADES`
+    grades: [usize; 4],
+    /// Number of lost games by exponent of the max tile at the end
+    final_max_tile: BTreeMap<u8, usize>,
+    /// Number of lost games by cause of death
+    causes: BTreeMap<DeathCause, usize>,
+    num_lost: usize,
+    /// Number of blunders per position, in the compact notation
+    misplayed: HashMap<String, usize>,
+}
+
+impl ReplayAnalysis {
+    /// Grades every move of the game against a search with the given parameters.
+    pub fn of_game(record: &GameRecord, params: &SearchParams) -> anyhow::Result<ReplayAnalysis> {
+        let positions = record.positions()?;
+        let grades: Vec<(PlayableBoard, MoveGrade)> = record
+            .moves
+            .par_iter()
+            .zip(&positions)
+            .filter_map(|(&(played, _), &before)| {
+                let values = search::action_values(before, params);
+                let (_, best_value, loss) = move_loss(&values, played)?;
+                Some((before, MoveGrade::of(best_value, loss)))
+            })
+            .collect();
+
+        let mut analysis = ReplayAnalysis {
+            num_games: 1,
+            num_moves: grades.len(),
+            ..Default::default()
+        };
+        for (before, grade) in grades {
+            analysis.grades[grade as usize] += 1;
+            if grade == MoveGrade::Blunder {
+                *analysis.misplayed.entry(before.board().compact()).or_default() += 1;
+            }
+        }
+        if record.outcome == GameOutcome::Lost {
+            let last = positions.last().expect("positions contain at least the initial board");
+            analysis.num_lost = 1;
+            *analysis.final_max_tile.entry(last.max_tile()).or_default() += 1;
+            // games recorded before the classifier existed have no cause
+            let cause = record
+                .cause
+                .unwrap_or_else(|| death::classify(&last.board(), record.moves.last().map(|&(_, spawn)| spawn)));
+            analysis.causes.insert(cause, 1);
+        }
+        Ok(analysis)
+    }
+
+    /// Analyzes all the games of the directory played with the rules of the given
+    /// `Ruleset::id` (all of them if `None`), skipping (with a message) the unreadable ones.
+    pub fn of_dir(dir: &Path, params: &SearchParams, ruleset: Option<&str>) -> anyhow::Result<ReplayAnalysis> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).with_context(|| format!("Could not read {}", dir.display()))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == NOTATION_EXTENSION) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let other_rules = AtomicUsize::new(0);
+        let analysis = paths
+            .par_iter()
+            .filter_map(|path| {
+                let record = GameRecord::load(path);
+                if let (Ok(record), Some(id)) = (&record, ruleset) {
+                    if record.ruleset.id() != id {
+                        other_rules.fetch_add(1, Ordering::Relaxed);
+                        return None;
+                    }
+                }
+                match record.and_then(|record| ReplayAnalysis::of_game(&record, params)) {
+                    Ok(analysis) => Some(analysis),
+                    Err(e) => {
+                        println!("Skipping {}: {e:#}", path.display());
+                        None
+                    }
+                }
+            })
+            .reduce(ReplayAnalysis::default, |mut a, b| {
+                a.merge(&b);
+                a
+            });
+        let other_rules = other_rules.into_inner();
+        if other_rules > 0 {
+            println!("Ignored {other_rules} games played with other rules");
+        }
+        Ok(analysis)
+    }
+
+    /// The positions with the most blunders (at most NUM_MISPLAYED), most misplayed first
+    fn most_misplayed(&self) -> Vec<(&String, usize)> {
+        let mut misplayed: Vec<_> = self.misplayed.iter().map(|(position, count)| (position, *count)).collect();
+        misplayed.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        misplayed.truncate(NUM_MISPLAYED);
+        misplayed
+    }
+
+    /// Writes the analysis as a Markdown report, with a PNG image of each misplayed position
+    /// saved next to it.
+    pub fn write_markdown(&self, path: &Path) -> anyhow::Result<()> {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("report");
+        let mut out = format!("# Replay analysis\n\n```text\n{self}```\n");
+        if !self.misplayed.is_empty() {
+            out.push_str("\n## Most frequently misplayed positions\n");
+        }
+        for (i, (position, count)) in self.most_misplayed().into_iter().enumerate() {
+            let image_name = format!("{stem}-misplayed-{}.png", i + 1);
+            let board = Board::from_compact(position)?;
+            let png = render::encode_png(&render::render_board(&board, 0.5));
+            std::fs::write(dir.join(&image_name), png).with_context(|| format!("Could not write {image_name}"))?;
+            out.push_str(&format!("\n`{position}`: {count} blunder(s)\n\n![{position}]({image_name})\n"));
+        }
+        std::fs::write(path, out).with_context(|| format!("Could not write {}", path.display()))
+    }
+
+    pub fn merge(&mut self, other: &ReplayAnalysis) {
+        self.num_games += other.num_games;
+        self.num_moves += other.num_moves;
+        for (a, b) in self.grades.iter_mut().zip(other.grades) {
+            *a += b;
+        }
+        for (tile, count) in &other.final_max_tile {
+            *self.final_max_tile.entry(*tile).or_default() += count;
+        }
+        for (cause, count) in &other.causes {
+            *self.causes.entry(*cause).or_default() += count;
+        }
+        self.num_lost += other.num_lost;
+        for (position, count) in &other.misplayed {
+            *self.misplayed.entry(position.clone()).or_default() += count;
+        }
+    }
+}
+
+impl Display for ReplayAnalysis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Games analyzed: {}  ({} moves)", self.num_games, self.num_moves)?;
+        if self.num_games == 0 {
+            return Ok(());
+        }
+
+        writeln!(f, "\nMove quality:")?;
+        for grade in ALL_GRADES {
+            let count = self.grades[grade as usize];
+            writeln!(
+                f,
+                "{:>12}: {:>7} ({:>5.1}%)",
+                format!("{grade:?}"),
+                count,
+                count as f32 / self.num_moves.max(1) as f32 * 100.0
+            )?;
+        }
+        writeln!(
+            f,
+            "Average blunders per game: {:.2}",
+            self.grades[MoveGrade::Blunder as usize] as f32 / self.num_games as f32
+        )?;
+
+        writeln!(f, "\nLost games: {}", self.num_lost)?;
+        if self.num_lost > 0 {
+            writeln!(f, "Causes of death:")?;
+            for (cause, count) in &self.causes {
+                writeln!(
+                    f,
+                    "{:>15}: {count} ({:.1}%)",
+                    cause.name(),
+                    *count as f32 / self.num_lost as f32 * 100.0
+                )?;
+            }
+            writeln!(f, "Max tile at the end:")?;
+            for (tile, count) in &self.final_max_tile {
+                writeln!(f, "{:>6}: {count}", 2u32.pow(*tile as u32))?;
+            }
+        }
+
+        let misplayed = self.most_misplayed();
+        if !misplayed.is_empty() {
+            writeln!(f, "\nMost frequently misplayed positions:")?;
+            for (position, count) in misplayed {
+                writeln!(f, "{position}: {count} blunder(s)")?;
+            }
+        }
+        Ok(())
+    }
+}
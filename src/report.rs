@@ -0,0 +1,167 @@
+//! Static HTML report of a batch of games: score distribution, max tile histogram, comparison of
+//! the strategies and images of the best and worst games. The report is a single file, the
+//! images are embedded as data URIs.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::board::*;
+use crate::render;
+
+// Number of bars of the score histogram
+const SCORE_BINS: usize = 10;
+// Scale of the embedded board images (1.0 is the size of the game window)
+const IMAGE_SCALE: f32 = 0.4;
+
+/// The games played by one strategy
+pub struct StrategyRun {
+    /// Name of the strategy and of its parameters
+    pub name: String,
+    /// Score and final board of each game
+    pub games: Vec<(f32, Board)>,
+}
+
+impl StrategyRun {
+    fn average_score(&self) -> f32 {
+        self.games.iter().map(|(score, _)| score).sum::<f32>() / self.games.len().max(1) as f32
+    }
+
+    fn reach_rate(&self, exponent: u8) -> f32 {
+        let count = self.games.iter().filter(|(_, board)| board.max_tile() >= exponent).count();
+        count as f32 / self.games.len().max(1) as f32 * 100.0
+    }
+}
+
+/// Writes the report of the given runs to `path`.
+pub fn write_html(path: &Path, runs: &[StrategyRun]) -> anyhow::Result<()> {
+    std::fs::write(path, html(runs)).with_context(|| format!("Could not write {}", path.display()))
+}
+
+fn html(runs: &[StrategyRun]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>2048 batch report</title>\n<style>\n\
+         body { font-family: sans-serif; background: #faf8ef; color: #776e65; margin: 2em; }\n\
+         table { border-collapse: collapse; } td, th { padding: 2px 10px; text-align: right; }\n\
+         .bar { background: #f59563; height: 14px; display: inline-block; }\n\
+         figure { display: inline-block; margin: 0 1em 1em 0; }\n\
+         </style></head><body>\n<h1>2048 batch report</h1>\n",
+    );
+
+    let numbers = crate::layout::layout().numbers;
+
+    // comparison of the strategies
+    let tiles: Vec<u8> = (8..=15).collect();
+    out.push_str("<h2>Strategies</h2>\n<table>\n<tr><th>Strategy</th><th>Games</th><th>Average score</th>");
+    for tile in &tiles {
+        write!(out, "<th>{}</th>", numbers.format(1 << tile)).unwrap();
+    }
+    out.push_str("</tr>\n");
+    for run in runs {
+        write!(out, "<tr><td>{}</td><td>{}</td><td>{:.1}</td>", escape(&run.name), run.games.len(), run.average_score()).unwrap();
+        for &tile in &tiles {
+            write!(out, "<td>{:.1}%</td>", run.reach_rate(tile)).unwrap();
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n");
+
+    for run in runs.iter().filter(|run| !run.games.is_empty()) {
+        writeln!(out, "<h2>{}</h2>", escape(&run.name)).unwrap();
+
+        out.push_str("<h3>Score distribution</h3>\n");
+        let (min, max) = run
+            .games
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), (score, _)| (min.min(*score), max.max(*score)));
+        let width = ((max - min) / SCORE_BINS as f32).max(1.0);
+        let mut bins = [0; SCORE_BINS];
+        for (score, _) in &run.games {
+            bins[(((score - min) / width) as usize).min(SCORE_BINS - 1)] += 1;
+        }
+        let bars: Vec<(String, usize)> = bins
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (format!("{:.0} - {:.0}", min + i as f32 * width, min + (i + 1) as f32 * width), *count))
+            .collect();
+        histogram(&mut out, &bars);
+
+        out.push_str("<h3>Max tile</h3>\n");
+        let max_exponent = run.games.iter().map(|(_, board)| board.max_tile()).max().unwrap_or(0);
+        let min_exponent = run.games.iter().map(|(_, board)| board.max_tile()).min().unwrap_or(0);
+        let bars: Vec<(String, usize)> = (min_exponent..=max_exponent)
+            .map(|exponent| {
+                let count = run.games.iter().filter(|(_, board)| board.max_tile() == exponent).count();
+                (numbers.format(1 << exponent), count)
+            })
+            .collect();
+        histogram(&mut out, &bars);
+
+        out.push_str("<h3>Best and worst games</h3>\n");
+        let by_score = |a: &&(f32, Board), b: &&(f32, Board)| a.0.total_cmp(&b.0);
+        let best = run.games.iter().max_by(by_score).unwrap();
+        let worst = run.games.iter().min_by(by_score).unwrap();
+        for (label, (score, board)) in [("Best", best), ("Worst", worst)] {
+            let png = render::encode_png(&render::render_board(board, IMAGE_SCALE));
+            writeln!(
+                out,
+                "<figure><img src=\"data:image/png;base64,{}\" alt=\"{}\"><figcaption>{label}: score {score:.0}</figcaption></figure>",
+                base64(&png),
+                board.compact()
+            )
+            .unwrap();
+        }
+    }
+    out.push_str("</body></html>\n");
+    out
+}
+
+// Horizontal bar chart as a table
+fn histogram(out: &mut String, bars: &[(String, usize)]) {
+    let max = bars.iter().map(|(_, count)| *count).max().unwrap_or(0).max(1);
+    out.push_str("<table>\n");
+    for (label, count) in bars {
+        writeln!(
+            out,
+            "<tr><td>{label}</td><td style=\"text-align: left\"><span class=\"bar\" style=\"width: {}px\"></span> {count}</td></tr>",
+            count * 300 / max
+        )
+        .unwrap();
+    }
+    out.push_str("</table>\n");
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+}
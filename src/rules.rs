@@ -0,0 +1,343 @@
+//! Variants of the rules of the game, declared in a ruleset file instead of being compiled in,
+//! so that rule experiments need no rebuild.
+//!
+//! The ruleset of the process is read once at startup from the file named by `RULES_VAR`, in a
+//! small subset of TOML: one `key = value` per line, `#` comments. Missing keys keep the rules
+//! of the original game.
+//!
+//! ```toml
+//! # 2048 with more 4s, a move limit and decaying tiles
+//! target_tile = 4096
+//! four_probability = 0.25
+//! move_limit = 1000
+//! decay_interval = 50
+//! # more 4s from a tile sum of 1000, and some 8s from 4000
+//! ramp = 1000:0.2 4000:0.3:0.02
+//! ```
+//!
+//! - `target_tile`: the tile winning the game (the game can go on after it)
+//! - `four_probability`: probability that a spawned tile is a 4 rather than a 2, known to the
+//!   search
+//! - `move_limit`: the game ends after this number of moves (0: no limit)
+//! - `decay_interval`: every `decay_interval` moves, the smallest tile of the board disappears
+//!   (0: never). The search does not anticipate the decay.
+//! - `ramp`: a difficulty ramp for casual play, as levels separated by spaces, by increasing
+//!   tile sum: `<tile sum>:<probability of a 4>[:<probability of an 8>]`. Once the sum of the
+//!   tiles of the board reaches the tile sum of a level, the spawned tiles follow its
+//!   probabilities instead of `four_probability`. The tile sum grows with the score (every
+//!   spawn adds to it, the merges keep it) and, unlike the score, is known from the board
+//!   alone: the chance nodes of the search spawn with the probabilities of their level.
+//!
+//! Everything saved about games (game notation, summaries, reports, telemetry) is tagged with
+//! the `Ruleset::id` of their rules, so that games of different variants are never mixed.
+
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use anyhow::{bail, ensure, Context};
+
+use crate::board::*;
+use crate::compat;
+
+/// Environment variable giving the ruleset file
+pub const RULES_VAR: &str = "AI2048_RULES";
+/// Maximum number of levels of a difficulty ramp
+pub const MAX_LEVELS: usize = 8;
+
+/// A level of the difficulty ramp
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Level {
+    /// Sum of the tiles of the board from which the level applies
+    pub tile_sum: u32,
+    pub four_probability: f64,
+    pub eight_probability: f64,
+}
+
+/// The levels of a difficulty ramp, by increasing tile sum (none in the original game)
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Ramp {
+    levels: [Level; MAX_LEVELS],
+    len: usize,
+}
+
+impl Ramp {
+    pub fn levels(&self) -> &[Level] {
+        &self.levels[..self.len]
+    }
+
+    // Parses the levels in the format of the module documentation
+    fn parse(text: &str) -> anyhow::Result<Ramp> {
+        let mut ramp = Ramp::default();
+        for level in text.split_whitespace() {
+            ensure!(ramp.len < MAX_LEVELS, "at most {MAX_LEVELS} levels");
+            let fields: Vec<&str> = level.split(':').collect();
+            let [tile_sum, four, eight @ ..] = fields.as_slice() else {
+                bail!("expected `<tile sum>:<probability of a 4>[:<probability of an 8>]`, got `{level}`");
+            };
+            ensure!(eight.len() <= 1, "too many fields in `{level}`");
+            let level = Level {
+                tile_sum: tile_sum.parse().with_context(|| format!("invalid tile sum `{tile_sum}`"))?,
+                four_probability: four.parse().with_context(|| format!("invalid probability `{four}`"))?,
+                eight_probability: match eight {
+                    [eight] => eight.parse().with_context(|| format!("invalid probability `{eight}`"))?,
+                    _ => 0.0,
+                },
+            };
+            ensure!(
+                level.four_probability >= 0.0 && level.eight_probability >= 0.0 && level.four_probability + level.eight_probability <= 1.0,
+                "the probabilities of a level must be between 0 and 1, and add up to at most 1"
+            );
+            ensure!(ramp.levels().last().is_none_or(|last| last.tile_sum < level.tile_sum), "the tile sums of the levels must increase");
+            ramp.levels[ramp.len] = level;
+            ramp.len += 1;
+        }
+        Ok(ramp)
+    }
+}
+
+impl std::fmt::Debug for Ramp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.levels()).finish()
+    }
+}
+
+impl Display for Ramp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, level) in self.levels().iter().enumerate() {
+            let separator = if i == 0 { "" } else { " " };
+            write!(f, "{separator}{}:{}", level.tile_sum, level.four_probability)?;
+            if level.eight_probability > 0.0 {
+                write!(f, ":{}", level.eight_probability)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rules of a variant of the game
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ruleset {
+    pub target_tile: u32,
+    pub four_probability: f64,
+    /// 0 for no limit
+    pub move_limit: usize,
+    /// 0 for no decay
+    pub decay_interval: usize,
+    pub ramp: Ramp,
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Ruleset { target_tile: 2048, four_probability: 0.1, move_limit: 0, decay_interval: 0, ramp: Ramp::default() }
+    }
+}
+
+impl Ruleset {
+    /// Parses and validates a ruleset in the format of the module documentation.
+    pub fn parse(text: &str) -> anyhow::Result<Ruleset> {
+        let mut ruleset = Ruleset::default();
+        let mut seen = Vec::new();
+        for (number, line) in text.lines().enumerate().map(|(i, line)| (i + 1, line)) {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line.split_once('=').with_context(|| format!("line {number}: expected `key = value`"))?;
+            let (key, value) = (key.trim(), value.trim());
+            ensure!(!seen.contains(&key), "line {number}: `{key}` is defined twice");
+            seen.push(key);
+            let invalid = || format!("line {number}: invalid value of `{key}`: `{value}`");
+            match key {
+                "target_tile" => ruleset.target_tile = value.parse().with_context(invalid)?,
+                "four_probability" => ruleset.four_probability = value.parse().with_context(invalid)?,
+                "move_limit" => ruleset.move_limit = value.parse().with_context(invalid)?,
+                "decay_interval" => ruleset.decay_interval = value.parse().with_context(invalid)?,
+                "ramp" => ruleset.ramp = Ramp::parse(value).with_context(|| format!("line {number}: invalid ramp"))?,
+                _ => bail!("line {number}: unknown rule `{key}` (expected target_tile, four_probability, move_limit, decay_interval or ramp)"),
+            }
+        }
+        ensure!(
+            ruleset.target_tile.is_power_of_two() && (4..=1 << MAX_EXPONENT).contains(&ruleset.target_tile),
+            "the target tile must be a power of two between 4 and {}",
+            1 << MAX_EXPONENT
+        );
+        ensure!((0.0..=1.0).contains(&ruleset.four_probability), "the probability of a 4 must be between 0 and 1");
+        Ok(ruleset)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Ruleset> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+        Ruleset::parse(&text).with_context(|| format!("Invalid ruleset {}", path.display()))
+    }
+
+    /// Parses a ruleset written on a single line by `Display`, with commas between the rules.
+    pub fn parse_inline(text: &str) -> anyhow::Result<Ruleset> {
+        Ruleset::parse(&text.replace(',', "\n"))
+    }
+
+    /// Short identifier of the rules: `standard` for the original game, a hash of the rules
+    /// otherwise (8 hexadecimal digits, the same on all platforms and versions)
+    pub fn id(&self) -> String {
+        if *self == Ruleset::default() {
+            return "standard".to_string();
+        }
+        format!("{:08x}", compat::fnv1a(self.to_string().as_bytes()) as u32)
+    }
+
+    /// Level of the difficulty ramp reached by the board: 0 before the first level of the
+    /// ramp (or without ramp), `i + 1` from the tile sum of `ramp.levels()[i]` on
+    pub fn level(&self, board: &Board) -> usize {
+        let levels = self.ramp.levels();
+        if levels.is_empty() {
+            return 0;
+        }
+        let tile_sum = board.tile_sum();
+        levels.iter().take_while(|level| level.tile_sum <= tile_sum).count()
+    }
+
+    /// Probabilities that a tile spawned on the board is a 4 and an 8
+    pub fn four_eight_probabilities(&self, board: &Board) -> (f64, f64) {
+        match self.level(board) {
+            0 => (self.four_probability, 0.0),
+            level => {
+                let level = self.ramp.levels()[level - 1];
+                (level.four_probability, level.eight_probability)
+            }
+        }
+    }
+
+    /// Probability that a tile spawned on the board is a 2
+    pub fn two_probability(&self, board: &Board) -> f64 {
+        let (four, eight) = self.four_eight_probabilities(board);
+        1.0 - four - eight
+    }
+
+    /// Exponents of the tiles spawned on the board with their probabilities, for the search
+    /// (the 8s have a probability only in the levels of a ramp)
+    pub fn spawn_probabilities(&self, board: &Board) -> [(u8, f32); 3] {
+        let (four, eight) = self.four_eight_probabilities(board);
+        [(1, self.two_probability(board) as f32), (2, four as f32), (3, eight as f32)]
+    }
+
+    /// Whether the board holds the target tile
+    pub fn is_won(&self, board: &Board) -> bool {
+        board.max_tile() >= self.target_tile.trailing_zeros() as u8
+    }
+
+    /// Whether the game ends after `num_moves` moves, whatever the board
+    pub fn move_limit_reached(&self, num_moves: usize) -> bool {
+        self.move_limit > 0 && num_moves >= self.move_limit
+    }
+
+    /// Removes the smallest tile of the board (the first one, row by row) if the move number
+    /// `num_moves` just played is a multiple of the decay interval and the board holds at least
+    /// two tiles. Returns the cell of the removed tile.
+    pub fn decay(&self, board: &mut Board, num_moves: usize) -> Option<(usize, usize)> {
+        if self.decay_interval == 0 || num_moves == 0 || !num_moves.is_multiple_of(self.decay_interval) || N * N - board.num_empty() < 2 {
+            return None;
+        }
+        let (row, col) = (0..N)
+            .flat_map(|row| (0..N).map(move |col| (row, col)))
+            .filter(|&(row, col)| board.get(row, col) != 0)
+            .min_by_key(|&(row, col)| board.get(row, col))?;
+        board.set(row, col, 0);
+        Some((row, col))
+    }
+}
+
+impl Display for Ruleset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "target_tile = {}, four_probability = {}, move_limit = {}, decay_interval = {}",
+            self.target_tile, self.four_probability, self.move_limit, self.decay_interval
+        )?;
+        // (only with a ramp, so that the ids of the rules without one do not change)
+        if !self.ramp.levels().is_empty() {
+            write!(f, ", ramp = {}", self.ramp)?;
+        }
+        Ok(())
+    }
+}
+
+static RULES: OnceLock<Ruleset> = OnceLock::new();
+
+/// Loads the ruleset of `RULES_VAR`, if set, as the ruleset of the process. Called once at
+/// startup, so that an invalid ruleset is reported before any game.
+pub fn init() -> anyhow::Result<&'static Ruleset> {
+    let ruleset = match std::env::var_os(RULES_VAR) {
+        Some(path) => Ruleset::load(Path::new(&path))?,
+        None => Ruleset::default(),
+    };
+    Ok(RULES.get_or_init(|| ruleset))
+}
+
+/// The ruleset of the process, the rules of the original game unless `init` loaded another one
+pub fn rules() -> &'static Ruleset {
+    RULES.get_or_init(Ruleset::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Ruleset::parse("# nothing\n\n").unwrap(), Ruleset::default());
+        let ruleset = Ruleset::parse("target_tile = 4096\nfour_probability = 0.25 # more 4s\nmove_limit=10").unwrap();
+        assert_eq!(ruleset, Ruleset { target_tile: 4096, four_probability: 0.25, move_limit: 10, ..Default::default() });
+        let board = Board::from_compact("0000/0000/0000/0011").unwrap();
+        assert_eq!(ruleset.spawn_probabilities(&board), [(1, 0.75), (2, 0.25), (3, 0.0)]);
+        // the default probabilities are exactly the ones of the original game
+        assert_eq!(Ruleset::default().spawn_probabilities(&board), [(1, 0.9), (2, 0.1), (3, 0.0)]);
+
+        assert!(Ruleset::parse("target_tile = 3000").is_err());
+        assert!(Ruleset::parse("four_probability = 1.5").is_err());
+        assert!(Ruleset::parse("gravity = 1").is_err());
+        assert!(Ruleset::parse("move_limit = 1\nmove_limit = 2").is_err());
+        assert!(Ruleset::parse("move_limit").is_err());
+    }
+
+    #[test]
+    fn test_ramp() {
+        let ruleset = Ruleset::parse("ramp = 100:0.2 1000:0.3:0.05").unwrap();
+        let board = |compact: &str| Board::from_compact(compact).unwrap();
+        // tile sums 64, 128 and 1024
+        assert_eq!(ruleset.level(&board("0000/0000/0000/0006")), 0);
+        assert_eq!(ruleset.spawn_probabilities(&board("0000/0000/0000/0006")), [(1, 0.9), (2, 0.1), (3, 0.0)]);
+        assert_eq!(ruleset.level(&board("0000/0000/0000/0007")), 1);
+        assert_eq!(ruleset.spawn_probabilities(&board("0000/0000/0000/0007")), [(1, 0.8), (2, 0.2), (3, 0.0)]);
+        assert_eq!(ruleset.level(&board("0000/0000/0000/000a")), 2);
+        let probabilities = ruleset.spawn_probabilities(&board("0000/0000/0000/000a"));
+        assert_eq!(probabilities.map(|(exponent, _)| exponent), [1, 2, 3]);
+        assert!((probabilities.iter().map(|&(_, p)| p).sum::<f32>() - 1.0).abs() < 1e-6 && probabilities[2].1 == 0.05);
+
+        assert_eq!(Ruleset::parse_inline(&ruleset.to_string()).unwrap(), ruleset);
+        assert_ne!(ruleset.id(), Ruleset::default().id());
+        assert!(Ruleset::parse("ramp = 100:0.2 50:0.3").is_err());
+        assert!(Ruleset::parse("ramp = 100:0.8:0.3").is_err());
+        assert!(Ruleset::parse("ramp = 100").is_err());
+    }
+
+    #[test]
+    fn test_id() {
+        let ruleset = Ruleset { target_tile: 4096, four_probability: 0.25, ..Default::default() };
+        assert_eq!(Ruleset::parse_inline(&ruleset.to_string()).unwrap(), ruleset);
+        assert_eq!(Ruleset::default().id(), "standard");
+        assert_eq!(ruleset.id().len(), 8);
+        assert_ne!(ruleset.id(), Ruleset { move_limit: 1, ..ruleset }.id());
+    }
+
+    #[test]
+    fn test_limits_and_decay() {
+        let ruleset = Ruleset { move_limit: 3, decay_interval: 2, ..Default::default() };
+        assert!(!ruleset.move_limit_reached(2) && ruleset.move_limit_reached(3));
+        let mut board = Board::from_compact("0000/0210/0000/0001").unwrap();
+        assert_eq!(ruleset.decay(&mut board, 3), None);
+        assert_eq!(ruleset.decay(&mut board, 4), Some((1, 2)));
+        assert_eq!(board.compact(), "0000/0200/0000/0001");
+        assert!(ruleset.is_won(&Board::from_compact("0000/0000/0000/000b").unwrap()));
+        assert!(!ruleset.is_won(&board));
+    }
+}
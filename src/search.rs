@@ -1,29 +1,199 @@
-use std::iter::successors;
+//! Expectimax search of the best action.
+//!
+//! The values are computed in `f32` with a fixed order of operations: the successors are
+//! visited in the same order (`ALL_ACTIONS`, then the empty cells row by row), sums are never
+//! parallelized, and only the basic IEEE-754 operations are used (no `powf` or fused
+//! multiply-add, Rust never reassociates or contracts them). The values, and therefore the
+//! games played from a seed, are bit-identical across platforms and optimization levels (on
+//! all targets with IEEE-754 floats, i.e. not the x87 FPU of `i586`). Keep it that way: the
+//! golden tests, the recorded games and the benchmarks comparing runs rely on it.
 
-use hashbrown::HashMap;
+use std::time::{Duration, Instant};
+
+use arrayvec::ArrayVec;
 use rand::Rng as _;
-use rayon::range; // import trait to make the `random_range` method available (Rng = Random number generator)
+use rayon::prelude::*;
 
 use crate::board::*;
+use crate::cache::{self, CacheCapacity, LruCache};
+use crate::eval::Weights;
 
-pub fn select_action(board: PlayableBoard) -> Option<Action> {
-    //select_action_randomly(board)
-    //select_action_greedily(board)
-    select_action_expectimax(board, 3)
+/// Limits on the effort spent on a single decision. The limits combine: the search stops at
+/// the first one reached.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Budget {
+    /// Number of actions to look ahead, overriding the depth of the search parameters
+    pub depth: Option<usize>,
+    /// Maximum time for the decision
+    pub time: Option<Duration>,
+    /// Maximum number of positions evaluated with the heuristic
+    pub nodes: Option<usize>,
 }
 
-pub fn select_action_randomly(board: PlayableBoard) -> Option<Action> {
-    // iterate through all actions and keep the applicable ones
-    let mut applicable_actions: Vec<Action> = Vec::new();
-    for action in ALL_ACTIONS {
-        if let Some(_succ) = board.apply(action) {
-            // action is applicable
-            applicable_actions.push(action);
-        } else {
-            // action is not aplicable, ignore
+/// Tunable parameters of the expectimax search
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchParams {
+    /// Number of actions to look ahead
+    pub depth: usize,
+    /// Blend between expectation and worst case at chance nodes:
+    /// 0.0 is pure expectimax, 1.0 is pure minimax (the worst spawn always happens).
+    pub adversarial_mix: f32,
+    /// Weights of the heuristic used at the leaves
+    pub weights: Weights,
+    /// Weight of the game score gained along a line of play. The value of a line is
+    /// `score_weight * score gained + heuristic at the leaf`, 0.0 ignores the score.
+    pub score_weight: f32,
+    /// Maximum number of extra plies searched along a line when the horizon is reached on a
+    /// forced move (single legal action) or a large merge, see `evaluate_playable`.
+    pub extensions: usize,
+    /// Maximum size of the cache of the values of chance nodes
+    pub cache_capacity: CacheCapacity,
+    /// Relative margin within which the values of the root actions are considered tied, see
+    /// `break_tie`. 0.0 always plays the best action.
+    pub hysteresis: f32,
+    /// Choice among the root actions of exactly equal value
+    pub tiebreak: Tiebreak,
+    /// Chance nodes reached from the root with a probability (product of the probabilities of
+    /// the spawns along the line) below this one are evaluated with the heuristic instead of
+    /// searched, see `evaluate_randable`. 0.0 searches all of them.
+    pub probability_cutoff: f32,
+}
+
+/// How the agent chooses among the root actions of exactly equal value. All the policies are
+/// deterministic: the same position always gets the same choice, so that the same seeds and
+/// options reproduce the same games.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tiebreak {
+    /// The first one in the order of `ALL_ACTIONS`
+    #[default]
+    Fixed,
+    /// Drawn with a seed of the move, mixed from this seed and the position: runs with
+    /// different seeds break the ties differently
+    Seeded(u64),
+    /// The one leading to the position of best heuristic value, then the first one
+    Heuristic,
+}
+
+impl Tiebreak {
+    /// Parses `fixed`, `heuristic` or `seeded:<seed>` (the format of `Display`)
+    pub fn parse(text: &str) -> anyhow::Result<Tiebreak> {
+        match text.trim().split_once(':') {
+            None if text.trim() == "fixed" => Ok(Tiebreak::Fixed),
+            None if text.trim() == "heuristic" => Ok(Tiebreak::Heuristic),
+            Some(("seeded", seed)) => Ok(Tiebreak::Seeded(seed.trim().parse()?)),
+            _ => anyhow::bail!("unknown tiebreak `{text}` (expected fixed, heuristic or seeded:<seed>)"),
         }
     }
 
+    // Chooses among the `tied` actions of `board`, in the order of `ALL_ACTIONS`
+    fn choose(&self, board: PlayableBoard, tied: &[Action], weights: &Weights) -> Option<Action> {
+        match *self {
+            _ if tied.len() <= 1 => tied.first().copied(),
+            Tiebreak::Fixed => tied.first().copied(),
+            Tiebreak::Seeded(seed) => {
+                let draw = splitmix64(seed ^ board.board().hash64());
+                Some(tied[(draw % tied.len() as u64) as usize])
+            }
+            Tiebreak::Heuristic => {
+                let value = |action: Action| board.apply(action).map_or(0.0, |succ| succ.evaluate_with(weights));
+                // (`max_by` keeps the last maximum: the tied actions are visited backwards)
+                tied.iter().rev().copied().max_by(|&a, &b| value(a).total_cmp(&value(b)))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Tiebreak {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tiebreak::Fixed => write!(f, "fixed"),
+            Tiebreak::Seeded(seed) => write!(f, "seeded:{seed}"),
+            Tiebreak::Heuristic => write!(f, "heuristic"),
+        }
+    }
+}
+
+// The SplitMix64 mixing function: spreads the bits of a seed, the same on all platforms
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Default bound of the cache of the search: long runs at high depths would otherwise grow
+/// the memory without limit.
+pub const DEFAULT_CACHE_CAPACITY: CacheCapacity = CacheCapacity::Megabytes(256);
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        SearchParams {
+            depth: 3,
+            adversarial_mix: 0.0,
+            weights: Weights::default(),
+            score_weight: 0.0,
+            extensions: 1,
+            cache_capacity: DEFAULT_CACHE_CAPACITY,
+            hysteresis: 0.0,
+            tiebreak: Tiebreak::Fixed,
+            probability_cutoff: 0.0,
+        }
+    }
+}
+
+/// The action chosen on a position, with what the search learned on the way, so that its
+/// consumers (window, logs, replays, hints) do not search again for it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    /// The action to play
+    pub best: Action,
+    /// Value of each action of `ALL_ACTIONS` (in the same order), `None` for the actions that
+    /// are not applicable or were not evaluated
+    pub values: [Option<f32>; 4],
+    /// Number of actions looked ahead, 0 for a choice made without search
+    pub depth: usize,
+    /// Number of positions evaluated with the heuristic (the values found in the cache of a
+    /// `Searcher` are not counted)
+    pub nodes: usize,
+    /// Time taken by the choice
+    pub time: Duration,
+    /// Whether the search was cut short by the time or the nodes of its `Budget`: `depth` is
+    /// then the deepest search completed, short of the one asked for
+    pub truncated: bool,
+    /// Principal variation: the line the search expects, from `best`, each move followed by the
+    /// most probable spawn (the worst one for the player among equally probable ones). It may
+    /// be shorter than `depth` when the values of the line are not known.
+    pub pv: Vec<Action>,
+}
+
+impl SearchResult {
+    /// The result of a choice made without search
+    pub fn unsearched(best: Action, time: Duration) -> SearchResult {
+        SearchResult { best, values: [None; 4], depth: 0, nodes: 0, time, truncated: false, pv: vec![best] }
+    }
+
+    /// Value of the action to play, if it was evaluated
+    pub fn value(&self) -> Option<f32> {
+        self.values[ALL_ACTIONS.iter().position(|&a| a == self.best).unwrap()]
+    }
+}
+
+pub fn select_action(board: PlayableBoard) -> Option<SearchResult> {
+    select_action_with(board, &SearchParams::default())
+}
+
+/// Selects an action using the given search parameters.
+pub fn select_action_with(board: PlayableBoard, params: &SearchParams) -> Option<SearchResult> {
+    //select_action_randomly(board)
+    //select_action_greedily(board)
+    select_action_expectimax(board, params)
+}
+
+pub fn select_action_randomly(board: PlayableBoard) -> Option<SearchResult> {
+    let start = Instant::now();
+    // keep the applicable actions
+    let applicable_actions = board.legal_actions();
+
     // if there is no available actions, return `None` immediately
     let num_actions = applicable_actions.len();
     if num_actions == 0 {
@@ -34,7 +204,7 @@ pub fn select_action_randomly(board: PlayableBoard) -> Option<Action> {
     // otherwise, randomly pick an action among the applicable ones
     let randomly_selected_action_index = rand::rng().random_range(0..num_actions);
     let randomly_selected_action = applicable_actions[randomly_selected_action_index];
-    Some(randomly_selected_action)
+    Some(SearchResult::unsearched(randomly_selected_action, start.elapsed()))
 }
 
     /*
@@ -43,15 +213,18 @@ pub fn select_action_randomly(board: PlayableBoard) -> Option<Action> {
     return the action with the highest evaluation
     or return None if there were no applicable action
     */
-pub fn select_action_greedily(board: PlayableBoard) -> Option<Action> {
+pub fn select_action_greedily(board: PlayableBoard) -> Option<SearchResult> {
+        let start = Instant::now();
 
         // iterate through all actions and keep the applicable ones
         let mut best_action: Option<Action> =None ;
         let mut best_score: f32 = 0.0;
-        for action in ALL_ACTIONS {
+        let mut values = [None; 4];
+        for (i, action) in ALL_ACTIONS.into_iter().enumerate() {
             if let Some(_succ) = board.apply(action) {
                 // action is applicable, we check if its better than the current best
                 let current_eval= _succ.evaluate();
+                values[i] = Some(current_eval);
                 if current_eval > best_score{
                     best_action = Some(action);
                     best_score = current_eval;
@@ -60,31 +233,303 @@ pub fn select_action_greedily(board: PlayableBoard) -> Option<Action> {
                 // action is not aplicable, ignore
             }
         }
-        return best_action;
+        let best = best_action?;
+        let nodes = values.iter().flatten().count();
+        Some(SearchResult { best, values, depth: 1, nodes, time: start.elapsed(), truncated: false, pv: vec![best] })
 }
 
 //select_action_expecitmax(board, max_depth):
 //  applicable_actions = { actions that are applicable in board }
 //  return applicable action a that maximizes eval_randable(result(board, a))
-pub fn select_action_expectimax(board: PlayableBoard, max_actions: usize) -> Option<Action> {
-    let mut remaining_actions:usize = max_actions;
-    let mut cache: HashMap<RandableBoard, (f32, usize)> = HashMap::new();
+//
+// The applicable actions are searched in parallel (rayon), each with a cache of its own taking
+// an equal share of `params.cache_capacity`. The value of each action does not depend on the
+// threads, but may differ from the one found with the cache of a `Searcher`, shared by all the
+// actions (see `evaluate_playable`).
+pub fn select_action_expectimax(board: PlayableBoard, params: &SearchParams) -> Option<SearchResult> {
+    let start = Instant::now();
+    let actions = board.legal_actions();
+    let params = &SearchParams { cache_capacity: params.cache_capacity.split(actions.len().max(1)), ..*params };
+    let searches: Vec<(Action, f32, Searcher, Stats)> = actions
+        .par_iter()
+        .map(|&action| {
+            let mut searcher = Searcher::default();
+            let mut stats = Stats::default();
+            searcher.prepare(params);
+            let value = searcher.root_value(board, action, params, None, &mut stats).expect("the action is applicable");
+            (action, value, searcher, stats)
+        })
+        .collect();
+    let mut values = [None; 4];
     let mut stats = Stats::default();
-    let mut best_action: Option<Action> =None ;
-    let mut best_score: f32 = 0.0;
-    for action in ALL_ACTIONS {
-        if let Some(_succ) = board.apply(action) {
-            // action is applicable, we check if its better than the current best
-            let current_eval = evaluate_randable(_succ, remaining_actions-1, &mut stats, &mut cache);
-            if current_eval > best_score{
-                best_action = Some(action);
-                best_score = current_eval;
+    for (action, value, _, action_stats) in &searches {
+        values[ALL_ACTIONS.iter().position(|a| a == action).unwrap()] = Some(*value);
+        stats.num_evals += action_stats.num_evals;
+    }
+    let best = best_action(board, &values, params)?;
+    let (_, _, searcher, _) = searches.iter().find(|(action, ..)| *action == best).unwrap();
+    Some(searcher.result(board, best, values, &stats, params, start))
+}
+
+/// Selects an action with the default parameters, searching deeper and deeper (up to
+/// `MAX_TIMED_DEPTH`) until `time` is up, from the deepest search completed in time.
+pub fn select_action_timed(board: PlayableBoard, time: Duration) -> Option<SearchResult> {
+    let budget = Budget { depth: Some(MAX_TIMED_DEPTH), time: Some(time), ..Default::default() };
+    Searcher::default().select_action_within(board, &SearchParams::default(), budget)
+}
+
+/// Deepest search of `select_action_timed`, reached in time on nearly full boards only
+pub const MAX_TIMED_DEPTH: usize = 12;
+
+/// Returns the expectimax value of each action of `ALL_ACTIONS` (in the same order),
+/// or `None` for the actions that are not applicable.
+pub fn action_values(board: PlayableBoard, params: &SearchParams) -> [Option<f32>; 4] {
+    Searcher::default().action_values(board, params)
+}
+
+/// Returns the action with the highest value (whatever its sign), or `None` if no action is
+/// applicable. The actions of exactly equal value are told apart by `params.tiebreak`.
+fn best_action(board: PlayableBoard, values: &[Option<f32>; 4], params: &SearchParams) -> Option<Action> {
+    let best_score = values.iter().flatten().copied().filter(|value| !value.is_nan()).max_by(f32::total_cmp)?;
+    let tied: ArrayVec<Action, 4> = ALL_ACTIONS
+        .into_iter()
+        .zip(values)
+        .filter(|(_, value)| **value == Some(best_score))
+        .map(|(action, _)| action)
+        .collect();
+    params.tiebreak.choose(board, &tied, &params.weights)
+}
+
+/// Among the actions whose value is within `margin` (relative) of the best one, prefers the
+/// `previous` direction, then a move keeping the largest tile in its corner, so that the agent
+/// does not alternate between directions of nearly equal values.
+fn break_tie(board: PlayableBoard, values: &[Option<f32>; 4], previous: Option<Action>, params: &SearchParams) -> Option<Action> {
+    let margin = params.hysteresis;
+    let best = best_action(board, values, params)?;
+    let value = |action: Action| values[ALL_ACTIONS.iter().position(|&a| a == action).unwrap()];
+    let best_value = value(best).unwrap();
+    let tied = |action: Action| value(action).is_some_and(|v| v >= best_value - margin * best_value.abs());
+    if let Some(previous) = previous.filter(|&previous| tied(previous)) {
+        return Some(previous);
+    }
+    let in_corner = |board: &Board| {
+        let max = board.max_tile();
+        [(0, 0), (0, N - 1), (N - 1, 0), (N - 1, N - 1)].iter().any(|&(i, j)| board.get(i, j) == max)
+    };
+    let keeps_corner = |action: Action| board.apply(action).is_some_and(|succ| in_corner(&succ.board()));
+    if in_corner(&board.board()) && !keeps_corner(best) {
+        let keeping = ALL_ACTIONS
+            .into_iter()
+            .filter(|&action| tied(action) && keeps_corner(action))
+            .max_by(|&a, &b| value(a).unwrap().total_cmp(&value(b).unwrap()));
+        if keeping.is_some() {
+            return keeping;
+        }
+    }
+    Some(best)
+}
+
+/// An expectimax search that keeps its cache of chance nodes from one search to the next.
+///
+/// The values are reused by the searches of the same positions at the same depth: the deeper
+/// searches of a decision within a `Budget`, the positions analyzed again, or a warm cache
+/// (`warmcache.rs`). Between the moves of a game they are seldom reused, the positions after
+/// the spawn having been searched one action shallower than needed: the agent forgets them
+/// (`clear`). The cache is bounded by `SearchParams::cache_capacity`, the least recently used
+/// values being evicted first. When a trim of the caches is requested
+/// (`cache::request_trim`), the least recently used half is evicted before the next search;
+/// the values recomputed then may differ from the evicted ones (see `evaluate_playable`).
+pub struct Searcher {
+    cache: LruCache<RandableBoard, (f32, usize)>,
+    /// Parameters with which the values of the cache were computed
+    cached_params: Option<SearchParams>,
+    /// Action selected by the previous search, for the hysteresis
+    previous: Option<Action>,
+    /// Trims of the caches requested when the cache was last trimmed
+    trims: u64,
+}
+
+impl Default for Searcher {
+    fn default() -> Self {
+        Searcher {
+            cache: LruCache::new(DEFAULT_CACHE_CAPACITY),
+            cached_params: None,
+            previous: None,
+            trims: cache::trim_requests(),
+        }
+    }
+}
+
+impl Searcher {
+    /// Number of values in the cache, its capacity and the number of values evicted so far
+    pub fn cache_usage(&self) -> (usize, usize, u64) {
+        (self.cache.len(), self.cache.capacity(), self.cache.evictions())
+    }
+
+    /// A searcher whose cache holds the given values (value and depth of each chance node),
+    /// computed with `params`. The last entries are the most recently used.
+    pub fn warm(params: &SearchParams, entries: impl IntoIterator<Item = (RandableBoard, (f32, usize))>) -> Searcher {
+        let mut cache = LruCache::new(params.cache_capacity);
+        for (board, value) in entries {
+            cache.insert(board, value);
+        }
+        Searcher { cache, cached_params: Some(*params), previous: None, trims: cache::trim_requests() }
+    }
+
+    /// Forgets the values of the cache
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Parameters with which the values of the cache were computed, if any
+    pub fn cached_params(&self) -> Option<&SearchParams> {
+        self.cached_params.as_ref()
+    }
+
+    /// The values of the cache, from the most recently used
+    pub fn cached_values(&self) -> impl Iterator<Item = (&RandableBoard, &(f32, usize))> {
+        self.cache.iter()
+    }
+
+    pub fn select_action(&mut self, board: PlayableBoard, params: &SearchParams) -> Option<SearchResult> {
+        self.select_action_within(board, params, Budget::default())
+    }
+
+    /// Same as `select_action`, within the limits of `budget`. With a limit of time or nodes,
+    /// the search deepens one action at a time (the values of the cache carrying over) and
+    /// the deepest search completed within the limits is kept.
+    pub fn select_action_within(&mut self, board: PlayableBoard, params: &SearchParams, budget: Budget) -> Option<SearchResult> {
+        self.select_action_previewed(board, params, budget, None)
+    }
+
+    /// Same as `select_action_within`, knowing the exponent of the tile spawned after the move
+    /// (see `spawn::PreviewSpawns`), if shown: only its cell is left to chance at the first
+    /// chance node, the following ones spawn as usual.
+    pub fn select_action_previewed(&mut self, board: PlayableBoard, params: &SearchParams, budget: Budget, next: Option<u8>) -> Option<SearchResult> {
+        let start = Instant::now();
+        let target = budget.depth.unwrap_or(params.depth);
+        let mut stats = Stats { deadline: budget.time.map(|time| start + time), max_nodes: budget.nodes, ..Default::default() };
+        let limited = budget.time.is_some() || budget.nodes.is_some();
+        let mut completed = None;
+        for depth in if limited { 1 } else { target }..=target {
+            let params = SearchParams { depth, ..*params };
+            let values = self.search(board, &params, next, &mut stats);
+            // (a search of depth 1 only evaluates the successors, it is never cut short)
+            if stats.stopped && completed.is_some() {
+                break;
+            }
+            completed = Some((values, params));
+            if depth < target && stats.exhausted() {
+                break;
             }
+        }
+        let (values, params) = completed?;
+        self.previous = if params.hysteresis > 0.0 {
+            break_tie(board, &values, self.previous, &params)
         } else {
-            // action is not aplicable, ignore
+            best_action(board, &values, &params)
+        };
+        let best = self.previous?;
+        Some(self.result(board, best, values, &stats, &params, start))
+    }
+
+    /// Same as the `action_values` function, reusing the values computed by previous searches.
+    pub fn action_values(&mut self, board: PlayableBoard, params: &SearchParams) -> [Option<f32>; 4] {
+        self.search(board, params, None, &mut Stats::default())
+    }
+
+    fn search(&mut self, board: PlayableBoard, params: &SearchParams, next: Option<u8>, stats: &mut Stats) -> [Option<f32>; 4] {
+        self.prepare(params);
+        ALL_ACTIONS.map(|action| self.root_value(board, action, params, next, stats))
+    }
+
+    // Readies the cache for a search with `params`
+    fn prepare(&mut self, params: &SearchParams) {
+        // the values of the cache know their depth: only the other parameters invalidate them
+        if self.cached_params.map(|cached| SearchParams { depth: params.depth, ..cached }).as_ref() != Some(params) {
+            // values computed with other parameters are meaningless
+            if self.cached_params.map(|cached| cached.cache_capacity) == Some(params.cache_capacity) {
+                self.cache.clear();
+            } else {
+                self.cache = LruCache::new(params.cache_capacity);
+            }
+            self.cached_params = Some(*params);
+        }
+        if cache::trim_requests() != self.trims {
+            self.trims = cache::trim_requests();
+            self.cache.trim(self.cache.len() / 2);
         }
     }
-    return best_action;
+
+    // Value of `action` on the root `board`, `None` if it is not applicable
+    fn root_value(&mut self, board: PlayableBoard, action: Action, params: &SearchParams, next: Option<u8>, stats: &mut Stats) -> Option<f32> {
+        // (a search of depth 0 evaluates the successors, as one of depth 1)
+        let remaining_actions: usize = params.depth.max(1);
+        let (_succ, gained) = board.apply_scored(action)?;
+        // the score obtained before the root is the same for all actions and can be ignored
+        let continuation = match next {
+            Some(exponent) => evaluate_previewed(_succ, exponent, remaining_actions - 1, params, stats, &mut self.cache),
+            None => evaluate_randable(_succ, remaining_actions - 1, params.extensions, 1.0, params, stats, &mut self.cache),
+        };
+        Some(params.score_weight * gained as f32 + continuation)
+    }
+
+    fn result(&self, board: PlayableBoard, best: Action, values: [Option<f32>; 4], stats: &Stats, params: &SearchParams, start: Instant) -> SearchResult {
+        let pv = self.principal_variation(board, best, params);
+        SearchResult { best, values, depth: params.depth, nodes: stats.num_evals, time: start.elapsed(), truncated: stats.stopped, pv }
+    }
+
+    // The line expected from `best`, read from the values of the cache left by the search of
+    // `board` (without marking them as used, which would change the evictions, and so the
+    // values of the next searches). The values of the extensions are not told apart.
+    fn principal_variation(&self, board: PlayableBoard, best: Action, params: &SearchParams) -> Vec<Action> {
+        let mut pv = vec![best];
+        let mut randable = board.apply(best).expect("the best action is applicable");
+        for remaining in (1..params.depth).rev() {
+            let successors: Vec<(f32, PlayableBoard)> = randable.successors().collect();
+            let max_proba = successors.iter().fold(0.0, |max: f32, &(proba, _)| max.max(proba));
+            let mut line: Option<(f32, Action, RandableBoard)> = None;
+            for &(_, succ) in successors.iter().filter(|(proba, _)| *proba == max_proba) {
+                let Some((value, action, next)) = self.cached_decision(succ, remaining, params) else {
+                    return pv;
+                };
+                if line.is_none_or(|(worst, _, _)| value < worst) {
+                    line = Some((value, action, next));
+                }
+            }
+            let Some((_, action, next)) = line else {
+                break;
+            };
+            pv.push(action);
+            randable = next;
+        }
+        pv
+    }
+
+    // The best action of a decision node with `remaining` actions left, its value and the
+    // position after it, from the values of the cache. `None` if the game is lost there or a
+    // value is missing.
+    fn cached_decision(&self, board: PlayableBoard, remaining: usize, params: &SearchParams) -> Option<(f32, Action, RandableBoard)> {
+        let mut best: Option<(f32, Action, RandableBoard)> = None;
+        for action in ALL_ACTIONS {
+            let Some((succ, gained)) = board.apply_scored(action) else {
+                continue;
+            };
+            let continuation = if remaining == 1 {
+                succ.evaluate_with(&params.weights)
+            } else {
+                match self.cache.peek(&succ) {
+                    Some(&(value, depth)) if depth == remaining - 1 => value,
+                    _ => return None,
+                }
+            };
+            let value = params.score_weight * gained as f32 + continuation;
+            if best.is_none_or(|(best_value, _, _)| value > best_value) {
+                best = Some((value, action, succ));
+            }
+        }
+        best
+    }
 }
 
 
@@ -92,47 +537,101 @@ pub fn select_action_expectimax(board: PlayableBoard, max_actions: usize) -> Opt
 //   if remaining_actions == 0:
 //     evaluate(board)
 //   else
-//     Sum { p * eval_action(succ, remaining_actions) | (p, succ) in successors(board) }
-// we evaluate te average board depending on the placement of the 2 or 4 tile.
-fn evaluate_randable(board: RandableBoard, remaining_actions: usize, stats: &mut Stats, cache:&mut HashMap<RandableBoard, (f32, usize)>) -> f32 {
-    let mut sum: f32 = 0.0;
-    if cache.contains_key(&board) && cache[&board].1 == remaining_actions{
-        return cache[&board].0;
+//     (1 - λ) * Sum { p * eval_action(succ, remaining_actions) | (p, succ) in successors(board) }
+//       + λ * Min { eval_action(succ, remaining_actions) | (p, succ) in successors(board) }
+// we evaluate te average board depending on the placement of the 2 or 4 tile,
+// optionally blended with the worst placement (λ = params.adversarial_mix).
+//
+// `probability` is the probability of reaching the node from the root: below
+// `params.probability_cutoff`, the node is too unlikely to be worth a search and its heuristic
+// value is used instead (not cached, as a search from a likelier line would find another
+// value). Like the extensions, the cache does not tell apart values computed with cut
+// branches from others.
+fn evaluate_randable(board: RandableBoard, remaining_actions: usize, extensions: usize, probability: f32, params: &SearchParams, stats: &mut Stats, cache: &mut LruCache<RandableBoard, (f32, usize)>) -> f32 {
+    if let Some(&(value, depth)) = cache.get(&board) {
+        if depth == remaining_actions {
+            return value;
+        }
     }
-    else if (remaining_actions == 0){ //if there is no actions possible after this state
-        return board.evaluate();
+    if remaining_actions == 0 || probability < params.probability_cutoff { //if there is no actions possible after this state
+        stats.num_evals += 1;
+        return board.evaluate_with(&params.weights);
     }
-    else{
-        for (proba, succ) in board.successors(){
-            sum = sum + proba * evaluate_playable(succ, remaining_actions, stats, cache);
-            cache.insert(board, (sum, remaining_actions));
-        }
+    if stats.exhausted() {
+        // out of budget: the value is a guess, the search being discarded
+        return board.evaluate_with(&params.weights);
+    }
+    let mut expectation: f32 = 0.0;
+    let mut worst: f32 = f32::INFINITY;
+    for (proba, succ) in board.successors() {
+        let value = evaluate_playable(succ, remaining_actions, extensions, probability * proba, params, stats, cache);
+        expectation += proba * value;
+        worst = worst.min(value);
+    }
+    let lambda = params.adversarial_mix;
+    let value = (1.0 - lambda) * expectation + lambda * worst;
+    if !stats.stopped {
+        cache.insert(board, (value, remaining_actions));
     }
-    return sum;
+    value
+}
+
+// The value of a chance node whose tile is known to have the exponent `exponent` (a previewed
+// spawn): the tile spawns on a uniformly chosen empty cell, blended with the worst cell as in
+// `evaluate_randable`. Not cached: the cache holds the values of the unknown tiles.
+fn evaluate_previewed(board: RandableBoard, exponent: u8, remaining_actions: usize, params: &SearchParams, stats: &mut Stats, cache: &mut LruCache<RandableBoard, (f32, usize)>) -> f32 {
+    if remaining_actions == 0 {
+        stats.num_evals += 1;
+        return board.evaluate_with(&params.weights);
+    }
+    let empty = board.board().num_empty();
+    let proba = 1.0 / empty as f32;
+    let mut expectation: f32 = 0.0;
+    let mut worst: f32 = f32::INFINITY;
+    for (row, col) in (0..N).flat_map(|row| (0..N).map(move |col| (row, col))) {
+        let Some(succ) = board.with_spawn(Spawn { row, col, exponent }) else {
+            continue;
+        };
+        let value = evaluate_playable(succ, remaining_actions, params.extensions, proba, params, stats, cache);
+        expectation += proba * value;
+        worst = worst.min(value);
+    }
+    let lambda = params.adversarial_mix;
+    (1.0 - lambda) * expectation + lambda * worst
 }
 
 // eval_playable(s, d) =
 // applicable_actions = { actions that are applicable in s }
 // successors = { result(s, action)  |  action in applicable_actions}
-// max { eval_chance(succ, d-1)  | succ in successors }
+// max { score_weight * gained(s, action) + eval_chance(succ, d-1)  | succ in successors }
 // we choose the best action
-fn evaluate_playable(board: PlayableBoard, remaining_actions: usize, stats: &mut Stats, cache:&mut HashMap<RandableBoard, (f32, usize)>) -> f32 {
+//
+// Just before the horizon (d = 1), a forced move (single applicable action) or a move merging
+// at least the value of the largest tile is searched one ply deeper instead (at most
+// `extensions` times along a line): cutting off right there hides what happens next, e.g. the
+// board being stuck after the forced move. The cache does not distinguish values computed
+// with or without extensions.
+fn evaluate_playable(board: PlayableBoard, remaining_actions: usize, extensions: usize, probability: f32, params: &SearchParams, stats: &mut Stats, cache: &mut LruCache<RandableBoard, (f32, usize)>) -> f32 {
+    let successors = ALL_ACTIONS.map(|action| board.apply_scored(action));
+    let num_applicable = successors.iter().flatten().count();
+    let large_merge = 2u32.pow(board.max_tile() as u32);
+
     // iterate through all actions and keep the applicable ones
-    let mut best_action: Option<Action> =None ;
     let mut best_score: f32 = 0.0;
-    for action in ALL_ACTIONS {
-        if let Some(_succ) = board.apply(action) {
-            // action is applicable, we check if its better than the current best
-            let current_eval = evaluate_randable(_succ, remaining_actions-1, stats, cache);
-                if current_eval > best_score{
-                best_action = Some(action);
-                best_score = current_eval;
-            }
+    for (_succ, gained) in successors.into_iter().flatten() {
+        let extend = remaining_actions == 1 && extensions > 0 && (num_applicable == 1 || gained >= large_merge);
+        let continuation = if extend {
+            evaluate_randable(_succ, remaining_actions, extensions - 1, probability, params, stats, cache)
         } else {
-            // action is not aplicable, ignore
+            evaluate_randable(_succ, remaining_actions - 1, extensions, probability, params, stats, cache)
+        };
+        // action is applicable, we check if its better than the current best
+        let current_eval = params.score_weight * gained as f32 + continuation;
+        if current_eval > best_score {
+            best_score = current_eval;
         }
     }
-    return best_score;
+    best_score
 }
 
 /// A small structure to accumulated statistics accros deeply nested calls
@@ -140,6 +639,28 @@ fn evaluate_playable(board: PlayableBoard, remaining_actions: usize, stats: &mut
 struct Stats {
     /// number of time the evaluation method is called on
     pub num_evals: usize,
+    /// Limits of the `Budget` of the decision
+    deadline: Option<Instant>,
+    max_nodes: Option<usize>,
+    /// Number of checks of the limits, the clock being read every `CLOCK_CHECKS`
+    checks: usize,
+    /// Whether a limit was reached: the values computed since then are wrong
+    stopped: bool,
+}
+
+// Checks of the limits between two readings of the clock
+const CLOCK_CHECKS: usize = 256;
+
+impl Stats {
+    // Whether a limit of the budget is reached
+    fn exhausted(&mut self) -> bool {
+        if !self.stopped {
+            self.checks += 1;
+            self.stopped = self.max_nodes.is_some_and(|max_nodes| self.num_evals >= max_nodes)
+                || self.deadline.is_some_and(|deadline| self.checks.is_multiple_of(CLOCK_CHECKS) && Instant::now() >= deadline);
+        }
+        self.stopped
+    }
 }
 
 impl std::fmt::Display for Stats {
@@ -148,3 +669,166 @@ impl std::fmt::Display for Stats {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_break_tie() {
+        let board = PlayableBoard::from_board(Board::from_compact("1000/0000/0010/9100").unwrap());
+        // Up, Down, Left, Right
+        let values = [Some(100.0), Some(97.0), Some(98.0), Some(90.0)];
+        let margin = |hysteresis: f32| SearchParams { hysteresis, ..Default::default() };
+        assert_eq!(break_tie(board, &values, None, &margin(0.0)), Some(Action::Up));
+        // the previous direction is kept when nearly as good
+        assert_eq!(break_tie(board, &values, Some(Action::Left), &margin(0.05)), Some(Action::Left));
+        // otherwise, the 512 stays in its corner
+        assert_eq!(break_tie(board, &values, Some(Action::Right), &margin(0.05)), Some(Action::Left));
+        assert_eq!(break_tie(board, &values, None, &margin(0.05)), Some(Action::Left));
+        assert_eq!(break_tie(board, &values, None, &margin(0.01)), Some(Action::Up));
+    }
+
+    #[test]
+    fn test_negative_values() {
+        let board = PlayableBoard::from_board(Board::from_compact("1000/0000/0010/9100").unwrap());
+        let params = SearchParams::default();
+        let values = [Some(-5.0), None, Some(-2.0), Some(-3.0)];
+        assert_eq!(best_action(board, &values, &params), Some(Action::Left));
+        assert_eq!(best_action(board, &[None; 4], &params), None);
+        // extreme weights still give a move on a live board
+        for (name, weight) in [("sum", 1e6), ("monotonicity", -1e30), ("empty", -1e30)] {
+            let mut params = SearchParams { depth: 2, ..Default::default() };
+            params.weights.set(name, weight).unwrap();
+            assert!(select_action_with(board, &params).is_some(), "{name} = {weight}");
+            assert!(Searcher::default().select_action(board, &params).is_some(), "{name} = {weight}");
+        }
+        let params = SearchParams { depth: 2, score_weight: -1e9, ..Default::default() };
+        assert!(select_action_with(board, &params).is_some());
+        // a depth of 0 searches as a depth of 1
+        let shallow = |depth: usize| action_values(board, &SearchParams { depth, ..Default::default() });
+        assert_eq!(shallow(0), shallow(1));
+    }
+
+    #[test]
+    fn test_tiebreak() {
+        let board = PlayableBoard::from_board(Board::from_compact("1000/0000/0000/0001").unwrap());
+        // Up and Right are tied, Down and Left are worse
+        let values = [Some(5.0), Some(4.0), Some(1.0), Some(5.0)];
+        let choose = |tiebreak: Tiebreak| best_action(board, &values, &SearchParams { tiebreak, ..Default::default() });
+        assert_eq!(choose(Tiebreak::Fixed), Some(Action::Up));
+        // the same seed always gives the same choice, another seed may not
+        let seeded: Vec<Option<Action>> = (0..16).map(|seed| choose(Tiebreak::Seeded(seed))).collect();
+        assert_eq!(seeded, (0..16).map(|seed| choose(Tiebreak::Seeded(seed))).collect::<Vec<_>>());
+        assert!(seeded.contains(&Some(Action::Up)) && seeded.contains(&Some(Action::Right)));
+        assert!(seeded.iter().all(|&action| action == Some(Action::Up) || action == Some(Action::Right)));
+        // the move to the position of best heuristic value
+        let heuristic = |action: Action| board.apply(action).unwrap().evaluate();
+        let expected = if heuristic(Action::Right) > heuristic(Action::Up) { Action::Right } else { Action::Up };
+        assert_eq!(choose(Tiebreak::Heuristic), Some(expected));
+
+        for tiebreak in [Tiebreak::Fixed, Tiebreak::Heuristic, Tiebreak::Seeded(7)] {
+            assert_eq!(Tiebreak::parse(&tiebreak.to_string()).unwrap(), tiebreak);
+        }
+        assert!(Tiebreak::parse("random").is_err());
+    }
+
+    #[test]
+    fn test_search_result() {
+        let board = PlayableBoard::from_board(Board::from_compact("0000/0000/1000/9800").unwrap());
+        let params = SearchParams { depth: 3, ..Default::default() };
+        let result = select_action_with(board, &params).unwrap();
+        assert_eq!(result.values, action_values(board, &params));
+        assert_eq!(result.value(), result.values.iter().flatten().copied().reduce(f32::max));
+        assert_eq!((result.depth, result.truncated), (3, false));
+        assert!(result.nodes > 0);
+        // one move per level of the search, from the best one
+        assert_eq!(result.pv.len(), 3);
+        assert_eq!(result.pv[0], result.best);
+        // the actions searched in parallel find the same on a single thread
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let single = pool.install(|| select_action_with(board, &params)).unwrap();
+        assert_eq!(SearchResult { time: result.time, ..single }, result);
+        // the searcher finds the same, its cache saving evaluations the second time
+        let mut searcher = Searcher::default();
+        let first = searcher.select_action(board, &params).unwrap();
+        assert_eq!((first.best, first.values, &first.pv), (result.best, result.values, &result.pv));
+        assert!(searcher.select_action(board, &params).unwrap().nodes < first.nodes);
+
+        assert!(select_action_greedily(board).is_some_and(|greedy| greedy.depth == 1 && greedy.pv == [greedy.best]));
+        let lost = PlayableBoard::from_board(Board::from_compact("1212/2121/1212/2121").unwrap());
+        assert!(select_action_with(lost, &params).is_none());
+    }
+
+    #[test]
+    fn test_budget() {
+        let board = PlayableBoard::from_board(Board::from_compact("1210/4100/3000/000b").unwrap());
+        let params = SearchParams { depth: 3, ..Default::default() };
+        let full = Searcher::default().select_action(board, &params).unwrap();
+        // a budget without limits changes nothing, a large one deepens up to the same values
+        let within = |budget: Budget| Searcher::default().select_action_within(board, &params, budget).unwrap();
+        let unlimited = within(Budget::default());
+        assert_eq!(unlimited, SearchResult { time: unlimited.time, ..full.clone() });
+        let large = within(Budget { nodes: Some(usize::MAX), time: Some(Duration::from_secs(60)), ..Default::default() });
+        assert_eq!((large.best, large.values, large.depth, large.truncated), (full.best, full.values, 3, false));
+
+        // out of nodes, the deepest completed search is kept
+        let cut = within(Budget { nodes: Some(full.nodes / 2), ..Default::default() });
+        assert!(cut.truncated && (1..3).contains(&cut.depth), "{cut:?}");
+        let shallow = within(Budget { depth: Some(cut.depth), ..Default::default() });
+        assert_eq!((cut.best, cut.values), (shallow.best, shallow.values));
+        assert_eq!(within(Budget { depth: Some(1), nodes: Some(0), ..Default::default() }).depth, 1);
+        // `select_action_timed` deepens while a crowded board leaves few moves
+        let crowded = PlayableBoard::from_board(Board::from_compact("1234/2345/3456/4560").unwrap());
+        let timed = select_action_timed(crowded, Duration::from_millis(100)).unwrap();
+        assert!(timed.depth > 3 && timed.time < Duration::from_secs(5), "{timed:?}");
+        // out of time
+        let late = within(Budget { depth: Some(20), time: Some(Duration::from_millis(50)), ..Default::default() });
+        assert!(late.truncated && late.depth < 20 && late.time < Duration::from_secs(5), "{late:?}");
+    }
+
+    #[test]
+    fn test_previewed_spawn() {
+        // a crowded board, where the value of the next tile matters
+        let board = PlayableBoard::from_board(Board::from_compact("1212/2101/1212/2121").unwrap());
+        let params = SearchParams { depth: 2, ..Default::default() };
+        let previewed = |next: Option<u8>| Searcher::default().select_action_previewed(board, &params, Budget::default(), next).unwrap();
+        let (two, four, unknown) = (previewed(Some(1)), previewed(Some(2)), previewed(None));
+        assert_eq!(unknown.values, action_values(board, &params));
+        for ((two, four), unknown) in two.values.iter().zip(four.values).zip(unknown.values) {
+            let (Some(two), Some(four), Some(unknown)) = (*two, four, unknown) else {
+                continue;
+            };
+            // the unknown tile is a 2 or a 4, with the probabilities of the rules
+            let mixed = 0.9 * two + 0.1 * four;
+            assert!((unknown - mixed).abs() <= 1e-5 * unknown.abs(), "{unknown} {mixed}");
+        }
+    }
+
+    #[test]
+    fn test_probability_cutoff() {
+        let board = PlayableBoard::from_board(Board::from_compact("1210/4100/3000/000b").unwrap());
+        let params = SearchParams { depth: 3, ..Default::default() };
+        let search = |params: &SearchParams| Searcher::default().select_action(board, params).unwrap();
+        let full = search(&params);
+        let pruned = search(&SearchParams { probability_cutoff: 0.05, ..params });
+        assert!(pruned.nodes < full.nodes, "{} {}", pruned.nodes, full.nodes);
+        assert_eq!(pruned.depth, 3);
+        // above the probability of any spawn, only the chance nodes after the root actions
+        // are searched: the lines stop at the heuristic one move later
+        let root_only = search(&SearchParams { probability_cutoff: 1.0, ..params });
+        assert_eq!(root_only.values, search(&SearchParams { depth: 2, extensions: 0, ..params }).values);
+    }
+
+    #[test]
+    fn test_values_are_bit_identical() {
+        // golden values: any change of the evaluation, of the search or of the order of the
+        // floating point operations shows up here, whatever the platform and optimization level
+        let values = |compact: &str| {
+            let board = PlayableBoard::from_board(Board::from_compact(compact).unwrap());
+            action_values(board, &SearchParams { depth: 2, ..Default::default() }).map(|value| value.map(f32::to_bits))
+        };
+        assert_eq!(values("1210/4100/3000/000b"), [Some(1236668138), Some(1236660137), Some(1236697170), Some(1236732788)]);
+        assert_eq!(values("0000/0000/1000/9800"), [Some(1236425832), None, None, Some(1236689332)]);
+    }
+}
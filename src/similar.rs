@@ -0,0 +1,177 @@
+//! Index of the positions of the recorded games, to answer "have I seen this position before,
+//! and what happened?" from the analysis mode.
+//!
+//! Positions are indexed by `Board::canonical_hash64`, so a position matches the recorded ones
+//! equal to it up to a rotation or a reflection. The move played in a match is given in the
+//! orientation of the queried position.
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use hashbrown::HashMap;
+
+use crate::board::*;
+use crate::death::DeathCause;
+use crate::notation::{GameOutcome, GameRecord, NOTATION_EXTENSION};
+use crate::rules;
+
+/// A recorded position and the end of its game
+#[derive(Clone, Debug)]
+pub struct Occurrence {
+    /// File of the game
+    pub game: PathBuf,
+    /// Number of moves played before the position
+    pub move_number: usize,
+    /// The position, as recorded
+    pub board: Board,
+    /// Action played on the position, as recorded (`None` for the final position)
+    pub played: Option<Action>,
+    /// Number of moves played after the position until the end of the game
+    pub moves_left: usize,
+    pub outcome: GameOutcome,
+    pub cause: Option<DeathCause>,
+    /// Exponent of the largest tile at the end of the game
+    pub final_max_tile: u8,
+}
+
+/// An occurrence matching a queried position
+pub struct Match<'a> {
+    pub occurrence: &'a Occurrence,
+    /// The action played, as seen on the queried position
+    pub played: Option<Action>,
+}
+
+/// The positions of a set of recorded games
+#[derive(Default)]
+pub struct PositionIndex {
+    positions: HashMap<u64, Vec<Occurrence>>,
+    num_games: usize,
+}
+
+impl PositionIndex {
+    /// Indexes all the games of `dir` played with the rules of the process, skipping the
+    /// invalid ones.
+    pub fn of_dir(dir: &Path) -> anyhow::Result<PositionIndex> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).with_context(|| format!("Could not read {}", dir.display()))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == NOTATION_EXTENSION) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut index = PositionIndex::default();
+        let ruleset = *rules::rules();
+        for path in paths {
+            let result = GameRecord::load(&path).and_then(|record| match record.ruleset == ruleset {
+                true => index.add_game(&path, &record),
+                false => Ok(()),
+            });
+            if let Err(e) = result {
+                println!("Skipping {}: {e:#}", path.display());
+            }
+        }
+        Ok(index)
+    }
+
+    /// Adds all the positions of the game `record`, saved in the file `game`.
+    pub fn add_game(&mut self, game: &Path, record: &GameRecord) -> anyhow::Result<()> {
+        let positions = record.positions()?;
+        let last = positions.len() - 1;
+        let final_max_tile = positions[last].max_tile();
+        for (move_number, position) in positions.iter().enumerate() {
+            let board = position.board();
+            self.positions.entry(board.canonical_hash64()).or_default().push(Occurrence {
+                game: game.to_path_buf(),
+                move_number,
+                board,
+                played: record.moves.get(move_number).map(|&(action, _)| action),
+                moves_left: last - move_number,
+                outcome: record.outcome,
+                cause: record.cause,
+                final_max_tile,
+            });
+        }
+        self.num_games += 1;
+        Ok(())
+    }
+
+    pub fn num_games(&self) -> usize {
+        self.num_games
+    }
+
+    /// The recorded positions equal to `board` up to a symmetry
+    pub fn query(&self, board: &Board) -> Vec<Match<'_>> {
+        let Some(occurrences) = self.positions.get(&board.canonical_hash64()) else {
+            return Vec::new();
+        };
+        occurrences
+            .iter()
+            .filter_map(|occurrence| {
+                let k = (0..NUM_SYMMETRIES).find(|&k| occurrence.board.symmetric(k) == *board)?;
+                Some(Match { occurrence, played: occurrence.played.map(|action| symmetric_action(action, k)) })
+            })
+            .collect()
+    }
+}
+
+impl Display for Match<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let occurrence = self.occurrence;
+        write!(f, "{} move {}: ", occurrence.game.display(), occurrence.move_number)?;
+        match self.played {
+            Some(action) => write!(f, "played {action:?}, ")?,
+            None => write!(f, "final position, ")?,
+        }
+        write!(
+            f,
+            "{} {} moves later with {}",
+            occurrence.outcome.name(),
+            occurrence.moves_left,
+            1u32 << occurrence.final_max_tile
+        )?;
+        if let Some(cause) = occurrence.cause {
+            write!(f, " ({})", cause.name())?;
+        }
+        Ok(())
+    }
+}
+
+/// One-line summary of the matches of a position, for the status bar
+pub fn summarize(matches: &[Match]) -> String {
+    if matches.is_empty() {
+        return "Never seen in the recorded games".to_string();
+    }
+    let best = matches.iter().map(|m| m.occurrence.final_max_tile).max().unwrap_or(0);
+    let mean_left = matches.iter().map(|m| m.occurrence.moves_left).sum::<usize>() / matches.len();
+    let times = if matches.len() == 1 { "once".to_string() } else { format!("{} times", matches.len()) };
+    format!("Seen {times}: best end {}, {mean_left} moves left on average", 1u32 << best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_up_to_symmetry() {
+        let mut record = GameRecord::new(Board::from_compact("0000/0000/0000/1100").unwrap());
+        record.push(Action::Left, Spawn { row: 0, col: 0, exponent: 1 });
+        record.push(Action::Up, Spawn { row: 3, col: 3, exponent: 1 });
+        record.lose(&record.positions().unwrap().last().unwrap().board());
+        let mut index = PositionIndex::default();
+        index.add_game(Path::new("game.2gn"), &record).unwrap();
+
+        // the first position, mirrored left/right: the move is mirrored too
+        let mirrored = Board::from_compact("0000/0000/0000/0011").unwrap();
+        let matches = index.query(&mirrored);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].played, Some(Action::Right));
+        assert_eq!(matches[0].occurrence.moves_left, 2);
+        assert!(matches[0].to_string().starts_with("game.2gn move 0: played Right, lost 2 moves later with 4"), "{}", matches[0]);
+
+        assert!(index.query(&Board::from_compact("0000/0000/0000/1200").unwrap()).is_empty());
+        assert_eq!(summarize(&matches), "Seen once: best end 4, 2 moves left on average");
+    }
+}
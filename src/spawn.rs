@@ -0,0 +1,224 @@
+//! Sources of the tiles spawned by the chance player after each move, so that the games can be
+//! played with the random spawns of the real game, reproducibly from a seed, with a scripted
+//! sequence (e.g. in tests) or adversarially, to stress test the strategies. Any of them can
+//! show the value of the next tile in advance (`PreviewSpawns`), as in Threes.
+
+use std::collections::VecDeque;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::board::*;
+use crate::search::{SearchParams, Searcher};
+
+/// Moves searched ahead by the hostile spawns, unless told otherwise
+pub const DEFAULT_HOSTILE_DEPTH: usize = 1;
+
+/// Chooses the tiles spawned after each move
+pub trait SpawnStream {
+    /// Tile to spawn on `board`, which has at least one empty cell.
+    fn next_spawn(&mut self, board: &Board) -> Spawn;
+
+    /// Exponent of the next tile to spawn, for the streams showing it before the move
+    fn preview(&self) -> Option<u8> {
+        None
+    }
+}
+
+impl<S: SpawnStream + ?Sized> SpawnStream for Box<S> {
+    fn next_spawn(&mut self, board: &Board) -> Spawn {
+        (**self).next_spawn(board)
+    }
+
+    fn preview(&self) -> Option<u8> {
+        (**self).preview()
+    }
+}
+
+/// The spawns of the real game: a 2 (90%) or a 4 (10%) on a uniformly chosen empty cell
+pub struct RandomSpawns;
+
+impl SpawnStream for RandomSpawns {
+    fn next_spawn(&mut self, board: &Board) -> Spawn {
+        let mut board = *board;
+        board.add_random()
+    }
+}
+
+/// The spawns of the real game drawn from a seeded generator: the same seed always gives the
+/// same spawns when the same moves are played.
+pub struct SeededSpawns {
+    rng: StdRng,
+}
+
+impl SeededSpawns {
+    pub fn new(seed: u64) -> SeededSpawns {
+        SeededSpawns { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl SpawnStream for SeededSpawns {
+    fn next_spawn(&mut self, board: &Board) -> Spawn {
+        let mut board = *board;
+        board.add_random_with(&mut self.rng)
+    }
+}
+
+/// A fixed sequence of spawns, given in advance
+pub struct ScriptedSpawns {
+    spawns: VecDeque<Spawn>,
+}
+
+impl ScriptedSpawns {
+    pub fn new(spawns: impl IntoIterator<Item = Spawn>) -> ScriptedSpawns {
+        ScriptedSpawns { spawns: spawns.into_iter().collect() }
+    }
+
+    /// Number of spawns not used yet
+    pub fn remaining(&self) -> usize {
+        self.spawns.len()
+    }
+}
+
+impl SpawnStream for ScriptedSpawns {
+    /// Panics when the script is exhausted or when the scripted cell is not empty: the script
+    /// does not match the game being played.
+    fn next_spawn(&mut self, board: &Board) -> Spawn {
+        let spawn = self.spawns.pop_front().expect("no more scripted spawns");
+        assert_eq!(board.get(spawn.row, spawn.col), 0, "scripted spawn {spawn:?} on an occupied cell of\n{board}");
+        spawn
+    }
+}
+
+/// The spawns of another stream whose values are drawn one tile ahead, so that the player
+/// sees the value of the next tile before moving; only its cell is left to chance. The value
+/// is known from the second spawn of the stream on (the first ones spawn as usual).
+pub struct PreviewSpawns<S> {
+    spawns: S,
+    next: Option<u8>,
+}
+
+impl<S: SpawnStream> PreviewSpawns<S> {
+    pub fn new(spawns: S) -> PreviewSpawns<S> {
+        PreviewSpawns { spawns, next: None }
+    }
+}
+
+impl<S: SpawnStream> SpawnStream for PreviewSpawns<S> {
+    fn next_spawn(&mut self, board: &Board) -> Spawn {
+        let spawn = self.spawns.next_spawn(board);
+        let spawn = Spawn { exponent: self.next.unwrap_or(spawn.exponent), ..spawn };
+        // (drawn on the same board: the values do not depend on the cells)
+        self.next = Some(self.spawns.next_spawn(board).exponent);
+        spawn
+    }
+
+    fn preview(&self) -> Option<u8> {
+        self.next
+    }
+}
+
+/// Adversarial spawns, to measure the robustness of a strategy in the worst case: the tile
+/// (empty cell and value allowed by the rules) leaving the worst position for the player,
+/// according to the evaluation of the agent searched `params.depth` moves ahead. The
+/// probabilities of the values are ignored, a 4 spawns whenever it hurts more than a 2. Among
+/// equally bad tiles, the first cell in reading order and the first value of the rules win, so
+/// the spawns are deterministic.
+pub struct HostileSpawns {
+    params: SearchParams,
+    searcher: Searcher,
+}
+
+impl HostileSpawns {
+    pub fn new(params: &SearchParams) -> HostileSpawns {
+        HostileSpawns { params: SearchParams { depth: params.depth.max(1), ..*params }, searcher: Searcher::default() }
+    }
+
+    // Value of the position for the player, the lost ones being the worst of all
+    fn value(&mut self, board: PlayableBoard) -> f32 {
+        let values = self.searcher.action_values(board, &self.params);
+        values.into_iter().flatten().reduce(f32::max).unwrap_or(f32::NEG_INFINITY)
+    }
+}
+
+impl SpawnStream for HostileSpawns {
+    fn next_spawn(&mut self, board: &Board) -> Spawn {
+        let values = crate::rules::rules().spawn_probabilities(board).into_iter().filter(|&(_, proba)| proba > 0.0);
+        let mut worst: Option<(f32, Spawn)> = None;
+        for (row, col) in (0..N).flat_map(|row| (0..N).map(move |col| (row, col))) {
+            if board.get(row, col) != 0 {
+                continue;
+            }
+            for (exponent, _) in values.clone() {
+                let spawn = Spawn { row, col, exponent };
+                let next = RandableBoard::from_board(*board).with_spawn(spawn).expect("the cell is empty");
+                let value = self.value(next);
+                if worst.is_none_or(|(worst, _)| value < worst) {
+                    worst = Some((value, spawn));
+                }
+            }
+        }
+        worst.expect("a tile spawns on a board with an empty cell").1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Plays a game pushing in turn in every direction, returning all the spawns
+    fn play(spawns: &mut impl SpawnStream) -> Vec<Spawn> {
+        let mut board = PlayableBoard::init_with(spawns);
+        let mut played = Vec::new();
+        for action in ALL_ACTIONS.into_iter().cycle() {
+            if board.legal_actions().is_empty() {
+                break;
+            }
+            if let Some(next) = board.apply(action) {
+                let (next, spawn) = next.with_spawn_from(spawns);
+                played.push(spawn);
+                board = next;
+            }
+        }
+        played
+    }
+
+    #[test]
+    fn test_seeded_spawns() {
+        let game = play(&mut SeededSpawns::new(7));
+        assert_eq!(play(&mut SeededSpawns::new(7)), game);
+        assert_ne!(play(&mut SeededSpawns::new(8)), game);
+    }
+
+    #[test]
+    fn test_preview_spawns() {
+        let mut spawns = PreviewSpawns::new(SeededSpawns::new(7));
+        assert_eq!(spawns.preview(), None);
+        let mut board = PlayableBoard::init_with(&mut spawns);
+        for action in ALL_ACTIONS.into_iter().cycle().take(200) {
+            if let Some(next) = board.apply(action) {
+                let previewed = spawns.preview();
+                let (next, spawn) = next.with_spawn_from(&mut spawns);
+                assert_eq!(previewed, Some(spawn.exponent));
+                board = next;
+            }
+        }
+        // the values follow the rules: mostly 2s, some 4s
+        let values: Vec<u8> = (0..100).map(|_| spawns.next_spawn(&Board::from_cells([[0; N]; N])).exponent).collect();
+        assert!(values.contains(&1) && values.contains(&2) && values.iter().all(|&exponent| exponent <= 2));
+    }
+
+    #[test]
+    fn test_hostile_spawns() {
+        let params = SearchParams { depth: DEFAULT_HOSTILE_DEPTH, ..Default::default() };
+        // a single empty cell: a 2 would merge with the 2s around it, a 4 locks the board
+        let board = Board::from_compact("1212/2101/1212/2121").unwrap();
+        let spawn = HostileSpawns::new(&params).next_spawn(&board);
+        assert_eq!(spawn, Spawn { row: 1, col: 2, exponent: 2 });
+
+        // deterministic, and never better for the player than the random spawns
+        let game = play(&mut HostileSpawns::new(&params));
+        assert_eq!(play(&mut HostileSpawns::new(&params)), game);
+        assert!(game.len() < play(&mut SeededSpawns::new(7)).len());
+    }
+}
@@ -0,0 +1,107 @@
+//! Budget-split decisions of the agent: a shallow search answers at once, so that the window
+//! shows the move without waiting, while the full search verifies it in the background. The
+//! move is only played (and the tile spawned) once the verification is done or its time is up,
+//! so a disagreeing verification can still replace the quick move. The verification stops by
+//! itself when its time is up, so that it never outlives its decision.
+
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::board::*;
+use crate::search::{self, Budget, SearchParams, SearchResult, Searcher};
+
+/// Depth of the quick search
+pub const QUICK_DEPTH: usize = 1;
+
+/// A decision whose quick answer is known and whose verification may still be running
+pub struct SplitDecision {
+    quick: Action,
+    deep: JoinHandle<Option<SearchResult>>,
+}
+
+/// The outcome of a decision
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// The verification agrees with the quick move
+    Confirmed(Action),
+    /// The verification chose another move: `(quick, deep)`
+    Corrected(Action, Action),
+    /// The verification did not finish in time, the quick move is played
+    Unverified(Action),
+}
+
+impl Resolution {
+    /// The move to play
+    pub fn action(&self) -> Action {
+        match *self {
+            Resolution::Confirmed(action) | Resolution::Unverified(action) | Resolution::Corrected(_, action) => action,
+        }
+    }
+}
+
+impl SplitDecision {
+    /// Searches `board` at `QUICK_DEPTH` and starts the verification with `params` in the
+    /// background, given `time` to complete. `None` if no action is applicable.
+    pub fn start(board: PlayableBoard, params: &SearchParams, time: Duration) -> Option<SplitDecision> {
+        let quick = search::select_action_with(board, &SearchParams { depth: QUICK_DEPTH, ..*params })?.best;
+        let params = *params;
+        let budget = Budget { time: Some(time), ..Default::default() };
+        let deep = std::thread::spawn(move || Searcher::default().select_action_within(board, &params, budget));
+        Some(SplitDecision { quick, deep })
+    }
+
+    pub fn quick(&self) -> Action {
+        self.quick
+    }
+
+    pub fn is_verified(&self) -> bool {
+        self.deep.is_finished()
+    }
+
+    /// The move to play: the one of the verification if it completed, the quick one otherwise
+    /// (a verification still running is then left to stop at the end of its time).
+    pub fn resolve(self) -> Resolution {
+        if !self.deep.is_finished() {
+            return Resolution::Unverified(self.quick);
+        }
+        match self.deep.join().ok().flatten() {
+            // out of time before the full depth
+            Some(deep) if deep.truncated => Resolution::Unverified(self.quick),
+            Some(deep) if deep.best != self.quick => Resolution::Corrected(self.quick, deep.best),
+            _ => Resolution::Confirmed(self.quick),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_decision() {
+        let board = PlayableBoard::from_board(Board::from_compact("1210/4100/3000/000b").unwrap());
+        let params = SearchParams { depth: 3, ..Default::default() };
+        let decision = SplitDecision::start(board, &params, Duration::from_secs(60)).unwrap();
+        assert_eq!(decision.quick(), search::select_action_with(board, &SearchParams { depth: QUICK_DEPTH, ..params }).unwrap().best);
+        while !decision.is_verified() {
+            std::thread::yield_now();
+        }
+        // the move played is always the one of the full search once verified
+        let resolution = decision.resolve();
+        assert_eq!(resolution.action(), Searcher::default().select_action(board, &params).unwrap().best);
+        assert!(!matches!(resolution, Resolution::Unverified(_)));
+
+        // a verification out of time stops by itself, unverified
+        let deep = SearchParams { depth: 12, ..params };
+        let late = SplitDecision::start(board, &deep, Duration::from_millis(20)).unwrap();
+        let (quick, start) = (late.quick(), std::time::Instant::now());
+        while !late.is_verified() {
+            assert!(start.elapsed() < Duration::from_secs(5), "the verification did not stop");
+            std::thread::yield_now();
+        }
+        assert_eq!(late.resolve(), Resolution::Unverified(quick));
+
+        let lost = PlayableBoard::from_board(Board::from_compact("1212/2121/1212/2121").unwrap());
+        assert!(SplitDecision::start(lost, &params, Duration::from_secs(60)).is_none());
+    }
+}
@@ -0,0 +1,250 @@
+//! Agents choosing the moves, behind a common trait so that every mode (GUI, benchmark, ...)
+//! can play with any of them.
+//!
+//! A new agent implements `Strategy` and is added to `STRATEGIES` to be selectable by name.
+//! External engines are selected with `external:<command>` (see `external.rs`).
+
+use crate::board::*;
+use crate::calibration::Calibration;
+use crate::search::{self, SearchParams, SearchResult, Searcher};
+pub use crate::search::Budget;
+
+/// An agent playing the game
+pub trait Strategy: Send {
+    /// Name of the strategy, with its main parameters
+    fn name(&self) -> String;
+
+    /// Chooses the action to play on `board`, with what the strategy knows about it, `None` if
+    /// no action is applicable.
+    fn choose(&mut self, board: PlayableBoard, budget: Budget) -> Option<SearchResult>;
+
+    /// Updates the search parameters (e.g. from the developer console), for the strategies
+    /// that have some.
+    fn configure(&mut self, _params: &SearchParams) {}
+
+    /// Tells the exponent of the tile spawned after the next move, when the spawns show it
+    /// (see `spawn::PreviewSpawns`), for the strategies that can take it into account.
+    fn preview(&mut self, _next: Option<u8>) {}
+
+    /// Statistics of the strategy worth reporting at the end of a game, if any
+    fn report(&self) -> Option<String> {
+        None
+    }
+
+    /// Entries, capacity and evictions of the cache of the search, for the strategies that
+    /// have one
+    fn cache_usage(&self) -> Option<(usize, usize, u64)> {
+        None
+    }
+}
+
+/// Plays a random applicable action
+pub struct RandomStrategy;
+
+impl Strategy for RandomStrategy {
+    fn name(&self) -> String {
+        "random".to_string()
+    }
+
+    fn choose(&mut self, board: PlayableBoard, _budget: Budget) -> Option<SearchResult> {
+        search::select_action_randomly(board)
+    }
+}
+
+/// Plays the action with the best immediate evaluation
+pub struct GreedyStrategy;
+
+impl Strategy for GreedyStrategy {
+    fn name(&self) -> String {
+        "greedy".to_string()
+    }
+
+    fn choose(&mut self, board: PlayableBoard, _budget: Budget) -> Option<SearchResult> {
+        search::select_action_greedily(board)
+    }
+}
+
+/// The expectimax search, keeping its cache from one move to the next
+pub struct ExpectimaxStrategy {
+    params: SearchParams,
+    searcher: Searcher,
+    /// Predicted values of the moves against their outcomes
+    calibration: Calibration,
+    /// Position after the last chosen move (before the spawn), to detect the moves played by
+    /// someone else
+    last_played: Option<Board>,
+    /// Exponent of the tile spawned after the next move, if shown
+    next_spawn: Option<u8>,
+}
+
+impl ExpectimaxStrategy {
+    pub fn new(params: SearchParams) -> ExpectimaxStrategy {
+        ExpectimaxStrategy { params, searcher: Searcher::default(), calibration: Calibration::default(), last_played: None, next_spawn: None }
+    }
+}
+
+// Whether `next` is `played` with one more tile, i.e. no move was played in between
+fn follows(played: &Board, next: &Board) -> bool {
+    let cells = played.exponents().zip(next.exponents());
+    cells.clone().all(|(a, b)| a == b || a == 0) && cells.filter(|(a, b)| a != b).count() == 1
+}
+
+impl Strategy for ExpectimaxStrategy {
+    fn name(&self) -> String {
+        format!(
+            "expectimax (depth {}, adversarial mix {}, score weight {})",
+            self.params.depth, self.params.adversarial_mix, self.params.score_weight
+        )
+    }
+
+    fn choose(&mut self, board: PlayableBoard, budget: Budget) -> Option<SearchResult> {
+        if !self.last_played.is_some_and(|played| follows(&played, &board.board())) {
+            self.calibration.interrupt();
+        }
+        // (the values of the previous move are searched one action too shallow to be reused)
+        self.searcher.clear();
+        let result = self.searcher.select_action_previewed(board, &self.params, budget, self.next_spawn)?;
+        let (played, gained) = board.apply_scored(result.best)?;
+        self.calibration.predict(result.value()?, result.depth, self.params.score_weight);
+        self.calibration.observe(gained, played.evaluate_with(&self.params.weights));
+        self.last_played = Some(played.board());
+        Some(result)
+    }
+
+    fn configure(&mut self, params: &SearchParams) {
+        self.params = *params;
+    }
+
+    fn preview(&mut self, next: Option<u8>) {
+        self.next_spawn = next;
+    }
+
+    fn cache_usage(&self) -> Option<(usize, usize, u64)> {
+        Some(self.searcher.cache_usage())
+    }
+
+    fn report(&self) -> Option<String> {
+        let (len, capacity, evictions) = self.searcher.cache_usage();
+        Some(format!("cache: {len}/{capacity} entries, {evictions} evictions\n{}", self.calibration))
+    }
+}
+
+/// A registered strategy: its name, a description and how to build it from search parameters
+pub struct StrategyEntry {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub build: fn(&SearchParams) -> Box<dyn Strategy>,
+}
+
+/// All the strategies selectable by name, the first one is the default
+pub const STRATEGIES: &[StrategyEntry] = &[
+    StrategyEntry {
+        name: "expectimax",
+        description: "expectimax search with the heuristic evaluation at the leaves",
+        build: |params| Box::new(ExpectimaxStrategy::new(*params)),
+    },
+    StrategyEntry {
+        name: "greedy",
+        description: "best immediate evaluation",
+        build: |_| Box::new(GreedyStrategy),
+    },
+    StrategyEntry {
+        name: "random",
+        description: "random applicable move",
+        build: |_| Box::new(RandomStrategy),
+    },
+];
+
+/// Builds the strategy registered under `name`, or starts the external engine of
+/// `external:<command>`.
+pub fn by_name(name: &str, params: &SearchParams) -> anyhow::Result<Box<dyn Strategy>> {
+    if let Some(command) = name.strip_prefix("external:") {
+        return Ok(Box::new(crate::external::ExternalStrategy::spawn(command)?));
+    }
+    let entry = STRATEGIES.iter().find(|entry| entry.name == name).ok_or_else(|| {
+        let names: Vec<&str> = STRATEGIES.iter().map(|entry| entry.name).collect();
+        anyhow::anyhow!("unknown strategy `{name}` (expected one of: {})", names.join(", "))
+    })?;
+    Ok((entry.build)(params))
+}
+
+/// The default strategy
+pub fn default_strategy(params: &SearchParams) -> Box<dyn Strategy> {
+    (STRATEGIES[0].build)(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn::ScriptedSpawns;
+
+    fn board(compact: &str) -> PlayableBoard {
+        PlayableBoard::from_board(Board::from_compact(compact).unwrap())
+    }
+
+    fn expectimax(depth: usize) -> ExpectimaxStrategy {
+        ExpectimaxStrategy::new(SearchParams { depth, ..Default::default() })
+    }
+
+    /// Plays until the spawns run out, returning the actions and the positions after them.
+    fn play_scripted(strategy: &mut dyn Strategy, mut board: PlayableBoard, mut spawns: ScriptedSpawns) -> Vec<(Action, Board)> {
+        let mut game = Vec::new();
+        while spawns.remaining() > 0 {
+            let action = strategy.choose(board, Budget::default()).expect("the scripted game is not lost").best;
+            board = board.apply(action).unwrap().with_spawn_from(&mut spawns).0;
+            game.push((action, board.board()));
+        }
+        game
+    }
+
+    #[test]
+    fn test_registry() {
+        let board = PlayableBoard::from_board(Board::from_compact("0000/0000/0000/1100").unwrap());
+        for entry in STRATEGIES {
+            let mut strategy = by_name(entry.name, &SearchParams::default()).unwrap();
+            let result = strategy.choose(board, Budget { depth: Some(1), ..Default::default() });
+            assert!(result.is_some_and(|result| board.apply(result.best).is_some()), "{}", entry.name);
+        }
+        assert!(by_name("unknown", &SearchParams::default()).is_err());
+    }
+
+    #[test]
+    fn test_expectimax_takes_free_merge() {
+        // merging the two 256 vertically is free, pushing right only moves them
+        let board = board("0000/0000/8000/8000");
+        for depth in 1..=3 {
+            let action = expectimax(depth).choose(board, Budget::default()).unwrap().best;
+            assert!(matches!(action, Action::Up | Action::Down), "depth {depth}: {action:?}");
+        }
+    }
+
+    #[test]
+    fn test_expectimax_keeps_corner() {
+        // pushing up is the only move taking the 512 out of its corner
+        let board = PlayableBoard::from_board(
+            Board::from_ascii_art(
+                "  2   .   .   .
+                   .   .   .   .
+                   .   .   .   .
+                 512 256  64   2",
+            )
+            .unwrap(),
+        );
+        for depth in 1..=3 {
+            let action = expectimax(depth).choose(board, Budget::default()).unwrap().best;
+            assert_ne!(action, Action::Up, "depth {depth}");
+        }
+    }
+
+    #[test]
+    fn test_scripted_game() {
+        let initial = board("0000/0000/1000/9800");
+        let script = || ScriptedSpawns::new([(0, 0), (0, 3), (0, 0), (0, 3)].map(|(row, col)| Spawn { row, col, exponent: 1 }));
+        let game = play_scripted(&mut expectimax(2), initial, script());
+        assert_eq!(game.len(), 4);
+        // the 512 never leaves the bottom row
+        assert!(game.iter().all(|(_, board)| board.cells()[N - 1].contains(&9)), "{game:?}");
+        // with the same spawns, the same game is played again
+        assert_eq!(play_scripted(&mut expectimax(2), initial, script()), game);
+    }
+}
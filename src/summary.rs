@@ -0,0 +1,173 @@
+//! Machine-readable summaries of the finished games, one JSON object per line, so that scripts
+//! orchestrating experiments do not have to parse the human-oriented logs.
+//!
+//! Summaries are only written when a destination is given: `-` for the standard output or the
+//! path of a file the lines are appended to, from the `SUMMARY_VAR` environment variable (or
+//! the `--summary` option of the benchmark).
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+
+use crate::notation::GameRecord;
+
+/// Environment variable giving the destination of the summaries
+pub const SUMMARY_VAR: &str = "AI2048_SUMMARY";
+
+/// Where the summaries are written
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Destination {
+    Stdout,
+    /// File the summaries are appended to
+    File(PathBuf),
+}
+
+impl Destination {
+    /// `-` for the standard output, a path otherwise
+    pub fn parse(text: &str) -> Destination {
+        match text.trim() {
+            "-" => Destination::Stdout,
+            path => Destination::File(PathBuf::from(path)),
+        }
+    }
+
+    /// The destination of `SUMMARY_VAR`, if set
+    pub fn from_env() -> Option<Destination> {
+        std::env::var(SUMMARY_VAR).ok().filter(|text| !text.trim().is_empty()).map(|text| Destination::parse(&text))
+    }
+
+    pub fn write(&self, summary: &GameSummary) -> anyhow::Result<()> {
+        let line = summary.to_json();
+        match self {
+            Destination::Stdout => println!("{line}"),
+            Destination::File(path) => std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{line}"))
+                .with_context(|| format!("Could not write the game summary to {}", path.display()))?,
+        }
+        Ok(())
+    }
+}
+
+/// Summary of a finished game
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameSummary {
+    /// Seed of the spawns, `None` for random spawns
+    pub seed: Option<u64>,
+    /// How the game was played (`bench`, `agent`, `human`, `mixed`)
+    pub mode: String,
+    /// Strategy playing at the end of the game
+    pub strategy: String,
+    /// `Ruleset::id` of the rules of the game
+    pub ruleset: String,
+    /// Points of the merges, as in the original game
+    pub score: u32,
+    /// Value of the largest tile
+    pub max_tile: u32,
+    pub moves: usize,
+    pub duration: Duration,
+    /// Decisions the strategy could not complete while a move was legal (e.g. an external
+    /// engine timing out)
+    pub truncated_decisions: usize,
+    /// `lost`, `timeout` or `unfinished`
+    pub outcome: &'static str,
+    /// Why the game was lost, if known
+    pub cause: Option<&'static str>,
+}
+
+impl GameSummary {
+    /// Summary of the game of `record`, whose score and max tile are found by replaying it
+    pub fn of_record(
+        record: &GameRecord,
+        seed: Option<u64>,
+        mode: &str,
+        strategy: &str,
+        duration: Duration,
+        truncated_decisions: usize,
+    ) -> anyhow::Result<GameSummary> {
+        let positions = record.positions()?;
+        let score = *record.scores()?.last().expect("the final score is always present");
+        let last = positions.last().expect("the final position is always present");
+        Ok(GameSummary {
+            seed,
+            mode: mode.to_string(),
+            strategy: strategy.to_string(),
+            ruleset: record.ruleset.id(),
+            score,
+            max_tile: 1 << last.max_tile(),
+            moves: record.moves.len(),
+            duration,
+            truncated_decisions,
+            outcome: record.outcome.name(),
+            cause: record.cause.map(|cause| cause.name()),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+        format!(
+            "{{\"version\": \"{}\", \"seed\": {}, \"mode\": {}, \"strategy\": {}, \"ruleset\": \"{}\", \"score\": {}, \"max_tile\": {}, \"moves\": {}, \"duration_s\": {:.3}, \"truncated_decisions\": {}, \"outcome\": \"{}\", \"cause\": {}}}",
+            env!("CARGO_PKG_VERSION"),
+            optional(self.seed.map(|seed| seed.to_string())),
+            json_string(&self.mode),
+            json_string(&self.strategy),
+            self.ruleset,
+            self.score,
+            self.max_tile,
+            self.moves,
+            self.duration.as_secs_f64(),
+            self.truncated_decisions,
+            self.outcome,
+            optional(self.cause.map(json_string)),
+        )
+    }
+}
+
+/// `text` as a JSON string, quoted and escaped
+pub fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::*;
+    use crate::death::DeathCause;
+    use crate::notation::GameOutcome;
+
+    #[test]
+    fn test_summary() {
+        let mut record = GameRecord::new(Board::from_compact("0000/0000/0000/1100").unwrap());
+        // 2 + 2 = 4 (8 points with the 4 + 4 of the second move)
+        record.push(Action::Left, Spawn { row: 0, col: 0, exponent: 2 });
+        record.push(Action::Down, Spawn { row: 0, col: 3, exponent: 1 });
+        record.outcome = GameOutcome::Lost;
+        record.cause = Some(DeathCause::Other);
+        let summary = GameSummary::of_record(&record, Some(7), "bench", "external (say \"hi\")", Duration::from_millis(1500), 1).unwrap();
+        assert_eq!((summary.score, summary.max_tile, summary.moves), (12, 8, 2));
+        let json = summary.to_json();
+        assert!(json.contains("\"seed\": 7, \"mode\": \"bench\", \"strategy\": \"external (say \\\"hi\\\")\", \"ruleset\": \"standard\""), "{json}");
+        assert!(json.ends_with("\"duration_s\": 1.500, \"truncated_decisions\": 1, \"outcome\": \"lost\", \"cause\": \"other\"}"), "{json}");
+
+        record.cause = None;
+        let summary = GameSummary::of_record(&record, None, "human", "", Duration::ZERO, 0).unwrap();
+        assert!(summary.to_json().contains("\"seed\": null") && summary.to_json().ends_with("\"cause\": null}"));
+        assert_eq!(Destination::parse("-"), Destination::Stdout);
+    }
+}
@@ -0,0 +1,127 @@
+//! Sensitivity of the agent to the weights of the heuristic: one or two weights are swept over
+//! a grid of values, a quick batch of games is played at every point and the average scores
+//! are rendered as a heatmap.
+
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use anyhow::Context;
+use rayon::prelude::*;
+
+use crate::eval::Weights;
+use crate::render;
+
+// Size in pixels of a point of the heatmap
+const CELL_SIZE: usize = 48;
+
+/// A weight of the heuristic swept over evenly spaced values
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sweep {
+    pub name: String,
+    pub values: Vec<f32>,
+}
+
+impl Sweep {
+    /// Parses `name=start:end:steps`, e.g. `empty=0:500:6` for 0, 100, ..., 500.
+    pub fn parse(text: &str) -> Result<Sweep, String> {
+        let (name, range) = text.split_once('=').ok_or("expected `name=start:end:steps`")?;
+        let bounds: Vec<&str> = range.split(':').collect();
+        let [start, end, steps] = bounds[..] else {
+            return Err("expected `start:end:steps` after the name".to_string());
+        };
+        let start: f32 = start.parse().map_err(|_| format!("invalid start `{start}`"))?;
+        let end: f32 = end.parse().map_err(|_| format!("invalid end `{end}`"))?;
+        let steps: usize = steps.parse().map_err(|_| format!("invalid number of steps `{steps}`"))?;
+        if steps == 0 {
+            return Err("at least one step is needed".to_string());
+        }
+        // fails early on an unknown weight
+        Weights::default().set(name, start).map_err(|e| e.to_string())?;
+        let values = (0..steps)
+            .map(|i| if steps == 1 { start } else { start + (end - start) * i as f32 / (steps - 1) as f32 })
+            .collect();
+        Ok(Sweep { name: name.to_string(), values })
+    }
+}
+
+/// Average score at every point of the grid of weights
+pub struct Surface {
+    /// Weight on the horizontal axis
+    pub x: Sweep,
+    /// Weight on the vertical axis, if any
+    pub y: Option<Sweep>,
+    /// `scores[row][col]` for the `row`-th value of `y` and the `col`-th value of `x`
+    pub scores: Vec<Vec<f32>>,
+}
+
+impl Surface {
+    /// Computes the score of every point of the grid, starting from the `base` weights, on the
+    /// thread pool.
+    pub fn compute(x: Sweep, y: Option<Sweep>, base: &Weights, score: impl Fn(&Weights) -> f32 + Sync) -> anyhow::Result<Surface> {
+        let rows: Vec<Option<f32>> = match &y {
+            Some(y) => y.values.iter().copied().map(Some).collect(),
+            None => vec![None],
+        };
+        let points: Vec<Weights> = rows
+            .iter()
+            .flat_map(|&y_value| x.values.iter().map(move |&x_value| (y_value, x_value)))
+            .map(|(y_value, x_value)| {
+                let mut weights = *base;
+                weights.set(&x.name, x_value)?;
+                if let (Some(y), Some(y_value)) = (&y, y_value) {
+                    weights.set(&y.name, y_value)?;
+                }
+                Ok(weights)
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let scores: Vec<f32> = points.par_iter().map(&score).collect();
+        let scores = scores.chunks(x.values.len()).map(<[f32]>::to_vec).collect();
+        Ok(Surface { x, y, scores })
+    }
+
+    /// Writes the heatmap of the scores as a PNG image, the first value of `y` at the top.
+    pub fn write_png(&self, path: &Path) -> anyhow::Result<()> {
+        let image = render::render_heatmap(&self.scores, CELL_SIZE);
+        std::fs::write(path, render::encode_png(&image)).with_context(|| format!("Could not write {}", path.display()))
+    }
+}
+
+impl Display for Surface {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let y_name = self.y.as_ref().map_or("", |y| y.name.as_str());
+        write!(f, "{:>14}", format!("{y_name} \\ {}", self.x.name))?;
+        for x in &self.x.values {
+            write!(f, " {x:>9.2}")?;
+        }
+        writeln!(f)?;
+        for (i, row) in self.scores.iter().enumerate() {
+            match &self.y {
+                Some(y) => write!(f, "{:>14.2}", y.values[i])?,
+                None => write!(f, "{:>14}", "")?,
+            }
+            for score in row {
+                write!(f, " {score:>9.1}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep() {
+        assert_eq!(Sweep::parse("empty=0:500:6").unwrap().values, vec![0.0, 100.0, 200.0, 300.0, 400.0, 500.0]);
+        assert_eq!(Sweep::parse("sum=3:7:1").unwrap().values, vec![3.0]);
+        assert!(Sweep::parse("unknown=0:1:2").is_err());
+        assert!(Sweep::parse("empty=0:1").is_err());
+
+        let x = Sweep::parse("empty=0:2:3").unwrap();
+        let y = Sweep::parse("sum=10:20:2").unwrap();
+        let surface = Surface::compute(x, Some(y), &Weights::default(), |weights| weights.empty + weights.sum).unwrap();
+        assert_eq!(surface.scores, vec![vec![10.0, 11.0, 12.0], vec![20.0, 21.0, 22.0]]);
+    }
+}
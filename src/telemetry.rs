@@ -0,0 +1,208 @@
+//! Opt-in submission of the aggregate results of benchmark runs to a community endpoint, to
+//! compare the strength of the agents across machines.
+//!
+//! Nothing is sent unless an endpoint is given explicitly, and setting the `OFF_SWITCH`
+//! environment variable disables the telemetry even then. The results are anonymized: the
+//! strategy (without the command of external engines), the depth, the distribution of the
+//! scores and max tiles, and the version of the crate.
+//!
+//! Results are first written to a local queue, then posted as JSON to the endpoint (plain
+//! HTTP). Results that could not be sent stay in the queue and are sent with the next run.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{ensure, Context};
+
+/// Environment variable disabling the telemetry when set, whatever the command line says
+pub const OFF_SWITCH: &str = "AI2048_TELEMETRY_OFF";
+// Time allowed to connect and to exchange with the endpoint
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether the telemetry is disabled by the off switch
+pub fn disabled() -> bool {
+    std::env::var_os(OFF_SWITCH).is_some()
+}
+
+/// Anonymized results of a batch of games
+pub struct BatchSummary {
+    /// Name of the strategy in the registry, `external` for all the external engines
+    pub strategy: String,
+    pub depth: usize,
+    /// `Ruleset::id` of the rules the games were played with
+    pub ruleset: String,
+    /// Score (number of actions) of each game
+    pub scores: Vec<f32>,
+    /// Exponent of the largest tile of each game
+    pub max_tiles: Vec<u8>,
+}
+
+impl BatchSummary {
+    pub fn to_json(&self) -> String {
+        let mut scores = self.scores.clone();
+        scores.sort_by(f32::total_cmp);
+        let quantile = |q: f32| scores.get((scores.len().saturating_sub(1) as f32 * q).round() as usize).copied().unwrap_or(0.0);
+        let mean = scores.iter().sum::<f32>() / scores.len().max(1) as f32;
+        let max_tiles: Vec<String> = (1..=crate::board::MAX_EXPONENT)
+            .filter_map(|exponent| {
+                let count = self.max_tiles.iter().filter(|&&max| max == exponent).count();
+                (count > 0).then(|| format!("\"{}\": {count}", 2u32.pow(exponent as u32)))
+            })
+            .collect();
+        format!(
+            "{{\"version\": \"{}\", \"strategy\": \"{}\", \"depth\": {}, \"ruleset\": \"{}\", \"games\": {}, \"score\": {{\"min\": {}, \"q1\": {}, \"median\": {}, \"q3\": {}, \"max\": {}, \"mean\": {mean:.2}}}, \"max_tiles\": {{{}}}}}\n",
+            env!("CARGO_PKG_VERSION"),
+            self.strategy,
+            self.depth,
+            self.ruleset,
+            scores.len(),
+            quantile(0.0),
+            quantile(0.25),
+            quantile(0.5),
+            quantile(0.75),
+            quantile(1.0),
+            max_tiles.join(", ")
+        )
+    }
+}
+
+/// Submitter of results, through a local queue
+pub struct Telemetry {
+    endpoint: String,
+    queue: PathBuf,
+}
+
+impl Telemetry {
+    /// Submits to `endpoint` (`http://host[:port]/path`), queueing the results in `queue`.
+    pub fn new(endpoint: &str, queue: &Path) -> Telemetry {
+        Telemetry { endpoint: endpoint.to_string(), queue: queue.to_path_buf() }
+    }
+
+    /// Adds a result to the queue.
+    pub fn enqueue(&self, payload: &str) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.queue).with_context(|| format!("Could not create {}", self.queue.display()))?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        let path = self.queue.join(format!("{timestamp}.json"));
+        std::fs::write(&path, payload).with_context(|| format!("Could not write {}", path.display()))
+    }
+
+    /// Sends the queued results, oldest first, removing them from the queue once accepted.
+    /// Stops at the first failure, returning the number of results sent so far otherwise.
+    pub fn flush(&self) -> anyhow::Result<usize> {
+        let Ok(entries) = std::fs::read_dir(&self.queue) else {
+            return Ok(0);
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        for path in &paths {
+            let payload = std::fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+            post(&self.endpoint, &payload).with_context(|| format!("Could not submit to {}", self.endpoint))?;
+            std::fs::remove_file(path)?;
+        }
+        Ok(paths.len())
+    }
+}
+
+/// Posts a JSON body to a plain HTTP endpoint, failing unless the answer is a success (2xx)
+pub fn post(endpoint: &str, body: &str) -> anyhow::Result<()> {
+    let rest = endpoint
+        .strip_prefix("http://")
+        .context("only plain `http://` endpoints are supported")?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let address = if authority.contains(':') { authority.to_string() } else { format!("{authority}:80") };
+    let address = address
+        .to_socket_addrs()?
+        .next()
+        .with_context(|| format!("unknown host `{authority}`"))?;
+
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    write!(
+        stream,
+        "POST {path} HTTP/1.1\r\nHost: {authority}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()?;
+
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status)?;
+    let code: u16 = status
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .with_context(|| format!("invalid answer `{}`", status.trim()))?;
+    ensure!((200..300).contains(&code), "the endpoint answered `{}`", status.trim());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    // Accepts `count` requests, answering 200 to each, and returns their bodies.
+    fn serve(listener: TcpListener, count: usize) -> std::thread::JoinHandle<Vec<String>> {
+        std::thread::spawn(move || {
+            (0..count)
+                .map(|_| {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream);
+                    let mut length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if let Some(value) = line.strip_prefix("Content-Length: ") {
+                            length = value.trim().parse().unwrap();
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                    }
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+                    reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+                    String::from_utf8(body).unwrap()
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_queue_and_submit() {
+        let summary = BatchSummary { strategy: "greedy".to_string(), depth: 3, ruleset: "standard".to_string(), scores: vec![300.0, 100.0, 200.0], max_tiles: vec![7, 8, 7] };
+        let payload = summary.to_json();
+        assert!(payload.contains("\"score\": {\"min\": 100, \"q1\": 200, \"median\": 200, \"q3\": 300, \"max\": 300, \"mean\": 200.00}"), "{payload}");
+        assert!(payload.contains("\"depth\": 3, \"ruleset\": \"standard\", \"games\": 3"), "{payload}");
+        assert!(payload.contains("\"max_tiles\": {\"128\": 2, \"256\": 1}"), "{payload}");
+
+        let queue = std::env::temp_dir().join(format!("2048-telemetry-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&queue);
+
+        // the endpoint is down: the result stays in the queue
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let telemetry = Telemetry::new(&format!("http://{closed}/results"), &queue);
+        telemetry.enqueue(&payload).unwrap();
+        assert!(telemetry.flush().is_err());
+
+        // the next run sends both results
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let telemetry = Telemetry::new(&format!("http://{}/results", listener.local_addr().unwrap()), &queue);
+        let server = serve(listener, 2);
+        telemetry.enqueue(&payload).unwrap();
+        assert_eq!(telemetry.flush().unwrap(), 2);
+        assert_eq!(server.join().unwrap(), vec![payload.clone(), payload]);
+        assert_eq!(telemetry.flush().unwrap(), 0);
+
+        std::fs::remove_dir_all(&queue).unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+//! Builders of boards for fixtures (tests, benchmarks, puzzles, examples), more readable or
+//! more varied than the compact notation.
+
+use anyhow::{bail, ensure, Context};
+use rand::seq::index;
+use rand::Rng;
+
+use crate::board::*;
+
+// Characters of the frames drawn around boards, ignored by `from_ascii_art`
+const FRAME_CHARS: &[char] = &['|', '║', '-', '═', '+', '╔', '╗', '╚', '╝'];
+
+impl Board {
+    /// Board with `num_tiles` tiles on random cells, with random exponents in `1..=max_exponent`.
+    ///
+    /// Panics if `num_tiles` is more than the number of cells or `max_exponent` is not a
+    /// valid exponent.
+    pub fn random_with(num_tiles: usize, max_exponent: u8, rng: &mut impl Rng) -> Board {
+        assert!(num_tiles <= N * N, "a board has only {} cells", N * N);
+        assert!((1..=MAX_EXPONENT).contains(&max_exponent), "invalid exponent {max_exponent}");
+        let mut board = Board::from_cells([[0; N]; N]);
+        for cell in index::sample(rng, N * N, num_tiles) {
+            board.set(cell / N, cell % N, rng.random_range(1..=max_exponent));
+        }
+        board
+    }
+
+    /// Parses a board drawn with the values of the tiles, one row per line, `.` (or `0`) for
+    /// the empty cells:
+    ///
+    /// ```text
+    ///    .   .   .   2
+    ///    .   .   4   2
+    ///    .  16   8   .
+    ///  512 256  64  32
+    /// ```
+    ///
+    /// Blank lines and frames (`|`, `-`, `+` and the box characters of `Display`) are ignored,
+    /// so that printed boards can be pasted back.
+    pub fn from_ascii_art(art: &str) -> anyhow::Result<Board> {
+        let rows: Vec<Vec<&str>> = art
+            .lines()
+            .map(|line| line.split(|c: char| c.is_whitespace() || FRAME_CHARS.contains(&c)).filter(|cell| !cell.is_empty()).collect())
+            .filter(|cells: &Vec<&str>| !cells.is_empty())
+            .collect();
+        ensure!(rows.len() == N, "expected {N} rows but got {}", rows.len());
+
+        let mut board = Board::from_cells([[0; N]; N]);
+        for (i, row) in rows.iter().enumerate() {
+            ensure!(row.len() == N, "row {}: expected {N} cells but got {}", i + 1, row.len());
+            for (j, cell) in row.iter().enumerate() {
+                let exponent = match *cell {
+                    "." | "0" => 0,
+                    value => {
+                        let value: u32 = value.parse().with_context(|| format!("row {}: invalid tile `{value}`", i + 1))?;
+                        if !value.is_power_of_two() || value < 2 || value.trailing_zeros() > MAX_EXPONENT as u32 {
+                            bail!("row {}: `{value}` is not a tile", i + 1);
+                        }
+                        value.trailing_zeros() as u8
+                    }
+                };
+                board.set(i, j, exponent);
+            }
+        }
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_from_ascii_art() {
+        let art = "
+            .   .   .   2
+            .   .   4   2
+            .  16   8   0
+          512 256  64  32
+        ";
+        assert_eq!(Board::from_ascii_art(art).unwrap(), Board::from_compact("0001/0021/0430/9865").unwrap());
+        // a printed board can be pasted back
+        let board = Board::from_compact("1210/4100/3000/000b").unwrap();
+        let mut printed = board.to_string();
+        // without the colors of the terminal
+        while let Some(start) = printed.find('\x1b') {
+            let end = start + printed[start..].find('m').unwrap();
+            printed.replace_range(start..=end, "");
+        }
+        assert_eq!(Board::from_ascii_art(&printed).unwrap(), board);
+
+        assert!(Board::from_ascii_art(". . . 3\n. . . .\n. . . .\n. . . .").is_err());
+        assert!(Board::from_ascii_art(". . . 2\n. . . .\n. . . .").is_err());
+    }
+
+    #[test]
+    fn test_random_with() {
+        let mut rng = StdRng::seed_from_u64(2048);
+        for num_tiles in 0..=N * N {
+            let board = Board::random_with(num_tiles, 5, &mut rng);
+            assert_eq!(board.num_empty(), N * N - num_tiles);
+            assert!(board.max_tile() <= 5);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt::{Display, Formatter};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use rayon::prelude::*;
+
+use crate::board::*;
+use crate::search::{self, SearchParams};
+
+/// A move of the player that the deep search considers clearly worse than the best one
+pub struct Blunder {
+    /// Number of the move in the game (starting at 1)
+    pub move_number: usize,
+    pub before: PlayableBoard,
+    pub played: Action,
+    pub best: Action,
+    /// Value of the best action minus the value of the played one
+    pub loss: f32,
+}
+
+/// Quality of a move compared to the best one according to the search
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MoveGrade {
+    Best,
+    Good,
+    Inaccuracy,
+    Blunder,
+}
+
+pub const ALL_GRADES: [MoveGrade; 4] = [MoveGrade::Best, MoveGrade::Good, MoveGrade::Inaccuracy, MoveGrade::Blunder];
+
+impl MoveGrade {
+    /// Grades a move that loses `loss` compared to the best action, whose value is `best_value`.
+    /// The loss is taken relatively to the best value, as the scale of the heuristic grows
+    /// with the tiles on the board.
+    pub fn of(best_value: f32, loss: f32) -> MoveGrade {
+        let relative = loss / best_value.abs().max(1.0);
+        if loss <= 0.0 {
+            MoveGrade::Best
+        } else if relative < 0.02 {
+            MoveGrade::Good
+        } else if relative < 0.10 {
+            MoveGrade::Inaccuracy
+        } else {
+            MoveGrade::Blunder
+        }
+    }
+}
+
+/// Strength of the assistant suggesting moves to the player and reviewing theirs: the weaker
+/// ones suggest moves that a new player can follow and understand, rather than the ones of a
+/// deep search.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Assistant {
+    /// The move of best immediate evaluation (greedy, one ply)
+    Beginner,
+    /// A search of depth 2
+    Intermediate,
+    /// The search of the agent, at its full depth
+    #[default]
+    Expert,
+}
+
+pub const ALL_ASSISTANTS: [Assistant; 3] = [Assistant::Beginner, Assistant::Intermediate, Assistant::Expert];
+
+// Depth of the search of the intermediate assistant
+const INTERMEDIATE_DEPTH: usize = 2;
+
+impl Assistant {
+    pub fn name(self) -> &'static str {
+        match self {
+            Assistant::Beginner => "beginner",
+            Assistant::Intermediate => "intermediate",
+            Assistant::Expert => "expert",
+        }
+    }
+
+    /// Values of the actions according to the assistant, as `search::action_values`, the agent
+    /// searching with `params`
+    pub fn action_values(self, board: PlayableBoard, params: &SearchParams) -> [Option<f32>; 4] {
+        match self {
+            Assistant::Beginner => search::select_action_greedily(board).map_or([None; 4], |result| result.values),
+            Assistant::Intermediate => search::action_values(board, &SearchParams { depth: INTERMEDIATE_DEPTH, ..*params }),
+            Assistant::Expert => search::action_values(board, params),
+        }
+    }
+
+    /// The move the assistant suggests on `board`, `None` if no action is applicable
+    pub fn hint(self, board: PlayableBoard, params: &SearchParams) -> Option<Action> {
+        let values = self.action_values(board, params);
+        ALL_ACTIONS
+            .into_iter()
+            .zip(values)
+            .filter_map(|(action, value)| value.map(|v| (action, v)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(action, _)| action)
+    }
+}
+
+/// Given the values of all actions (as returned by `search::action_values`), returns the best
+/// action, its value and the value lost by playing `played` instead.
+/// `None` if no action or the played one is not applicable.
+pub fn move_loss(values: &[Option<f32>; 4], played: Action) -> Option<(Action, f32, f32)> {
+    let (best, best_value) = ALL_ACTIONS
+        .into_iter()
+        .zip(*values)
+        .filter_map(|(action, value)| value.map(|v| (action, v)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    let played_value = values[ALL_ACTIONS.iter().position(|&a| a == played)?]?;
+    Some((best, best_value, best_value - played_value))
+}
+
+/// Analyzes all the `(position, action)` pairs played in a game and returns the `count` moves
+/// with the largest value drop compared to the best move, largest first.
+pub fn find_blunders(history: &[(PlayableBoard, Action)], params: &SearchParams, count: usize) -> Vec<Blunder> {
+    let mut blunders: Vec<Blunder> = history
+        .par_iter()
+        .enumerate()
+        .filter_map(|(i, &(before, played))| {
+            let values = search::action_values(before, params);
+            let (best, _, loss) = move_loss(&values, played)?;
+            (best != played).then_some(Blunder {
+                move_number: i + 1,
+                before,
+                played,
+                best,
+                loss,
+            })
+        })
+        .collect();
+    blunders.sort_by(|a, b| b.loss.total_cmp(&a.loss));
+    blunders.truncate(count);
+    blunders
+}
+
+/// What the agent would have played instead of a move of the player
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Review {
+    /// Number of the move in the game (starting at 1)
+    pub move_number: usize,
+    pub played: Action,
+    pub best: Action,
+    /// Value of the best action minus the value of the played one
+    pub loss: f32,
+    pub grade: MoveGrade,
+}
+
+impl Review {
+    /// Reviews `played` on `before` with the given assistant, the agent searching with `params`,
+    /// `None` if the move is not applicable.
+    pub fn of(move_number: usize, before: PlayableBoard, played: Action, assistant: Assistant, params: &SearchParams) -> Option<Review> {
+        let (best, best_value, loss) = move_loss(&assistant.action_values(before, params), played)?;
+        Some(Review { move_number, played, best, loss, grade: MoveGrade::of(best_value, loss) })
+    }
+}
+
+/// Reviews the moves of the player on a background thread as they are played, so that the
+/// reviews can be looked at during the game without slowing it down.
+pub struct LiveReview {
+    requests: Sender<(usize, PlayableBoard, Action, SearchParams)>,
+    results: Receiver<Review>,
+    reviews: Vec<Review>,
+}
+
+impl Default for LiveReview {
+    fn default() -> Self {
+        LiveReview::new(Assistant::default())
+    }
+}
+
+impl LiveReview {
+    /// Reviews with the given assistant
+    pub fn new(assistant: Assistant) -> LiveReview {
+        let (requests, queue) = mpsc::channel::<(usize, PlayableBoard, Action, SearchParams)>();
+        let (sender, results) = mpsc::channel();
+        // stops once the `LiveReview` is dropped and the queue is empty
+        std::thread::spawn(move || {
+            for (move_number, before, played, params) in queue {
+                if let Some(review) = Review::of(move_number, before, played, assistant, &params) {
+                    if sender.send(review).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        LiveReview { requests, results, reviews: Vec::new() }
+    }
+
+    /// Queues the review of the move `played` on `before`, with the search parameters the
+    /// agent would have used (the assistant may search less).
+    pub fn submit(&self, move_number: usize, before: PlayableBoard, played: Action, params: &SearchParams) {
+        let _ = self.requests.send((move_number, before, played, *params));
+    }
+
+    /// The moves reviewed so far, in the order they were played
+    pub fn reviews(&mut self) -> &[Review] {
+        self.reviews.extend(self.results.try_iter());
+        &self.reviews
+    }
+}
+
+/// Number of moves of each grade, e.g. `12 best, 3 good, 1 inaccuracy, 0 blunders`
+pub fn grade_counts(reviews: &[Review]) -> String {
+    let counts: Vec<String> = ALL_GRADES
+        .iter()
+        .map(|&grade| {
+            let count = reviews.iter().filter(|review| review.grade == grade).count();
+            let name = match grade {
+                MoveGrade::Best => "best",
+                MoveGrade::Good => "good",
+                MoveGrade::Inaccuracy if count == 1 => "inaccuracy",
+                MoveGrade::Inaccuracy => "inaccuracies",
+                MoveGrade::Blunder if count == 1 => "blunder",
+                MoveGrade::Blunder => "blunders",
+            };
+            format!("{count} {name}")
+        })
+        .collect();
+    counts.join(", ")
+}
+
+impl Display for Review {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.played == self.best {
+            write!(f, "Move {}: {:?}, as the agent", self.move_number, self.played)
+        } else {
+            write!(
+                f,
+                "Move {}: {:?}, the agent plays {:?} ({:?}, value loss {:.0})",
+                self.move_number, self.played, self.best, self.grade, self.loss
+            )
+        }
+    }
+}
+
+impl Display for Blunder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Move {}: played {:?} but {:?} was better (value loss {:.0})",
+            self.move_number, self.played, self.best, self.loss
+        )?;
+        writeln!(f, "Before:\n{}", self.before)?;
+        if let Some(after) = self.before.apply(self.played) {
+            writeln!(f, "After {:?}:\n{}", self.played, after)?;
+        }
+        if let Some(after) = self.before.apply(self.best) {
+            writeln!(f, "After {:?} instead:\n{}", self.best, after)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_grade() {
+        let values = [Some(1000.0), None, Some(990.0), Some(500.0)];
+        assert_eq!(move_loss(&values, Action::Up), Some((Action::Up, 1000.0, 0.0)));
+        assert_eq!(move_loss(&values, Action::Down), None);
+        let (_, best, loss) = move_loss(&values, Action::Left).unwrap();
+        assert_eq!(MoveGrade::of(best, loss), MoveGrade::Good);
+        let (_, best, loss) = move_loss(&values, Action::Right).unwrap();
+        assert_eq!(MoveGrade::of(best, loss), MoveGrade::Blunder);
+    }
+
+    #[test]
+    fn test_live_review() {
+        let params = SearchParams { depth: 1, ..Default::default() };
+        let board = PlayableBoard::from_board(Board::from_compact("0000/0000/0000/1100").unwrap());
+        let mut review = LiveReview::default();
+        review.submit(1, board, Action::Left, &params);
+        review.submit(2, board, Action::Up, &params);
+        while review.reviews().len() < 2 {
+            std::thread::yield_now();
+        }
+        let reviews = review.reviews();
+        assert_eq!((reviews[0].move_number, reviews[0].best, reviews[0].grade), (1, Action::Up, MoveGrade::Good));
+        assert_eq!(reviews[0].to_string(), "Move 1: Left, the agent plays Up (Good, value loss 365)");
+        assert_eq!(reviews[1].to_string(), "Move 2: Up, as the agent");
+        assert_eq!(grade_counts(reviews), "1 best, 1 good, 0 inaccuracies, 0 blunders");
+    }
+
+    #[test]
+    fn test_assistant() {
+        let params = SearchParams { depth: 3, ..Default::default() };
+        let board = PlayableBoard::from_board(Board::from_compact("1210/4100/3000/000b").unwrap());
+        let greedy = search::select_action_greedily(board).unwrap();
+        assert_eq!(Assistant::Beginner.action_values(board, &params), greedy.values);
+        assert_eq!(Assistant::Beginner.hint(board, &params), Some(greedy.best));
+        let depth = |depth: usize| search::action_values(board, &SearchParams { depth, ..params });
+        assert_eq!(Assistant::Intermediate.action_values(board, &params), depth(2));
+        assert_eq!(Assistant::Expert.action_values(board, &params), depth(3));
+
+        let lost = PlayableBoard::from_board(Board::from_compact("1212/2121/1212/2121").unwrap());
+        assert!(ALL_ASSISTANTS.iter().all(|assistant| assistant.hint(lost, &params).is_none()));
+    }
+}
@@ -0,0 +1,120 @@
+use macroquad::prelude::*;
+
+use crate::board::*;
+
+// Marker file of the profile, created once the tutorial has been completed (or skipped)
+const TUTORIAL_DONE_FILE: &str = ".2048-tutorial-done";
+
+/// A scripted position teaching one idea of the game
+struct Lesson {
+    title: &'static str,
+    /// Explanation shown above the board
+    text: &'static [&'static str],
+    /// Starting position, in the compact board notation
+    board: &'static str,
+    /// Whether the lesson is completed, given the current board and the number of moves played
+    goal: fn(&PlayableBoard, u32) -> bool,
+}
+
+const LESSONS: [Lesson; 4] = [
+    Lesson {
+        title: "1. Merging",
+        text: &["Push the tiles with the arrow keys.", "Two equal tiles merge: make a 4!"],
+        board: "0000/0000/0000/1001",
+        goal: |board, _| board.has_at_least_tile(2),
+    },
+    Lesson {
+        title: "2. Chains",
+        text: &["Merged tiles can merge again.", "Build an 8."],
+        board: "0000/0000/0000/2110",
+        goal: |board, _| board.has_at_least_tile(3),
+    },
+    Lesson {
+        title: "3. Corner strategy",
+        text: &["Keep your largest tile in a corner and", "grow the others next to it. Build a 64."],
+        board: "0000/0000/1000/5432",
+        goal: |board, _| board.has_at_least_tile(6) && largest_in_corner(board),
+    },
+    Lesson {
+        title: "4. Beware of the 4s",
+        text: &["1 new tile in 10 is a 4, not a 2: it can block", "a crowded board. Survive 10 moves."],
+        board: "1212/2121/0000/0000",
+        goal: |_, moves| moves >= 10,
+    },
+];
+
+/// Whether the tutorial should start automatically (first run)
+pub fn is_first_run() -> bool {
+    !crate::profile::path(TUTORIAL_DONE_FILE).exists()
+}
+
+fn largest_in_corner(board: &PlayableBoard) -> bool {
+    let cells = board.board().cells();
+    let max = board.max_tile();
+    [cells[0][0], cells[0][N - 1], cells[N - 1][0], cells[N - 1][N - 1]].contains(&max)
+}
+
+// Runs the lessons one after the other (ASYNC). `Escape` skips the tutorial,
+// `R` restarts the current lesson.
+pub async fn play_tutorial() {
+    'lessons: for lesson in &LESSONS {
+        let start = PlayableBoard::from_board(Board::from_compact(lesson.board).expect("valid lesson board"));
+        let mut cur = start;
+        let mut num_moves = 0;
+        let mut score = 0;
+        loop {
+            cur.draw(num_moves, Some(score), 0.0);
+            draw_lesson(lesson);
+
+            if is_key_pressed(KeyCode::Escape) {
+                break 'lessons;
+            }
+            if is_key_pressed(KeyCode::R) || cur.legal_actions().is_empty() {
+                cur = start;
+                num_moves = 0;
+                score = 0;
+            }
+            if let Some((played, gained)) = crate::input::read_action_key().and_then(|act| cur.apply_scored(act)) {
+                cur = played.with_random_tile();
+                num_moves += 1;
+                score += gained;
+            }
+            if (lesson.goal)(&cur, num_moves) {
+                // show the completed lesson for a moment
+                for _ in 0..60 {
+                    cur.draw(num_moves, Some(score), 0.0);
+                    draw_text("Well done!", 200.0, 380.0, 60.0, DARKGREEN);
+                    next_frame().await;
+                }
+                break;
+            }
+            next_frame().await;
+        }
+    }
+    if let Err(e) = std::fs::write(crate::profile::path(TUTORIAL_DONE_FILE), "") {
+        println!("Could not record the tutorial as done: {e}");
+    }
+}
+
+// Draws the explanation of the lesson over the top of the grid
+fn draw_lesson(lesson: &Lesson) {
+    draw_rectangle(0.0, 70.0, screen_width(), 110.0, Color::new(0.0, 0.0, 0.0, 0.75));
+    draw_text(lesson.title, 15.0, 100.0, 30.0, YELLOW);
+    for (i, line) in lesson.text.iter().enumerate() {
+        draw_text(*line, 15.0, 130.0 + 22.0 * i as f32, 22.0, WHITE);
+    }
+    draw_text("[R] restart lesson   [Esc] skip tutorial", 15.0, 172.0, 18.0, LIGHTGRAY);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lessons_start_unsolved() {
+        for lesson in &LESSONS {
+            let board = PlayableBoard::from_board(Board::from_compact(lesson.board).unwrap());
+            assert!(!(lesson.goal)(&board, 0), "{} is solved from the start", lesson.title);
+        }
+    }
+}
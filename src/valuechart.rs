@@ -0,0 +1,142 @@
+//! Chart of the expected value of the positions of a recorded game, move by move, drawn in the
+//! strip below the grid of the replay mode. The steepest drops are annotated, each one told
+//! apart as a misplay (the move played was worth much less than the best one) or an unlucky
+//! spawn (the move was fine, the tile that came was not), which gives the story of the game at
+//! a glance.
+//!
+//! The value of a position is the value of its best action according to the search. The drop
+//! from a position to the next one is split in two: the value lost by the move (best action
+//! minus played action) and the value lost by the spawn (played action, an expectation over
+//! the spawns, minus the value of the position reached). The larger part names the drop.
+
+use macroquad::prelude::*;
+
+use crate::board::*;
+use crate::chart::CHART_HEIGHT;
+
+const VALUE_COLOR: Color = Color::new(0.2, 0.45, 0.8, 1.0);
+const MISPLAY_COLOR: Color = RED;
+const SPAWN_COLOR: Color = ORANGE;
+/// Number of drops annotated
+pub const NUM_DROPS: usize = 3;
+
+/// What caused a drop of the value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DropCause {
+    /// The move played was worse than the best one
+    Misplay,
+    /// The tile spawned after the move was a bad one
+    UnluckySpawn,
+}
+
+/// A drop of the value over a move
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ValueDrop {
+    /// Index of the move (and of the position before it)
+    pub move_index: usize,
+    /// Value lost, relatively to the value of the position before the move
+    pub relative: f32,
+    pub cause: DropCause,
+}
+
+/// Values of the positions and of the moves of a game, filled as the analysis goes
+pub struct ValueChart {
+    /// Value of each position, the final one included; 0 for a lost position
+    positions: Vec<Option<f32>>,
+    /// Value of the action played from each position
+    played: Vec<Option<f32>>,
+}
+
+impl ValueChart {
+    pub fn new(num_moves: usize) -> ValueChart {
+        ValueChart { positions: vec![None; num_moves + 1], played: vec![None; num_moves] }
+    }
+
+    /// Records the values of the actions of the position `index`, and the action played from
+    /// it (`None` for the final position).
+    pub fn analyzed(&mut self, index: usize, values: &[Option<f32>; 4], played: Option<Action>) {
+        self.positions[index] = Some(values.iter().flatten().copied().fold(0.0, f32::max));
+        if let Some(played) = played {
+            self.played[index] = values[ALL_ACTIONS.iter().position(|&a| a == played).unwrap()];
+        }
+    }
+
+    /// The `count` steepest drops of the positions analyzed so far, steepest first. The drop
+    /// into a lost position is left out: the end of the game has its cause of death.
+    pub fn steepest_drops(&self, count: usize) -> Vec<ValueDrop> {
+        let mut drops: Vec<ValueDrop> = (0..self.played.len())
+            .filter_map(|i| {
+                let (before, played, after) = (self.positions[i]?, self.played[i]?, self.positions[i + 1]?);
+                if before <= 0.0 || after <= 0.0 || after >= before {
+                    return None;
+                }
+                let (misplay, spawn) = (before - played, played - after);
+                let cause = if misplay >= spawn { DropCause::Misplay } else { DropCause::UnluckySpawn };
+                Some(ValueDrop { move_index: i, relative: (before - after) / before, cause })
+            })
+            .collect();
+        drops.sort_by(|a, b| b.relative.total_cmp(&a.relative));
+        drops.truncate(count);
+        drops
+    }
+
+    /// Draws the values in the strip below the grid, scaled between their smallest and largest
+    /// ones, with the steepest drops and the `current` position marked. The drop of the move
+    /// played from `current`, if it is one of the steepest, is spelled out.
+    pub fn draw(&self, current: usize) {
+        let top = PADDING + UI_HEIGHT + GRID_SIZE + PADDING;
+        let (left, width, height) = (PADDING, GRID_SIZE, CHART_HEIGHT - 2.0 * PADDING);
+        draw_rectangle(left, top, width, height, Color::new(0.93, 0.89, 0.85, 1.0));
+        let (min, max) = self.positions.iter().flatten().fold((f32::MAX, 0.0f32), |(min, max), &v| (min.min(v), max.max(v)));
+        let x = |i: usize| left + width * i as f32 / self.played.len().max(1) as f32;
+        let y = |value: f32| top + height * (1.0 - if max > min { (value - min) / (max - min) } else { 0.5 });
+        for (i, pair) in self.positions.windows(2).enumerate() {
+            if let [Some(a), Some(b)] = *pair {
+                draw_line(x(i), y(a), x(i + 1), y(b), 2.0, VALUE_COLOR);
+            }
+        }
+        for (rank, drop) in self.steepest_drops(NUM_DROPS).iter().enumerate() {
+            let (color, cause) = match drop.cause {
+                DropCause::Misplay => (MISPLAY_COLOR, "misplay"),
+                DropCause::UnluckySpawn => (SPAWN_COLOR, "unlucky spawn"),
+            };
+            let at = x(drop.move_index) + 0.5 * (x(1) - x(0));
+            draw_line(at, top, at, top + height, 1.0, color);
+            draw_text((rank + 1).to_string(), at + 2.0, top + height - 4.0, 16.0, color);
+            if drop.move_index == current {
+                let text = format!("Drop {}: -{:.0}% ({cause})", rank + 1, drop.relative * 100.0);
+                draw_text(text, left + 240.0, top + 16.0, 16.0, BLACK);
+            }
+        }
+        draw_line(x(current), top, x(current), top + height, 2.0, BLACK);
+        draw_text("Value", left + 6.0, top + 16.0, 18.0, VALUE_COLOR);
+        draw_text("misplay", left + 60.0, top + 16.0, 16.0, MISPLAY_COLOR);
+        draw_text("unlucky spawn", left + 120.0, top + 16.0, 16.0, SPAWN_COLOR);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steepest_drops() {
+        let mut chart = ValueChart::new(4);
+        let values = |best: f32, played: f32| [Some(best), Some(played), None, None];
+        // move 0: Down loses 10 of 100, the spawn nothing more
+        chart.analyzed(0, &values(100.0, 90.0), Some(Action::Down));
+        // move 1: the best move, but the position reached is worth 40 less
+        chart.analyzed(1, &values(90.0, 80.0), Some(Action::Up));
+        chart.analyzed(2, &values(50.0, 45.0), Some(Action::Up));
+        // move 3 leads to a lost position
+        chart.analyzed(3, &values(55.0, 40.0), Some(Action::Down));
+        chart.analyzed(4, &[None; 4], None);
+
+        let drops = chart.steepest_drops(NUM_DROPS);
+        assert_eq!(drops.len(), 2);
+        assert_eq!((drops[0].move_index, drops[0].cause), (1, DropCause::UnluckySpawn));
+        assert!((drops[0].relative - 40.0 / 90.0).abs() < 1e-6);
+        assert_eq!((drops[1].move_index, drops[1].cause), (0, DropCause::Misplay));
+        assert_eq!(chart.steepest_drops(1).len(), 1);
+    }
+}
@@ -0,0 +1,148 @@
+//! Verification mode (`--features verify`): the invariants of the engine are asserted on every
+//! action applied, every tile spawned and every chance node expanded, so that a regression of
+//! the engine (e.g. while developing a variant of the rules) fails loudly where it happens
+//! instead of silently skewing the games and the searches.
+//!
+//! - tile conservation: an action keeps the sum of the tiles, a spawn adds exactly its tile
+//! - score: an action gains points if and only if it merges tiles, an even number of them
+//! - legal exponents: every cell holds an exponent up to `MAX_EXPONENT`, every random spawn
+//!   one the rules give a probability (the spawns placed on purpose, e.g. by a script, may be
+//!   any tile)
+//! - probability sums: the successors of a chance node add up to 1
+//! - consistency: `hash64` and the compact notation give back the same board, and
+//!   `can_apply` agrees with `apply`
+//!
+//! A violation panics with a diagnostic bundle (the invariant, the positions in all their
+//! notations, the action or the spawn, the rules), which the crash reports (`crash.rs`) save
+//! with the state of the game. The checks slow the searches down a lot: they are meant for
+//! tests and short benchmark runs.
+
+use std::fmt::Write as _;
+
+use crate::board::*;
+
+// Tolerance on the sum of the probabilities of the successors of a chance node
+const PROBABILITY_TOLERANCE: f32 = 1e-4;
+
+// Panics with the diagnostic bundle of the violated `invariant`, about the given positions
+fn violation(invariant: &str, what: &str, boards: &[(&str, &Board)]) -> ! {
+    let mut bundle = format!("engine invariant violated: {invariant}\n{what}\n");
+    for (name, board) in boards {
+        let _ = writeln!(bundle, "{name}: {} (hash {:016x})\n{board}", board.compact(), board.hash64());
+    }
+    let rules = crate::rules::rules();
+    let _ = write!(bundle, "rules {} ({})", rules.id(), rules);
+    panic!("{bundle}");
+}
+
+fn num_tiles(board: &Board) -> usize {
+    N * N - board.num_empty()
+}
+
+// The invariants of any board: legal exponents and consistent notations
+fn check_board(name: &str, board: &Board) {
+    if let Some(cell) = board.exponents().find(|&cell| cell > MAX_EXPONENT) {
+        violation("legal exponents", &format!("exponent {cell} on the {name} board"), &[(name, board)]);
+    }
+    if Board::from_hash64(board.hash64()) != *board {
+        violation("consistency", &format!("hash64 does not give back the {name} board"), &[(name, board)]);
+    }
+    if Board::from_compact(&board.compact()).ok() != Some(*board) {
+        violation("consistency", &format!("the compact notation does not give back the {name} board"), &[(name, board)]);
+    }
+}
+
+/// Checks the result of `before.apply_scored(action)`.
+pub fn check_apply(before: &Board, action: Action, result: Option<(Board, u32)>) {
+    check_board("before", before);
+    let what = format!("action {action:?}, result {result:?}");
+    let Some((after, gained)) = result else {
+        if before.can_apply(action) {
+            violation("consistency", &format!("{what}: can_apply allows the action"), &[("before", before)]);
+        }
+        return;
+    };
+    let boards = [("before", before), ("after", &after)];
+    check_board("after", &after);
+    if !before.can_apply(action) {
+        violation("consistency", &format!("{what}: can_apply rejects the action"), &boards);
+    }
+    if after.tile_sum() != before.tile_sum() {
+        violation("tile conservation", &format!("{what}: tile sum {} -> {}", before.tile_sum(), after.tile_sum()), &boards);
+    }
+    let merges = num_tiles(before) - num_tiles(&after);
+    if (gained > 0) != (merges > 0) || gained % 2 != 0 {
+        violation("score", &format!("{what}: {gained} points for {merges} merges"), &boards);
+    }
+}
+
+/// Checks that `after` is `before` with the tile `spawn` on an empty cell.
+pub fn check_spawn(before: &Board, spawn: Spawn, after: &Board) {
+    let boards = [("before", before), ("after", after)];
+    let what = format!("spawn {spawn:?}");
+    check_board("after", after);
+    if spawn.row >= N || spawn.col >= N || before.get(spawn.row, spawn.col) != 0 || spawn.exponent == 0 {
+        violation("tile conservation", &format!("{what} on an occupied cell, or without tile"), &boards);
+    }
+    let mut expected = *before;
+    expected.set(spawn.row, spawn.col, spawn.exponent);
+    if expected != *after {
+        violation("tile conservation", &format!("{what}: other cells changed"), &boards);
+    }
+}
+
+/// Same as `check_spawn` for a random spawn, which must also be a tile the rules spawn on
+/// `before`.
+pub fn check_random_spawn(before: &Board, spawn: Spawn, after: &Board) {
+    check_spawn(before, spawn, after);
+    let boards = [("before", before), ("after", after)];
+    let what = format!("spawn {spawn:?}");
+    let probabilities = crate::rules::rules().spawn_probabilities(before);
+    if !probabilities.iter().any(|&(exponent, proba)| exponent == spawn.exponent && proba > 0.0) {
+        violation("legal exponents", &format!("{what}: the rules spawn {probabilities:?}"), &boards);
+    }
+}
+
+/// Checks the successors of the chance node `before`, with their probabilities.
+pub fn check_successors(before: &Board, successors: impl Iterator<Item = (f32, Board)>) {
+    let mut sum = 0.0;
+    for (proba, succ) in successors {
+        if !(proba > 0.0 && proba <= 1.0) {
+            violation("probability sums", &format!("successor of probability {proba}"), &[("before", before), ("successor", &succ)]);
+        }
+        if num_tiles(&succ) != num_tiles(before) + 1 || succ.tile_sum() <= before.tile_sum() {
+            violation("tile conservation", "the successor does not add exactly one tile", &[("before", before), ("successor", &succ)]);
+        }
+        sum += proba;
+    }
+    if before.num_empty() > 0 && (sum - 1.0).abs() > PROBABILITY_TOLERANCE {
+        violation("probability sums", &format!("the successors add up to {sum}"), &[("before", before)]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invariants_hold() {
+        let mut board = Board::from_compact("1100/0220/0003/1000").unwrap();
+        for action in ALL_ACTIONS.into_iter().cycle().take(40) {
+            if let Some(next) = board.apply(action) {
+                check_successors(&next, next.random_successors());
+                board = next;
+                if board.num_empty() > 0 {
+                    board.add_random();
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "engine invariant violated: tile conservation")]
+    fn test_violation() {
+        let before = Board::from_compact("1100/0000/0000/0000").unwrap();
+        // a merge losing its tiles
+        check_apply(&before, Action::Left, Some((Board::from_compact("1000/0000/0000/0000").unwrap(), 4)));
+    }
+}
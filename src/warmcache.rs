@@ -0,0 +1,157 @@
+//! Persistence of the cache of the search between runs, so that analyzing positions similar to
+//! the ones of a previous session starts with the values already computed.
+//!
+//! The file starts with a text line identifying the values: the format, the version of the
+//! engine (`compat::ENGINE_VERSION`) and of the crate, the rules, the evaluation plugin and the
+//! parameters of the search that change the values. A file written by another version, for
+//! other rules, with another plugin or with other parameters is ignored, its values would be
+//! wrong. The line is followed by the entries, `MAX_SAVED` at most, 13 bytes each
+//! (little-endian): the `Board::hash64` of the position, the bits of its `f32` value and the
+//! depth it was searched at. The file ends with the `compat::checksum` of the entries (4 bytes,
+//! little-endian).
+//!
+//! The deepest values are kept first: a single one of them saves a whole subtree of the search.
+//! Since the values of the cache depend on the order in which positions were searched, a warm
+//! cache makes the search differ slightly from a cold one; it is meant for the analysis, not
+//! for the reproducible runs of the benchmark.
+
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context};
+
+use crate::board::*;
+use crate::compat::{self, ENGINE_VERSION};
+use crate::plugin;
+use crate::rules;
+use crate::search::{SearchParams, Searcher};
+
+/// Environment variable giving the file of the cache of the analysis mode
+pub const WARM_CACHE_VAR: &str = "AI2048_WARM_CACHE";
+// Bump when the file format changes, or when the search or the evaluation change the values
+// without changing the version of the crate
+const FORMAT_VERSION: u32 = 2;
+/// Maximum number of entries written (about 6.5 MB)
+pub const MAX_SAVED: usize = 500_000;
+const ENTRY_SIZE: usize = 13;
+
+// First line of the file for values computed with `params`
+fn header(params: &SearchParams) -> String {
+    let mut header = format!(
+        "ai-2048 warm cache {FORMAT_VERSION} engine {ENGINE_VERSION} {} rules {} eval {} {:?} {:?} {:?} {}\n",
+        env!("CARGO_PKG_VERSION"),
+        rules::rules().id(),
+        plugin::active_id(),
+        params.weights,
+        params.adversarial_mix,
+        params.score_weight,
+        params.extensions
+    );
+    // (only when set, so that the caches saved without cutoff are still loaded)
+    if params.probability_cutoff > 0.0 {
+        header.insert_str(header.len() - 1, &format!(" cutoff {:?}", params.probability_cutoff));
+    }
+    header
+}
+
+/// Writes the most valuable entries of the cache of `searcher` to `path`, returning their number.
+pub fn save(searcher: &Searcher, path: &Path) -> anyhow::Result<usize> {
+    let Some(params) = searcher.cached_params() else {
+        return Ok(0);
+    };
+    let mut entries: Vec<_> = searcher.cached_values().collect();
+    // stable: the most recently used first among the values of the same depth
+    entries.sort_by_key(|&(_, &(_, depth))| std::cmp::Reverse(depth));
+    entries.truncate(MAX_SAVED);
+
+    let file = std::fs::File::create(path).with_context(|| format!("Could not create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(header(params).as_bytes())?;
+    let mut bytes = Vec::with_capacity(entries.len() * ENTRY_SIZE);
+    for (board, &(value, depth)) in &entries {
+        bytes.extend_from_slice(&board.board().hash64().to_le_bytes());
+        bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+        bytes.push(depth.min(u8::MAX as usize) as u8);
+    }
+    writer.write_all(&bytes)?;
+    writer.write_all(&compat::checksum(&bytes).to_le_bytes())?;
+    writer.flush().with_context(|| format!("Could not write {}", path.display()))?;
+    Ok(entries.len())
+}
+
+/// A searcher whose cache holds the values saved in `path`, which must have been computed with
+/// the same `params`.
+pub fn load(path: &Path, params: &SearchParams) -> anyhow::Result<Searcher> {
+    let file = std::fs::File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+    let mut bytes = Vec::new();
+    BufReader::new(file).read_to_end(&mut bytes)?;
+    let expected = header(params);
+    let Some(entries) = bytes.strip_prefix(expected.as_bytes()) else {
+        let line = bytes.split(|&byte| byte == b'\n').next().unwrap_or_default();
+        let words: Vec<&str> = std::str::from_utf8(line).unwrap_or_default().split(' ').collect();
+        match words[..] {
+            ["ai-2048", "warm", "cache", format, ..] if format != FORMAT_VERSION.to_string() => {
+                bail!("{} is in the format {format} of the warm caches, this version reads the format {FORMAT_VERSION}", path.display())
+            }
+            [_, _, _, _, "engine", engine, ..] if engine != ENGINE_VERSION.to_string() => {
+                bail!("{} was computed by the engine version {engine}, this one is the version {ENGINE_VERSION}", path.display())
+            }
+            ["ai-2048", "warm", "cache", ..] => {
+                bail!("{} was written by another version, for other rules, another evaluation plugin or with other search parameters", path.display())
+            }
+            _ => bail!("{} is not a warm cache", path.display()),
+        }
+    };
+    let Some((entries, checksum)) = entries.split_last_chunk::<4>() else {
+        bail!("{} is truncated", path.display());
+    };
+    if entries.len() % ENTRY_SIZE != 0 || compat::checksum(entries) != u32::from_le_bytes(*checksum) {
+        bail!("{} is truncated or corrupted", path.display());
+    }
+    let values = entries.chunks_exact(ENTRY_SIZE).map(|entry| {
+        let board = Board::from_hash64(u64::from_le_bytes(entry[..8].try_into().unwrap()));
+        let value = f32::from_bits(u32::from_le_bytes(entry[8..12].try_into().unwrap()));
+        (RandableBoard::from_board(board), (value, entry[12] as usize))
+    });
+    // the most valuable entries are the last inserted, i.e. the last to be evicted
+    let values: Vec<_> = values.collect();
+    Ok(Searcher::warm(params, values.into_iter().rev()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_load() {
+        let path = std::env::temp_dir().join(format!("2048-warm-cache-{}.bin", std::process::id()));
+        let params = SearchParams { depth: 3, ..Default::default() };
+        let board = PlayableBoard::from_board(Board::from_compact("1210/4100/3000/000b").unwrap());
+        let mut searcher = Searcher::default();
+        let values = searcher.action_values(board, &params);
+        let saved = save(&searcher, &path).unwrap();
+        assert_eq!(saved, searcher.cached_values().count());
+        assert!(saved > 0);
+
+        // the values are found again, the deepest ones first
+        let mut warm = load(&path, &params).unwrap();
+        assert_eq!(warm.cached_values().count(), saved);
+        let depths: Vec<usize> = warm.cached_values().map(|(_, &(_, depth))| depth).collect();
+        assert!(depths.windows(2).all(|pair| pair[0] >= pair[1]) && depths[0] == 2, "{depths:?}");
+        assert_eq!(warm.action_values(board, &params), values);
+
+        // values computed with other weights, or damaged, are rejected
+        let other = SearchParams { score_weight: 1.0, ..params };
+        assert!(load(&path, &other).is_err());
+        assert!(load(&path, &SearchParams { probability_cutoff: 0.001, ..params }).is_err());
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 5;
+        bytes[last] ^= 1;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(load(&path, &params).err().unwrap().to_string().contains("corrupted"));
+        let old = String::from_utf8_lossy(&bytes).replacen("cache 2 ", "cache 1 ", 1);
+        std::fs::write(&path, old.as_bytes()).unwrap();
+        assert!(load(&path, &params).err().unwrap().to_string().contains("format 1"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
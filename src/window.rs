@@ -0,0 +1,67 @@
+//! Platform integration of the game window: size, title and icon.
+//!
+//! macroquad 0.4 only lets us configure the window before it is created: the title cannot be
+//! changed afterwards and there is no access to the taskbar, so live information (score,
+//! max tile, progress) is shown in the window itself.
+
+use macroquad::miniquad::conf::Icon;
+use macroquad::prelude::*;
+
+use crate::board::WINDOW_WIDTH;
+use crate::chart::CHART_HEIGHT;
+
+pub const WINDOW_TITLE: &str = "2048 Expectimax";
+// Extra space above the grid for the statistics
+const UI_HEIGHT: f32 = 60.0;
+
+// Icon colors: grid background (#bbada0) and the tiles 2, 4, 8 and 2048
+const ICON_BACKGROUND: [u8; 4] = [187, 173, 160, 255];
+const ICON_TILES: [[u8; 4]; 4] = [
+    [238, 228, 218, 255], // #eee4da
+    [237, 224, 200, 255], // #ede0c8
+    [242, 177, 121, 255], // #f2b179
+    [237, 194, 46, 255],  // #edc22e
+];
+
+/// Configuration of the window, to be used with `#[macroquad::main(window_conf)]`
+pub fn window_conf() -> Conf {
+    Conf {
+        window_title: WINDOW_TITLE.to_string(),
+        window_width: WINDOW_WIDTH as i32,
+        window_height: (WINDOW_WIDTH + UI_HEIGHT + CHART_HEIGHT) as i32,
+        icon: Some(icon()),
+        ..Default::default()
+    }
+}
+
+/// The icon of the window: a 2x2 grid of tiles, at the three sizes required by miniquad
+fn icon() -> Icon {
+    Icon {
+        small: icon_pixels::<{ 16 * 16 * 4 }>(16),
+        medium: icon_pixels::<{ 32 * 32 * 4 }>(32),
+        big: icon_pixels::<{ 64 * 64 * 4 }>(64),
+    }
+}
+
+/// RGBA pixels (row-major) of a `size` x `size` icon
+fn icon_pixels<const LEN: usize>(size: usize) -> [u8; LEN] {
+    let mut pixels = [0; LEN];
+    let gap = (size / 16).max(1);
+    let tile = (size - 3 * gap) / 2;
+    for y in 0..size {
+        for x in 0..size {
+            // index of the tile under the pixel along each axis, if not in a gap
+            let tile_index = |v: usize| match v {
+                _ if v >= gap && v < gap + tile => Some(0),
+                _ if v >= 2 * gap + tile && v < 2 * gap + 2 * tile => Some(1),
+                _ => None,
+            };
+            let color = match (tile_index(y), tile_index(x)) {
+                (Some(i), Some(j)) => ICON_TILES[2 * i + j],
+                _ => ICON_BACKGROUND,
+            };
+            pixels[4 * (y * size + x)..4 * (y * size + x) + 4].copy_from_slice(&color);
+        }
+    }
+    pixels
+}